@@ -0,0 +1,52 @@
+//! How long a scan of a big tree takes, and how much sizing the result list up front from the
+//! last scan ([`ScanOptions::expected_images`]) saves. Runs against a [`MemoryFileSystem`] so the
+//! disk (and whatever's cached of it) doesn't come into it.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use photoview::{MemoryFileSystem, ScanOptions, Scanner};
+
+use std::hint::black_box;
+use std::path::Path;
+
+/// `dirs` directories of `per_dir` images each, spread over two levels, plus a sidecar next to
+/// every image for the extension check to throw out
+fn synthetic_tree(dirs: usize, per_dir: usize) -> MemoryFileSystem {
+    let mut fs = MemoryFileSystem::new();
+    for dir in 0..dirs {
+        let dir = format!("/photos/{}/{dir}", dir % 10);
+        for image in 0..per_dir {
+            fs.add_file(format!("{dir}/IMG_{image:04}.jpg"), 4096);
+            fs.add_file(format!("{dir}/IMG_{image:04}.xmp"), 256);
+        }
+    }
+    fs
+}
+
+fn scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    for (dirs, per_dir) in [(10, 100), (100, 100)] {
+        let images = dirs * per_dir;
+        let fs = synthetic_tree(dirs, per_dir);
+        for expected_images in [0, images] {
+            let scanner = Scanner::new(
+                fs.clone(),
+                ScanOptions {
+                    expected_images,
+                    ..Default::default()
+                },
+            );
+            let name = if expected_images == 0 {
+                "growing"
+            } else {
+                "presized"
+            };
+            group.bench_with_input(BenchmarkId::new(name, images), &scanner, |b, scanner| {
+                b.iter(|| scanner.scan(black_box(Path::new("/photos"))).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,331 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+    window::RequestRedraw,
+};
+use image::{
+    AnimationDecoder, DynamicImage,
+    codecs::{gif::GifDecoder, webp::WebPDecoder},
+};
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{
+    AtlasSlot, DirWatchingSet, ImageDisplayMaterial, ImageLoadState, ImageMarker, ImageModified,
+    ImageTexture,
+};
+
+/// Play animated GIFs and WebPs on their quads, rather than just their first frame. Every frame
+/// is decoded up front and kept as a texture of its own, outside the [`crate::TextureBudget`], so
+/// animations that come to more than `max_bytes` decoded stay still. Packed quads do too, their
+/// thumbnail is the first frame.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct AnimatedImages {
+    pub enabled: bool,
+    pub max_bytes: usize,
+}
+
+impl Default for AnimatedImages {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bytes: 64 << 20,
+        }
+    }
+}
+
+impl AnimatedImages {
+    /// Animations being decoded at once
+    const MAX_DECODING: usize = 4;
+}
+
+/// The frames of an animated image, on its quad while it's playing
+#[derive(Component, Debug, Clone)]
+pub struct FrameSequence {
+    /// Each frame, and how long it's shown for
+    pub frames: Vec<(Handle<Image>, Duration)>,
+    pub current: usize,
+    /// How long the current frame has been showing
+    pub elapsed: Duration,
+}
+
+impl FrameSequence {
+    pub fn new(frames: Vec<(Handle<Image>, Duration)>) -> Self {
+        Self {
+            frames,
+            current: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn frame(&self) -> Option<&Handle<Image>> {
+        self.frames.get(self.current).map(|(frame, _)| frame)
+    }
+
+    /// Play on for `delta`, returning whether that got to another frame
+    pub fn advance(&mut self, delta: Duration) -> bool {
+        let total: Duration = self.frames.iter().map(|(_, delay)| *delay).sum();
+        if total.is_zero() {
+            return false;
+        }
+        let before = self.current;
+        self.elapsed += delta;
+        // Coming back after a long while shouldn't mean stepping through every loop since
+        if self.elapsed >= total {
+            self.elapsed =
+                Duration::from_nanos((self.elapsed.as_nanos() % total.as_nanos()) as u64);
+        }
+        while let Some((_, delay)) = self.frames.get(self.current)
+            && self.elapsed >= *delay
+        {
+            self.elapsed -= *delay;
+            self.current = (self.current + 1) % self.frames.len();
+        }
+        self.current != before
+    }
+}
+
+/// Where a quad is at before it has a [`FrameSequence`]
+#[derive(Component)]
+enum FrameDecoding {
+    Decoding(Task<Option<Vec<(Image, Duration)>>>),
+    /// Not animated, too big, or couldn't be decoded: nothing to play
+    Still,
+}
+
+pub struct AnimatedImagePlugin;
+
+impl Plugin for AnimatedImagePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AnimatedImages>();
+        app.add_systems(
+            Update,
+            (
+                stop_animations,
+                queue_frame_decoding,
+                poll_frame_decoding,
+                play_animations,
+            )
+                .chain()
+                .after(DirWatchingSet::SpawnQuads)
+                .run_if(|animated: Res<AnimatedImages>| animated.enabled),
+        );
+    }
+}
+
+/// Anything quicker than this plays at [`DEFAULT_DELAY`], the way browsers do it (and so the way
+/// files made for them expect)
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+fn is_animatable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif") || ext.eq_ignore_ascii_case("webp"))
+}
+
+/// Drop the frames of quads whose texture was evicted, they're decoded again once it's back, and
+/// of images that were rewritten, putting their own texture back while it reloads
+fn stop_animations(
+    mut commands: Commands,
+    mut modified: EventReader<ImageModified>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut quads: Query<
+        (
+            Entity,
+            &ImageMarker,
+            &ImageTexture,
+            &ImageLoadState,
+            Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+            Option<&mut Sprite>,
+        ),
+        Or<(With<FrameSequence>, With<FrameDecoding>)>,
+    >,
+) {
+    let modified: HashSet<PathBuf> = modified.read().map(|event| event.path.clone()).collect();
+    for (entity, marker, texture, state, material, sprite) in &mut quads {
+        let rewritten = modified.contains(&marker.target);
+        if *state != ImageLoadState::Evicted && !rewritten {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .remove::<(FrameSequence, FrameDecoding)>();
+        if !rewritten {
+            continue;
+        }
+        if let Some(material) = material.and_then(|material| materials.get_mut(&material.0)) {
+            material.base_color_texture = Some(texture.0.clone());
+        }
+        if let Some(mut sprite) = sprite {
+            sprite.image = texture.0.clone();
+        }
+    }
+}
+
+/// Start decoding the frames of GIFs and WebPs once their first frame has loaded, a few at a time
+fn queue_frame_decoding(
+    mut commands: Commands,
+    animated: Res<AnimatedImages>,
+    decoding: Query<&FrameDecoding>,
+    quads: Query<
+        (Entity, &ImageMarker, &ImageLoadState),
+        (
+            Without<FrameSequence>,
+            Without<FrameDecoding>,
+            Without<AtlasSlot>,
+        ),
+    >,
+) {
+    let mut room = AnimatedImages::MAX_DECODING.saturating_sub(
+        decoding
+            .iter()
+            .filter(|decoding| matches!(decoding, FrameDecoding::Decoding(_)))
+            .count(),
+    );
+    let pool = AsyncComputeTaskPool::get();
+    for (entity, marker, state) in &quads {
+        if *state != ImageLoadState::Loaded {
+            continue;
+        }
+        if !is_animatable(&marker.target) {
+            commands.entity(entity).insert(FrameDecoding::Still);
+            continue;
+        }
+        if room == 0 {
+            continue;
+        }
+        room -= 1;
+        let (path, max_bytes) = (marker.target.clone(), animated.max_bytes);
+        let task = pool.spawn(async move { decode_frames(&path, max_bytes) });
+        commands
+            .entity(entity)
+            .insert(FrameDecoding::Decoding(task));
+    }
+}
+
+fn poll_frame_decoding(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut quads: Query<(Entity, &mut FrameDecoding)>,
+) {
+    for (entity, mut decoding) in &mut quads {
+        let FrameDecoding::Decoding(task) = &mut *decoding else {
+            continue;
+        };
+        let Some(frames) = block_on(future::poll_once(task)) else {
+            continue;
+        };
+        let Some(frames) = frames else {
+            *decoding = FrameDecoding::Still;
+            continue;
+        };
+        let frames = frames
+            .into_iter()
+            .map(|(frame, delay)| (images.add(frame), delay))
+            .collect();
+        commands
+            .entity(entity)
+            .remove::<FrameDecoding>()
+            .insert(FrameSequence::new(frames));
+    }
+}
+
+/// Step the animations that are on screen through their frames. Ones that aren't just wait,
+/// picking up where they left off.
+fn play_animations(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut redraw: EventWriter<RequestRedraw>,
+    mut quads: Query<
+        (
+            &mut FrameSequence,
+            &ImageLoadState,
+            &ViewVisibility,
+            Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+            Option<&mut Sprite>,
+        ),
+        Without<AtlasSlot>,
+    >,
+) {
+    let mut playing = false;
+    for (mut sequence, state, visibility, material, sprite) in &mut quads {
+        if *state != ImageLoadState::Loaded || !visibility.get() {
+            continue;
+        }
+        playing = true;
+        if !sequence.advance(time.delta()) && !sequence.is_added() {
+            continue;
+        }
+        let Some(frame) = sequence.frame().cloned() else {
+            continue;
+        };
+        if let Some(material) = material.and_then(|material| materials.get_mut(&material.0)) {
+            material.base_color_texture = Some(frame.clone());
+        }
+        if let Some(mut sprite) = sprite {
+            sprite.image = frame;
+        }
+    }
+    // Nothing else would wake the app up for the next frame
+    if playing {
+        redraw.write(RequestRedraw);
+    }
+}
+
+/// Every frame of the animated image at `path`, each composited onto the whole canvas. `None`
+/// if it has only the one, or they'd take more than `max_bytes`. Runs on the
+/// [`AsyncComputeTaskPool`].
+fn decode_frames(path: &Path, max_bytes: usize) -> Option<Vec<(Image, Duration)>> {
+    let reader = BufReader::new(File::open(path).ok()?);
+    let is_gif = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    let frames = if is_gif {
+        GifDecoder::new(reader).ok()?.into_frames()
+    } else {
+        let decoder = WebPDecoder::new(reader).ok()?;
+        if !decoder.has_animation() {
+            return None;
+        }
+        decoder.into_frames()
+    };
+
+    let mut decoded = vec![];
+    let mut bytes = 0;
+    for frame in frames {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::debug!("Couldn't decode the frames of {path:?}, leaving it still: {e}");
+                return None;
+            }
+        };
+        let (numerator, denominator) = frame.delay().numer_denom_ms();
+        let delay = Duration::from_millis((numerator / denominator.max(1)).into());
+        let delay = if delay < MIN_DELAY {
+            DEFAULT_DELAY
+        } else {
+            delay
+        };
+        let pixels = frame.into_buffer();
+        bytes += pixels.len();
+        if bytes > max_bytes {
+            log::debug!("{path:?} has too many frames to play, leaving it still");
+            return None;
+        }
+        // Only ever drawn, never read back
+        let image = Image::from_dynamic(
+            DynamicImage::ImageRgba8(pixels),
+            true,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        decoded.push((image, delay));
+    }
+    (decoded.len() > 1).then_some(decoded)
+}
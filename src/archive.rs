@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use tempfile::TempDir;
+use zip::ZipArchive;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{ImageMarker, ScanConfig, WatchedDirs, normalize_path};
+
+/// A ZIP file whose images are shown as if it were a watched directory, see
+/// [`WatchedDirs::add_archive`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveSource(pub PathBuf);
+
+/// Which archive a quad's image was extracted from
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct SourceArchive(pub PathBuf);
+
+/// An archive and the images extracted from it. The images live in a temporary directory that's
+/// deleted when this is dropped (or [`Self::close`]d).
+#[derive(Debug)]
+pub(crate) struct Archive {
+    pub(crate) source: ArchiveSource,
+    /// Whether it's been read at all yet. Archives are extracted by the first scan after they're
+    /// added rather than straight away.
+    read: bool,
+    /// The archive's modification time when it was last read, to spot it being replaced
+    modified: Option<SystemTime>,
+    extracted: Option<TempDir>,
+    pub(crate) images: Vec<PathBuf>,
+}
+
+impl Archive {
+    pub(crate) fn new(source: ArchiveSource) -> Self {
+        Self {
+            source,
+            read: false,
+            modified: None,
+            extracted: None,
+            images: vec![],
+        }
+    }
+
+    /// Has it not been read yet, or been changed since it was?
+    pub(crate) fn is_stale(&self) -> bool {
+        !self.read || modified(&self.source.0) != self.modified
+    }
+
+    /// Take in what a scan extracted, replacing the images from last time. If extracting failed
+    /// the old ones are kept, and it isn't tried again until the archive changes.
+    pub(crate) fn update(&mut self, read: ExtractedArchive) -> io::Result<()> {
+        self.read = true;
+        self.modified = read.modified;
+        let (extracted, images) = read.extracted?;
+        self.extracted = Some(extracted);
+        self.images = images;
+        Ok(())
+    }
+
+    /// Is `image` one of the files extracted from this archive?
+    pub(crate) fn contains(&self, image: &Path) -> bool {
+        self.images.iter().any(|extracted| extracted == image)
+    }
+
+    /// Delete the extracted images
+    pub(crate) fn close(&mut self) {
+        self.images.clear();
+        if let Some(extracted) = self.extracted.take() {
+            let path = extracted.path().to_path_buf();
+            if let Err(e) = extracted.close() {
+                log::warn!("Couldn't delete extracted images in {path:?}: {e}");
+            }
+        }
+    }
+}
+
+/// An archive's images extracted into a fresh temporary directory by a scan, off the main thread,
+/// for [`Archive::update`] to take in
+pub(crate) struct ExtractedArchive {
+    pub(crate) source: PathBuf,
+    /// From before extracting, so a change made partway through is picked up next time
+    modified: Option<SystemTime>,
+    extracted: io::Result<(TempDir, Vec<PathBuf>)>,
+}
+
+impl ExtractedArchive {
+    pub(crate) fn new(path: &Path, config: &ScanConfig) -> Self {
+        Self {
+            source: path.to_path_buf(),
+            modified: modified(path),
+            extracted: extract(path, config),
+        }
+    }
+}
+
+/// Copy every entry of the ZIP at `path` with an image extension into a new temporary
+/// directory, keeping the folders inside the archive. Entries whose names would land outside
+/// the directory are skipped.
+fn extract(path: &Path, config: &ScanConfig) -> io::Result<(TempDir, Vec<PathBuf>)> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let dir = tempfile::Builder::new().prefix("photoview-").tempdir()?;
+    let mut images = vec![];
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        let Some(name) = entry.enclosed_name() else {
+            log::warn!("Skipping {:?} in {path:?}, it points outside", entry.name());
+            continue;
+        };
+        if !entry.is_file() || !config.is_supported_image(&name) {
+            continue;
+        }
+
+        let destination = dir.path().join(&name);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&destination)?)?;
+        images.push(normalize_path(&destination));
+    }
+    Ok((dir, images))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Tags quads with the archive their image came out of, and deletes the extracted images on the
+/// way out
+pub struct ArchivePlugin;
+
+impl Plugin for ArchivePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, tag_archive_images);
+        app.add_systems(Last, cleanup_temp_dirs_system);
+    }
+}
+
+fn tag_archive_images(
+    mut commands: Commands,
+    watched_dirs: Res<WatchedDirs>,
+    quads: Query<(Entity, &ImageMarker), Added<ImageMarker>>,
+) {
+    for (entity, marker) in &quads {
+        if let Some(archive) = watched_dirs.archive_of(&marker.target) {
+            commands
+                .entity(entity)
+                .insert(SourceArchive(archive.0.clone()));
+        }
+    }
+}
+
+fn cleanup_temp_dirs_system(
+    mut exits: EventReader<AppExit>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+) {
+    if exits.read().count() > 0 {
+        watched_dirs.close_archives();
+    }
+}
@@ -0,0 +1,405 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::{
+        mesh::VertexAttributeValues,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+use image::RgbaImage;
+
+use std::collections::HashMap;
+
+use crate::{
+    DirWatchingSet, ExifOrientation, GridConfig, ImageDisplayMaterial, ImageLoadState, ImageMarker,
+    ImageTexture, RenderMode, Selection,
+};
+
+/// Pack the grid's images into a few big textures as thumbnails, instead of giving every quad a
+/// texture (and a material) of its own. Quads on the same page of an atlas, with the same colours,
+/// share one material and get drawn together, so a grid of thousands of images takes a handful of
+/// draw calls rather than thousands.
+///
+/// Once an image is packed its full-size texture is let go, the same as if the [`TextureBudget`]
+/// had evicted it, so packed images are only ever as sharp as [`Self::thumbnail_size`]. An image
+/// that's selected before it's been packed waits until it isn't, so it stays sharp to look at.
+/// Only [`RenderMode::Mesh3d`] quads are packed, sprites already batch by texture.
+///
+/// [`TextureBudget`]: crate::TextureBudget
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThumbnailAtlas {
+    pub enabled: bool,
+    /// Images are shrunk to fit in a square this many pixels across before they're packed
+    pub thumbnail_size: u32,
+    /// Width and height of each atlas texture
+    pub page_size: u32,
+}
+
+impl Default for ThumbnailAtlas {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            thumbnail_size: 256,
+            page_size: 4096,
+        }
+    }
+}
+
+impl ThumbnailAtlas {
+    /// Edge pixels are repeated this far out around each thumbnail, so filtering (bicubic reaches
+    /// two texels out) never picks up the neighbours
+    const GUTTER: u32 = 2;
+
+    /// Thumbnails shrinking at once, each holds a copy of its full-size image while it does
+    const MAX_SHRINKING: usize = 16;
+
+    /// Side of the square each thumbnail gets on a page, gutter and all
+    fn cell_size(&self) -> u32 {
+        self.thumbnail_size + Self::GUTTER * 2
+    }
+
+    fn cells_per_row(&self) -> u32 {
+        (self.page_size / self.cell_size()).max(1)
+    }
+
+    fn cells_per_page(&self) -> usize {
+        (self.cells_per_row() * self.cells_per_row()) as usize
+    }
+
+    /// Top-left pixel of the thumbnail (inside its gutter) in `cell`
+    fn cell_origin(&self, cell: usize) -> UVec2 {
+        let row = self.cells_per_row() as usize;
+        UVec2::new((cell % row) as u32, (cell / row) as u32) * self.cell_size()
+            + UVec2::splat(Self::GUTTER)
+    }
+}
+
+/// Where a quad's thumbnail is packed. Removing it (or despawning the quad) frees its cell for
+/// another image.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct AtlasSlot {
+    pub page: usize,
+    cell: usize,
+    /// Pixel size of the thumbnail
+    pub size: UVec2,
+    /// The thumbnail's part of the page, in UV coordinates
+    pub uv: Rect,
+}
+
+/// On quads that need a material of their own (to play a video in, say), so they're never packed
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub(crate) struct KeepUnpacked;
+
+/// A quad's image being shrunk down to a thumbnail in the background
+#[derive(Component)]
+enum Thumbnail {
+    Shrinking(Task<Option<RgbaImage>>),
+    /// Its pixels couldn't be read back, so this quad keeps a texture of its own
+    Unpackable,
+}
+
+/// The atlas pages, and the materials quads on them share
+#[derive(Resource, Default)]
+pub struct ThumbnailAtlases {
+    pages: Vec<AtlasPage>,
+    /// By page and the bits of their tint and highlight
+    materials: HashMap<(usize, [u32; 4], [u32; 4]), Handle<ImageDisplayMaterial>>,
+}
+
+struct AtlasPage {
+    image: Handle<Image>,
+    /// Cells that were used and given back
+    free: Vec<usize>,
+    /// Cells from here on haven't been used yet
+    next: usize,
+}
+
+impl ThumbnailAtlases {
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The texture of atlas page `page`, kept on the CPU side too
+    pub(crate) fn page_image(&self, page: usize) -> Option<&Handle<Image>> {
+        self.pages.get(page).map(|page| &page.image)
+    }
+
+    /// A free cell, on a new page if the others are full
+    fn allocate(&mut self, config: &ThumbnailAtlas, images: &mut Assets<Image>) -> (usize, usize) {
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(cell) = page.free.pop() {
+                return (index, cell);
+            }
+            if page.next < config.cells_per_page() {
+                page.next += 1;
+                return (index, page.next - 1);
+            }
+        }
+
+        let image = Image::new_fill(
+            Extent3d {
+                width: config.page_size,
+                height: config.page_size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+            // Kept on this side too, to pack more thumbnails into
+            RenderAssetUsages::default(),
+        );
+        self.pages.push(AtlasPage {
+            image: images.add(image),
+            free: vec![],
+            next: 1,
+        });
+        log::debug!("Started thumbnail atlas page {}", self.pages.len());
+        (self.pages.len() - 1, 0)
+    }
+
+    /// The material for quads on `page` coloured like `material`, shared by all of them
+    fn shared_material(
+        &mut self,
+        page: usize,
+        mut material: ImageDisplayMaterial,
+        materials: &mut Assets<ImageDisplayMaterial>,
+    ) -> Handle<ImageDisplayMaterial> {
+        let bits = |color: LinearRgba| color.to_f32_array().map(f32::to_bits);
+        let key = (page, bits(material.tint), bits(material.highlight));
+        let image = self.pages[page].image.clone();
+        self.materials
+            .entry(key)
+            .or_insert_with(|| {
+                material.base_color_texture = Some(image);
+                materials.add(material)
+            })
+            .clone()
+    }
+
+    /// Change a quad's material without the change reaching any other quad. Packed quads share
+    /// theirs, so they're moved over to the page's material in the new colours instead.
+    pub(crate) fn restyle(
+        &mut self,
+        material: &mut MeshMaterial3d<ImageDisplayMaterial>,
+        slot: Option<&AtlasSlot>,
+        materials: &mut Assets<ImageDisplayMaterial>,
+        change: impl FnOnce(&mut ImageDisplayMaterial),
+    ) {
+        match slot {
+            Some(slot) => {
+                let Some(mut styled) = materials.get(&material.0).cloned() else {
+                    return;
+                };
+                change(&mut styled);
+                material.0 = self.shared_material(slot.page, styled, materials);
+            }
+            None => {
+                if let Some(material) = materials.get_mut(&material.0) {
+                    change(material);
+                }
+            }
+        }
+    }
+}
+
+pub struct ThumbnailAtlasPlugin;
+
+impl Plugin for ThumbnailAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ThumbnailAtlas>();
+        app.init_resource::<ThumbnailAtlases>();
+        app.add_observer(free_atlas_slot);
+        app.add_systems(
+            Update,
+            (
+                shrink_loaded_images,
+                pack_thumbnails,
+                resize_packed_quads.run_if(resource_changed::<GridConfig>),
+            )
+                .chain()
+                .after(DirWatchingSet::SpawnQuads)
+                .run_if(|atlas: Res<ThumbnailAtlas>| atlas.enabled)
+                .run_if(resource_equals(RenderMode::Mesh3d)),
+        );
+    }
+}
+
+/// Start shrinking the images that have loaded since last time
+fn shrink_loaded_images(
+    mut commands: Commands,
+    atlas: Res<ThumbnailAtlas>,
+    selection: Res<Selection>,
+    images: Res<Assets<Image>>,
+    shrinking: Query<(), With<Thumbnail>>,
+    quads: Query<
+        (Entity, &ImageMarker, &ImageTexture, &ImageLoadState),
+        (Without<Thumbnail>, Without<KeepUnpacked>),
+    >,
+) {
+    let room = ThumbnailAtlas::MAX_SHRINKING.saturating_sub(shrinking.iter().len());
+    let thumbnail_size = atlas.thumbnail_size;
+    let loaded = quads.iter().filter(|(_, marker, texture, state)| {
+        **state == ImageLoadState::Loaded
+            // Packed already, unless it's been loaded again since
+            && texture.0.is_strong()
+            && selection.path() != Some(&marker.target)
+    });
+    for (entity, _, texture, _) in loaded.take(room) {
+        let Some(image) = images.get(&texture.0).cloned() else {
+            continue;
+        };
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let mut image = image.try_into_dynamic().ok()?;
+            if image.width() > thumbnail_size || image.height() > thumbnail_size {
+                image = image.thumbnail(thumbnail_size, thumbnail_size);
+            }
+            Some(image.to_rgba8())
+        });
+        commands.entity(entity).insert(Thumbnail::Shrinking(task));
+    }
+}
+
+/// Copy finished thumbnails into the atlas and point their quads at it
+fn pack_thumbnails(
+    mut commands: Commands,
+    atlas: Res<ThumbnailAtlas>,
+    grid_config: Res<GridConfig>,
+    mut atlases: ResMut<ThumbnailAtlases>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut quads: Query<(
+        Entity,
+        &ImageMarker,
+        &mut Thumbnail,
+        &mut ImageTexture,
+        &mut Mesh3d,
+        &mut MeshMaterial3d<ImageDisplayMaterial>,
+        Option<&AtlasSlot>,
+        Option<&ExifOrientation>,
+    )>,
+) {
+    let mut packed = 0;
+    for (entity, marker, mut thumbnail, mut texture, mut mesh, mut material, slot, orientation) in
+        &mut quads
+    {
+        let Thumbnail::Shrinking(task) = &mut *thumbnail else {
+            continue;
+        };
+        let Some(result) = block_on(future::poll_once(task)) else {
+            continue;
+        };
+        let Some(mut pixels) = result else {
+            log::warn!("Couldn't make a thumbnail of {:?}", marker.target);
+            *thumbnail = Thumbnail::Unpackable;
+            continue;
+        };
+        commands.entity(entity).remove::<Thumbnail>();
+
+        // Rewritten on disk and loaded again, the new thumbnail fits in the old one's cell
+        let (page, cell) = match slot {
+            Some(slot) => (slot.page, slot.cell),
+            None => atlases.allocate(&atlas, &mut images),
+        };
+        let Some(page_image) = images.get_mut(&atlases.pages[page].image) else {
+            continue;
+        };
+        // The page's material is shared, so mirrored images go on it mirrored already
+        if orientation.is_some_and(|orientation| orientation.mirrored) {
+            image::imageops::flip_horizontal_in_place(&mut pixels);
+        }
+        let origin = atlas.cell_origin(cell);
+        blit(page_image, &pixels, origin);
+
+        let size = UVec2::new(pixels.width(), pixels.height());
+        let page_size = Vec2::splat(atlas.page_size as f32);
+        let slot = AtlasSlot {
+            page,
+            cell,
+            size,
+            uv: Rect::from_corners(
+                origin.as_vec2() / page_size,
+                (origin + size).as_vec2() / page_size,
+            ),
+        };
+        mesh.0 = meshes.add(thumbnail_mesh(&slot, grid_config.quad_size));
+        let colors = materials.get(&material.0).cloned();
+        if let Some(mut colors) = colors {
+            colors.mirrored = 0;
+            material.0 = atlases.shared_material(page, colors, &mut materials);
+        }
+        // Nothing else holds on to the full-size texture, so this frees it
+        texture.0 = texture.0.clone_weak();
+        commands.entity(entity).insert(slot);
+        packed += 1;
+    }
+    if packed > 0 {
+        log::debug!(
+            "Packed {packed} thumbnails, {} atlas pages",
+            atlases.pages()
+        );
+    }
+}
+
+/// Copy `pixels` onto `page` at `origin`, repeating its edges out into the gutter around it
+fn blit(page: &mut Image, pixels: &RgbaImage, origin: UVec2) {
+    let page_width = page.width() as i64;
+    let Some(data) = page.data.as_mut() else {
+        return;
+    };
+    let gutter = ThumbnailAtlas::GUTTER as i64;
+    let (width, height) = (pixels.width() as i64, pixels.height() as i64);
+    for y in -gutter..height + gutter {
+        let row = (origin.y as i64 + y) * page_width;
+        for x in -gutter..width + gutter {
+            let source =
+                pixels.get_pixel(x.clamp(0, width - 1) as u32, y.clamp(0, height - 1) as u32);
+            let at = ((row + origin.x as i64 + x) * 4) as usize;
+            if let Some(target) = data.get_mut(at..at + 4) {
+                target.copy_from_slice(&source.0);
+            }
+        }
+    }
+}
+
+/// A quad with the thumbnail's aspect ratio that just fits in a `quad_size` square, showing its
+/// part of the page
+fn thumbnail_mesh(slot: &AtlasSlot, quad_size: f32) -> Mesh {
+    let size = slot.size.max(UVec2::ONE).as_vec2();
+    let mut mesh = Mesh::from(Rectangle::from_size(size * quad_size / size.max_element()));
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        for uv in uvs {
+            let mapped = slot.uv.min + Vec2::from(*uv) * slot.uv.size();
+            *uv = mapped.into();
+        }
+    }
+    mesh
+}
+
+/// The packed quads' meshes are their own, so they need making again when quads change size
+fn resize_packed_quads(
+    grid_config: Res<GridConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut quad_size: Local<Option<f32>>,
+    mut quads: Query<(&AtlasSlot, &mut Mesh3d)>,
+) {
+    if quad_size.replace(grid_config.quad_size) == Some(grid_config.quad_size) {
+        return;
+    }
+    for (slot, mut mesh) in &mut quads {
+        mesh.0 = meshes.add(thumbnail_mesh(slot, grid_config.quad_size));
+    }
+}
+
+fn free_atlas_slot(
+    trigger: Trigger<OnRemove, AtlasSlot>,
+    slots: Query<&AtlasSlot>,
+    mut atlases: ResMut<ThumbnailAtlases>,
+) {
+    if let Ok(slot) = slots.get(trigger.target())
+        && let Some(page) = atlases.pages.get_mut(slot.page)
+    {
+        page.free.push(slot.cell);
+    }
+}
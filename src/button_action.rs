@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+
+use crate::{
+    ExportKind, ExportRequested, RescanRequested, ScanPaused, TurnPage, UiTheme, platform,
+    watch_dir,
+};
+
+/// What a UI button does when it's pressed. Put it next to [`Button`] and the press is turned
+/// into the same event or resource change the matching hotkey makes, so the button needs no
+/// system of its own.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonAction {
+    /// Pick a folder with the system dialog and start watching it
+    AddFolder,
+    RescanNow,
+    /// Pause or resume background scanning
+    TogglePause,
+    ToggleTheme,
+    NextPage,
+    PrevPage,
+    Export(ExportKind),
+}
+
+/// Styles every [`Button`] from the [`UiTheme`] as it's hovered and pressed, and runs the
+/// [`ButtonAction`] of the ones that get pressed. Labels are left alone, they belong to the
+/// buttons.
+pub struct ButtonActionPlugin;
+
+impl Plugin for ButtonActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RescanRequested>();
+        app.add_event::<TurnPage>();
+        app.add_event::<ExportRequested>();
+        app.init_resource::<ScanPaused>();
+        app.add_systems(Update, (button_style_system, button_action_system));
+    }
+}
+
+fn button_style_system(
+    theme: Res<UiTheme>,
+    mut buttons: Query<
+        (&Interaction, &mut BackgroundColor, &mut BorderColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut background, mut border) in &mut buttons {
+        let (color, border_color) = theme.button_colors(*interaction);
+        background.0 = color;
+        border.0 = border_color;
+    }
+}
+
+/// `Interaction` is only written when it actually changes, so a changed `Pressed` is a fresh
+/// press rather than a button being held down
+fn button_action_system(
+    mut commands: Commands,
+    buttons: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    mut rescan_requests: EventWriter<RescanRequested>,
+    mut turns: EventWriter<TurnPage>,
+    mut exports: EventWriter<ExportRequested>,
+    mut paused: ResMut<ScanPaused>,
+    mut theme: ResMut<UiTheme>,
+) {
+    for (interaction, action) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *action {
+            ButtonAction::AddFolder => {
+                if let Some(dir) = platform::pick_folder() {
+                    commands.run_system_cached_with(watch_dir, dir);
+                }
+            }
+            ButtonAction::RescanNow => {
+                rescan_requests.write(RescanRequested::all());
+            }
+            ButtonAction::TogglePause => paused.0 = !paused.0,
+            ButtonAction::ToggleTheme => *theme = theme.toggled(),
+            ButtonAction::NextPage => {
+                turns.write(TurnPage(1));
+            }
+            ButtonAction::PrevPage => {
+                turns.write(TurnPage(-1));
+            }
+            ButtonAction::Export(kind) => {
+                exports.write(ExportRequested(kind));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::EventCursor;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<RescanRequested>();
+        app.add_event::<TurnPage>();
+        app.add_event::<ExportRequested>();
+        app.init_resource::<ScanPaused>();
+        app.init_resource::<UiTheme>();
+        app.add_systems(Update, button_action_system);
+        app
+    }
+
+    /// Spawn a button for `action` and take it through a press, being held down and being let
+    /// go, counting what `fired` saw happen after each of those frames. `Interaction` is written
+    /// the way Bevy's own focus system does it, only when it changes.
+    fn press(
+        action: ButtonAction,
+        label: bool,
+        mut fired: impl FnMut(&World) -> usize,
+    ) -> Vec<usize> {
+        let mut app = app();
+        let mut button = app.world_mut().spawn((Button, action));
+        if label {
+            button.with_child(Text::new("Label"));
+        }
+        let button = button.id();
+        app.update();
+        assert_eq!(
+            fired(app.world()),
+            0,
+            "{action:?} fired before being pressed"
+        );
+
+        let mut counts = vec![];
+        for interaction in [
+            Interaction::Pressed,
+            Interaction::Pressed,
+            Interaction::None,
+        ] {
+            app.world_mut()
+                .get_mut::<Interaction>(button)
+                .unwrap()
+                .set_if_neq(interaction);
+            app.update();
+            counts.push(fired(app.world()));
+        }
+        counts
+    }
+
+    /// Counts the `E`s matching `matches` written since it was last called
+    fn events<E: Event>(matches: impl Fn(&E) -> bool) -> impl FnMut(&World) -> usize {
+        let mut cursor: Option<EventCursor<E>> = None;
+        move |world| {
+            let events = world.resource::<Events<E>>();
+            cursor
+                .get_or_insert_with(|| events.get_cursor())
+                .read(events)
+                .filter(|event| matches(event))
+                .count()
+        }
+    }
+
+    /// Counts the times a resource has changed value since it was last called
+    fn toggles<R: Resource, V: PartialEq>(value: impl Fn(&R) -> V) -> impl FnMut(&World) -> usize {
+        let mut last = None;
+        move |world| {
+            let current = value(world.resource::<R>());
+            let toggled = last.as_ref().is_some_and(|last| *last != current);
+            last = Some(current);
+            usize::from(toggled)
+        }
+    }
+
+    // `AddFolder` opens the system folder picker, which a test can't answer
+
+    #[test]
+    fn rescan_now_requests_one_rescan_of_everything() {
+        let counts = press(
+            ButtonAction::RescanNow,
+            true,
+            events(|request: &RescanRequested| request.dir.is_none()),
+        );
+        assert_eq!(counts, [1, 0, 0]);
+    }
+
+    #[test]
+    fn toggle_pause_flips_it_once() {
+        let counts = press(
+            ButtonAction::TogglePause,
+            true,
+            toggles(|paused: &ScanPaused| paused.0),
+        );
+        assert_eq!(counts, [1, 0, 0]);
+    }
+
+    #[test]
+    fn toggle_theme_switches_it_once() {
+        let counts = press(
+            ButtonAction::ToggleTheme,
+            true,
+            toggles(|theme: &UiTheme| theme.kind),
+        );
+        assert_eq!(counts, [1, 0, 0]);
+    }
+
+    #[test]
+    fn page_buttons_turn_one_page_their_way() {
+        let next = press(
+            ButtonAction::NextPage,
+            true,
+            events(|turn: &TurnPage| turn.0 == 1),
+        );
+        assert_eq!(next, [1, 0, 0]);
+        let prev = press(
+            ButtonAction::PrevPage,
+            true,
+            events(|turn: &TurnPage| turn.0 == -1),
+        );
+        assert_eq!(prev, [1, 0, 0]);
+    }
+
+    #[test]
+    fn export_buttons_request_their_own_kind() {
+        for kind in [
+            ExportKind::Screenshot,
+            ExportKind::ContactSheet,
+            ExportKind::Gallery,
+        ] {
+            let counts = press(
+                ButtonAction::Export(kind),
+                true,
+                events(move |request: &ExportRequested| request.0 == kind),
+            );
+            assert_eq!(counts, [1, 0, 0], "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn buttons_without_a_label_still_work() {
+        let counts = press(
+            ButtonAction::RescanNow,
+            false,
+            events(|request: &RescanRequested| request.dir.is_none()),
+        );
+        assert_eq!(counts, [1, 0, 0]);
+    }
+}
@@ -0,0 +1,622 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+use rusqlite::{Connection, Row, params};
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    CachedImage, CaptureDate, ExifOrientation, FileStamp, GpsPosition, ImageMarker, PhotoMetadata,
+    ProbedDimensions, ScanCounter, ScanErrors, Tags, ThumbnailCache, WatchedDirs, file_stamp,
+    metadata::MetadataReader,
+    rating::{Rating, Ratings},
+    scan_cache::{ScanCacheState, load_scan_cache},
+    thumbnails::KnownThumbnails,
+};
+
+/// Keep what's been worked out about every scanned image in a SQLite database at `path`: its
+/// modification time and size, pixel dimensions, capture date and the rest of its
+/// [`PhotoMetadata`], its thumbnail in the [`ThumbnailCache`], its [`Rating`], and a copy of its
+/// [`Tags`] (the sidecars are still what's read back). It's read at startup, so the grid can be
+/// laid out before the first scan (unless a [`crate::ScanCache`] already was) and nothing is
+/// read again from files that haven't changed since.
+///
+/// Rows for files that have gone are dropped, ones outside the watched directories are kept for
+/// whenever they're watched again.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Catalog {
+    /// The database file, `None` to keep nothing between launches
+    pub path: Option<PathBuf>,
+}
+
+impl Catalog {
+    /// Changes are written at most this often, so a scan's worth go in together
+    const WRITE_INTERVAL: Duration = Duration::from_secs(2);
+    /// Bump whenever the schema changes. Databases of other versions are left alone, they hold
+    /// ratings that nothing else does.
+    const VERSION: i32 = 1;
+
+    pub fn enabled(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS images (
+    path TEXT PRIMARY KEY NOT NULL,
+    -- Nanoseconds since the Unix epoch
+    modified INTEGER,
+    len INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    width INTEGER,
+    height INTEGER,
+    -- Whether `taken` has been looked for
+    dated INTEGER NOT NULL,
+    taken INTEGER,
+    -- Whether the EXIF columns have been read
+    metadata INTEGER NOT NULL,
+    camera TEXT,
+    lens TEXT,
+    iso INTEGER,
+    aperture REAL,
+    shutter REAL,
+    quarter_turns INTEGER NOT NULL,
+    mirrored INTEGER NOT NULL,
+    latitude REAL,
+    longitude REAL,
+    thumbnail TEXT,
+    thumbnail_edge INTEGER,
+    rating INTEGER NOT NULL,
+    -- Comma separated, which tags can't have in them
+    tags TEXT NOT NULL
+);
+";
+
+const COLUMNS: &str = "path, modified, len, position, width, height, dated, taken, metadata, \
+    camera, lens, iso, aperture, shutter, quarter_turns, mirrored, latitude, longitude, \
+    thumbnail, thumbnail_edge, rating, tags";
+
+/// One image's row. Everything but `rating` and `tags` was read from the file as it was at
+/// `stamp`, and is only used while it's still like that.
+#[derive(Debug, Clone, PartialEq)]
+struct CatalogEntry {
+    stamp: FileStamp,
+    /// Where it came in the image list, to put the grid back in order
+    position: usize,
+    dimensions: Option<(u32, u32)>,
+    /// When it was taken, `Some(None)` if it doesn't say
+    taken: Option<Option<SystemTime>>,
+    metadata: Option<PhotoMetadata>,
+    /// And how big thumbnails were being made at the time
+    thumbnail: Option<(PathBuf, u32)>,
+    rating: u8,
+    tags: Vec<String>,
+}
+
+/// The open catalog, and what it's known to hold
+#[derive(Resource)]
+struct CatalogDb {
+    /// Only ever used by one write at a time, but resources have to be `Sync`
+    connection: Arc<Mutex<Connection>>,
+    written: HashMap<PathBuf, CatalogEntry>,
+    /// Tags as they were last seen on quads
+    tags: HashMap<PathBuf, Vec<String>>,
+    writing: Option<Task<rusqlite::Result<()>>>,
+    /// Something's changed since the last write
+    dirty: bool,
+    last_write: Duration,
+}
+
+pub struct CatalogPlugin;
+
+impl Plugin for CatalogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Catalog>();
+        app.add_systems(
+            Startup,
+            load_catalog
+                .after(load_scan_cache)
+                .run_if(|catalog: Res<Catalog>| catalog.enabled()),
+        );
+        app.add_systems(Update, note_tags.run_if(resource_exists::<CatalogDb>));
+        app.add_systems(Last, write_catalog.run_if(resource_exists::<CatalogDb>));
+    }
+}
+
+/// Open the catalog and put back what it holds: ratings, the image list (unless the scan cache
+/// already has), and whatever was read from files that are the same as they were then
+fn load_catalog(
+    mut commands: Commands,
+    catalog: Res<Catalog>,
+    thumbnail_cache: Res<ThumbnailCache>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut cache_state: ResMut<ScanCacheState>,
+    mut reader: ResMut<MetadataReader>,
+    mut thumbnails: ResMut<KnownThumbnails>,
+    mut ratings: ResMut<Ratings>,
+    mut errors: ResMut<ScanErrors>,
+) {
+    let Some(path) = &catalog.path else {
+        return;
+    };
+    let (connection, entries) = match open(path) {
+        Ok(opened) => opened,
+        Err(e) => {
+            log::warn!("Couldn't open the catalog {path:?}, nothing will be kept: {e}");
+            errors.push(format!("Couldn't open the catalog {}: {e}", path.display()));
+            return;
+        }
+    };
+
+    let mut seeded = vec![];
+    let mut dimensions = vec![];
+    for (image, entry) in &entries {
+        if entry.rating > 0 {
+            ratings.0.insert(image.clone(), Rating(entry.rating));
+        }
+        let Some(stamp) = file_stamp(image) else {
+            continue;
+        };
+        if watched_dirs.dir_of(image).is_some() {
+            let cached = CachedImage {
+                path: image.clone(),
+                modified: entry.stamp.modified,
+                dimensions: None,
+            };
+            seeded.push((entry.position, cached));
+        }
+        // Anything else was read from an older version of the file
+        if stamp != entry.stamp {
+            continue;
+        }
+        if let Some(size) = entry.dimensions {
+            let probed = ProbedDimensions {
+                modified: stamp.modified,
+                size: Some(size),
+            };
+            dimensions.push((image.clone(), probed));
+        }
+        if let Some(taken) = entry.taken {
+            let date = CaptureDate {
+                modified: stamp.modified,
+                taken,
+            };
+            watched_dirs.captured.insert(image.clone(), date);
+        }
+        if let Some(metadata) = &entry.metadata {
+            reader.read.insert(image.clone(), metadata.clone());
+        }
+        if let Some((file, edge)) = &entry.thumbnail
+            && *edge == thumbnail_cache.max_edge
+            && file.is_file()
+        {
+            thumbnails
+                .0
+                .insert(image.clone(), (file.clone(), stamp.modified));
+        }
+    }
+    watched_dirs.add_dimensions(dimensions);
+    if !cache_state.loaded && !seeded.is_empty() {
+        seeded.sort_by_key(|(position, _)| *position);
+        let images = seeded.into_iter().map(|(_, image)| image).collect();
+        cache_state.seed(&mut watched_dirs, images);
+    }
+
+    log::debug!("Read {} images from the catalog {path:?}", entries.len());
+    commands.insert_resource(CatalogDb {
+        connection: Arc::new(Mutex::new(connection)),
+        written: entries,
+        tags: HashMap::new(),
+        writing: None,
+        dirty: false,
+        last_write: Duration::ZERO,
+    });
+}
+
+/// Keep up with tags as they're read from their sidecars or edited
+fn note_tags(mut db: ResMut<CatalogDb>, quads: Query<(&ImageMarker, &Tags), Changed<Tags>>) {
+    for (marker, tags) in &quads {
+        db.tags.insert(marker.target.clone(), tags.0.clone());
+        db.dirty = true;
+    }
+}
+
+/// Every so often write whatever's changed to the catalog, in the background. On exit anything
+/// still to go is written before the app does.
+fn write_catalog(
+    time: Res<Time>,
+    mut exits: EventReader<AppExit>,
+    mut db: ResMut<CatalogDb>,
+    (watched_dirs, scans, reader, thumbnails, thumbnail_cache, ratings): (
+        Res<WatchedDirs>,
+        Res<ScanCounter>,
+        Res<MetadataReader>,
+        Res<KnownThumbnails>,
+        Res<ThumbnailCache>,
+        Res<Ratings>,
+    ),
+) {
+    if watched_dirs.is_changed()
+        || reader.is_changed()
+        || thumbnails.is_changed()
+        || ratings.is_changed()
+    {
+        db.dirty = true;
+    }
+    let exiting = exits.read().count() > 0;
+    if let Some(task) = &mut db.writing {
+        let result = if exiting {
+            Some(block_on(task))
+        } else {
+            block_on(future::poll_once(task))
+        };
+        let Some(result) = result else {
+            return;
+        };
+        db.writing = None;
+        if let Err(e) = result {
+            log::warn!("Couldn't write to the catalog: {e}");
+        }
+    }
+    if !db.dirty || (!exiting && time.elapsed() - db.last_write < Catalog::WRITE_INTERVAL) {
+        return;
+    }
+    db.dirty = false;
+    db.last_write = time.elapsed();
+
+    let images = watched_dirs.all_images();
+    let mut upserts = vec![];
+    for (position, image) in images.iter().enumerate() {
+        // Only what's been scanned, which leaves out playlists and archives
+        let Some(stamp) = watched_dirs.stamp(image) else {
+            continue;
+        };
+        let written = db.written.get(image);
+        let entry = CatalogEntry {
+            stamp,
+            position,
+            dimensions: watched_dirs
+                .dimensions
+                .get(image)
+                .filter(|probed| probed.modified == stamp.modified)
+                .and_then(|probed| probed.size),
+            taken: watched_dirs
+                .captured
+                .get(image)
+                .filter(|date| date.modified == stamp.modified)
+                .map(|date| date.taken),
+            metadata: reader.read.get(image).cloned(),
+            thumbnail: thumbnails
+                .0
+                .get(image)
+                .filter(|(_, modified)| *modified == stamp.modified)
+                .map(|(file, _)| (file.clone(), thumbnail_cache.max_edge)),
+            rating: ratings.0.get(image).map_or(0, |rating| rating.0),
+            tags: db
+                .tags
+                .get(image)
+                .or(written.map(|entry| &entry.tags))
+                .cloned()
+                .unwrap_or_default(),
+        };
+        if written != Some(&entry) {
+            upserts.push((image.clone(), entry));
+        }
+    }
+    // Only files that really have gone, not ones past the scan limit, and not before there's
+    // been a scan to tell
+    let removed: Vec<PathBuf> = if scans.0 == 0 {
+        vec![]
+    } else {
+        let current: HashSet<&PathBuf> = images.iter().collect();
+        db.written
+            .keys()
+            .filter(|image| !current.contains(image) && watched_dirs.dir_of(image).is_some())
+            .filter(|image| !image.exists())
+            .cloned()
+            .collect()
+    };
+    if upserts.is_empty() && removed.is_empty() {
+        return;
+    }
+
+    for image in &removed {
+        db.written.remove(image);
+    }
+    db.written.extend(upserts.iter().cloned());
+    let connection = db.connection.clone();
+    let write = move || {
+        let mut connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+        write_entries(&mut connection, &upserts, &removed)
+    };
+    if exiting {
+        if let Err(e) = write() {
+            log::warn!("Couldn't write to the catalog: {e}");
+        }
+        return;
+    }
+    db.writing = Some(IoTaskPool::get().spawn(async move { write() }));
+}
+
+/// Open the catalog at `path`, making it if it isn't there yet, and read every row in it
+fn open(path: &Path) -> Result<(Connection, HashMap<PathBuf, CatalogEntry>), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let connection = Connection::open(path)?;
+    let version: i32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    match version {
+        0 => {
+            connection.execute_batch(SCHEMA)?;
+            connection.pragma_update(None, "user_version", Catalog::VERSION)?;
+        }
+        Catalog::VERSION => {}
+        other => return Err(format!("it's in another format (version {other})").into()),
+    }
+
+    let mut entries = HashMap::new();
+    {
+        let mut statement = connection.prepare(&format!("SELECT {COLUMNS} FROM images"))?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                PathBuf::from(row.get::<_, String>(0)?),
+                entry_from_row(row)?,
+            ))
+        })?;
+        for row in rows {
+            let (image, entry) = row?;
+            entries.insert(image, entry);
+        }
+    }
+    Ok((connection, entries))
+}
+
+/// A row read with [`COLUMNS`]
+fn entry_from_row(row: &Row) -> rusqlite::Result<CatalogEntry> {
+    let taken = row.get::<_, Option<i64>>(7)?.map(from_nanos);
+    let metadata = if row.get(8)? {
+        Some(PhotoMetadata {
+            taken,
+            camera: row.get(9)?,
+            lens: row.get(10)?,
+            iso: row.get(11)?,
+            aperture: row.get(12)?,
+            shutter: row.get(13)?,
+            orientation: ExifOrientation {
+                quarter_turns: row.get(14)?,
+                mirrored: row.get(15)?,
+            },
+            gps: match (row.get(16)?, row.get(17)?) {
+                (Some(latitude), Some(longitude)) => Some(GpsPosition {
+                    latitude,
+                    longitude,
+                }),
+                _ => None,
+            },
+        })
+    } else {
+        None
+    };
+    let thumbnail = match (row.get::<_, Option<String>>(18)?, row.get(19)?) {
+        (Some(file), Some(edge)) => Some((PathBuf::from(file), edge)),
+        _ => None,
+    };
+    Ok(CatalogEntry {
+        stamp: FileStamp {
+            modified: row.get::<_, Option<i64>>(1)?.map(from_nanos),
+            len: row.get::<_, i64>(2)? as u64,
+        },
+        position: row.get::<_, i64>(3)? as usize,
+        dimensions: match (row.get(4)?, row.get(5)?) {
+            (Some(width), Some(height)) => Some((width, height)),
+            _ => None,
+        },
+        taken: row.get::<_, bool>(6)?.then_some(taken),
+        metadata,
+        thumbnail,
+        rating: row.get(20)?,
+        tags: row
+            .get::<_, String>(21)?
+            .split(',')
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+/// Write `upserts` over whatever rows they had and drop the `removed` ones, all at once
+fn write_entries(
+    connection: &mut Connection,
+    upserts: &[(PathBuf, CatalogEntry)],
+    removed: &[PathBuf],
+) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    {
+        let mut upsert = transaction.prepare_cached(&format!(
+            "INSERT OR REPLACE INTO images ({COLUMNS}) VALUES \
+             (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, \
+             ?19, ?20, ?21, ?22)"
+        ))?;
+        for (image, entry) in upserts {
+            // Couldn't be read back as the same path
+            let Some(path) = image.to_str() else {
+                continue;
+            };
+            let metadata = entry.metadata.as_ref();
+            let taken = entry
+                .taken
+                .flatten()
+                .or_else(|| metadata?.taken)
+                .and_then(to_nanos);
+            let gps = metadata.and_then(|metadata| metadata.gps);
+            let thumbnail = entry.thumbnail.as_ref();
+            upsert.execute(params![
+                path,
+                entry.stamp.modified.and_then(to_nanos),
+                entry.stamp.len as i64,
+                entry.position as i64,
+                entry.dimensions.map(|(width, _)| width),
+                entry.dimensions.map(|(_, height)| height),
+                entry.taken.is_some(),
+                taken,
+                metadata.is_some(),
+                metadata.and_then(|metadata| metadata.camera.as_deref()),
+                metadata.and_then(|metadata| metadata.lens.as_deref()),
+                metadata.and_then(|metadata| metadata.iso),
+                metadata.and_then(|metadata| metadata.aperture),
+                metadata.and_then(|metadata| metadata.shutter),
+                metadata.map_or(0, |metadata| metadata.orientation.quarter_turns),
+                metadata.is_some_and(|metadata| metadata.orientation.mirrored),
+                gps.map(|gps| gps.latitude),
+                gps.map(|gps| gps.longitude),
+                thumbnail.and_then(|(file, _)| file.to_str()),
+                thumbnail.map(|(_, edge)| *edge),
+                entry.rating,
+                entry.tags.join(","),
+            ])?;
+        }
+        let mut delete = transaction.prepare_cached("DELETE FROM images WHERE path = ?1")?;
+        for image in removed {
+            if let Some(path) = image.to_str() {
+                delete.execute([path])?;
+            }
+        }
+    }
+    transaction.commit()
+}
+
+/// `None` for times before 1970, which files shouldn't have
+fn to_nanos(time: SystemTime) -> Option<i64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos()
+        .try_into()
+        .ok()
+}
+
+fn from_nanos(nanos: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(nanos.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(nanos: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(nanos)
+    }
+
+    /// Something different in every column, so reading one back from the wrong index shows up
+    fn full_entry() -> CatalogEntry {
+        let taken = at(1_600_000_000_123_456_789);
+        CatalogEntry {
+            stamp: FileStamp {
+                modified: Some(at(1_700_000_000_987_654_321)),
+                len: 12_345,
+            },
+            position: 7,
+            dimensions: Some((4000, 3000)),
+            taken: Some(Some(taken)),
+            metadata: Some(PhotoMetadata {
+                taken: Some(taken),
+                camera: Some("Nikon Z 6".to_string()),
+                lens: Some("24-70mm f/4".to_string()),
+                iso: Some(400),
+                aperture: Some(5.6),
+                shutter: Some(0.004),
+                orientation: ExifOrientation {
+                    quarter_turns: 3,
+                    mirrored: true,
+                },
+                gps: Some(GpsPosition {
+                    latitude: 51.5,
+                    longitude: -0.12,
+                }),
+            }),
+            thumbnail: Some((PathBuf::from("/cache/thumbs/a.png"), 512)),
+            rating: 4,
+            tags: vec!["beach".to_string(), "sunset".to_string()],
+        }
+    }
+
+    /// Nothing known beyond what the scan found
+    fn bare_entry() -> CatalogEntry {
+        CatalogEntry {
+            stamp: FileStamp {
+                modified: None,
+                len: 1,
+            },
+            position: 0,
+            dimensions: None,
+            taken: None,
+            metadata: None,
+            thumbnail: None,
+            rating: 0,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn entries_read_back_as_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog").join("photoview.db");
+        let (mut connection, entries) = open(&path).unwrap();
+        assert!(entries.is_empty());
+
+        let undated = CatalogEntry {
+            taken: Some(None),
+            ..bare_entry()
+        };
+        let written = vec![
+            (PathBuf::from("/photos/full.nef"), full_entry()),
+            (PathBuf::from("/photos/bare.jpg"), bare_entry()),
+            (PathBuf::from("/photos/undated.jpg"), undated),
+        ];
+        write_entries(&mut connection, &written, &[]).unwrap();
+        drop(connection);
+
+        let (_, entries) = open(&path).unwrap();
+        assert_eq!(entries, written.into_iter().collect());
+    }
+
+    #[test]
+    fn entries_are_replaced_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photoview.db");
+        let (kept, dropped) = (
+            PathBuf::from("/photos/a.jpg"),
+            PathBuf::from("/photos/b.jpg"),
+        );
+        let (mut connection, _) = open(&path).unwrap();
+        write_entries(
+            &mut connection,
+            &[
+                (kept.clone(), bare_entry()),
+                (dropped.clone(), bare_entry()),
+            ],
+            &[],
+        )
+        .unwrap();
+
+        let rated = CatalogEntry {
+            rating: 5,
+            ..bare_entry()
+        };
+        write_entries(
+            &mut connection,
+            &[(kept.clone(), rated.clone())],
+            &[dropped],
+        )
+        .unwrap();
+        drop(connection);
+
+        let (_, entries) = open(&path).unwrap();
+        assert_eq!(entries, HashMap::from([(kept, rated)]));
+    }
+}
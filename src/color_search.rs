@@ -0,0 +1,408 @@
+use bevy::{
+    ecs::spawn::SpawnIter,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+    ui::RelativeCursorPosition,
+};
+use image::DynamicImage;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{AtlasSlot, ImageLoadState, ImageTexture, Themed, ThumbnailAtlases, UiTheme};
+
+/// An image's most common colours, most common first. Worked out in the background for every
+/// quad once its texture has loaded.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct ColorPalette {
+    pub colors: Vec<Color>,
+}
+
+impl ColorPalette {
+    /// Images are shrunk to this size (either way) before counting, a palette doesn't need more
+    const SAMPLE_SIZE: u32 = 64;
+
+    /// Colours kept per image
+    const MAX_COLORS: usize = 5;
+
+    /// Colours covering less of the image than this don't count as dominant
+    const MIN_SHARE: f32 = 0.05;
+
+    /// Palettes being worked out at once, each holds a copy of its image while it is
+    const MAX_IN_FLIGHT: usize = 8;
+
+    /// Bucket every pixel by the top 3 bits of each channel, and average the biggest buckets
+    fn compute(image: DynamicImage) -> Self {
+        let sample = image.thumbnail(Self::SAMPLE_SIZE, Self::SAMPLE_SIZE);
+        let pixels = sample.to_rgb8();
+        let total = pixels.pixels().len().max(1) as f32;
+
+        let mut buckets: HashMap<u16, (u32, [u32; 3])> = HashMap::new();
+        for pixel in pixels.pixels() {
+            let [r, g, b] = pixel.0;
+            let key = ((r as u16 >> 5) << 6) | ((g as u16 >> 5) << 3) | (b as u16 >> 5);
+            let (count, sum) = buckets.entry(key).or_default();
+            *count += 1;
+            for (sum, channel) in sum.iter_mut().zip([r, g, b]) {
+                *sum += channel as u32;
+            }
+        }
+
+        let mut buckets: Vec<_> = buckets.into_values().collect();
+        buckets.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+        let colors = buckets
+            .into_iter()
+            .filter(|(count, _)| *count as f32 / total >= Self::MIN_SHARE)
+            .take(Self::MAX_COLORS)
+            .map(|(count, [r, g, b])| {
+                let average = |sum: u32| (sum / count) as u8;
+                Color::srgb_u8(average(r), average(g), average(b))
+            })
+            .collect();
+        Self { colors }
+    }
+
+    /// How far the closest of the palette's colours is from `color`, see [`color_distance`]
+    pub fn distance_to(&self, color: Color) -> Option<f32> {
+        self.colors
+            .iter()
+            .map(|own| color_distance(*own, color))
+            .min_by(f32::total_cmp)
+    }
+}
+
+/// How different two colours look, from 0 for the same colour to about 1 for opposites. Worked
+/// out in HSV, with differences in hue counting for less the greyer or darker the colours are,
+/// since a grey's hue says nothing about it.
+pub fn color_distance(a: Color, b: Color) -> f32 {
+    let (a, b) = (Hsva::from(a), Hsva::from(b));
+    let hue = (a.hue - b.hue).rem_euclid(360.0);
+    let hue = hue.min(360.0 - hue) / 180.0;
+    let chroma = (a.saturation * a.value).min(b.saturation * b.value);
+    let saturation = a.saturation - b.saturation;
+    let value = a.value - b.value;
+    ((hue * chroma).powi(2) + saturation.powi(2) + value.powi(2)).sqrt() / 3f32.sqrt()
+}
+
+/// Only show images with a dominant colour close to `target_color`: the closest colour in their
+/// [`ColorPalette`] has to be within `tolerance` (see [`color_distance`]). Images whose palette
+/// hasn't been worked out yet are let through until it has. Picking a swatch in the sidebar turns
+/// it on, picking the same one again turns it off.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ColorSearchFilter {
+    pub target_color: Color,
+    pub tolerance: f32,
+    pub active: bool,
+}
+
+impl Default for ColorSearchFilter {
+    fn default() -> Self {
+        Self {
+            target_color: Color::WHITE,
+            tolerance: 0.2,
+            active: false,
+        }
+    }
+}
+
+impl ColorSearchFilter {
+    /// The tolerance slider goes up to this
+    pub const MAX_TOLERANCE: f32 = 0.5;
+
+    pub fn matches(&self, palette: Option<&ColorPalette>) -> bool {
+        if !self.active {
+            return true;
+        }
+        match palette {
+            Some(palette) => palette
+                .distance_to(self.target_color)
+                .is_some_and(|distance| distance <= self.tolerance),
+            None => true,
+        }
+    }
+}
+
+/// The swatches to search by, in sidebar order
+const SWATCHES: [(&str, Color); 12] = [
+    ("Red", Color::srgb(0.8, 0.15, 0.15)),
+    ("Sunset orange", Color::srgb(0.95, 0.5, 0.15)),
+    ("Yellow", Color::srgb(0.95, 0.85, 0.2)),
+    ("Green", Color::srgb(0.25, 0.6, 0.2)),
+    ("Teal", Color::srgb(0.15, 0.6, 0.6)),
+    ("Sky blue", Color::srgb(0.45, 0.7, 0.95)),
+    ("Deep blue", Color::srgb(0.1, 0.2, 0.6)),
+    ("Purple", Color::srgb(0.5, 0.25, 0.65)),
+    ("Pink", Color::srgb(0.95, 0.55, 0.7)),
+    ("Brown", Color::srgb(0.45, 0.3, 0.15)),
+    ("White", Color::srgb(0.95, 0.95, 0.95)),
+    ("Black", Color::srgb(0.05, 0.05, 0.05)),
+];
+
+/// A palette being worked out in the background for the quad it's on
+#[derive(Component)]
+struct PaletteTask(Task<Option<ColorPalette>>);
+
+/// One of the sidebar's colour swatches
+#[derive(Component)]
+struct ColorSwatch(Color);
+
+/// The tolerance slider's track, click or drag along it to set [`ColorSearchFilter::tolerance`]
+#[derive(Component)]
+struct ToleranceSlider;
+
+/// The filled part of the tolerance slider
+#[derive(Component)]
+struct ToleranceFill;
+
+#[derive(Component)]
+struct ToleranceText;
+
+/// A grid of colour swatches and a tolerance slider driving the [`ColorSearchFilter`], for
+/// dropping into a sidebar
+pub fn color_search_bar() -> impl Bundle {
+    let swatches: Vec<_> = SWATCHES
+        .iter()
+        .map(|(_, color)| {
+            (
+                ColorSwatch(*color),
+                Interaction::default(),
+                Node {
+                    width: Val::Px(24.0),
+                    height: Val::Px(24.0),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                BackgroundColor(*color),
+                BorderColor(Color::NONE),
+                BorderRadius::all(Val::Px(4.0)),
+            )
+        })
+        .collect();
+
+    (
+        Node {
+            flex_direction: FlexDirection::Column,
+            margin: UiRect::top(Val::Px(8.0)),
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        children![
+            (
+                Node {
+                    width: Val::Px(6.0 * 28.0),
+                    flex_wrap: FlexWrap::Wrap,
+                    column_gap: Val::Px(4.0),
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                Children::spawn(SpawnIter(swatches.into_iter())),
+            ),
+            (
+                ToleranceText,
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                Themed::Text,
+            ),
+            (
+                ToleranceSlider,
+                Interaction::default(),
+                RelativeCursorPosition::default(),
+                Node {
+                    width: Val::Px(6.0 * 28.0),
+                    height: Val::Px(8.0),
+                    ..default()
+                },
+                BorderRadius::all(Val::Px(4.0)),
+                Themed::Background,
+                children![(
+                    ToleranceFill,
+                    Node {
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BorderRadius::all(Val::Px(4.0)),
+                    Themed::Accent,
+                )],
+            ),
+        ],
+    )
+}
+
+pub struct ColorSearchPlugin;
+
+impl Plugin for ColorSearchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ColorSearchFilter>();
+        app.add_systems(
+            Update,
+            (
+                forget_stale_palettes,
+                start_palette_tasks,
+                poll_palette_tasks,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Update,
+            (
+                swatch_system,
+                tolerance_slider_system,
+                sync_color_search_bar
+                    .run_if(resource_changed::<ColorSearchFilter>.or(resource_changed::<UiTheme>)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Images rewritten on disk get their palette worked out again once the new pixels are in
+fn forget_stale_palettes(
+    mut commands: Commands,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    quads: Query<(Entity, &ImageTexture), With<ColorPalette>>,
+) {
+    let modified: HashSet<AssetId<Image>> = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    if modified.is_empty() {
+        return;
+    }
+    for (entity, texture) in &quads {
+        if modified.contains(&texture.0.id()) {
+            commands.entity(entity).remove::<ColorPalette>();
+        }
+    }
+}
+
+/// Start on the palettes of images that have loaded since last time. Packed quads have let their
+/// texture go, so theirs is read off the atlas page instead.
+fn start_palette_tasks(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    atlases: Res<ThumbnailAtlases>,
+    in_flight: Query<(), With<PaletteTask>>,
+    quads: Query<
+        (Entity, &ImageTexture, &ImageLoadState, Option<&AtlasSlot>),
+        (Without<ColorPalette>, Without<PaletteTask>),
+    >,
+) {
+    let room = ColorPalette::MAX_IN_FLIGHT.saturating_sub(in_flight.iter().len());
+    let loaded = quads
+        .iter()
+        .filter(|(.., state, _)| **state == ImageLoadState::Loaded);
+    for (entity, texture, _, slot) in loaded.take(room) {
+        let source = match (images.get(&texture.0), slot) {
+            (Some(image), _) => Some((image.clone(), None)),
+            (None, Some(slot)) => atlases
+                .page_image(slot.page)
+                .and_then(|page| images.get(page))
+                .map(|page| (page.clone(), Some(slot.uv))),
+            (None, None) => None,
+        };
+        let Some((image, uv)) = source else {
+            continue;
+        };
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let mut image = image.try_into_dynamic().ok()?;
+            if let Some(uv) = uv {
+                let size = Vec2::new(image.width() as f32, image.height() as f32);
+                let (min, max) = ((uv.min * size).as_uvec2(), (uv.max * size).as_uvec2());
+                image = image.crop_imm(min.x, min.y, max.x - min.x, max.y - min.y);
+            }
+            Some(ColorPalette::compute(image))
+        });
+        commands.entity(entity).insert(PaletteTask(task));
+    }
+}
+
+fn poll_palette_tasks(mut commands: Commands, mut tasks: Query<(Entity, &mut PaletteTask)>) {
+    for (entity, mut task) in &mut tasks {
+        let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        let mut entity = commands.entity(entity);
+        entity.remove::<PaletteTask>();
+        match result {
+            Some(palette) => {
+                entity.insert(palette);
+            }
+            // An empty palette never matches, so this doesn't get retried every frame
+            None => {
+                log::warn!("Couldn't read pixels for a colour palette");
+                entity.insert(ColorPalette { colors: vec![] });
+            }
+        }
+    }
+}
+
+/// Clicking a swatch searches by its colour, clicking the one being searched by stops searching
+fn swatch_system(
+    swatches: Query<(&Interaction, &ColorSwatch), Changed<Interaction>>,
+    mut filter: ResMut<ColorSearchFilter>,
+) {
+    for (interaction, swatch) in &swatches {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if filter.active && filter.target_color == swatch.0 {
+            filter.active = false;
+        } else {
+            filter.target_color = swatch.0;
+            filter.active = true;
+        }
+    }
+}
+
+/// Follow the cursor along the tolerance slider while it's held down
+fn tolerance_slider_system(
+    sliders: Query<(&Interaction, &RelativeCursorPosition), With<ToleranceSlider>>,
+    mut filter: ResMut<ColorSearchFilter>,
+) {
+    for (interaction, cursor) in &sliders {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Relative to the middle of the track, so the left edge is -0.5
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        let tolerance = (position.x + 0.5).clamp(0.0, 1.0) * ColorSearchFilter::MAX_TOLERANCE;
+        if filter.tolerance != tolerance {
+            filter.tolerance = tolerance;
+        }
+    }
+}
+
+/// Outline the swatch being searched by, and size the slider to the tolerance
+fn sync_color_search_bar(
+    filter: Res<ColorSearchFilter>,
+    theme: Res<UiTheme>,
+    mut swatches: Query<(&ColorSwatch, &mut BorderColor)>,
+    mut fill: Single<&mut Node, With<ToleranceFill>>,
+    mut text: Single<&mut Text, With<ToleranceText>>,
+) {
+    for (swatch, mut border) in &mut swatches {
+        border.0 = if filter.active && filter.target_color == swatch.0 {
+            theme.selection
+        } else {
+            Color::NONE
+        };
+    }
+    fill.width = Val::Percent(filter.tolerance / ColorSearchFilter::MAX_TOLERANCE * 100.0);
+    text.0 = match SWATCHES
+        .iter()
+        .find(|(_, color)| *color == filter.target_color)
+    {
+        Some((name, _)) if filter.active => {
+            format!("{name}, tolerance {:.2}", filter.tolerance)
+        }
+        _ => format!("Colour tolerance {:.2}", filter.tolerance),
+    };
+}
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+
+use std::path::PathBuf;
+
+use crate::{
+    CurrentPage, ImageFilter, PageConfig, RescanRequested, ScanConfig, Selection, SortOrder,
+    TurnPage, WatchedDirs, filter::set_filter_query, paging::page_of, unwatch_dir, watch_dir,
+};
+
+/// Something for the viewer to do, for apps that embed it to write instead of reaching into its
+/// resources. Adding and removing directories go through the same systems as dropping a folder
+/// on the window, so they show in the status bar and can be undone.
+///
+/// `AddDir`, `Rescan` and `SetSortOrder` start a scan, and the grid changes once it's done. The
+/// rest take effect on the next frame, laying out the grid again where the images on it change.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum ViewerCommand {
+    /// Start watching a directory and scan it, see [`watch_dir`]
+    AddDir(PathBuf),
+    /// Stop watching a directory, see [`unwatch_dir`]. Its images go without a rescan.
+    RemoveDir(PathBuf),
+    /// Scan every watched directory again
+    Rescan,
+    /// Select an image, turning to its page first if it's on another. The camera follows the
+    /// selection like it does for the arrow keys. Ignored for images that haven't been scanned
+    /// yet, or that the filter hides.
+    Focus(PathBuf),
+    /// Go to a page, counting from 0. Clamped to the pages there are.
+    SetPage(usize),
+    /// Change the order images are laid out in, which rescans since the scan is what sorts them
+    SetSortOrder(SortOrder),
+    /// Replace the [`ImageFilter`] query, and what the filter bar says along with it
+    SetFilter(String),
+}
+
+pub struct ViewerCommandPlugin;
+
+impl Plugin for ViewerCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ViewerCommand>();
+        app.add_systems(
+            Update,
+            viewer_command_system.run_if(on_event::<ViewerCommand>),
+        );
+    }
+}
+
+fn viewer_command_system(
+    mut commands: Commands,
+    mut viewer_commands: EventReader<ViewerCommand>,
+    watched_dirs: Res<WatchedDirs>,
+    page_config: Res<PageConfig>,
+    filter: Res<ImageFilter>,
+    page: Res<CurrentPage>,
+    mut scan_config: ResMut<ScanConfig>,
+    mut selection: ResMut<Selection>,
+    mut rescans: EventWriter<RescanRequested>,
+    mut turns: EventWriter<TurnPage>,
+) {
+    for command in viewer_commands.read() {
+        match command {
+            ViewerCommand::AddDir(dir) => {
+                commands.run_system_cached_with(watch_dir, dir.clone());
+            }
+            ViewerCommand::RemoveDir(dir) => {
+                commands.run_system_cached_with(unwatch_dir, dir.clone());
+            }
+            ViewerCommand::Rescan => {
+                rescans.write(RescanRequested::all());
+            }
+            ViewerCommand::Focus(path) => {
+                let Some(index) = page_of(&watched_dirs, &page_config, &filter, path) else {
+                    log::warn!("Can't focus {path:?}, it isn't in the grid");
+                    continue;
+                };
+                if index != page.index {
+                    turns.write(TurnPage(index as isize - page.index as isize));
+                }
+                selection.select(path.clone());
+            }
+            ViewerCommand::SetPage(index) => {
+                turns.write(TurnPage(*index as isize - page.index as isize));
+            }
+            ViewerCommand::SetSortOrder(sort) => {
+                if scan_config.sort != *sort {
+                    scan_config.sort = *sort;
+                    rescans.write(RescanRequested::all());
+                }
+            }
+            ViewerCommand::SetFilter(query) => {
+                commands.run_system_cached_with(set_filter_query, query.clone());
+            }
+        }
+    }
+}
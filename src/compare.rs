@@ -0,0 +1,242 @@
+use bevy::{
+    input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit},
+    prelude::*,
+};
+
+use std::path::PathBuf;
+
+use crate::{Selection, StatusBar, Themed, ViewMode, text_input_inactive};
+
+/// The two images being compared and how far into them we've zoomed. Zoom and pan are shared so
+/// both sides always show the same part of their image.
+#[derive(Resource, Debug, Clone)]
+pub struct CompareView {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    /// 1 fits each image in its half of the window
+    pub zoom: f32,
+    /// Offset from centred, in logical pixels
+    pub pan: Vec2,
+}
+
+impl CompareView {
+    const MAX_ZOOM: f32 = 32.0;
+
+    fn new(left: PathBuf, right: PathBuf) -> Self {
+        Self {
+            left,
+            right,
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// The clipping box each image sits in
+#[derive(Component)]
+struct ComparePane(Side);
+
+#[derive(Component)]
+struct CompareImage(Side);
+
+#[derive(Component)]
+struct CompareLabel(Side);
+
+/// C with exactly two images selected puts them side by side. The wheel zooms both, dragging pans
+/// both, Tab swaps them over and Escape goes back to the grid.
+pub struct ComparePlugin;
+
+impl Plugin for ComparePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            compare_hotkey_system.run_if(in_state(ViewMode::Grid).and(text_input_inactive)),
+        );
+        app.add_systems(OnEnter(ViewMode::Compare), spawn_compare_view);
+        app.add_systems(OnExit(ViewMode::Compare), |mut commands: Commands| {
+            commands.remove_resource::<CompareView>();
+        });
+        app.add_systems(
+            Update,
+            (compare_input_system, update_compare_images)
+                .chain()
+                .run_if(in_state(ViewMode::Compare)),
+        );
+    }
+}
+
+fn compare_hotkey_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    mut status: ResMut<StatusBar>,
+    mut view_mode: ResMut<NextState<ViewMode>>,
+) {
+    // Ctrl+C is copying paths
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl || !keys.just_pressed(KeyCode::KeyC) {
+        return;
+    }
+    let [left, right] = selection.paths() else {
+        status.set(format!(
+            "Select exactly two images to compare ({} selected)",
+            selection.len()
+        ));
+        return;
+    };
+    commands.insert_resource(CompareView::new(left.clone(), right.clone()));
+    view_mode.set(ViewMode::Compare);
+}
+
+fn spawn_compare_view(mut commands: Commands, assets: Res<AssetServer>, view: Res<CompareView>) {
+    let pane = |side: Side, path: &PathBuf| {
+        (
+            ComparePane(side),
+            Node {
+                flex_grow: 1.0,
+                flex_basis: Val::Px(0.0),
+                height: Val::Percent(100.0),
+                overflow: Overflow::clip(),
+                ..default()
+            },
+            children![
+                (
+                    CompareImage(side),
+                    ImageNode::new(assets.load(path.to_string_lossy().to_string())),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                ),
+                (
+                    CompareLabel(side),
+                    Text::new(file_name(path)),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    Themed::Text,
+                    Node {
+                        position_type: PositionType::Absolute,
+                        bottom: Val::Px(8.0),
+                        left: Val::Px(8.0),
+                        ..default()
+                    },
+                ),
+            ],
+        )
+    };
+
+    commands.spawn((
+        StateScoped(ViewMode::Compare),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            column_gap: Val::Px(2.0),
+            ..default()
+        },
+        GlobalZIndex(40),
+        Themed::Background,
+        children![pane(Side::Left, &view.left), pane(Side::Right, &view.right)],
+    ));
+}
+
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+        .to_string()
+}
+
+fn compare_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    scroll: Res<AccumulatedMouseScroll>,
+    motion: Res<AccumulatedMouseMotion>,
+    mut view: ResMut<CompareView>,
+    mut view_mode: ResMut<NextState<ViewMode>>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        view_mode.set(ViewMode::Grid);
+        return;
+    }
+    if keys.just_pressed(KeyCode::Tab) {
+        let view = &mut *view;
+        std::mem::swap(&mut view.left, &mut view.right);
+    }
+
+    if scroll.delta.y != 0.0 {
+        let notches = match scroll.unit {
+            MouseScrollUnit::Line => scroll.delta.y,
+            MouseScrollUnit::Pixel => scroll.delta.y / 40.0,
+        };
+        let zoom = (view.zoom * 1.1_f32.powf(notches)).clamp(1.0, CompareView::MAX_ZOOM);
+        // Zoom about the middle of each pane
+        let ratio = zoom / view.zoom;
+        view.pan *= ratio;
+        view.zoom = zoom;
+    }
+    if mouse.pressed(MouseButton::Left) && motion.delta != Vec2::ZERO {
+        view.pan += motion.delta;
+    }
+}
+
+/// Size and place both images from the shared zoom and pan, fitting them to their panes first
+fn update_compare_images(
+    view: Res<CompareView>,
+    assets: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+    panes: Query<(&ComparePane, &ComputedNode)>,
+    mut image_nodes: Query<(&CompareImage, &mut ImageNode, &mut Node)>,
+    mut labels: Query<(&CompareLabel, &mut Text)>,
+) {
+    let path_for = |side: Side| match side {
+        Side::Left => &view.left,
+        Side::Right => &view.right,
+    };
+
+    // Swapping is just handing each side the other's image and name
+    if view.is_changed() {
+        for (label, mut text) in &mut labels {
+            let name = file_name(path_for(label.0));
+            if text.0 != name {
+                text.0 = name;
+            }
+        }
+    }
+
+    for (image, mut image_node, mut node) in &mut image_nodes {
+        if view.is_changed() {
+            // Already loaded, so this just hands back the same handle
+            let handle = assets.load(path_for(image.0).to_string_lossy().to_string());
+            if image_node.image != handle {
+                image_node.image = handle;
+            }
+        }
+
+        let Some(pane) = panes
+            .iter()
+            .find(|(pane, _)| pane.0 == image.0)
+            .map(|(_, computed)| computed.size() * computed.inverse_scale_factor())
+        else {
+            continue;
+        };
+        let Some(size) = images.get(&image_node.image).map(|image| image.size_f32()) else {
+            continue;
+        };
+
+        let fit = (pane.x / size.x).min(pane.y / size.y);
+        let shown = size * fit * view.zoom;
+        let corner = (pane - shown) * 0.5 + view.pan;
+        node.width = Val::Px(shown.x);
+        node.height = Val::Px(shown.y);
+        node.left = Val::Px(corner.x);
+        node.top = Val::Px(corner.y);
+    }
+}
@@ -0,0 +1,328 @@
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{
+    CurrentPage, GridConfig, ScanCompleted, ScanConfig, SceneBackground, Selection, SortOrder,
+    ThemeKind, TurnPage, UiTheme, WatchedDirs,
+};
+
+/// Everything worth remembering between launches. Every field has a default, so older files with
+/// missing fields still load, and unknown fields (from a newer version) are skipped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PhotoviewConfig {
+    pub dirs: Vec<PathBuf>,
+    /// Watched directories whose subdirectories are left out
+    pub shallow_dirs: Vec<PathBuf>,
+    pub scan_interval_secs: f32,
+    pub sort: SortOrder,
+    pub grid: GridConfig,
+    pub theme: ThemeKind,
+    /// sRGB scene background used instead of the theme's, see [`SceneBackground`]
+    pub background: Option<[f32; 3]>,
+    pub camera: Option<CameraPose>,
+    /// Logical width and height of the main window, while it's not fullscreen
+    pub window_size: Option<[f32; 2]>,
+    pub fullscreen: bool,
+    /// Keep the [`SessionState`] too, and go back to it at startup
+    pub remember_session: bool,
+    pub session: Option<SessionState>,
+}
+
+impl Default for PhotoviewConfig {
+    fn default() -> Self {
+        let scan = ScanConfig::default();
+        Self {
+            dirs: vec![],
+            shallow_dirs: vec![],
+            scan_interval_secs: scan.interval.as_secs_f32(),
+            sort: scan.sort,
+            grid: GridConfig::default(),
+            theme: ThemeKind::default(),
+            background: None,
+            camera: None,
+            window_size: None,
+            fullscreen: false,
+            remember_session: false,
+            session: None,
+        }
+    }
+}
+
+/// A camera [`Transform`] without the scale, in plain arrays so the file stays readable
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+}
+
+impl From<&Transform> for CameraPose {
+    fn from(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+        }
+    }
+}
+
+impl From<CameraPose> for Transform {
+    fn from(pose: CameraPose) -> Self {
+        Transform::from_translation(Vec3::from_array(pose.translation))
+            .with_rotation(Quat::from_array(pose.rotation).normalize())
+    }
+}
+
+/// Where the user was when they left off, with [`PhotoviewConfig::remember_session`]. The camera
+/// is kept either way, in [`PhotoviewConfig::camera`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SessionState {
+    /// Index of the [`CurrentPage`]
+    pub page: usize,
+    /// The [`Selection`], primary selection last
+    pub selection: Vec<PathBuf>,
+}
+
+impl PhotoviewConfig {
+    /// `photoview/config.ron` in the platform's config directory, if it has one
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("photoview").join("config.ron"))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    /// [`Self::load`], except a missing or broken file gets replaced with the defaults instead of
+    /// being an error
+    pub fn load_or_default(path: &Path) -> Self {
+        match Self::load(path) {
+            Ok(config) => return config,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Replacing unreadable config {path:?}: {e}"),
+        }
+
+        let config = Self::default();
+        if let Err(e) = config.save(path) {
+            log::warn!("Couldn't write default config to {path:?}: {e}");
+        }
+        config
+    }
+
+    pub fn scene_background(&self) -> SceneBackground {
+        SceneBackground(self.background.map(Color::srgb_from_array))
+    }
+
+    pub fn scan_interval(&self) -> Duration {
+        Duration::try_from_secs_f32(self.scan_interval_secs)
+            .ok()
+            .filter(|interval| !interval.is_zero())
+            .unwrap_or(ScanConfig::default().interval)
+    }
+}
+
+/// Keeps a [`PhotoviewConfig`] file in sync with the running app: the theme, background and
+/// camera are restored from it at startup (and the page and selection, if it remembers the
+/// [`SessionState`]), and it's rewritten a second after anything in it changes, and on exit. The
+/// scan and grid settings are up to whoever builds the [`crate::DirWatchingPlugin`], see
+/// [`crate::DirWatchingPlugin::from_config`].
+pub struct ConfigPersistencePlugin {
+    pub path: PathBuf,
+    pub config: PhotoviewConfig,
+}
+
+/// Where the config lives, and the last state of it we saw
+#[derive(Resource, Debug)]
+struct PersistedConfig {
+    path: PathBuf,
+    config: PhotoviewConfig,
+}
+
+impl PersistedConfig {
+    /// Quiet period before a change gets written out
+    const DEBOUNCE: Duration = Duration::from_secs(1);
+
+    fn save(&self) {
+        if let Err(e) = self.config.save(&self.path) {
+            log::warn!("Couldn't save config to {:?}: {e}", self.path);
+        }
+    }
+}
+
+impl Plugin for ConfigPersistencePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(UiTheme::from_kind(self.config.theme));
+        app.insert_resource(self.config.scene_background());
+        app.insert_resource(PersistedConfig {
+            path: self.path.clone(),
+            config: self.config.clone(),
+        });
+        app.add_systems(PostStartup, restore_camera_pose);
+        app.add_systems(Last, (track_config_changes, save_config_on_exit).chain());
+        if self.config.remember_session
+            && let Some(session) = &self.config.session
+        {
+            app.insert_resource(PendingSession {
+                session: session.clone(),
+                turned: false,
+                scanned: false,
+            });
+            app.add_systems(
+                Update,
+                restore_session_system.run_if(resource_exists::<PendingSession>),
+            );
+        }
+    }
+}
+
+/// The [`SessionState`] to go back to, until it's been restored or given up on
+#[derive(Resource, Debug)]
+struct PendingSession {
+    session: SessionState,
+    /// Whether we've asked for the saved page yet
+    turned: bool,
+    /// Whether a scan has finished since startup, so anything not found by now has gone
+    scanned: bool,
+}
+
+/// Once there are images, go to the saved page, then select the saved selection as soon as its
+/// images are there. If the primary selection doesn't turn up by the end of the first scan it's
+/// been moved or deleted, and the grid is left at the saved camera position with nothing
+/// selected.
+fn restore_session_system(
+    mut commands: Commands,
+    mut pending: ResMut<PendingSession>,
+    mut scans: EventReader<ScanCompleted>,
+    page: Res<CurrentPage>,
+    mut turns: EventWriter<TurnPage>,
+    mut selection: ResMut<Selection>,
+) {
+    if scans.read().count() > 0 {
+        pending.scanned = true;
+    }
+    if page.images().is_empty() && !pending.scanned {
+        return;
+    }
+    if !pending.turned {
+        pending.turned = true;
+        if pending.session.page != page.index {
+            turns.write(TurnPage(
+                pending.session.page as isize - page.index as isize,
+            ));
+            return;
+        }
+    }
+
+    let Some(primary) = pending.session.selection.last() else {
+        commands.remove_resource::<PendingSession>();
+        return;
+    };
+    if page.images().contains(primary) {
+        selection.0 = pending
+            .session
+            .selection
+            .iter()
+            .filter(|path| page.images().contains(path))
+            .cloned()
+            .collect();
+        commands.remove_resource::<PendingSession>();
+    } else if pending.scanned {
+        log::info!("{primary:?} from last time isn't there any more, not selecting anything");
+        commands.remove_resource::<PendingSession>();
+    }
+}
+
+fn restore_camera_pose(
+    persisted: Res<PersistedConfig>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+) {
+    if let Some(pose) = persisted.config.camera {
+        **camera = pose.into();
+    }
+}
+
+/// Fold the live settings back into the config, and save once they've settled
+fn track_config_changes(
+    mut persisted: ResMut<PersistedConfig>,
+    watched_dirs: Res<WatchedDirs>,
+    scan_config: Res<ScanConfig>,
+    grid_config: Res<GridConfig>,
+    (theme, scene_background): (Res<UiTheme>, Res<SceneBackground>),
+    (page, selection): (Res<CurrentPage>, Res<Selection>),
+    restoring: Option<Res<PendingSession>>,
+    camera: Option<Single<&Transform, With<Camera3d>>>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    time: Res<Time<Real>>,
+    mut dirty_since: Local<Option<Duration>>,
+) {
+    let mut current = persisted.config.clone();
+    let (dirs, shallow_dirs): (Vec<_>, Vec<_>) = watched_dirs
+        .watched_dirs()
+        .iter()
+        .partition(|dir| dir.recursive);
+    current.dirs = dirs.into_iter().map(|dir| dir.path.clone()).collect();
+    current.shallow_dirs = shallow_dirs
+        .into_iter()
+        .map(|dir| dir.path.clone())
+        .collect();
+    current.scan_interval_secs = scan_config.interval.as_secs_f32();
+    current.sort = scan_config.sort;
+    current.grid = grid_config.clone();
+    current.theme = theme.kind;
+    current.background = scene_background.0.map(|color| {
+        let srgba = color.to_srgba();
+        [srgba.red, srgba.green, srgba.blue]
+    });
+    // Until it's back, the page and selection are just where the app starts, not the session
+    if current.remember_session && restoring.is_none() {
+        current.session = Some(SessionState {
+            page: page.index,
+            selection: selection.paths().to_vec(),
+        });
+    }
+    if let Some(camera) = camera {
+        current.camera = Some(CameraPose::from(*camera));
+    }
+    if let Some(window) = window {
+        current.fullscreen = window.mode != WindowMode::Windowed;
+        if !current.fullscreen {
+            current.window_size = Some([window.width(), window.height()]);
+        }
+    }
+
+    if current != persisted.config {
+        persisted.config = current;
+        *dirty_since = Some(time.elapsed());
+    }
+
+    if let Some(since) = *dirty_since
+        && time.elapsed() - since >= PersistedConfig::DEBOUNCE
+    {
+        persisted.save();
+        *dirty_since = None;
+    }
+}
+
+fn save_config_on_exit(mut exits: EventReader<AppExit>, persisted: Res<PersistedConfig>) {
+    if exits.read().count() > 0 {
+        persisted.save();
+    }
+}
@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+
+use std::path::PathBuf;
+
+use crate::{
+    Action, AppState, Favorites, History, ImageMarker, Selection, StatusBar, Themed, WatchedDirs,
+    platform, request_delete,
+    selection::{copy_image_to_clipboard, copy_paths_to_clipboard},
+    set_dir_recursive, unwatch_dir,
+};
+
+/// An open right-click menu for one image. The menu UI exists exactly as long as this resource.
+#[derive(Resource, Debug, Clone)]
+pub struct ContextMenu {
+    pub target: PathBuf,
+    /// What adding to favorites and trashing act on: the whole selection when the menu was opened
+    /// on one of the selected images, otherwise just `target`
+    pub batch: Vec<PathBuf>,
+    /// Where the menu's top-left corner goes, in logical window pixels
+    pub position: Vec2,
+}
+
+/// Root node of the spawned menu
+#[derive(Component)]
+struct ContextMenuRoot;
+
+/// Attached to each menu button
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextMenuAction {
+    OpenExternally,
+    CopyPath,
+    CopyImage,
+    RevealInFileManager,
+    ToggleFavorite,
+    MoveToTrash,
+    UnwatchFolder,
+    ToggleSubfolders,
+}
+
+pub struct ContextMenuPlugin;
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(open_context_menu);
+        app.add_systems(
+            Update,
+            (
+                context_menu_action_system,
+                dismiss_context_menu_system,
+                sync_context_menu_ui,
+            )
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        );
+    }
+}
+
+/// Right-clicking a quad opens the menu for its image
+fn open_context_menu(
+    trigger: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+    quads: Query<&ImageMarker>,
+    selection: Res<Selection>,
+    state: Res<State<AppState>>,
+) {
+    if trigger.button != PointerButton::Secondary || *state.get() != AppState::Running {
+        return;
+    }
+    let Ok(marker) = quads.get(trigger.target()) else {
+        return;
+    };
+
+    let batch = if selection.contains(&marker.target) {
+        selection.paths().to_vec()
+    } else {
+        vec![marker.target.clone()]
+    };
+    commands.insert_resource(ContextMenu {
+        target: marker.target.clone(),
+        batch,
+        position: trigger.pointer_location.position,
+    });
+}
+
+/// Escape, or pressing a mouse button anywhere that isn't one of the menu items, closes the menu
+fn dismiss_context_menu_system(
+    mut commands: Commands,
+    menu: Option<Res<ContextMenu>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    items: Query<&Interaction, With<ContextMenuAction>>,
+) {
+    if menu.is_none() {
+        return;
+    }
+
+    let over_menu = items.iter().any(|i| *i != Interaction::None);
+    let clicked_outside = mouse.get_just_pressed().next().is_some() && !over_menu;
+    if keys.just_pressed(KeyCode::Escape) || clicked_outside {
+        commands.remove_resource::<ContextMenu>();
+    }
+}
+
+fn context_menu_action_system(
+    mut commands: Commands,
+    menu: Option<Res<ContextMenu>>,
+    items: Query<(&Interaction, &ContextMenuAction), Changed<Interaction>>,
+) {
+    let Some(menu) = menu else {
+        return;
+    };
+
+    for (interaction, action) in &items {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let target = menu.target.clone();
+        match action {
+            ContextMenuAction::OpenExternally => {
+                commands.run_system_cached_with(open_externally, target)
+            }
+            ContextMenuAction::CopyPath => {
+                commands.run_system_cached_with(copy_paths, menu.batch.clone())
+            }
+            ContextMenuAction::CopyImage => {
+                commands.run_system_cached_with(copy_image_to_clipboard, target)
+            }
+            ContextMenuAction::RevealInFileManager => {
+                commands.run_system_cached_with(reveal_in_file_manager, target)
+            }
+            ContextMenuAction::ToggleFavorite => {
+                commands.run_system_cached_with(toggle_favorite, (target, menu.batch.clone()))
+            }
+            ContextMenuAction::MoveToTrash => {
+                commands.run_system_cached_with(request_delete, menu.batch.clone())
+            }
+            ContextMenuAction::UnwatchFolder => {
+                commands.run_system_cached_with(unwatch_folder_of, target)
+            }
+            ContextMenuAction::ToggleSubfolders => {
+                commands.run_system_cached_with(toggle_subfolders_of, target)
+            }
+        }
+        commands.remove_resource::<ContextMenu>();
+    }
+}
+
+fn open_externally(In(target): In<PathBuf>) {
+    if let Err(e) = platform::open_path(&target) {
+        log::warn!("Couldn't open {target:?}: {e}");
+    }
+}
+
+fn copy_paths(In(batch): In<Vec<PathBuf>>, mut status: ResMut<StatusBar>) {
+    copy_paths_to_clipboard(&batch, &mut status);
+}
+
+fn reveal_in_file_manager(In(target): In<PathBuf>) {
+    if let Err(e) = platform::reveal_in_file_manager(&target) {
+        log::warn!("Couldn't reveal {target:?}: {e}");
+    }
+}
+
+/// Flip `target`, and take the rest of the batch along to wherever it ended up
+fn toggle_favorite(
+    In((target, batch)): In<(PathBuf, Vec<PathBuf>)>,
+    mut favorites: ResMut<Favorites>,
+    mut history: ResMut<History>,
+) {
+    let mut previous = vec![(target.clone(), favorites.contains(&target))];
+    previous.extend(
+        batch
+            .iter()
+            .filter(|path| **path != target)
+            .map(|path| (path.clone(), favorites.contains(path))),
+    );
+
+    let favorite = favorites.toggle(target);
+    for path in batch {
+        favorites.set(path, favorite);
+    }
+    history.record(Action::SetFavorite { favorite, previous });
+}
+
+/// Stop watching the innermost watched directory `target` is in
+fn unwatch_folder_of(
+    In(target): In<PathBuf>,
+    mut commands: Commands,
+    watched_dirs: Res<WatchedDirs>,
+    mut status: ResMut<StatusBar>,
+) {
+    match watched_dirs.dir_of(&target) {
+        Some(dir) => commands.run_system_cached_with(unwatch_dir, dir.path.clone()),
+        None => status.set(format!("{} isn't in a watched folder", target.display())),
+    }
+}
+
+/// Flip whether the watched directory `target` is in gets scanned all the way down
+fn toggle_subfolders_of(
+    In(target): In<PathBuf>,
+    mut commands: Commands,
+    watched_dirs: Res<WatchedDirs>,
+    mut status: ResMut<StatusBar>,
+) {
+    match watched_dirs.dir_of(&target) {
+        Some(dir) => {
+            commands.run_system_cached_with(set_dir_recursive, (dir.path.clone(), !dir.recursive))
+        }
+        None => status.set(format!("{} isn't in a watched folder", target.display())),
+    }
+}
+
+/// (Re)build the menu whenever the resource changes, tear it down once it's removed
+fn sync_context_menu_ui(
+    mut commands: Commands,
+    menu: Option<Res<ContextMenu>>,
+    favorites: Res<Favorites>,
+    watched_dirs: Res<WatchedDirs>,
+    existing: Query<Entity, With<ContextMenuRoot>>,
+) {
+    let rebuild = menu.as_ref().is_some_and(|menu| menu.is_changed());
+    if menu.is_some() && !rebuild {
+        return;
+    }
+
+    for entity in &existing {
+        commands.entity(entity).despawn();
+    }
+
+    let Some(menu) = menu else {
+        return;
+    };
+
+    let favorite_label = if favorites.contains(&menu.target) {
+        "Remove from favorites"
+    } else {
+        "Add to favorites"
+    };
+
+    let subfolders_label = match watched_dirs.dir_of(&menu.target) {
+        Some(dir) if !dir.recursive => "Include subfolders",
+        _ => "Leave out subfolders",
+    };
+    let copy_label = if menu.batch.len() > 1 {
+        "Copy paths"
+    } else {
+        "Copy path"
+    };
+
+    commands.spawn((
+        ContextMenuRoot,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(menu.position.x),
+            top: Val::Px(menu.position.y),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        },
+        Themed::Panel,
+        BorderRadius::all(Val::Px(4.0)),
+        GlobalZIndex(50),
+        children![
+            menu_item(ContextMenuAction::OpenExternally, "Open externally"),
+            menu_item(ContextMenuAction::CopyPath, copy_label),
+            menu_item(ContextMenuAction::CopyImage, "Copy image"),
+            menu_item(
+                ContextMenuAction::RevealInFileManager,
+                "Reveal in file manager"
+            ),
+            menu_item(ContextMenuAction::ToggleFavorite, favorite_label),
+            menu_item(ContextMenuAction::MoveToTrash, "Move to trash"),
+            menu_item(
+                ContextMenuAction::UnwatchFolder,
+                "Stop watching this folder"
+            ),
+            menu_item(ContextMenuAction::ToggleSubfolders, subfolders_label),
+        ],
+    ));
+}
+
+fn menu_item(action: ContextMenuAction, label: &str) -> impl Bundle {
+    (
+        Button,
+        action,
+        Node {
+            padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+        children![(
+            Text::new(label),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            Themed::Text,
+        )],
+    )
+}
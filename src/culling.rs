@@ -0,0 +1,124 @@
+use bevy::{asset::LoadState, prelude::*, render::view::VisibilitySystems};
+
+use crate::{
+    AtlasSlot, FilteredOut, ImageDisplayMaterial, ImageLoadState, ImageMarker, ImageTexture,
+    TextureUnloaded, load_image,
+};
+
+/// Settings for hiding quads that are off screen. Bevy's own frustum culling already skips
+/// drawing them, this goes further and hides the entities so nothing else (texture streaming,
+/// animations, ...) has to bother with them either.
+#[derive(Resource, Debug, Clone)]
+pub struct VisibilityCulling {
+    pub enabled: bool,
+    /// Only recompute every this many frames, it's a walk over every quad
+    pub interval_frames: u32,
+    /// Extra room around the view, in normalized device coordinates (1.0 = a whole half-screen),
+    /// so quads are shown a little before they scroll into view
+    pub margin: f32,
+}
+
+impl Default for VisibilityCulling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_frames: 5,
+            margin: 0.25,
+        }
+    }
+}
+
+pub struct VisibilityCullingPlugin;
+
+impl Plugin for VisibilityCullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VisibilityCulling>();
+        app.add_systems(
+            PostUpdate,
+            cull_offscreen_quads.before(VisibilitySystems::VisibilityPropagate),
+        );
+    }
+}
+
+/// Hide quads whose centre falls outside the camera's view (plus the margin), show them again
+/// once they're back in it. Uses last frame's transforms, which is plenty given the margin.
+fn cull_offscreen_quads(
+    config: Res<VisibilityCulling>,
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut quads: Query<(
+        &GlobalTransform,
+        &mut Visibility,
+        &ImageMarker,
+        &mut ImageTexture,
+        &mut ImageLoadState,
+        &MeshMaterial3d<ImageDisplayMaterial>,
+        Has<FilteredOut>,
+        Has<AtlasSlot>,
+        Has<TextureUnloaded>,
+    )>,
+    mut frame: Local<u32>,
+) {
+    if !config.enabled {
+        // Switching culling off should bring everything back, not leave it frozen
+        if config.is_changed() {
+            for (_, mut visibility, .., filtered_out, _, _) in &mut quads {
+                if !filtered_out {
+                    visibility.set_if_neq(Visibility::Inherited);
+                }
+            }
+        }
+        return;
+    }
+
+    *frame = frame.wrapping_add(1);
+    if !config.is_changed() && !frame.is_multiple_of(config.interval_frames.max(1)) {
+        return;
+    }
+
+    let (camera, camera_transform) = *camera;
+    let limit = 1.0 + config.margin;
+    for (
+        transform,
+        mut visibility,
+        marker,
+        mut texture,
+        mut load_state,
+        material,
+        filtered_out,
+        packed,
+        unloaded,
+    ) in &mut quads
+    {
+        let on_screen = camera
+            .world_to_ndc(camera_transform, transform.translation())
+            .is_some_and(|ndc| ndc.x.abs() <= limit && ndc.y.abs() <= limit && ndc.z > 0.0);
+        let wanted = if on_screen && !filtered_out {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility == wanted {
+            continue;
+        }
+        *visibility = wanted;
+
+        // Coming back into view: if the texture got evicted while we weren't looking, queue it
+        // up again. Packed ones have their thumbnail, and let the texture go on purpose. Ones
+        // that were never loaded wait for the camera to get close, see `LazyTextures`.
+        if wanted == Visibility::Inherited
+            && !packed
+            && !unloaded
+            && *load_state != ImageLoadState::Failed
+            && matches!(asset_server.load_state(&texture.0), LoadState::NotLoaded)
+            && let Ok(handle) = load_image(&asset_server, &marker.target)
+        {
+            texture.0 = handle;
+            *load_state = ImageLoadState::Pending;
+            if let Some(material) = materials.get_mut(&material.0) {
+                material.base_color_texture = Some(texture.0.clone());
+            }
+        }
+    }
+}
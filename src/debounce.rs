@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Holds back scan results that differ from what's on screen until the filesystem has been quiet
+/// for [`crate::ScanConfig::debounce`], so copying in a few hundred photos turns into one grid
+/// update instead of one per scan. New files are re-stat'ed at the end of the window and any
+/// that are still growing (mid-copy) push it back out.
+#[derive(Default, Debug)]
+pub(crate) struct ScanDebounce {
+    pending: Option<PendingImages>,
+}
+
+#[derive(Debug)]
+struct PendingImages {
+    images: Vec<PathBuf>,
+    /// Sizes of the files that weren't there before, as of the last check
+    new_file_sizes: HashMap<PathBuf, Option<u64>>,
+    last_change: Duration,
+}
+
+impl ScanDebounce {
+    /// The newest list of images we know about, applied or not
+    pub(crate) fn latest<'a>(&'a self, current: &'a [PathBuf]) -> &'a [PathBuf] {
+        self.pending
+            .as_ref()
+            .map_or(current, |pending| &pending.images)
+    }
+
+    /// Feed in a fresh scan. Returns false if it matches `current` and nothing is pending, i.e.
+    /// there's nothing to wait for.
+    pub(crate) fn offer(
+        &mut self,
+        found: Vec<PathBuf>,
+        current: &[PathBuf],
+        now: Duration,
+    ) -> bool {
+        if let Some(pending) = &self.pending
+            && pending.images == found
+        {
+            return true;
+        }
+        if self.pending.is_none() && found == current {
+            return false;
+        }
+
+        let before: HashSet<&Path> = current.iter().map(PathBuf::as_path).collect();
+        let new_file_sizes = found
+            .iter()
+            .filter(|path| !before.contains(path.as_path()))
+            .map(|path| (path.clone(), file_size(path)))
+            .collect();
+        self.pending = Some(PendingImages {
+            images: found,
+            new_file_sizes,
+            last_change: now,
+        });
+        true
+    }
+
+    /// Hand back the pending images once they've been quiet for `window`
+    pub(crate) fn poll(&mut self, now: Duration, window: Duration) -> Option<Vec<PathBuf>> {
+        let pending = self.pending.as_mut()?;
+        if now.saturating_sub(pending.last_change) < window {
+            return None;
+        }
+
+        let mut still_copying = false;
+        for (path, size) in &mut pending.new_file_sizes {
+            let current = file_size(path);
+            if current != *size {
+                *size = current;
+                still_copying = true;
+            }
+        }
+        if still_copying {
+            pending.last_change = now;
+            return None;
+        }
+
+        self.pending.take().map(|pending| pending.images)
+    }
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|meta| meta.len())
+}
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+
+use crate::{
+    AppState, CurrentPage, DirWatchingSet, GridPlacement, ImageLoadState, ImageMarker,
+    text_input_inactive,
+};
+
+/// Which debug gizmos are drawn over the grid. F4 turns them all on or off together.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugVisualization {
+    /// Outline of the cell each image on the page should be in
+    pub show_grid: bool,
+    /// Outline of where each quad actually is, which lags behind the grid while it regrids
+    pub show_bounds: bool,
+    /// A dot in each quad's corner coloured by its [`ImageLoadState`]
+    pub show_load_state: bool,
+}
+
+impl DebugVisualization {
+    pub fn any(&self) -> bool {
+        self.show_grid || self.show_bounds || self.show_load_state
+    }
+}
+
+/// Where the debug gizmos get drawn, after this frame's quads have spawned and despawned
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GizmoSystemSet;
+
+pub struct DebugGizmosPlugin;
+
+impl Plugin for DebugGizmosPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugVisualization>();
+        app.configure_sets(Update, GizmoSystemSet.after(DirWatchingSet::DespawnQuads));
+        app.add_systems(
+            Update,
+            (
+                toggle_debug_visualization_hotkey_system.run_if(text_input_inactive),
+                draw_debug_gizmos_system
+                    .run_if(|debug: Res<DebugVisualization>| debug.any())
+                    .in_set(GizmoSystemSet),
+            )
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        );
+    }
+}
+
+/// F4 shows all the debug gizmos, or hides them if any are showing
+fn toggle_debug_visualization_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut debug: ResMut<DebugVisualization>,
+) {
+    if keys.just_pressed(KeyCode::F4) {
+        let show = !debug.any();
+        *debug = DebugVisualization {
+            show_grid: show,
+            show_bounds: show,
+            show_load_state: show,
+        };
+    }
+}
+
+fn draw_debug_gizmos_system(
+    mut gizmos: Gizmos,
+    debug: Res<DebugVisualization>,
+    page: Res<CurrentPage>,
+    placement: GridPlacement,
+    quads: Query<(&GlobalTransform, &ImageLoadState), With<ImageMarker>>,
+) {
+    const GRID: Color = Color::srgb(0.3, 0.6, 1.0);
+    const BOUNDS: Color = Color::srgb(1.0, 0.3, 0.9);
+
+    let config = &placement.config;
+    if debug.show_grid {
+        let count = page.images().len();
+        for (index, image) in page.images().iter().enumerate() {
+            let cell = placement.transform(index, image, count);
+            gizmos.rect(
+                Isometry3d::new(cell.translation, cell.rotation),
+                Vec2::splat(config.spacing),
+                GRID,
+            );
+        }
+    }
+
+    let dot_radius = config.quad_size * 0.04;
+    // Inset from the quad's top-left corner, in the quad's own plane
+    let corner = Vec3::new(
+        -config.quad_size * 0.5 + dot_radius * 2.0,
+        config.quad_size * 0.5 - dot_radius * 2.0,
+        0.0,
+    );
+    for (transform, state) in &quads {
+        let (_, rotation, translation) = transform.to_scale_rotation_translation();
+        if debug.show_bounds {
+            gizmos.rect(
+                Isometry3d::new(translation, rotation),
+                Vec2::splat(config.quad_size),
+                BOUNDS,
+            );
+        }
+        if debug.show_load_state {
+            let color = match state {
+                ImageLoadState::Loaded => Color::srgb(0.2, 0.9, 0.2),
+                ImageLoadState::Pending => Color::srgb(1.0, 0.85, 0.1),
+                ImageLoadState::Failed => Color::srgb(1.0, 0.15, 0.15),
+                ImageLoadState::Evicted => Color::srgb(0.5, 0.5, 0.5),
+            };
+            gizmos.circle(
+                Isometry3d::new(translation + rotation * corner, rotation),
+                dot_radius,
+                color,
+            );
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+
+use std::path::PathBuf;
+
+use crate::{
+    Action, AppState, History, ScanErrors, Selection, Themed, WatchedDirs, platform,
+    text_input_inactive,
+};
+
+/// Whether deleting asks first. On by default, trashing is recoverable but it's still an
+/// unpleasant surprise.
+#[derive(Resource, Debug, Clone)]
+pub struct DeleteConfig {
+    pub confirm: bool,
+}
+
+impl Default for DeleteConfig {
+    fn default() -> Self {
+        Self { confirm: true }
+    }
+}
+
+/// Images waiting on the user to confirm they should go to the trash. The confirmation dialog
+/// exists exactly as long as this resource.
+#[derive(Resource, Debug, Clone)]
+pub struct PendingDelete(pub Vec<PathBuf>);
+
+#[derive(Component)]
+struct ConfirmDeleteRoot;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfirmDeleteButton {
+    Confirm,
+    Cancel,
+}
+
+pub struct DeletePlugin;
+
+impl Plugin for DeletePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DeleteConfig>();
+        app.add_systems(
+            Update,
+            (
+                delete_hotkey_system.run_if(text_input_inactive),
+                confirm_delete_system,
+                sync_confirm_delete_ui,
+            )
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        );
+    }
+}
+
+/// Ask for `paths` to be moved to the trash, going through the confirmation dialog if the
+/// [`DeleteConfig`] wants one
+pub fn request_delete(
+    In(paths): In<Vec<PathBuf>>,
+    mut commands: Commands,
+    config: Res<DeleteConfig>,
+) {
+    if paths.is_empty() {
+        return;
+    }
+    if config.confirm {
+        commands.insert_resource(PendingDelete(paths));
+    } else {
+        commands.run_system_cached_with(move_to_trash, paths);
+    }
+}
+
+/// Delete trashes the selected images
+fn delete_hotkey_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selection>,
+    pending: Option<Res<PendingDelete>>,
+) {
+    if pending.is_some() || !keys.just_pressed(KeyCode::Delete) {
+        return;
+    }
+    if !selected.is_empty() {
+        commands.run_system_cached_with(request_delete, selected.paths().to_vec());
+    }
+}
+
+/// Yes/No buttons, or Enter/Escape, answer the dialog
+fn confirm_delete_system(
+    mut commands: Commands,
+    pending: Option<Res<PendingDelete>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Query<(&Interaction, &ConfirmDeleteButton), Changed<Interaction>>,
+) {
+    let Some(pending) = pending else {
+        return;
+    };
+
+    let mut answer = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| *button);
+    if keys.just_pressed(KeyCode::Enter) {
+        answer = Some(ConfirmDeleteButton::Confirm);
+    } else if keys.just_pressed(KeyCode::Escape) {
+        answer = Some(ConfirmDeleteButton::Cancel);
+    }
+
+    let Some(answer) = answer else {
+        return;
+    };
+    if answer == ConfirmDeleteButton::Confirm {
+        commands.run_system_cached_with(move_to_trash, pending.0.clone());
+    }
+    commands.remove_resource::<PendingDelete>();
+}
+
+/// Trash the files, and remember the ones that went in the [`History`] so they can be restored
+fn move_to_trash(In(paths): In<Vec<PathBuf>>, world: &mut World) {
+    let Ok(trashed) = world.run_system_cached_with(trash_files, paths) else {
+        return;
+    };
+    if !trashed.is_empty() {
+        world
+            .resource_mut::<History>()
+            .record(Action::Trash(trashed));
+    }
+}
+
+/// Actually trash the files. Each one that goes is dropped from [`WatchedDirs`], so its quad is
+/// despawned this frame rather than waiting for the next scan to notice; failures go to the
+/// [`ScanErrors`] banner. Returns the ones that went.
+pub(crate) fn trash_files(
+    In(paths): In<Vec<PathBuf>>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut selected: ResMut<Selection>,
+    mut errors: ResMut<ScanErrors>,
+) -> Vec<PathBuf> {
+    let mut trashed = vec![];
+    for path in paths {
+        if let Err(e) = platform::move_to_trash(&path) {
+            log::warn!("Couldn't move {path:?} to the trash: {e}");
+            errors.push(format!(
+                "Couldn't move {} to the trash: {e}",
+                path.display()
+            ));
+            continue;
+        }
+
+        log::info!("Moved {path:?} to the trash");
+        // Its quad goes with the rest of the stale ones, see `DirWatchingSet::DespawnQuads`
+        watched_dirs.remove_image(&path);
+        if selected.contains(&path) {
+            selected.remove(&path);
+        }
+        trashed.push(path);
+    }
+    trashed
+}
+
+/// (Re)build the dialog whenever the pending delete changes, tear it down once it's gone
+fn sync_confirm_delete_ui(
+    mut commands: Commands,
+    pending: Option<Res<PendingDelete>>,
+    existing: Query<Entity, With<ConfirmDeleteRoot>>,
+) {
+    let rebuild = pending.as_ref().is_some_and(|pending| pending.is_changed());
+    if pending.is_some() && !rebuild {
+        return;
+    }
+
+    for entity in &existing {
+        commands.entity(entity).despawn();
+    }
+
+    let Some(pending) = pending else {
+        return;
+    };
+
+    let question = match pending.0.as_slice() {
+        [path] => {
+            let name = path
+                .file_name()
+                .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy());
+            format!("Move {name} to the trash?")
+        }
+        paths => format!("Move {} images to the trash?", paths.len()),
+    };
+
+    commands.spawn((
+        ConfirmDeleteRoot,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        GlobalZIndex(60),
+        children![(
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.0),
+                padding: UiRect::all(Val::Px(16.0)),
+                ..default()
+            },
+            Themed::Panel,
+            BorderRadius::all(Val::Px(4.0)),
+            children![
+                (Text::new(question), Themed::Text),
+                (
+                    Node {
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    children![
+                        dialog_button(ConfirmDeleteButton::Confirm, "Move to trash"),
+                        dialog_button(ConfirmDeleteButton::Cancel, "Cancel"),
+                    ],
+                ),
+            ],
+        )],
+    ));
+}
+
+fn dialog_button(button: ConfirmDeleteButton, label: &str) -> impl Bundle {
+    (
+        Button,
+        button,
+        Node {
+            padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+        children![(Text::new(label), Themed::Text)],
+    )
+}
@@ -0,0 +1,157 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{
+    ImageLoadState, ImageMarker, LastScan, ScanConfig, TextureBudget, TextureUsage, Themed,
+    UiTheme, WatchedDirs, text_input_inactive,
+};
+
+/// Whether the F3 stats overlay is showing. It's spawned once up front and only hidden while this
+/// is false, so flipping it from elsewhere works too.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsOverlay {
+    pub visible: bool,
+}
+
+/// Root of the stats overlay
+#[derive(Component)]
+struct StatsOverlayRoot;
+
+#[derive(Component)]
+struct StatsOverlayText;
+
+/// F3 shows live counts of images, quads, textures and scan timings in the top-right corner
+pub struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin::default());
+        }
+        app.init_resource::<StatsOverlay>();
+        app.add_systems(Startup, setup_stats_overlay_system);
+        app.add_systems(
+            Update,
+            (
+                toggle_overlay_hotkey_system.run_if(text_input_inactive),
+                show_stats_overlay
+                    .run_if(resource_changed::<StatsOverlay>.or(resource_changed::<UiTheme>)),
+                // Nothing to update while it's hidden
+                refresh_stats_overlay_system.run_if(|overlay: Res<StatsOverlay>| overlay.visible),
+            )
+                .chain(),
+        );
+    }
+}
+
+fn setup_stats_overlay_system(mut commands: Commands) {
+    commands.spawn((
+        StatsOverlayRoot,
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        BorderRadius::all(Val::Px(4.0)),
+        // See-through, so it's coloured in `show_stats_overlay` rather than `Themed`
+        BackgroundColor::default(),
+        GlobalZIndex(10),
+        children![(
+            StatsOverlayText,
+            Text::default(),
+            TextFont {
+                font_size: 13.0,
+                ..default()
+            },
+            Themed::Text,
+        )],
+    ));
+}
+
+/// F3 shows and hides the overlay
+fn toggle_overlay_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<StatsOverlay>,
+) {
+    if keys.just_pressed(KeyCode::F3) {
+        overlay.visible = !overlay.visible;
+    }
+}
+
+fn show_stats_overlay(
+    overlay: Res<StatsOverlay>,
+    theme: Res<UiTheme>,
+    mut root: Single<(&mut Node, &mut BackgroundColor), With<StatsOverlayRoot>>,
+) {
+    let (node, background) = &mut *root;
+    node.display = if overlay.visible {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    background.0 = theme.panel.with_alpha(0.8);
+}
+
+fn refresh_stats_overlay_system(
+    diagnostics: Res<DiagnosticsStore>,
+    time: Res<Time<Real>>,
+    images: Res<Assets<Image>>,
+    watched_dirs: Res<WatchedDirs>,
+    usage: Res<TextureUsage>,
+    budget: Res<TextureBudget>,
+    config: Res<ScanConfig>,
+    last_scan: Res<LastScan>,
+    quads: Query<(&ImageLoadState, &ViewVisibility), With<ImageMarker>>,
+    mut text: Single<&mut Text, With<StatsOverlayText>>,
+) {
+    const MIB: u64 = 1 << 20;
+
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .map_or_else(|| "-".to_string(), |fps| format!("{fps:.0}"));
+
+    let (mut pending, mut loaded, mut failed, mut evicted) = (0, 0, 0, 0);
+    let mut visible = 0;
+    for (state, visibility) in &quads {
+        match state {
+            ImageLoadState::Pending => pending += 1,
+            ImageLoadState::Loaded => loaded += 1,
+            ImageLoadState::Failed => failed += 1,
+            ImageLoadState::Evicted => evicted += 1,
+        }
+        if visibility.get() {
+            visible += 1;
+        }
+    }
+    let total = pending + loaded + failed + evicted;
+
+    let contents = format!(
+        "FPS: {fps} ({:.1} ms)\n\
+         Images: {}\n\
+         Quads: {total} ({visible} visible, {} hidden)\n\
+         Loaded: {loaded}, pending: {pending}, failed: {failed}, evicted: {evicted}\n\
+         Photo textures: {}, ~{} of {} MiB\n\
+         Image assets: {}\n\
+         Scan interval: {:.1}s\n\
+         Last scan: {} files in {} ms",
+        time.delta_secs() * 1000.0,
+        watched_dirs.image_count(),
+        total - visible,
+        usage.textures,
+        usage.total() / MIB,
+        budget.max_bytes / MIB,
+        images.len(),
+        config.interval.as_secs_f32(),
+        last_scan.files,
+        last_scan.duration.as_millis(),
+    );
+    if text.0 != contents {
+        text.0 = contents;
+    }
+}
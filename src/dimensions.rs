@@ -0,0 +1,488 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    AtlasSlot, DirWatchingSet, GridConfig, ImageMarker, ImageModified, ProbedDimensions,
+    WatchedDirs, exif::Tiff,
+};
+
+/// Reads every image's pixel size out of its header in the background, a batch at a time, and
+/// gives each quad a mesh with its image's aspect ratio once that's known. Images whose size can't
+/// be read keep a square quad. The sizes are kept in the [`crate::ScanCache`], so each file is
+/// only probed once, or again once it's been rewritten in case it changed shape.
+pub struct DimensionsPlugin;
+
+/// Sent whenever a batch of sizes has been added to [`WatchedDirs`]
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DimensionsProbed {
+    pub probed: usize,
+    /// Images still waiting their turn
+    pub remaining: usize,
+}
+
+#[derive(Resource, Default)]
+struct DimensionProbe {
+    task: Option<Task<Vec<(PathBuf, ProbedDimensions)>>>,
+    /// The image list changed (or a batch finished) since we last looked for images to probe
+    dirty: bool,
+}
+
+impl DimensionProbe {
+    /// Small enough that the first quads change shape soon after the grid appears
+    const BATCH: usize = 256;
+}
+
+/// Meshes for the aspect ratios seen so far, each fitting in a [`GridConfig::quad_size`] square.
+/// Ratios are rounded to hundredths, so there aren't many.
+#[derive(Resource, Default)]
+struct AspectMeshes {
+    quad_size: f32,
+    meshes: HashMap<u32, Handle<Mesh>>,
+}
+
+/// The rounded aspect ratio a quad's mesh was made for, see [`AspectMeshes`]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+struct QuadAspect(u32);
+
+impl QuadAspect {
+    const SQUARE: Self = Self(100);
+
+    fn of(size: Option<(u32, u32)>) -> Self {
+        match size {
+            Some((width, height)) => Self(((width as f32 / height as f32) * 100.0).round() as u32),
+            None => Self::SQUARE,
+        }
+    }
+
+    /// Width and height of a quad with this aspect ratio that just fits in a `quad_size` square
+    fn fit(self, quad_size: f32) -> Vec2 {
+        let aspect = (self.0.max(1) as f32) / 100.0;
+        if aspect >= 1.0 {
+            Vec2::new(quad_size, quad_size / aspect)
+        } else {
+            Vec2::new(quad_size * aspect, quad_size)
+        }
+    }
+}
+
+impl Plugin for DimensionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DimensionProbe>();
+        app.init_resource::<AspectMeshes>();
+        app.add_event::<DimensionsProbed>();
+        app.add_systems(
+            Update,
+            (
+                start_dimension_probe,
+                poll_dimension_probe,
+                fit_quads_to_dimensions,
+            )
+                .chain()
+                .after(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+fn start_dimension_probe(
+    watched_dirs: Res<WatchedDirs>,
+    mut image_modified: EventReader<ImageModified>,
+    mut probe: ResMut<DimensionProbe>,
+) {
+    // Images seen changing by the file watcher don't touch the image list, so they don't show
+    // up as a change to it
+    if watched_dirs.is_changed() || image_modified.read().count() > 0 {
+        probe.dirty = true;
+    }
+    if !probe.dirty || probe.task.is_some() {
+        return;
+    }
+
+    probe.dirty = false;
+    let batch: Vec<_> = watched_dirs
+        .unprobed()
+        .take(DimensionProbe::BATCH)
+        .map(|path| {
+            (
+                path.clone(),
+                watched_dirs.stamp(path).and_then(|stamp| stamp.modified),
+            )
+        })
+        .collect();
+    if batch.is_empty() {
+        return;
+    }
+    probe.task = Some(IoTaskPool::get().spawn(async move {
+        batch
+            .into_iter()
+            .map(|(path, modified)| {
+                let size = probe_dimensions(&path);
+                (path, ProbedDimensions { modified, size })
+            })
+            .collect()
+    }));
+}
+
+fn poll_dimension_probe(
+    mut probe: ResMut<DimensionProbe>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut probed: EventWriter<DimensionsProbed>,
+) {
+    let Some(task) = &mut probe.task else {
+        return;
+    };
+    let Some(batch) = block_on(future::poll_once(task)) else {
+        return;
+    };
+
+    probe.task = None;
+    // There may well be more where these came from
+    probe.dirty = true;
+    let count = batch.len();
+    // The image list is the same as it was, so nothing watching that needs waking
+    let watched_dirs = watched_dirs.bypass_change_detection();
+    watched_dirs.add_dimensions(batch);
+    let remaining = watched_dirs.unprobed().count();
+    log::debug!("Probed the sizes of {count} images, {remaining} to go");
+    probed.write(DimensionsProbed {
+        probed: count,
+        remaining,
+    });
+}
+
+/// Give quads the shape of their image, once its size is known
+fn fit_quads_to_dimensions(
+    mut probed: EventReader<DimensionsProbed>,
+    watched_dirs: Res<WatchedDirs>,
+    grid_config: Res<GridConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut aspect_meshes: ResMut<AspectMeshes>,
+    mut commands: Commands,
+    added: Query<(), Added<ImageMarker>>,
+    // Packed quads have meshes of their own, already the shape of their thumbnail
+    mut quads: Query<(Entity, &ImageMarker, &mut Mesh3d, Option<&QuadAspect>), Without<AtlasSlot>>,
+) {
+    if probed.read().count() == 0 && added.is_empty() {
+        return;
+    }
+    if aspect_meshes.quad_size != grid_config.quad_size {
+        aspect_meshes.quad_size = grid_config.quad_size;
+        aspect_meshes.meshes.clear();
+    }
+
+    for (entity, marker, mut mesh, current) in &mut quads {
+        let aspect = QuadAspect::of(watched_dirs.dimensions(&marker.target));
+        if current.copied().unwrap_or(QuadAspect::SQUARE) == aspect {
+            continue;
+        }
+        let handle = aspect_meshes
+            .meshes
+            .entry(aspect.0)
+            .or_insert_with(|| meshes.add(Rectangle::from_size(aspect.fit(grid_config.quad_size))));
+        mesh.0 = handle.clone();
+        commands.entity(entity).insert(aspect);
+    }
+}
+
+/// Width and height of the quad for an image of this pixel size, just fitting in a `quad_size`
+/// square. Square for images whose size isn't known.
+pub(crate) fn fitted_quad_size(size: Option<(u32, u32)>, quad_size: f32) -> Vec2 {
+    QuadAspect::of(size).fit(quad_size)
+}
+
+/// The width and height of the image at `path`, read from its header without decoding it. Knows
+/// JPEG, PNG, GIF, BMP, WebP and TIFF, and the previews in RAW files with the `raw` feature (and
+/// HEIC, through libheif, with the `heic` one, and videos, through ffmpeg, with `video`).
+/// Anything else (or a header that doesn't make sense) is `None`.
+pub fn probe_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    let mut header = [0; 30];
+    let len = read_up_to(&mut file, &mut header).ok()?;
+    let header = &header[..len];
+
+    let size = match header {
+        [0xFF, 0xD8, rest @ ..] => jpeg_dimensions(rest.chain(file)).ok()??,
+        [0x89, b'P', b'N', b'G', ..] => (be32(header, 16)?, be32(header, 20)?),
+        [b'G', b'I', b'F', b'8', ..] => (le16(header, 6)?, le16(header, 8)?),
+        [b'B', b'M', ..] => bmp_dimensions(header)?,
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => webp_dimensions(header)?,
+        [b'I', b'I', 42, 0, ..] | [b'M', b'M', 0, 42, ..] => tiff_dimensions(path)?,
+        #[cfg(feature = "heic")]
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] if crate::heic::is_heic(path) => {
+            crate::heic::heic_dimensions(path)?
+        }
+        #[cfg(feature = "video")]
+        _ if crate::video::is_video(path) => crate::video::video_dimensions(path)?,
+        _ => return None,
+    };
+    (size.0 > 0 && size.1 > 0).then_some(size)
+}
+
+/// Fill as much of `buffer` as the file has
+fn read_up_to(file: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+fn be32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?).into())
+}
+
+fn le24(bytes: &[u8], at: usize) -> Option<u32> {
+    let bytes = bytes.get(at..at + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn le32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Walk the segments after the start of image marker up to the frame header, skipping over the
+/// (possibly big) metadata segments without keeping them
+fn jpeg_dimensions(mut stream: impl Read) -> io::Result<Option<(u32, u32)>> {
+    let mut marker = [0; 2];
+    loop {
+        stream.read_exact(&mut marker)?;
+        if marker[0] != 0xFF {
+            return Ok(None);
+        }
+        // Any number of 0xFF can pad out the gap before a marker
+        while marker[1] == 0xFF {
+            stream.read_exact(&mut marker[1..])?;
+        }
+        match marker[1] {
+            // Markers without a length
+            0x01 | 0xD0..=0xD8 => continue,
+            // The image data starts, or the file ends, without a frame header
+            0xD9 | 0xDA => return Ok(None),
+            _ => {}
+        }
+
+        let mut length = [0; 2];
+        stream.read_exact(&mut length)?;
+        let length = u16::from_be_bytes(length).saturating_sub(2);
+        // Start of frame, any kind but the ones that share the range without being one
+        if matches!(marker[1], 0xC0..=0xCF) && !matches!(marker[1], 0xC4 | 0xC8 | 0xCC) {
+            // Sample precision, then height and width
+            let mut frame = [0; 5];
+            stream.read_exact(&mut frame)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]);
+            let width = u16::from_be_bytes([frame[3], frame[4]]);
+            return Ok(Some((width.into(), height.into())));
+        }
+        io::copy(&mut (&mut stream).take(length.into()), &mut io::sink())?;
+    }
+}
+
+fn bmp_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    // The old OS/2 header has 16-bit sizes, everything since has signed 32-bit ones, with a
+    // negative height for rows stored top to bottom
+    if le32(header, 14)? == 12 {
+        return Some((le16(header, 18)?, le16(header, 20)?));
+    }
+    let width = le32(header, 18)? as i32;
+    let height = le32(header, 22)? as i32;
+    Some((width.unsigned_abs(), height.unsigned_abs()))
+}
+
+fn webp_dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    match header.get(12..16)? {
+        // Lossy, 14 bits each after the frame tag and start code
+        b"VP8 " => Some((le16(header, 26)? & 0x3FFF, le16(header, 28)? & 0x3FFF)),
+        // Lossless, 14 bits each minus one, packed after the signature byte
+        b"VP8L" => {
+            let bits = le32(header, 21)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        // Extended, 24 bits each minus one
+        b"VP8X" => Some((le24(header, 24)? + 1, le24(header, 27)? + 1)),
+        _ => None,
+    }
+}
+
+/// TIFFs can keep their first IFD anywhere in the file, so this one can't stop at the header
+fn tiff_dimensions(path: &Path) -> Option<(u32, u32)> {
+    const IMAGE_WIDTH: u16 = 0x0100;
+    const IMAGE_LENGTH: u16 = 0x0101;
+    // Plenty for any IFD that's near the start, which is where most writers put it
+    const LIMIT: u64 = 1 << 20;
+
+    let mut data = vec![];
+    File::open(path)
+        .ok()?
+        .take(LIMIT)
+        .read_to_end(&mut data)
+        .ok()?;
+    let tiff = Tiff::new(&data)?;
+    // The first IFD of a RAW file is usually a tiny thumbnail, not always the same shape
+    #[cfg(feature = "raw")]
+    if crate::raw::is_raw(path) {
+        return crate::raw::preview_size(&tiff, path);
+    }
+    let ifd = tiff.first_ifd()?;
+    let width = tiff.number(tiff.entry(ifd, IMAGE_WIDTH)?)?;
+    let height = tiff.number(tiff.entry(ifd, IMAGE_LENGTH)?)?;
+    Some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    fn probe(bytes: &[u8]) -> Option<(u32, u32)> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(bytes).unwrap();
+        probe_dimensions(file.path())
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(width.to_be_bytes());
+        png.extend(height.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0]);
+        png
+    }
+
+    /// An APP0 and a Huffman table (which sits in the start of frame range without being one)
+    /// ahead of a baseline frame header
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend([0xFF, 0xE0, 0, 16]);
+        jpeg.extend(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        jpeg.extend([0xFF, 0xC4, 0, 5, 0, 0, 0]);
+        jpeg.extend([0xFF, 0xC0, 0, 17, 8]);
+        jpeg.extend(height.to_be_bytes());
+        jpeg.extend(width.to_be_bytes());
+        jpeg.extend([3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        jpeg.extend([0xFF, 0xD9]);
+        jpeg
+    }
+
+    fn webp(chunk: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut webp = b"RIFF\0\0\0\0WEBP".to_vec();
+        webp.extend(chunk);
+        webp.extend((body.len() as u32).to_le_bytes());
+        webp.extend(body);
+        webp
+    }
+
+    /// A little-endian TIFF whose only IFD holds the width as a SHORT and the height as a LONG
+    fn tiff(width: u16, height: u32) -> Vec<u8> {
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        tiff.extend(2u16.to_le_bytes());
+        tiff.extend([0x00, 0x01, 3, 0, 1, 0, 0, 0]);
+        tiff.extend(width.to_le_bytes());
+        tiff.extend([0, 0]);
+        tiff.extend([0x01, 0x01, 4, 0, 1, 0, 0, 0]);
+        tiff.extend(height.to_le_bytes());
+        tiff.extend(0u32.to_le_bytes());
+        tiff
+    }
+
+    #[test]
+    fn reads_png() {
+        assert_eq!(probe(&png(640, 480)), Some((640, 480)));
+    }
+
+    #[test]
+    fn reads_jpeg_past_other_segments() {
+        assert_eq!(probe(&jpeg(4000, 3000)), Some((4000, 3000)));
+    }
+
+    #[test]
+    fn reads_gif() {
+        assert_eq!(probe(b"GIF89a\x40\x01\xf0\x00\0\0\0"), Some((320, 240)));
+    }
+
+    #[test]
+    fn reads_bmp() {
+        let mut bmp = b"BM".to_vec();
+        bmp.extend([0; 12]);
+        bmp.extend(40u32.to_le_bytes());
+        bmp.extend(800i32.to_le_bytes());
+        // Negative, rows run top to bottom
+        bmp.extend((-600i32).to_le_bytes());
+        bmp.extend([1, 0, 24, 0]);
+        assert_eq!(probe(&bmp), Some((800, 600)));
+
+        let mut os2 = b"BM".to_vec();
+        os2.extend([0; 12]);
+        os2.extend(12u32.to_le_bytes());
+        os2.extend(64u16.to_le_bytes());
+        os2.extend(32u16.to_le_bytes());
+        os2.extend([1, 0, 24, 0]);
+        assert_eq!(probe(&os2), Some((64, 32)));
+    }
+
+    #[test]
+    fn reads_every_kind_of_webp() {
+        let mut lossy = vec![0x50, 0x09, 0x00, 0x9D, 0x01, 0x2A];
+        lossy.extend(1024u16.to_le_bytes());
+        lossy.extend(768u16.to_le_bytes());
+        assert_eq!(probe(&webp(b"VP8 ", &lossy)), Some((1024, 768)));
+
+        let bits: u32 = (300 - 1) | ((200 - 1) << 14);
+        let mut lossless = vec![0x2F];
+        lossless.extend(bits.to_le_bytes());
+        assert_eq!(probe(&webp(b"VP8L", &lossless)), Some((300, 200)));
+
+        let mut extended = vec![0; 4];
+        extended.extend(&(5000u32 - 1).to_le_bytes()[..3]);
+        extended.extend(&(70000u32 - 1).to_le_bytes()[..3]);
+        assert_eq!(probe(&webp(b"VP8X", &extended)), Some((5000, 70000)));
+    }
+
+    #[test]
+    fn reads_tiff() {
+        assert_eq!(probe(&tiff(1200, 900)), Some((1200, 900)));
+    }
+
+    #[test]
+    fn truncated_headers_are_none() {
+        // Each cut off halfway through the height or width
+        assert_eq!(probe(&png(640, 480)[..22]), None);
+        assert_eq!(probe(&jpeg(4000, 3000)[..35]), None);
+        assert_eq!(probe(&tiff(1200, 900)[..32]), None);
+        assert_eq!(probe(b"GIF89a\x40"), None);
+        assert_eq!(probe(&webp(b"VP8X", &[0; 4])), None);
+        assert_eq!(probe(b""), None);
+    }
+
+    #[test]
+    fn zero_sizes_and_unknown_formats_are_none() {
+        assert_eq!(probe(&png(0, 480)), None);
+        assert_eq!(probe(b"not an image at all, just some text"), None);
+    }
+}
@@ -0,0 +1,243 @@
+use bevy::prelude::*;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    CurrentPage, DirWatchingSet, GridConfig, GridLayout, GridPlacement, Themed, WatchedDirs,
+    paging::update_current_page,
+};
+
+/// Where each image on the [`CurrentPage`] goes in the [`GridLayout::GroupByDirectory`] layout,
+/// and the groups they fall into. Empty with any other layout.
+///
+/// Images are grouped by the watched directory they're in (the deepest one, when watched
+/// directories are nested), in the order the directories are watched, then by the archive they
+/// came out of. Images from anywhere else, a playlist say, go in one last group. Each group is a
+/// square grid of its own, and the grids stand side by side with [`GridConfig::group_gap`]
+/// between them, lined up along their top rows.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct DirectoryGroups {
+    cells: HashMap<PathBuf, Vec2>,
+    groups: Vec<DirectoryGroup>,
+    /// Width and height of the whole layout, gaps included
+    extent: Option<Vec2>,
+}
+
+/// The images from one watched directory or archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryGroup {
+    /// The directory or archive, `None` for the images that aren't from one
+    pub dir: Option<PathBuf>,
+    /// Cell of the group's top-left image, see [`crate::calculate_grid_position_2d`]
+    pub first: Vec2,
+    pub len: usize,
+}
+
+impl DirectoryGroups {
+    /// Lay out `images`, grouped by which of `dirs` `dir_of` says each is from
+    pub fn new(
+        images: &[PathBuf],
+        dirs: &[PathBuf],
+        dir_of: impl Fn(&Path) -> Option<PathBuf>,
+        config: &GridConfig,
+    ) -> Self {
+        let mut grouped: Vec<(Option<PathBuf>, Vec<&PathBuf>)> =
+            dirs.iter().map(|dir| (Some(dir.clone()), vec![])).collect();
+        let mut elsewhere = vec![];
+        for path in images {
+            let group = dir_of(path).and_then(|dir| {
+                grouped
+                    .iter_mut()
+                    .find(|(group, _)| *group == Some(dir.clone()))
+            });
+            match group {
+                Some((_, paths)) => paths.push(path),
+                None => elsewhere.push(path),
+            }
+        }
+        grouped.push((None, elsewhere));
+        grouped.retain(|(_, paths)| !paths.is_empty());
+
+        // (columns, rows) of each group's grid
+        let sizes: Vec<(usize, usize)> = grouped
+            .iter()
+            .map(|(_, paths)| {
+                let columns = (paths.len() as f32).sqrt().ceil() as usize;
+                (columns, paths.len().div_ceil(columns))
+            })
+            .collect();
+        let spacing = config.spacing;
+        let width = sizes
+            .iter()
+            .map(|&(columns, _)| columns as f32 * spacing)
+            .sum::<f32>()
+            + config.group_gap * sizes.len().saturating_sub(1) as f32;
+        let height = sizes
+            .iter()
+            .map(|&(_, rows)| rows as f32 * spacing)
+            .fold(0.0, f32::max);
+
+        let mut layout = Self {
+            extent: Some(Vec2::new(width, height)),
+            ..default()
+        };
+        // Centred on the origin like the other layouts, cells being the middle of each quad
+        let top = (height - spacing) * 0.5;
+        let mut left = (spacing - width) * 0.5;
+        for ((dir, paths), (columns, _)) in grouped.into_iter().zip(sizes) {
+            let cell = |index: usize| {
+                Vec2::new(
+                    left + (index % columns) as f32 * spacing,
+                    top - (index / columns) as f32 * spacing,
+                )
+            };
+            layout.groups.push(DirectoryGroup {
+                dir,
+                first: cell(0),
+                len: paths.len(),
+            });
+            for (index, path) in paths.into_iter().enumerate() {
+                layout.cells.insert(path.clone(), cell(index));
+            }
+            left += columns as f32 * spacing + config.group_gap;
+        }
+        layout
+    }
+
+    /// Where `image` goes, `None` if it's not laid out
+    pub fn cell(&self, image: &Path) -> Option<Vec2> {
+        self.cells.get(image).copied()
+    }
+
+    pub fn groups(&self) -> &[DirectoryGroup] {
+        &self.groups
+    }
+
+    /// How wide and tall the layout is. `None` with any other layout.
+    pub fn extent(&self) -> Option<Vec2> {
+        self.extent
+    }
+}
+
+/// The directory over a [`DirectoryGroup`], floating just above its top-left corner
+#[derive(Component)]
+struct DirectoryGroupLabel {
+    /// Cell of the label's top-left corner
+    corner: Vec2,
+}
+
+pub struct DirectoryGroupsPlugin;
+
+impl Plugin for DirectoryGroupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DirectoryGroups>();
+        app.add_systems(
+            PreUpdate,
+            update_directory_groups
+                .run_if(
+                    resource_changed::<CurrentPage>
+                        .or(resource_changed::<GridConfig>)
+                        .or(resource_changed::<WatchedDirs>),
+                )
+                .after(update_current_page)
+                .before(DirWatchingSet::SpawnQuads),
+        );
+        app.add_systems(
+            Update,
+            (
+                sync_directory_group_labels.run_if(resource_changed::<DirectoryGroups>),
+                position_directory_group_labels,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn update_directory_groups(
+    page: Res<CurrentPage>,
+    grid_config: Res<GridConfig>,
+    watched_dirs: Res<WatchedDirs>,
+    mut groups: ResMut<DirectoryGroups>,
+) {
+    if grid_config.layout != GridLayout::GroupByDirectory {
+        groups.set_if_neq(DirectoryGroups::default());
+        return;
+    }
+    let dirs: Vec<PathBuf> = watched_dirs
+        .watched_dirs()
+        .iter()
+        .map(|dir| dir.path.clone())
+        .chain(watched_dirs.archives().map(|archive| archive.0.clone()))
+        .collect();
+    groups.set_if_neq(DirectoryGroups::new(
+        page.images(),
+        &dirs,
+        |path| {
+            // Extracted images are in a temporary directory, which is never watched
+            match watched_dirs.archive_of(path) {
+                Some(archive) => Some(archive.0.clone()),
+                None => watched_dirs.dir_of(path).map(|dir| dir.path.clone()),
+            }
+        },
+        &grid_config,
+    ));
+}
+
+fn sync_directory_group_labels(
+    mut commands: Commands,
+    groups: Res<DirectoryGroups>,
+    grid_config: Res<GridConfig>,
+    labels: Query<Entity, With<DirectoryGroupLabel>>,
+) {
+    for label in &labels {
+        commands.entity(label).despawn();
+    }
+    let half = grid_config.quad_size * 0.5;
+    for group in groups.groups() {
+        let name = match &group.dir {
+            Some(dir) => dir.display().to_string(),
+            None => "Elsewhere".to_string(),
+        };
+        let corner = group.first + Vec2::new(-half, grid_config.spacing - half);
+        commands.spawn((
+            DirectoryGroupLabel { corner },
+            Node {
+                position_type: PositionType::Absolute,
+                padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                display: Display::None,
+                ..default()
+            },
+            BorderRadius::all(Val::Px(3.0)),
+            Themed::Panel,
+            children![(
+                Text::new(format!("{name} ({})", group.len)),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                Themed::Text,
+            )],
+        ));
+    }
+}
+
+/// Pin the labels to their place in the world, hiding the ones that aren't on screen
+fn position_directory_group_labels(
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    placement: GridPlacement,
+    mut labels: Query<(&DirectoryGroupLabel, &mut Node)>,
+) {
+    let (camera, camera_transform) = *camera;
+    for (label, mut node) in &mut labels {
+        let corner = placement.place(label.corner).translation;
+        match camera.world_to_viewport(camera_transform, corner) {
+            Ok(position) => {
+                node.display = Display::Flex;
+                node.left = Val::Px(position.x);
+                node.top = Val::Px(position.y);
+            }
+            Err(_) => node.display = Display::None,
+        }
+    }
+}
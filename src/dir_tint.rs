@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::{AtlasSlot, ImageDisplayMaterial, ImageMarker, ThumbnailAtlases, WatchedDirs};
+
+/// A colour per watched directory, so images from different places can be told apart at a
+/// glance. The hue comes from a hash of the path, so a directory keeps its colour between runs.
+#[derive(Resource, Default, Debug, Clone, PartialEq)]
+pub struct DirectoryColorMap(pub HashMap<PathBuf, Color>);
+
+impl DirectoryColorMap {
+    /// Light enough that the image is still easy to make out underneath
+    const SATURATION: f32 = 0.5;
+    const LIGHTNESS: f32 = 0.85;
+
+    pub fn color_for(dir: &Path) -> Color {
+        let mut hasher = DefaultHasher::new();
+        dir.hash(&mut hasher);
+        let hue = (hasher.finish() % 360) as f32;
+        Color::hsl(hue, Self::SATURATION, Self::LIGHTNESS)
+    }
+
+    /// The colour of whichever watched directory `image` is in, the innermost if they nest
+    pub fn color_of(&self, image: &Path) -> Option<Color> {
+        self.0
+            .iter()
+            .filter(|(dir, _)| image.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map(|(_, color)| *color)
+    }
+}
+
+/// Whether quads get tinted with their directory's colour
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShowDirectoryTint(pub bool);
+
+pub struct DirectoryTintPlugin;
+
+impl Plugin for DirectoryTintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DirectoryColorMap>();
+        app.init_resource::<ShowDirectoryTint>();
+        app.add_systems(
+            Update,
+            (
+                update_directory_colors.run_if(resource_changed::<WatchedDirs>),
+                apply_directory_tint,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn update_directory_colors(watched_dirs: Res<WatchedDirs>, mut colors: ResMut<DirectoryColorMap>) {
+    let current = DirectoryColorMap(
+        watched_dirs
+            .watched_dirs()
+            .iter()
+            .map(|dir| (dir.path.clone(), DirectoryColorMap::color_for(&dir.path)))
+            .collect(),
+    );
+    colors.set_if_neq(current);
+}
+
+/// Tint new quads, and every quad when the colours or the toggle change
+fn apply_directory_tint(
+    show: Res<ShowDirectoryTint>,
+    colors: Res<DirectoryColorMap>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut atlases: ResMut<ThumbnailAtlases>,
+    mut quads: Query<(
+        Ref<ImageMarker>,
+        Option<&mut MeshMaterial3d<ImageDisplayMaterial>>,
+        Option<&AtlasSlot>,
+        Option<&mut Sprite>,
+    )>,
+) {
+    let everything = show.is_changed() || colors.is_changed();
+    for (marker, material, slot, sprite) in &mut quads {
+        if !everything && !marker.is_added() {
+            continue;
+        }
+        let tint = colors
+            .color_of(&marker.target)
+            .filter(|_| show.0)
+            .unwrap_or(Color::WHITE);
+
+        if let Some(mut material) = material {
+            atlases.restyle(&mut material, slot, &mut materials, |material| {
+                material.tint = tint.into();
+            });
+        }
+        if let Some(mut sprite) = sprite {
+            sprite.color = tint;
+        }
+    }
+}
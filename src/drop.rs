@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+
+use std::path::{Path, PathBuf};
+
+use crate::{ScanConfig, StatusBar, Themed, UiTheme, WatchedDirs, normalize_path, watch_dir};
+
+/// Watch folders dropped onto the window. An image dropped on its own brings in the folder it's
+/// in, or with [`Self::single_images`] just that image. Anything else dropped is ignored.
+#[derive(Default)]
+pub struct FileDropPlugin {
+    /// Add dropped images by themselves rather than watching their folder
+    pub single_images: bool,
+}
+
+/// Whether images dropped on their own come in by themselves, see [`FileDropPlugin`]
+#[derive(Resource, Debug, Clone, Copy, Default)]
+struct DropConfig {
+    single_images: bool,
+}
+
+/// Outlines the window while something's being dragged over it
+#[derive(Component)]
+struct DropIndicator;
+
+impl Plugin for FileDropPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DropConfig {
+            single_images: self.single_images,
+        });
+        app.add_systems(Startup, spawn_drop_indicator);
+        app.add_systems(Update, (handle_dropped_files, show_drop_indicator));
+    }
+}
+
+fn spawn_drop_indicator(mut commands: Commands) {
+    commands.spawn((
+        DropIndicator,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            border: UiRect::all(Val::Px(3.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::End,
+            padding: UiRect::bottom(Val::Px(48.0)),
+            display: Display::None,
+            ..default()
+        },
+        BorderColor(Color::NONE),
+        // Over everything else, but without getting in the way of the drop
+        GlobalZIndex(i32::MAX - 1),
+        Pickable::IGNORE,
+        children![(
+            Node {
+                padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+                ..default()
+            },
+            BorderRadius::all(Val::Px(4.0)),
+            Themed::Panel,
+            Pickable::IGNORE,
+            children![(Text::new("Drop to watch"), Themed::Text, Pickable::IGNORE)],
+        )],
+    ));
+}
+
+fn show_drop_indicator(
+    mut events: EventReader<FileDragAndDrop>,
+    theme: Res<UiTheme>,
+    mut indicator: Single<(&mut Node, &mut BorderColor), With<DropIndicator>>,
+) {
+    let Some(last) = events.read().last() else {
+        return;
+    };
+    let (node, border) = &mut *indicator;
+    match last {
+        FileDragAndDrop::HoveredFile { .. } => {
+            node.display = Display::Flex;
+            border.0 = theme.accent;
+        }
+        FileDragAndDrop::DroppedFile { .. } | FileDragAndDrop::HoveredFileCanceled { .. } => {
+            node.display = Display::None;
+        }
+    }
+}
+
+/// The watched directory that already takes in `dir`: itself, or one it's inside that's scanned
+/// recursively
+fn already_watching<'a>(watched_dirs: &'a WatchedDirs, dir: &Path) -> Option<&'a Path> {
+    watched_dirs
+        .watched_dirs()
+        .iter()
+        .find(|watched| {
+            let watched_path = normalize_path(&watched.path);
+            dir == watched_path || (watched.recursive && dir.starts_with(&watched_path))
+        })
+        .map(|watched| watched.path.as_path())
+}
+
+fn handle_dropped_files(
+    mut commands: Commands,
+    mut events: EventReader<FileDragAndDrop>,
+    drop_config: Res<DropConfig>,
+    scan_config: Res<ScanConfig>,
+    watched_dirs: Res<WatchedDirs>,
+    mut status: ResMut<StatusBar>,
+) {
+    let dropped: Vec<PathBuf> = events
+        .read()
+        .filter_map(|event| match event {
+            FileDragAndDrop::DroppedFile { path_buf, .. } => Some(normalize_path(path_buf)),
+            _ => None,
+        })
+        .collect();
+    // Several at once all arrive in the same frame, so check them against each other too
+    let mut accepted: Vec<PathBuf> = vec![];
+    for path in dropped {
+        let dir = if path.is_dir() {
+            path
+        } else if scan_config.is_supported_image(&path) {
+            if drop_config.single_images {
+                commands.run_system_cached_with(add_dropped_image, path);
+                continue;
+            }
+            match path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => continue,
+            }
+        } else {
+            log::debug!("Ignoring dropped file {path:?}, it's not an image or a folder");
+            continue;
+        };
+
+        if let Some(watched) = already_watching(&watched_dirs, &dir) {
+            status.set(if watched == dir.as_path() {
+                format!("Already watching {}", dir.display())
+            } else {
+                format!(
+                    "{} is already watched as part of {}",
+                    dir.display(),
+                    watched.display()
+                )
+            });
+            continue;
+        }
+        if accepted.contains(&dir) {
+            continue;
+        }
+        commands.run_system_cached_with(watch_dir, dir.clone());
+        accepted.push(dir);
+    }
+}
+
+fn add_dropped_image(
+    In(path): In<PathBuf>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut status: ResMut<StatusBar>,
+) {
+    if watched_dirs.add_image(&path) {
+        status.set(format!("Added {}", path.display()));
+    } else {
+        status.set(format!("Already showing {}", path.display()));
+    }
+}
@@ -0,0 +1,382 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{
+    AppState, DirWatchingSet, GridConfig, ImageMarker, Selection, StatusBar, Themed, WatchedDirs,
+    text_input_inactive,
+};
+
+/// Duplicate detection settings. Detection is off by default since the first run has to read
+/// every file that shares its size with another.
+#[derive(Resource, Debug, Clone)]
+pub struct DuplicateConfig {
+    /// Hash images in the background to find copies
+    pub detect: bool,
+    /// Only spawn one quad per group of copies, badged with the copy count
+    pub collapse: bool,
+}
+
+impl Default for DuplicateConfig {
+    fn default() -> Self {
+        Self {
+            detect: false,
+            collapse: true,
+        }
+    }
+}
+
+/// Groups of images with identical contents, keyed by content hash. Each group lists its paths
+/// in [`WatchedDirs`] order, and only groups of two or more are kept.
+#[derive(Resource, Default, Debug, Clone, PartialEq)]
+pub struct Duplicates {
+    groups: HashMap<u64, Vec<PathBuf>>,
+    by_path: HashMap<PathBuf, u64>,
+}
+
+impl Duplicates {
+    fn from_groups(groups: HashMap<u64, Vec<PathBuf>>) -> Self {
+        let by_path = groups
+            .iter()
+            .flat_map(|(hash, paths)| paths.iter().map(|path| (path.clone(), *hash)))
+            .collect();
+        Self { groups, by_path }
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = &[PathBuf]> {
+        self.groups.values().map(Vec::as_slice)
+    }
+
+    /// Every copy of `path`, itself included, or nothing if it's unique
+    pub fn copies_of(&self, path: &Path) -> &[PathBuf] {
+        self.by_path
+            .get(path)
+            .and_then(|hash| self.groups.get(hash))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Is `path` a copy that's folded into an earlier one when collapsing?
+    pub fn is_hidden_copy(&self, path: &Path) -> bool {
+        self.copies_of(path)
+            .first()
+            .is_some_and(|first| first != path)
+    }
+}
+
+/// What we know about a file from the last time it was hashed
+#[derive(Debug, Clone)]
+struct FileHash {
+    len: u64,
+    modified: Option<SystemTime>,
+    /// Hash of the first [`PARTIAL_HASH_BYTES`]
+    partial: u64,
+    /// Hash of the whole file, only worked out once another file matches on size and partial
+    full: Option<u64>,
+}
+
+pub(crate) const PARTIAL_HASH_BYTES: u64 = 64 * 1024;
+
+/// Background hashing state. At most one pass runs at a time, changes that come in meanwhile wait
+/// for it and then start another.
+#[derive(Resource, Default)]
+struct DuplicateScan {
+    task: Option<Task<(HashMap<PathBuf, FileHash>, Duplicates)>>,
+    hashes: HashMap<PathBuf, FileHash>,
+    dirty: bool,
+}
+
+/// A copy count drawn over a collapsed group's quad
+#[derive(Component)]
+struct DuplicateBadge {
+    quad: Entity,
+}
+
+pub struct DuplicatesPlugin;
+
+impl Plugin for DuplicatesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DuplicateConfig>();
+        app.init_resource::<Duplicates>();
+        app.init_resource::<DuplicateScan>();
+        app.add_systems(
+            Update,
+            (
+                start_duplicate_scan,
+                poll_duplicate_scan,
+                collapse_duplicates_system.in_set(DirWatchingSet::DespawnQuads),
+                sync_duplicate_badges,
+                position_duplicate_badges,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Update,
+            (
+                toggle_collapse_hotkey_system.run_if(text_input_inactive),
+                describe_selected_duplicates,
+            )
+                .run_if(in_state(AppState::Running)),
+        );
+    }
+}
+
+fn start_duplicate_scan(
+    config: Res<DuplicateConfig>,
+    watched_dirs: Res<WatchedDirs>,
+    mut scan: ResMut<DuplicateScan>,
+) {
+    if !config.detect {
+        return;
+    }
+    if watched_dirs.is_changed() || config.is_changed() {
+        scan.dirty = true;
+    }
+    if !scan.dirty || scan.task.is_some() {
+        return;
+    }
+
+    scan.dirty = false;
+    let images = watched_dirs.images().to_vec();
+    let previous = std::mem::take(&mut scan.hashes);
+    scan.task = Some(IoTaskPool::get().spawn(async move { find_duplicates(images, previous) }));
+}
+
+fn poll_duplicate_scan(mut scan: ResMut<DuplicateScan>, mut duplicates: ResMut<Duplicates>) {
+    let Some(task) = &mut scan.task else {
+        return;
+    };
+    let Some((hashes, found)) = block_on(future::poll_once(task)) else {
+        return;
+    };
+
+    scan.task = None;
+    scan.hashes = hashes;
+    log::debug!("Found {} groups of duplicate images", found.groups.len());
+    duplicates.set_if_neq(found);
+}
+
+/// Hash `images`, reusing `previous` hashes for files whose size and mtime haven't changed.
+/// Files are only read in full when another file has the same size and starts the same way.
+fn find_duplicates(
+    images: Vec<PathBuf>,
+    mut previous: HashMap<PathBuf, FileHash>,
+) -> (HashMap<PathBuf, FileHash>, Duplicates) {
+    let mut hashes = HashMap::new();
+    for path in &images {
+        let Ok(meta) = fs::metadata(path) else {
+            continue;
+        };
+        let (len, modified) = (meta.len(), meta.modified().ok());
+        let hash = match previous.remove(path) {
+            Some(hash) if hash.len == len && hash.modified == modified => hash,
+            _ => match hash_file(path, Some(PARTIAL_HASH_BYTES)) {
+                Ok(partial) => FileHash {
+                    len,
+                    modified,
+                    partial,
+                    // Small files were read in full already
+                    full: (len <= PARTIAL_HASH_BYTES).then_some(partial),
+                },
+                Err(e) => {
+                    log::warn!("Couldn't hash {path:?}: {e}");
+                    continue;
+                }
+            },
+        };
+        hashes.insert(path.clone(), hash);
+    }
+
+    let mut candidates: HashMap<(u64, u64), Vec<&PathBuf>> = HashMap::new();
+    for path in &images {
+        if let Some(hash) = hashes.get(path) {
+            candidates
+                .entry((hash.len, hash.partial))
+                .or_default()
+                .push(path);
+        }
+    }
+
+    let mut groups: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for paths in candidates.into_values().filter(|paths| paths.len() > 1) {
+        for path in paths {
+            let Some(hash) = hashes.get_mut(path) else {
+                continue;
+            };
+            if hash.full.is_none() {
+                match hash_file(path, None) {
+                    Ok(full) => hash.full = Some(full),
+                    Err(e) => {
+                        log::warn!("Couldn't hash {path:?}: {e}");
+                        continue;
+                    }
+                }
+            }
+            if let Some(full) = hash.full {
+                groups.entry(full).or_default().push(path.clone());
+            }
+        }
+    }
+    groups.retain(|_, paths| paths.len() > 1);
+
+    (hashes, Duplicates::from_groups(groups))
+}
+
+/// Hash the first `limit` bytes of a file, or all of it
+pub(crate) fn hash_file(path: &Path, limit: Option<u64>) -> io::Result<u64> {
+    let file = File::open(path)?;
+    let mut reader: Box<dyn Read> = match limit {
+        Some(limit) => Box::new(file.take(limit)),
+        None => Box::new(file),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&buffer[..read]);
+    }
+}
+
+/// Drop the quads of hidden copies while collapsing. Turning collapsing off lets the spawner
+/// bring them back.
+fn collapse_duplicates_system(
+    mut commands: Commands,
+    config: Res<DuplicateConfig>,
+    duplicates: Res<Duplicates>,
+    quads: Query<(Entity, &ImageMarker)>,
+) {
+    if !config.collapse || !(config.is_changed() || duplicates.is_changed()) {
+        return;
+    }
+    for (entity, marker) in &quads {
+        if duplicates.is_hidden_copy(&marker.target) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// One badge per collapsed group that has a quad
+fn sync_duplicate_badges(
+    mut commands: Commands,
+    config: Res<DuplicateConfig>,
+    duplicates: Res<Duplicates>,
+    quads: Query<(Entity, &ImageMarker)>,
+    added: Query<(), Added<ImageMarker>>,
+    badges: Query<Entity, With<DuplicateBadge>>,
+) {
+    if !config.is_changed() && !duplicates.is_changed() && added.is_empty() {
+        return;
+    }
+    for badge in &badges {
+        commands.entity(badge).despawn();
+    }
+    if !config.collapse {
+        return;
+    }
+
+    for (entity, marker) in &quads {
+        let copies = duplicates.copies_of(&marker.target);
+        if copies.len() < 2 || duplicates.is_hidden_copy(&marker.target) {
+            continue;
+        }
+        commands.spawn((
+            DuplicateBadge { quad: entity },
+            Node {
+                position_type: PositionType::Absolute,
+                padding: UiRect::axes(Val::Px(4.0), Val::Px(1.0)),
+                display: Display::None,
+                ..default()
+            },
+            BorderRadius::all(Val::Px(3.0)),
+            Themed::Panel,
+            children![(
+                Text::new(format!("×{}", copies.len())),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                Themed::Text,
+            )],
+        ));
+    }
+}
+
+/// Pin badges to the top-left corner of their quads, hiding them when the quad isn't on screen
+fn position_duplicate_badges(
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    quads: Query<(&GlobalTransform, &ViewVisibility), With<ImageMarker>>,
+    mut badges: Query<(&DuplicateBadge, &mut Node)>,
+    grid: Res<GridConfig>,
+) {
+    let (camera, camera_transform) = *camera;
+    let half = grid.quad_size * 0.5;
+    for (badge, mut node) in &mut badges {
+        let position = quads
+            .get(badge.quad)
+            .ok()
+            .filter(|(_, visibility)| visibility.get())
+            .and_then(|(transform, _)| {
+                let corner = transform.transform_point(Vec3::new(-half, half, 0.0));
+                camera.world_to_viewport(camera_transform, corner).ok()
+            });
+        match position {
+            Some(position) => {
+                node.display = Display::Flex;
+                node.left = Val::Px(position.x);
+                node.top = Val::Px(position.y);
+            }
+            None => node.display = Display::None,
+        }
+    }
+}
+
+/// D turns collapsing duplicates on and off
+fn toggle_collapse_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut config: ResMut<DuplicateConfig>,
+    mut status: ResMut<StatusBar>,
+) {
+    if !keys.just_pressed(KeyCode::KeyD) || !config.detect {
+        return;
+    }
+    config.collapse = !config.collapse;
+    status.set(if config.collapse {
+        "Collapsing duplicates"
+    } else {
+        "Showing every copy"
+    });
+}
+
+/// List where else the selected image lives
+fn describe_selected_duplicates(
+    selected: Res<Selection>,
+    duplicates: Res<Duplicates>,
+    mut status: ResMut<StatusBar>,
+) {
+    if !selected.is_changed() {
+        return;
+    }
+    let Some(path) = selected.path() else {
+        return;
+    };
+    let others: Vec<_> = duplicates
+        .copies_of(path)
+        .iter()
+        .filter(|copy| *copy != path)
+        .map(|copy| copy.display().to_string())
+        .collect();
+    if !others.is_empty() {
+        status.set(format!("Also at: {}", others.join(", ")));
+    }
+}
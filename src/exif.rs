@@ -0,0 +1,589 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::{ExifOrientation, GpsPosition, PhotoMetadata, timeline::days_from_civil};
+
+/// EXIF tags we look for
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const GPS_IFD_POINTER: u16 = 0x8825;
+const MAKE: u16 = 0x010F;
+const MODEL: u16 = 0x0110;
+const ORIENTATION: u16 = 0x0112;
+const DATE_TIME: u16 = 0x0132;
+const EXPOSURE_TIME: u16 = 0x829A;
+const F_NUMBER: u16 = 0x829D;
+const ISO_SPEED: u16 = 0x8827;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const LENS_MODEL: u16 = 0xA434;
+const GPS_LATITUDE_REF: u16 = 0x0001;
+const GPS_LATITUDE: u16 = 0x0002;
+const GPS_LONGITUDE_REF: u16 = 0x0003;
+const GPS_LONGITUDE: u16 = 0x0004;
+
+/// When the photo at `path` was taken, going by its EXIF `DateTimeOriginal`, or the plain
+/// `DateTime` if that's all there is. Only JPEGs and TIFFs (RAW files included) are looked in,
+/// anything else (or one without a date) is `None`.
+///
+/// EXIF dates don't say which time zone they're in, so they're read as UTC. That keeps a photo
+/// on the day its camera thought it was.
+pub(crate) fn capture_date(path: &Path) -> Option<SystemTime> {
+    photo_metadata(path)?.taken
+}
+
+/// Everything [`PhotoMetadata`] has room for out of the EXIF data of the JPEG or TIFF at `path`.
+/// `None` if it's neither, or hasn't got any.
+pub(crate) fn photo_metadata(path: &Path) -> Option<PhotoMetadata> {
+    let exif = read_exif(path).ok()??;
+    let tiff = Tiff::new(&exif)?;
+    let ifd0 = tiff.first_ifd()?;
+    let exif_ifd = tiff.sub_ifd(ifd0, EXIF_IFD_POINTER);
+    let in_exif = |tag| exif_ifd.and_then(|ifd| tiff.entry(ifd, tag));
+    let text = |entry: Option<usize>| {
+        let text = tiff.ascii(entry?)?.trim();
+        (!text.is_empty()).then(|| text.to_owned())
+    };
+
+    let taken = in_exif(DATE_TIME_ORIGINAL)
+        .or_else(|| tiff.entry(ifd0, DATE_TIME))
+        .and_then(|entry| tiff.ascii(entry))
+        .and_then(parse_date);
+    let camera = match (text(tiff.entry(ifd0, MAKE)), text(tiff.entry(ifd0, MODEL))) {
+        // Plenty of models say the make already ("Canon EOS R5", "NIKON D750")
+        (Some(make), Some(model)) => {
+            let brand = make.split_whitespace().next().unwrap_or(&make);
+            if model.to_lowercase().starts_with(&brand.to_lowercase()) {
+                Some(model)
+            } else {
+                Some(format!("{make} {model}"))
+            }
+        }
+        (make, model) => make.or(model),
+    };
+    Some(PhotoMetadata {
+        taken,
+        camera,
+        lens: text(in_exif(LENS_MODEL)),
+        iso: in_exif(ISO_SPEED).and_then(|entry| tiff.number(entry)),
+        aperture: in_exif(F_NUMBER)
+            .and_then(|entry| tiff.rational(entry))
+            .map(|f| f as f32),
+        shutter: in_exif(EXPOSURE_TIME)
+            .and_then(|entry| tiff.rational(entry))
+            .map(|seconds| seconds as f32),
+        orientation: tiff
+            .entry(ifd0, ORIENTATION)
+            .and_then(|entry| tiff.number(entry))
+            .map(ExifOrientation::from_tag)
+            .unwrap_or_default(),
+        gps: tiff
+            .sub_ifd(ifd0, GPS_IFD_POINTER)
+            .and_then(|gps_ifd| gps_position(&tiff, gps_ifd)),
+    })
+}
+
+/// Where the GPS IFD at `ifd` says the photo was taken
+fn gps_position(tiff: &Tiff, ifd: usize) -> Option<GpsPosition> {
+    // Degrees, minutes and seconds, negative to the south or west
+    let coordinate = |tag, ref_tag, negative| {
+        let [degrees, minutes, seconds] = tiff.rationals(tiff.entry(ifd, tag)?)?;
+        let value = degrees + minutes / 60.0 + seconds / 3_600.0;
+        let reference = tiff.entry(ifd, ref_tag).and_then(|entry| tiff.ascii(entry));
+        Some(if reference == Some(negative) {
+            -value
+        } else {
+            value
+        })
+    };
+    Some(GpsPosition {
+        latitude: coordinate(GPS_LATITUDE, GPS_LATITUDE_REF, "S")?,
+        longitude: coordinate(GPS_LONGITUDE, GPS_LONGITUDE_REF, "W")?,
+    })
+}
+
+/// The TIFF block with the EXIF data in: out of a JPEG's EXIF segment, or the start of a file
+/// that's a TIFF itself (RAW files mostly are). `Ok(None)` if it's neither.
+fn read_exif(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    // Plenty for any IFD that's near the start, which is where most writers put them
+    const TIFF_LIMIT: u64 = 1 << 20;
+
+    let mut file = BufReader::new(File::open(path)?);
+    let mut header = [0; 4];
+    file.read_exact(&mut header)?;
+    match &header {
+        [0xFF, 0xD8, ..] => read_jpeg_exif(header[2..].chain(file)),
+        b"II*\0" | b"MM\0*" => {
+            let mut data = header.to_vec();
+            file.take(TIFF_LIMIT).read_to_end(&mut data)?;
+            Ok(Some(data))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// [`read_exif`] for a JPEG, without reading any further into the file than its EXIF segment.
+/// `file` starts just after the start of image marker.
+fn read_jpeg_exif(mut file: impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut marker = [0; 2];
+    loop {
+        file.read_exact(&mut marker)?;
+        // Start of the image data, or the end of the file: no metadata after this
+        if marker[0] != 0xFF || marker[1] == 0xDA || marker[1] == 0xD9 {
+            return Ok(None);
+        }
+        let mut length = [0; 2];
+        file.read_exact(&mut length)?;
+        let length = u16::from_be_bytes(length).saturating_sub(2) as usize;
+        if marker[1] == 0xE1 {
+            let mut segment = vec![0; length];
+            file.read_exact(&mut segment)?;
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return Ok(Some(tiff.to_vec()));
+            }
+        } else {
+            io::copy(&mut (&mut file).take(length as u64), &mut io::sink())?;
+        }
+    }
+}
+
+/// Reads IFD entries out of a TIFF block (or a whole TIFF file), in whichever byte order it says
+/// it's in
+pub(crate) struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let bytes = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn first_ifd(&self) -> Option<usize> {
+        self.u32(4).map(|offset| offset as usize)
+    }
+
+    /// Offset of the entry for `tag` in the IFD at `ifd`
+    pub(crate) fn entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        (0..count)
+            .map(|index| ifd + 2 + index * 12)
+            .find(|&entry| self.u16(entry) == Some(tag))
+    }
+
+    /// The IFD after the one at `ifd`, unless it's the last
+    #[cfg_attr(not(feature = "raw"), allow(dead_code))]
+    pub(crate) fn next_ifd(&self, ifd: usize) -> Option<usize> {
+        let count = self.u16(ifd)? as usize;
+        let next = self.u32(ifd + 2 + count * 12)? as usize;
+        (next != 0).then_some(next)
+    }
+
+    /// Where the IFD that the pointer `tag` in the IFD at `ifd` points to starts
+    fn sub_ifd(&self, ifd: usize, tag: u16) -> Option<usize> {
+        self.long(self.entry(ifd, tag)?)
+            .map(|offset| offset as usize)
+    }
+
+    /// A LONG entry's value
+    fn long(&self, entry: usize) -> Option<u32> {
+        const LONG: u16 = 4;
+        (self.u16(entry + 2)? == LONG).then(|| self.u32(entry + 8))?
+    }
+
+    /// Every value of a LONG (or IFD) entry, like the offsets in a SubIFDs list
+    #[cfg_attr(not(feature = "raw"), allow(dead_code))]
+    pub(crate) fn longs(&self, entry: usize) -> Vec<u32> {
+        const LONG: u16 = 4;
+        const IFD: u16 = 13;
+        if !matches!(self.u16(entry + 2), Some(LONG | IFD)) {
+            return vec![];
+        }
+        let count = self.u32(entry + 4).unwrap_or(0) as usize;
+        // One fits in the entry itself
+        let start = if count <= 1 {
+            Some(entry + 8)
+        } else {
+            self.u32(entry + 8).map(|offset| offset as usize)
+        };
+        let Some(start) = start else {
+            return vec![];
+        };
+        (0..count)
+            .map_while(|index| self.u32(start + index * 4))
+            .collect()
+    }
+
+    /// A SHORT or LONG entry's value
+    pub(crate) fn number(&self, entry: usize) -> Option<u32> {
+        const SHORT: u16 = 3;
+        match self.u16(entry + 2)? {
+            SHORT => self.u16(entry + 8).map(u32::from),
+            _ => self.long(entry),
+        }
+    }
+
+    /// The first `N` values of a RATIONAL or SRATIONAL entry
+    fn rationals<const N: usize>(&self, entry: usize) -> Option<[f64; N]> {
+        const RATIONAL: u16 = 5;
+        const SRATIONAL: u16 = 10;
+        let signed = match self.u16(entry + 2)? {
+            RATIONAL => false,
+            SRATIONAL => true,
+            _ => return None,
+        };
+        if (self.u32(entry + 4)? as usize) < N {
+            return None;
+        }
+        // Eight bytes each, so they're never in the entry itself
+        let start = self.u32(entry + 8)? as usize;
+        let mut values = [0.0; N];
+        for (index, value) in values.iter_mut().enumerate() {
+            let at = start + index * 8;
+            let (numerator, denominator) = (self.u32(at)?, self.u32(at + 4)?);
+            if denominator == 0 {
+                return None;
+            }
+            *value = if signed {
+                numerator as i32 as f64 / denominator as i32 as f64
+            } else {
+                numerator as f64 / denominator as f64
+            };
+        }
+        Some(values)
+    }
+
+    fn rational(&self, entry: usize) -> Option<f64> {
+        self.rationals(entry).map(|[value]| value)
+    }
+
+    /// An ASCII entry's text, without the trailing NUL
+    fn ascii(&self, entry: usize) -> Option<&'a str> {
+        const ASCII: u16 = 2;
+        if self.u16(entry + 2)? != ASCII {
+            return None;
+        }
+        let len = self.u32(entry + 4)? as usize;
+        // Up to four bytes fit in the entry itself, longer values are somewhere else
+        let start = if len <= 4 {
+            entry + 8
+        } else {
+            self.u32(entry + 8)? as usize
+        };
+        let bytes = self.data.get(start..start.checked_add(len)?)?;
+        std::str::from_utf8(bytes)
+            .ok()
+            .map(|text| text.trim_end_matches('\0'))
+    }
+}
+
+/// An EXIF `YYYY:MM:DD HH:MM:SS` date. Cameras that don't know the date write zeros, or spaces,
+/// and those come out as `None`.
+fn parse_date(text: &str) -> Option<SystemTime> {
+    let numbers: Vec<i64> = text
+        .split([':', ' '])
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let &[year, month, day, hour, minute, second] = numbers.as_slice() else {
+        return None;
+    };
+    if year == 0 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month as u32, day as u32);
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    match u64::try_from(seconds) {
+        Ok(seconds) => SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds)),
+        Err(_) => SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs())),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    /// What goes in an IFD entry, for [`tiff`]
+    pub(crate) enum Value {
+        Short(u16),
+        Long(u32),
+        Ascii(&'static str),
+        Rationals(Vec<(u32, u32)>),
+        /// LONGs holding where these IFDs (by their index in the list) start
+        Ifds(Vec<usize>),
+        /// A LONG holding where these bytes end up, after all the IFDs. Only the RAW tests have
+        /// anything to point at.
+        #[cfg_attr(not(feature = "raw"), allow(dead_code))]
+        Offset(Vec<u8>),
+    }
+
+    pub(crate) struct Ifd {
+        pub(crate) entries: Vec<(u16, Value)>,
+        /// The index of the IFD that follows this one
+        pub(crate) next: Option<usize>,
+    }
+
+    /// Lay out a TIFF block: the header, then each IFD in turn, the first being the first IFD,
+    /// then everything that didn't fit in its entry
+    pub(crate) fn tiff(big_endian: bool, ifds: &[Ifd]) -> Vec<u8> {
+        let u16_bytes = |value: u16| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+        let u32_bytes = |value: u32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+
+        let mut starts = vec![];
+        let mut end = 8;
+        for ifd in ifds {
+            starts.push(end as u32);
+            end += 2 + ifd.entries.len() * 12 + 4;
+        }
+
+        let mut data = if big_endian {
+            b"MM\0*".to_vec()
+        } else {
+            b"II*\0".to_vec()
+        };
+        data.extend(u32_bytes(8));
+        let mut extra: Vec<u8> = vec![];
+        for ifd in ifds {
+            data.extend(u16_bytes(ifd.entries.len() as u16));
+            for (tag, value) in &ifd.entries {
+                // Inline values are left-justified in the four bytes
+                let mut put_aside = |bytes: &[u8]| {
+                    let offset = (end + extra.len()) as u32;
+                    extra.extend(bytes);
+                    u32_bytes(offset)
+                };
+                let (kind, count, field): (u16, usize, [u8; 4]) = match value {
+                    Value::Short(value) => {
+                        let [a, b] = u16_bytes(*value);
+                        (3, 1, [a, b, 0, 0])
+                    }
+                    Value::Long(value) => (4, 1, u32_bytes(*value)),
+                    Value::Ascii(text) => {
+                        let mut bytes = text.as_bytes().to_vec();
+                        bytes.push(0);
+                        let field = if bytes.len() <= 4 {
+                            let mut field = [0; 4];
+                            field[..bytes.len()].copy_from_slice(&bytes);
+                            field
+                        } else {
+                            put_aside(&bytes)
+                        };
+                        (2, bytes.len(), field)
+                    }
+                    Value::Rationals(values) => {
+                        let bytes: Vec<u8> = values
+                            .iter()
+                            .flat_map(|&(numerator, denominator)| {
+                                [u32_bytes(numerator), u32_bytes(denominator)]
+                            })
+                            .flatten()
+                            .collect();
+                        (5, values.len(), put_aside(&bytes))
+                    }
+                    Value::Ifds(indices) => {
+                        let offsets: Vec<u8> = indices
+                            .iter()
+                            .flat_map(|&index| u32_bytes(starts[index]))
+                            .collect();
+                        let field = match indices.len() {
+                            1 => u32_bytes(starts[indices[0]]),
+                            _ => put_aside(&offsets),
+                        };
+                        (4, indices.len(), field)
+                    }
+                    Value::Offset(bytes) => (4, 1, put_aside(bytes)),
+                };
+                data.extend(u16_bytes(*tag));
+                data.extend(u16_bytes(kind));
+                data.extend(u32_bytes(count as u32));
+                data.extend(field);
+            }
+            data.extend(u32_bytes(ifd.next.map_or(0, |next| starts[next])));
+        }
+        data.extend(extra);
+        data
+    }
+
+    /// A JPEG with `exif` in its APP1 segment, and no image to speak of
+    fn jpeg_with_exif(exif: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 4, 0, 0];
+        jpeg.extend([0xFF, 0xE1]);
+        jpeg.extend((exif.len() as u16 + 8).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(exif);
+        jpeg.extend([0xFF, 0xDA, 0, 2, 0xFF, 0xD9]);
+        jpeg
+    }
+
+    fn metadata(bytes: &[u8]) -> Option<PhotoMetadata> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(bytes).unwrap();
+        photo_metadata(file.path())
+    }
+
+    fn at(seconds: u64) -> Option<SystemTime> {
+        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    /// Everything there's a field for, in the three IFDs it's spread over
+    fn full_exif(big_endian: bool) -> Vec<u8> {
+        tiff(
+            big_endian,
+            &[
+                Ifd {
+                    entries: vec![
+                        (MAKE, Value::Ascii("Canon")),
+                        (MODEL, Value::Ascii("Canon EOS R5")),
+                        (ORIENTATION, Value::Short(6)),
+                        (DATE_TIME, Value::Ascii("2022:01:01 00:00:00")),
+                        (EXIF_IFD_POINTER, Value::Ifds(vec![1])),
+                        (GPS_IFD_POINTER, Value::Ifds(vec![2])),
+                    ],
+                    next: None,
+                },
+                Ifd {
+                    entries: vec![
+                        (DATE_TIME_ORIGINAL, Value::Ascii("2021:06:15 12:30:45")),
+                        (ISO_SPEED, Value::Short(400)),
+                        (F_NUMBER, Value::Rationals(vec![(28, 10)])),
+                        (EXPOSURE_TIME, Value::Rationals(vec![(1, 250)])),
+                        (LENS_MODEL, Value::Ascii("RF24-105mm F4 L IS USM")),
+                    ],
+                    next: None,
+                },
+                Ifd {
+                    entries: vec![
+                        (GPS_LATITUDE_REF, Value::Ascii("S")),
+                        (
+                            GPS_LATITUDE,
+                            Value::Rationals(vec![(33, 1), (51, 1), (3600, 100)]),
+                        ),
+                        (GPS_LONGITUDE_REF, Value::Ascii("E")),
+                        (
+                            GPS_LONGITUDE,
+                            Value::Rationals(vec![(151, 1), (12, 1), (0, 1)]),
+                        ),
+                    ],
+                    next: None,
+                },
+            ],
+        )
+    }
+
+    fn assert_full(metadata: PhotoMetadata) {
+        assert_eq!(metadata.taken, at(1_623_760_245));
+        assert_eq!(metadata.camera.as_deref(), Some("Canon EOS R5"));
+        assert_eq!(metadata.lens.as_deref(), Some("RF24-105mm F4 L IS USM"));
+        assert_eq!(metadata.iso, Some(400));
+        assert_eq!(metadata.aperture, Some(2.8));
+        assert_eq!(metadata.shutter, Some((1.0f64 / 250.0) as f32));
+        assert_eq!(metadata.orientation, ExifOrientation::from_tag(6));
+        let gps = metadata.gps.unwrap();
+        assert!((gps.latitude + (33.0 + 51.0 / 60.0 + 36.0 / 3600.0)).abs() < 1e-9);
+        assert!((gps.longitude - (151.0 + 12.0 / 60.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reads_everything_out_of_a_jpeg() {
+        assert_full(metadata(&jpeg_with_exif(&full_exif(false))).unwrap());
+        assert_full(metadata(&jpeg_with_exif(&full_exif(true))).unwrap());
+    }
+
+    #[test]
+    fn reads_everything_out_of_a_tiff() {
+        assert_full(metadata(&full_exif(true)).unwrap());
+    }
+
+    #[test]
+    fn falls_back_to_the_plain_date_and_keeps_the_make() {
+        let exif = tiff(
+            false,
+            &[Ifd {
+                entries: vec![
+                    (MAKE, Value::Ascii("NIKON CORPORATION")),
+                    (MODEL, Value::Ascii("D750")),
+                    (DATE_TIME, Value::Ascii("2019:03:02 08:00:00")),
+                    (ORIENTATION, Value::Long(1)),
+                ],
+                next: None,
+            }],
+        );
+        let metadata = metadata(&jpeg_with_exif(&exif)).unwrap();
+        assert_eq!(metadata.taken, at(1_551_513_600));
+        assert_eq!(metadata.camera.as_deref(), Some("NIKON CORPORATION D750"));
+        assert_eq!(metadata.gps, None);
+        assert_eq!(metadata.orientation, ExifOrientation::default());
+    }
+
+    #[test]
+    fn unknown_dates_are_none() {
+        assert_eq!(parse_date("0000:00:00 00:00:00"), None);
+        assert_eq!(parse_date("    :  :     :  :  "), None);
+        assert_eq!(parse_date("2021:13:01 00:00:00"), None);
+        assert_eq!(parse_date("2021:06:15"), None);
+        assert_eq!(
+            parse_date("1969:12:31 23:59:59"),
+            SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn truncated_exif_is_none() {
+        let jpeg = jpeg_with_exif(&full_exif(false));
+        // Cut off inside the EXIF segment
+        assert_eq!(metadata(&jpeg[..40]), None);
+        assert_eq!(metadata(&jpeg[..3]), None);
+
+        // The IFDs are there but what they point to isn't
+        let tiff = full_exif(false);
+        let metadata = metadata(&tiff[..120]).unwrap();
+        assert_eq!(metadata.taken, None);
+        assert_eq!(metadata.lens, None);
+        assert_eq!(metadata.gps, None);
+    }
+
+    #[test]
+    fn other_formats_have_none() {
+        assert_eq!(metadata(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"), None);
+        assert_eq!(metadata(&[0xFF, 0xD8, 0xFF, 0xDA, 0, 2]), None);
+    }
+}
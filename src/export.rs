@@ -0,0 +1,611 @@
+use bevy::{
+    prelude::*,
+    render::view::screenshot::{Screenshot, ScreenshotCaptured},
+    tasks::{AsyncComputeTaskPool, IoTaskPool, Task, block_on, futures_lite::future},
+    window::RequestRedraw,
+};
+use image::{Rgba, RgbaImage, imageops};
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    AppState, FilteredOut, GridPosition, ImageMarker, ImageTexture, PhotoMetadata, StatusBar, Tags,
+    Themed, UiTheme, WatchedDirs,
+    navigation::grid_order,
+    platform, text_input_inactive,
+    timeline::{civil_from_days, unix_seconds},
+};
+
+/// What to write out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    /// Whatever's in the window right now, UI and all
+    Screenshot,
+    /// The first [`ContactSheetConfig::max_images`] images tiled into one big image, composed on
+    /// the CPU so it doesn't depend on the window size (and has no UI in it)
+    ContactSheet,
+    /// A static web page of the images the filter lets through, see [`ExportGallery`]
+    Gallery,
+}
+
+/// Send this to ask the user where to save, then export there
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ExportRequested(pub ExportKind);
+
+#[derive(Resource, Debug, Clone)]
+pub struct ContactSheetConfig {
+    /// Width and height of the output, in pixels
+    pub size: u32,
+    pub max_images: usize,
+}
+
+impl Default for ContactSheetConfig {
+    fn default() -> Self {
+        Self {
+            size: 4096,
+            max_images: 256,
+        }
+    }
+}
+
+/// Write a static HTML gallery of the images the filter lets through into this directory, with
+/// the images copied into an `images/` folder next to its `index.html`
+#[derive(Event, Debug, Clone)]
+pub struct ExportGallery(pub PathBuf);
+
+/// A contact sheet being composed in the background, resolves to where it was saved
+#[derive(Component)]
+struct ContactSheetTask(Task<Result<PathBuf, String>>);
+
+/// A gallery being written out in the background, with how many images have been copied so far
+#[derive(Component)]
+struct GalleryTask {
+    task: Task<io::Result<PathBuf>>,
+    copied: Arc<AtomicUsize>,
+    total: usize,
+}
+
+/// Shows how far along the gallery export is
+#[derive(Component)]
+struct GalleryProgressText;
+
+/// One image going into the gallery
+struct GalleryImage {
+    source: PathBuf,
+    caption: String,
+}
+
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportRequested>();
+        app.add_event::<ExportGallery>();
+        app.init_resource::<ContactSheetConfig>();
+        app.add_systems(
+            Update,
+            (
+                export_hotkey_system.run_if(text_input_inactive),
+                export_system,
+                poll_contact_sheet_tasks,
+                export_gallery_system,
+                poll_gallery_tasks,
+            )
+                .chain()
+                .run_if(in_state(AppState::Running)),
+        );
+    }
+}
+
+/// Ctrl+S saves a screenshot, Ctrl+Shift+S a contact sheet
+fn export_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut exports: EventWriter<ExportRequested>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keys.just_pressed(KeyCode::KeyS) {
+        return;
+    }
+
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    exports.write(ExportRequested(if shift {
+        ExportKind::ContactSheet
+    } else {
+        ExportKind::Screenshot
+    }));
+}
+
+fn export_system(
+    mut commands: Commands,
+    mut requests: EventReader<ExportRequested>,
+    mut status: ResMut<StatusBar>,
+    mut redraw: EventWriter<RequestRedraw>,
+    mut galleries: EventWriter<ExportGallery>,
+    config: Res<ContactSheetConfig>,
+    theme: Res<UiTheme>,
+    watched_dirs: Res<WatchedDirs>,
+    images: Res<Assets<Image>>,
+    quads: Query<(&ImageMarker, &ImageTexture)>,
+) {
+    // Only one dialog per frame, however many times the key got mashed
+    let Some(&ExportRequested(kind)) = requests.read().last() else {
+        return;
+    };
+
+    let default_name = match kind {
+        ExportKind::Screenshot => "screenshot.png",
+        ExportKind::ContactSheet => "contact-sheet.png",
+        ExportKind::Gallery => {
+            if let Some(dir) = platform::pick_folder() {
+                galleries.write(ExportGallery(dir));
+            }
+            return;
+        }
+    };
+    // Blocks the app while the dialog is up, which is what you'd expect from a save dialog anyway
+    let Some(path) = platform::pick_save_file("PNG image", &["png"], default_name) else {
+        return;
+    };
+
+    match kind {
+        ExportKind::Screenshot => {
+            commands
+                .spawn(Screenshot::primary_window())
+                .observe(save_screenshot(path));
+            // The capture happens at the end of a rendered frame, don't wait for input to get one
+            redraw.write(RequestRedraw);
+        }
+        ExportKind::ContactSheet => {
+            let textures: HashMap<&Path, &Handle<Image>> = quads
+                .iter()
+                .map(|(marker, texture)| (marker.target.as_path(), &texture.0))
+                .collect();
+            // Copy the pixels out now, the composing happens off the main thread
+            let sources: Vec<Image> = watched_dirs
+                .images()
+                .iter()
+                .filter_map(|path| images.get(*textures.get(path.as_path())?))
+                .take(config.max_images)
+                .cloned()
+                .collect();
+            if sources.is_empty() {
+                status.set("Nothing loaded yet to put on a contact sheet");
+                return;
+            }
+
+            status.set(format!(
+                "Composing a contact sheet of {} images...",
+                sources.len()
+            ));
+            let size = config.size;
+            let background = Rgba(theme.background.to_srgba().to_u8_array());
+            let task = AsyncComputeTaskPool::get()
+                .spawn(async move { compose_contact_sheet(sources, size, background, path) });
+            commands.spawn(ContactSheetTask(task));
+        }
+        ExportKind::Gallery => unreachable!("handled before the save dialog"),
+    }
+}
+
+/// Like bevy's `save_to_disk`, but reports to the status bar instead of just the log
+fn save_screenshot(path: PathBuf) -> impl FnMut(Trigger<ScreenshotCaptured>, ResMut<StatusBar>) {
+    move |trigger, mut status| {
+        let result = trigger
+            .event()
+            .0
+            .clone()
+            .try_into_dynamic()
+            .map_err(|e| e.to_string())
+            // Drop alpha, with HDR on it holds brightness rather than transparency
+            .and_then(|image| image.to_rgb8().save(&path).map_err(|e| e.to_string()));
+
+        match result {
+            Ok(()) => status.set(format!("Saved screenshot to {}", path.display())),
+            Err(e) => {
+                log::warn!("Couldn't save screenshot to {path:?}: {e}");
+                status.set(format!(
+                    "Couldn't save screenshot to {}: {e}",
+                    path.display()
+                ));
+            }
+        }
+    }
+}
+
+fn compose_contact_sheet(
+    sources: Vec<Image>,
+    size: u32,
+    background: Rgba<u8>,
+    path: PathBuf,
+) -> Result<PathBuf, String> {
+    let columns = (sources.len() as f32).sqrt().ceil() as u32;
+    let rows = (sources.len() as u32).div_ceil(columns);
+    let cell = size / columns.max(rows);
+    let padding = cell / 32;
+
+    let mut sheet = RgbaImage::from_pixel(size, size, background);
+    for (index, source) in sources.into_iter().enumerate() {
+        let image = match source.try_into_dynamic() {
+            Ok(image) => image,
+            Err(e) => {
+                log::warn!("Leaving a gap in the contact sheet: {e}");
+                continue;
+            }
+        };
+        let thumbnail = image.thumbnail(cell - padding * 2, cell - padding * 2);
+
+        // Centre each thumbnail in its cell
+        let (col, row) = (index as u32 % columns, index as u32 / columns);
+        let x = col * cell + (cell - thumbnail.width()) / 2;
+        let y = row * cell + (cell - thumbnail.height()) / 2;
+        imageops::overlay(&mut sheet, &thumbnail.to_rgba8(), x.into(), y.into());
+    }
+
+    sheet.save(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn poll_contact_sheet_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut ContactSheetTask)>,
+    mut status: ResMut<StatusBar>,
+    mut redraw: EventWriter<RequestRedraw>,
+) {
+    for (entity, mut task) in &mut tasks {
+        let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+            // Keep the (reactive) event loop ticking so we notice when it's done
+            redraw.write(RequestRedraw);
+            continue;
+        };
+
+        match result {
+            Ok(path) => status.set(format!("Saved contact sheet to {}", path.display())),
+            Err(e) => {
+                log::warn!("Couldn't save contact sheet: {e}");
+                status.set(format!("Couldn't save contact sheet: {e}"));
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Copy the images that are showing, in grid order, and write an `index.html` for them
+fn export_gallery_system(
+    mut commands: Commands,
+    mut requests: EventReader<ExportGallery>,
+    mut status: ResMut<StatusBar>,
+    images: Res<Assets<Image>>,
+    quads: Query<
+        (
+            &ImageMarker,
+            &GridPosition,
+            &ImageTexture,
+            Option<&Tags>,
+            Option<&PhotoMetadata>,
+        ),
+        Without<FilteredOut>,
+    >,
+    running: Query<(), With<GalleryTask>>,
+) {
+    let Some(ExportGallery(dir)) = requests.read().last() else {
+        return;
+    };
+    if !running.is_empty() {
+        status.set("Already exporting a gallery");
+        return;
+    }
+
+    let showing: HashMap<&Path, _> = quads
+        .iter()
+        .map(|(marker, _, texture, tags, metadata)| {
+            (marker.target.as_path(), (texture, tags, metadata))
+        })
+        .collect();
+    let cells: Vec<_> = quads
+        .iter()
+        .map(|(marker, position, ..)| (marker.target.clone(), position.cell))
+        .collect();
+    let entries: Vec<GalleryImage> = grid_order(&cells)
+        .into_iter()
+        .filter_map(|path| {
+            let (texture, tags, metadata) = showing.get(path.as_path())?;
+            let caption = gallery_caption(&path, images.get(&texture.0), *tags, *metadata);
+            Some(GalleryImage {
+                source: path,
+                caption,
+            })
+        })
+        .collect();
+    if entries.is_empty() {
+        status.set("Nothing to put in a gallery");
+        return;
+    }
+
+    let copied = Arc::new(AtomicUsize::new(0));
+    let total = entries.len();
+    let task = {
+        let (dir, copied) = (dir.clone(), copied.clone());
+        IoTaskPool::get().spawn(async move { write_gallery(&dir, entries, &copied) })
+    };
+    commands.spawn((
+        GalleryTask {
+            task,
+            copied,
+            total,
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            right: Val::Px(12.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        BorderRadius::all(Val::Px(4.0)),
+        Themed::Panel,
+        children![(
+            GalleryProgressText,
+            Text::new(format!("Exporting gallery: 0 of {total}")),
+            Themed::Text,
+        )],
+    ));
+}
+
+/// File name, plus pixel size, what the EXIF data says about the shot and tags when we know them
+fn gallery_caption(
+    path: &Path,
+    image: Option<&Image>,
+    tags: Option<&Tags>,
+    metadata: Option<&PhotoMetadata>,
+) -> String {
+    let mut caption = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    if let Some(image) = image {
+        let _ = write!(caption, " · {}×{}", image.width(), image.height());
+    }
+    if let Some(metadata) = metadata {
+        for detail in shot_details(metadata) {
+            let _ = write!(caption, " · {detail}");
+        }
+    }
+    if let Some(tags) = tags.filter(|tags| !tags.0.is_empty()) {
+        let _ = write!(caption, " · {}", tags.0.join(", "));
+    }
+    caption
+}
+
+/// Camera, lens, exposure and when it was taken, leaving out whatever the photo doesn't say
+fn shot_details(metadata: &PhotoMetadata) -> Vec<String> {
+    let mut details: Vec<String> = [&metadata.camera, &metadata.lens]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+
+    let mut exposure = vec![];
+    if let Some(aperture) = metadata.aperture {
+        exposure.push(format!("f/{aperture:.1}"));
+    }
+    if let Some(shutter) = metadata.shutter.filter(|shutter| *shutter > 0.0) {
+        exposure.push(if shutter < 1.0 {
+            format!("1/{} s", (1.0 / shutter).round())
+        } else {
+            format!("{shutter} s")
+        });
+    }
+    if let Some(iso) = metadata.iso {
+        exposure.push(format!("ISO {iso}"));
+    }
+    if !exposure.is_empty() {
+        details.push(exposure.join(" "));
+    }
+
+    if let Some(taken) = metadata.taken {
+        let seconds = unix_seconds(taken);
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+        let minutes = seconds.rem_euclid(86_400) / 60;
+        details.push(format!(
+            "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+            minutes / 60,
+            minutes % 60
+        ));
+    }
+    details
+}
+
+fn write_gallery(
+    dir: &Path,
+    entries: Vec<GalleryImage>,
+    copied: &AtomicUsize,
+) -> io::Result<PathBuf> {
+    let images_dir = dir.join("images");
+    fs::create_dir_all(&images_dir)?;
+
+    let mut figures = String::new();
+    for (index, entry) in entries.iter().enumerate() {
+        // Numbered so images with the same name from different directories don't collide
+        let name = format!(
+            "{:04}-{}",
+            index + 1,
+            entry
+                .source
+                .file_name()
+                .map_or_else(|| "image".into(), |name| name.to_string_lossy())
+        );
+        fs::copy(&entry.source, images_dir.join(&name))?;
+        copied.fetch_add(1, Ordering::Relaxed);
+
+        let href = format!("images/{}", escape_html(&percent_encode(&name)));
+        let caption = escape_html(&entry.caption);
+        let _ = writeln!(
+            figures,
+            r#"<figure><a href="{href}"><img src="{href}" alt="{caption}" loading="lazy"></a><figcaption>{caption}</figcaption></figure>"#
+        );
+    }
+
+    let index = dir.join("index.html");
+    fs::write(&index, GALLERY_TEMPLATE.replace("{figures}", &figures))?;
+    Ok(index)
+}
+
+/// `segment` made safe to go in a URL path as it is, so names with spaces, `#`, `?` or `%` in
+/// them still point at the file
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Everything inline, so the page works straight off a USB stick
+const GALLERY_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Gallery</title>
+<style>
+body { margin: 0; padding: 16px; background: #1b1b1f; color: #ddd; font-family: sans-serif; }
+main { display: grid; grid-template-columns: repeat(auto-fill, minmax(220px, 1fr)); gap: 16px; }
+figure { margin: 0; }
+img { width: 100%; aspect-ratio: 1; object-fit: cover; border-radius: 4px; display: block; }
+figcaption { font-size: 13px; padding-top: 4px; overflow-wrap: anywhere; }
+</style>
+</head>
+<body>
+<main>
+{figures}</main>
+</body>
+</html>
+"#;
+
+fn poll_gallery_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut GalleryTask)>,
+    mut progress: Query<&mut Text, With<GalleryProgressText>>,
+    mut status: ResMut<StatusBar>,
+    mut redraw: EventWriter<RequestRedraw>,
+) {
+    for (entity, mut gallery) in &mut tasks {
+        let Some(result) = block_on(future::poll_once(&mut gallery.task)) else {
+            let copied = gallery.copied.load(Ordering::Relaxed);
+            for mut text in &mut progress {
+                text.0 = format!("Exporting gallery: {copied} of {}", gallery.total);
+            }
+            redraw.write(RequestRedraw);
+            continue;
+        };
+
+        match result {
+            Ok(index) => status.set(format!(
+                "Exported {} images to {}",
+                gallery.total,
+                index.display()
+            )),
+            Err(e) => {
+                log::warn!("Couldn't export gallery: {e}");
+                status.set(format!("Couldn't export gallery: {e}"));
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn names_are_percent_encoded() {
+        assert_eq!(percent_encode("IMG_0001.jpg"), "IMG_0001.jpg");
+        assert_eq!(
+            percent_encode("0001-a b#c?d%e.jpg"),
+            "0001-a%20b%23c%3Fd%25e.jpg"
+        );
+        assert_eq!(percent_encode("café.png"), "caf%C3%A9.png");
+        assert_eq!(percent_encode("\"<&>'"), "%22%3C%26%3E%27");
+    }
+
+    #[test]
+    fn caption_has_the_shot_details() {
+        let metadata = PhotoMetadata {
+            // 2024-03-09 14:05:30
+            taken: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_993_130)),
+            camera: Some("Nikon Z 6".to_string()),
+            lens: Some("24-70mm f/4".to_string()),
+            iso: Some(400),
+            aperture: Some(5.6),
+            shutter: Some(1.0 / 250.0),
+            ..default()
+        };
+        let tags = Tags(vec!["beach".to_string()]);
+        assert_eq!(
+            gallery_caption(
+                Path::new("/photos/a.nef"),
+                None,
+                Some(&tags),
+                Some(&metadata)
+            ),
+            "a.nef · Nikon Z 6 · 24-70mm f/4 · f/5.6 1/250 s ISO 400 · 2024-03-09 14:05 · beach"
+        );
+    }
+
+    #[test]
+    fn caption_leaves_out_what_it_doesnt_know() {
+        assert_eq!(
+            gallery_caption(Path::new("a.png"), None, None, None),
+            "a.png"
+        );
+        let metadata = PhotoMetadata {
+            shutter: Some(2.0),
+            ..default()
+        };
+        assert_eq!(
+            gallery_caption(Path::new("a.jpg"), None, None, Some(&metadata)),
+            "a.jpg · 2 s"
+        );
+        assert_eq!(
+            gallery_caption(
+                Path::new("a.jpg"),
+                None,
+                None,
+                Some(&PhotoMetadata::default())
+            ),
+            "a.jpg"
+        );
+    }
+}
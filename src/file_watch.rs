@@ -0,0 +1,105 @@
+use bevy::{prelude::*, window::RequestRedraw};
+
+use notify::EventKind;
+use notify::event::{AccessKind, AccessMode};
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{DirWatchingSet, WatchedDirs, fs_events::FsWatcher};
+
+/// Reloads an image as soon as it's rewritten on disk, instead of waiting for the next scan to
+/// notice its [`crate::FileStamp`] changed. The directories the images are in get watched,
+/// rather than the files themselves, so a file that's saved by writing a new one and moving it
+/// over the old one is still caught.
+///
+/// It shares [`crate::FsEventsPlugin`]'s watcher, and leaves alone the directories that one's
+/// already watching. Where there's no watcher this does nothing, and
+/// the scans are all there is.
+pub struct FileWatchPlugin;
+
+/// The images the watcher's heard about that haven't settled yet
+#[derive(Resource, Default)]
+struct ImageFileWatch {
+    /// When each changed image was last written to
+    pending: HashMap<PathBuf, Duration>,
+}
+
+impl ImageFileWatch {
+    /// Editors often write a file in a few goes, so an image is only reloaded once it's been
+    /// left alone this long
+    const SETTLE: Duration = Duration::from_millis(500);
+}
+
+impl Plugin for FileWatchPlugin {
+    fn build(&self, app: &mut App) {
+        if let Err(e) = FsWatcher::add_to(app, false, true) {
+            log::info!("Not watching images for changes, rescans will pick them up: {e}");
+            return;
+        }
+        app.init_resource::<ImageFileWatch>();
+        app.add_systems(
+            PreUpdate,
+            (collect_changed_images, reload_settled_images)
+                .chain()
+                .after(DirWatchingSet::Scan)
+                .before(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+/// Note down when each image was last written to
+fn collect_changed_images(
+    time: Res<Time<Real>>,
+    watched_dirs: Res<WatchedDirs>,
+    watcher: Res<FsWatcher>,
+    mut watch: ResMut<ImageFileWatch>,
+) {
+    // Written in place, or written elsewhere and moved over the old file
+    let written = watcher.events.iter().filter(|event| {
+        matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Modify(_)
+                | EventKind::Access(AccessKind::Close(AccessMode::Write))
+        )
+    });
+    let mut written = written.flat_map(|event| &event.paths).peekable();
+    if written.peek().is_none() {
+        return;
+    }
+    // Other files in the same directories (sidecars, say) don't count
+    let images: HashSet<&PathBuf> = watched_dirs.images().iter().collect();
+    for path in written {
+        if images.contains(path) {
+            watch.pending.insert(path.clone(), time.elapsed());
+        }
+    }
+}
+
+/// Hand the images that have settled over to be reloaded, and keep frames coming until they all
+/// have, since an idle window only updates on input
+fn reload_settled_images(
+    time: Res<Time<Real>>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut watch: ResMut<ImageFileWatch>,
+    mut redraw: EventWriter<RequestRedraw>,
+) {
+    if watch.pending.is_empty() {
+        return;
+    }
+    let now = time.elapsed();
+    watch.pending.retain(|path, written| {
+        if now - *written < ImageFileWatch::SETTLE {
+            return true;
+        }
+        log::debug!("{path:?} was rewritten");
+        // Not a change to the images themselves, so nothing watching those needs waking
+        watched_dirs.bypass_change_detection().mark_modified(path);
+        false
+    });
+    if !watch.pending.is_empty() {
+        redraw.write(RequestRedraw);
+    }
+}
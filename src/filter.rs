@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+
+use std::path::Path;
+
+use crate::{
+    AppState, ColorPalette, ColorSearchFilter, ImageMarker, ScanCounter, StatusBar, Tags,
+    TextInput, text_input_inactive,
+};
+
+/// What the filter bar currently says. Whitespace separated terms that all have to match: plain
+/// terms match against the file name, `tag:foo` terms against the image's [`Tags`]. Both are
+/// case-insensitive.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageFilter {
+    pub query: String,
+}
+
+impl ImageFilter {
+    pub fn is_active(&self) -> bool {
+        !self.query.trim().is_empty()
+    }
+
+    /// Only the plain terms, for when the tags aren't known. Anything [`Self::matches`] lets
+    /// through, this does too.
+    pub fn matches_name(&self, path: &Path) -> bool {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        self.query
+            .split_whitespace()
+            .filter(|term| !term.starts_with("tag:"))
+            .all(|term| file_name.contains(&term.to_lowercase()))
+    }
+
+    pub fn matches(&self, path: &Path, tags: Option<&Tags>) -> bool {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        self.query
+            .split_whitespace()
+            .all(|term| match term.strip_prefix("tag:") {
+                Some(tag) => tags.is_some_and(|tags| tags.contains(tag)),
+                None => file_name.contains(&term.to_lowercase()),
+            })
+    }
+}
+
+/// Which scan first turned up a quad's image, going by the [`ScanCounter`]. Images seeded from
+/// the scan cache before the first scan count as scan 0.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredAt {
+    pub scan_number: u64,
+}
+
+/// The "recently added" view: while it's active, only images found in the last `max_scans_ago`
+/// scans (on top of the current one) are shown. N toggles it.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct RecentlyAddedFilter {
+    pub max_scans_ago: u64,
+    pub active: bool,
+}
+
+impl Default for RecentlyAddedFilter {
+    fn default() -> Self {
+        Self {
+            max_scans_ago: 10,
+            active: false,
+        }
+    }
+}
+
+impl RecentlyAddedFilter {
+    pub fn matches(&self, discovered: Option<&DiscoveredAt>, current_scan: u64) -> bool {
+        !self.active
+            || discovered.is_some_and(|discovered| {
+                current_scan.saturating_sub(discovered.scan_number) <= self.max_scans_ago
+            })
+    }
+}
+
+/// On quads the current [`ImageFilter`], [`RecentlyAddedFilter`] or [`ColorSearchFilter`]
+/// rejects. They're hidden, and stay hidden whatever visibility culling thinks.
+#[derive(Component, Debug)]
+pub struct FilteredOut;
+
+/// The filter bar's text field
+#[derive(Component)]
+pub(crate) struct FilterInput;
+
+/// A text field that drives [`ImageFilter`] as you type, for dropping into a sidebar
+pub fn filter_bar() -> impl Bundle {
+    (
+        FilterInput,
+        TextInput::new("Filter, e.g. beach tag:2024"),
+        Node {
+            min_width: Val::Px(200.0),
+            margin: UiRect::top(Val::Px(8.0)),
+            padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+    )
+}
+
+pub struct FilterPlugin;
+
+impl Plugin for FilterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ImageFilter>();
+        app.init_resource::<RecentlyAddedFilter>();
+        app.add_systems(
+            Update,
+            (
+                recently_added_hotkey_system
+                    .run_if(text_input_inactive)
+                    .run_if(in_state(AppState::Running)),
+                sync_filter_from_input,
+                filter_by_tag_system,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn sync_filter_from_input(
+    inputs: Query<&TextInput, (With<FilterInput>, Changed<TextInput>)>,
+    mut filter: ResMut<ImageFilter>,
+) {
+    for input in &inputs {
+        if filter.query != input.value {
+            filter.query.clone_from(&input.value);
+        }
+    }
+}
+
+/// Replace the [`ImageFilter`], and what the filter bar says along with it
+pub(crate) fn set_filter_query(
+    In(query): In<String>,
+    mut filter: ResMut<ImageFilter>,
+    mut inputs: Query<&mut TextInput, With<FilterInput>>,
+) {
+    for mut input in &mut inputs {
+        if input.value != query {
+            input.value.clone_from(&query);
+        }
+    }
+    if filter.query != query {
+        filter.query = query;
+    }
+}
+
+/// N toggles the "recently added" view
+fn recently_added_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut recent: ResMut<RecentlyAddedFilter>,
+    mut status: ResMut<StatusBar>,
+) {
+    if !keys.just_pressed(KeyCode::KeyN) {
+        return;
+    }
+    recent.active = !recent.active;
+    status.set(if recent.active {
+        format!(
+            "Showing images added in the last {} scans",
+            recent.max_scans_ago
+        )
+    } else {
+        "Showing all images".to_string()
+    });
+}
+
+/// Hide quads that don't match the filters. Everything gets rechecked when a filter changes (or
+/// a scan moves "recently" along), otherwise only quads that are new, had their tags edited or
+/// just got their colour palette.
+fn filter_by_tag_system(
+    mut commands: Commands,
+    filter: Res<ImageFilter>,
+    recent: Res<RecentlyAddedFilter>,
+    color: Res<ColorSearchFilter>,
+    scan_counter: Res<ScanCounter>,
+    mut quads: Query<(
+        Entity,
+        Ref<ImageMarker>,
+        Option<Ref<Tags>>,
+        Option<&DiscoveredAt>,
+        Option<Ref<ColorPalette>>,
+        Has<FilteredOut>,
+        &mut Visibility,
+    )>,
+) {
+    let recheck_all = filter.is_changed()
+        || recent.is_changed()
+        || color.is_changed()
+        || (recent.active && scan_counter.is_changed());
+    for (entity, marker, tags, discovered, palette, filtered_out, mut visibility) in &mut quads {
+        let tags_changed = tags.as_ref().is_some_and(|tags| tags.is_changed());
+        let palette_changed = color.active && palette.as_ref().is_some_and(|p| p.is_changed());
+        if !recheck_all && !marker.is_added() && !tags_changed && !palette_changed {
+            continue;
+        }
+
+        let matches = filter.matches(&marker.target, tags.as_deref())
+            && recent.matches(discovered, scan_counter.0)
+            && color.matches(palette.as_deref());
+        if matches && filtered_out {
+            commands.entity(entity).remove::<FilteredOut>();
+            // Culling takes it from here if it's off screen
+            *visibility = Visibility::Inherited;
+        } else if !matches && !filtered_out {
+            commands.entity(entity).insert(FilteredOut);
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
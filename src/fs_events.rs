@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver};
+
+use crate::{DirWatchingSet, RescanRequested, WatchedDirs};
+
+/// Paths the filesystem says were created, changed, moved or deleted since the scan system last
+/// looked, see [`crate::ScanBackend::Events`]. Whether each is there now is checked when they're
+/// applied, rather than going by what the events said, since they can arrive out of order.
+#[derive(Resource, Default, Debug)]
+pub(crate) struct FsEventChanges {
+    /// Whether the watched directories have watches on them, so the periodic scan can be skipped
+    pub(crate) watching: bool,
+    /// The watched directories that couldn't be watched after all (out of watches, say), which
+    /// the periodic scan still covers
+    pub(crate) unwatched: Vec<PathBuf>,
+    pub(crate) paths: HashSet<PathBuf>,
+}
+
+/// The one filesystem watcher, shared by [`FsEventsPlugin`] and [`crate::FileWatchPlugin`] so a
+/// directory never gets watched twice. Whichever plugin is added first creates it.
+#[derive(Resource)]
+pub(crate) struct FsWatcher {
+    watcher: RecommendedWatcher,
+    /// Only ever drained from one system, but resources have to be `Sync`
+    receiver: Mutex<Receiver<notify::Result<notify::Event>>>,
+    /// What's being watched, and whether recursively
+    watches: HashMap<PathBuf, bool>,
+    /// Watch every watched directory, for [`FsEventsPlugin`]
+    roots: bool,
+    /// Watch every directory with an image in it, for [`crate::FileWatchPlugin`]. The ones
+    /// already under a root's watch are left to that.
+    image_dirs: bool,
+    /// Each root that doesn't exist yet, and the closest ancestor of it that does, watched in its
+    /// place
+    waiting: HashMap<PathBuf, PathBuf>,
+    /// Whatever's been heard this frame
+    pub(crate) events: Vec<notify::Event>,
+}
+
+impl FsWatcher {
+    /// Make sure there's a watcher, and ask it to watch the roots and/or the image directories
+    pub(crate) fn add_to(app: &mut App, roots: bool, image_dirs: bool) -> notify::Result<()> {
+        if !app.world().contains_resource::<FsWatcher>() {
+            let (sender, receiver) = mpsc::channel();
+            let watcher = notify::recommended_watcher(move |event| {
+                // Only fails once the app's gone
+                let _ = sender.send(event);
+            })?;
+            app.insert_resource(FsWatcher {
+                watcher,
+                receiver: Mutex::new(receiver),
+                watches: HashMap::new(),
+                roots: false,
+                image_dirs: false,
+                waiting: HashMap::new(),
+                events: vec![],
+            });
+            app.add_systems(
+                PreUpdate,
+                (
+                    update_watches.run_if(resource_changed::<WatchedDirs>),
+                    collect_fs_events,
+                )
+                    .chain()
+                    .before(DirWatchingSet::Scan),
+            );
+        }
+        let mut watcher = app.world_mut().resource_mut::<FsWatcher>();
+        watcher.roots |= roots;
+        watcher.image_dirs |= image_dirs;
+        Ok(())
+    }
+
+    /// Whether `dir` already hears about the files directly inside it
+    fn covers(&self, dir: &Path) -> bool {
+        self.watches.contains_key(dir)
+            || dir
+                .ancestors()
+                .skip(1)
+                .any(|parent| self.watches.get(parent) == Some(&true))
+    }
+}
+
+/// Keeps [`WatchedDirs`] up to date from the filesystem's own change notifications (inotify,
+/// FSEvents, ReadDirectoryChangesW) instead of walking every directory on an interval. Where the
+/// platform has no watcher the periodic scan carries on as before, and it still covers any
+/// directory that can't be watched. One that doesn't exist yet is waited for by watching the
+/// closest ancestor that does.
+pub struct FsEventsPlugin;
+
+impl Plugin for FsEventsPlugin {
+    fn build(&self, app: &mut App) {
+        if let Err(e) = FsWatcher::add_to(app, true, false) {
+            log::info!("No filesystem events here, scanning every so often instead: {e}");
+            return;
+        }
+        app.add_systems(
+            PreUpdate,
+            (note_changed_paths, rescan_waited_for_dirs)
+                .after(collect_fs_events)
+                .before(DirWatchingSet::Scan),
+        );
+    }
+}
+
+/// Watch whatever the plugins asked for: the watched directories as they're configured (or, for
+/// the ones that don't exist yet, the closest ancestor that does), then any directory with an
+/// image in it that isn't under one of those already. Everything else stops being watched.
+fn update_watches(
+    watched_dirs: Res<WatchedDirs>,
+    mut watcher: ResMut<FsWatcher>,
+    mut changes: ResMut<FsEventChanges>,
+    mut rescans: EventWriter<RescanRequested>,
+) {
+    let waiting: HashMap<&Path, &Path> = if watcher.roots {
+        watched_dirs
+            .unreachable_dirs()
+            .filter_map(|(dir, status)| Some((dir, status.waiting_on.as_deref()?)))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let roots: HashMap<&Path, bool> = if watcher.roots {
+        watched_dirs
+            .watched_dirs()
+            .iter()
+            .filter(|dir| !waiting.contains_key(dir.path.as_path()))
+            .map(|dir| (dir.path.as_path(), dir.recursive))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    // Any watch on an ancestor hears its children appear, so one a root already has covers it
+    let ancestors: HashSet<&Path> = waiting
+        .values()
+        .copied()
+        .filter(|&ancestor| {
+            !roots.iter().any(|(&root, &recursive)| {
+                ancestor == root || (recursive && ancestor.starts_with(root))
+            })
+        })
+        .collect();
+    let image_dirs: HashSet<&Path> = if watcher.image_dirs {
+        watched_dirs
+            .images()
+            .iter()
+            .filter_map(|image| image.parent())
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let watcher = &mut *watcher;
+    // A root that's changed whether it's recursive is dropped here and watched again below
+    watcher.watches.retain(|path, recursive| {
+        let keep = roots.get(path.as_path()) == Some(recursive)
+            || (!*recursive
+                && (image_dirs.contains(path.as_path()) || ancestors.contains(path.as_path())));
+        if !keep && let Err(e) = watcher.watcher.unwatch(path) {
+            log::debug!("Couldn't stop watching {path:?}: {e}");
+        }
+        keep
+    });
+
+    let mut unwatched = vec![];
+    for (&root, &recursive) in &roots {
+        if watcher.watches.get(root) == Some(&recursive) {
+            continue;
+        }
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        match watcher.watcher.watch(root, mode) {
+            Ok(()) => {
+                watcher.watches.insert(root.to_path_buf(), recursive);
+            }
+            // Gone since it was last scanned, or out of watches
+            Err(e) => {
+                log::debug!("Couldn't watch {root:?}, scanning it instead: {e}");
+                unwatched.push(root.to_path_buf());
+            }
+        }
+    }
+    for ancestor in ancestors {
+        if watcher.watches.contains_key(ancestor) {
+            continue;
+        }
+        match watcher.watcher.watch(ancestor, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                watcher.watches.insert(ancestor.to_path_buf(), false);
+            }
+            Err(e) => log::debug!("Couldn't watch {ancestor:?} for what's waited on in it: {e}"),
+        }
+    }
+    for dir in image_dirs {
+        if watcher.covers(dir) {
+            continue;
+        }
+        match watcher.watcher.watch(dir, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                watcher.watches.insert(dir.to_path_buf(), false);
+            }
+            // Most likely out of watches, the scans still catch changes in this one
+            Err(e) => log::warn!("Couldn't watch {dir:?} for changes: {e}"),
+        }
+    }
+    unwatched.extend(
+        waiting
+            .iter()
+            .filter(|&(_, &ancestor)| !watcher.covers(ancestor))
+            .map(|(&root, _)| root.to_path_buf()),
+    );
+
+    // Whatever turned up between the scan that moved a watch and the watch going on would
+    // otherwise go unnoticed
+    let moved = watcher
+        .waiting
+        .keys()
+        .map(PathBuf::as_path)
+        .filter(|&root| roots.contains_key(root))
+        .chain(
+            waiting
+                .iter()
+                .filter(|&(&root, &ancestor)| {
+                    watcher.waiting.get(root).map(PathBuf::as_path) != Some(ancestor)
+                })
+                .map(|(&root, _)| root),
+        )
+        .map(RescanRequested::dir);
+    rescans.write_batch(moved);
+    watcher.waiting = waiting
+        .into_iter()
+        .map(|(root, ancestor)| (root.to_path_buf(), ancestor.to_path_buf()))
+        .collect();
+
+    if !watcher.roots {
+        return;
+    }
+    unwatched.sort();
+    if !changes.watching {
+        changes.watching = true;
+        log::info!("Watching for filesystem events, periodic scans are off");
+    }
+    if changes.unwatched != unwatched {
+        if !unwatched.is_empty() {
+            log::info!(
+                "Not every directory can be watched, periodic scans are back on for {}",
+                unwatched.len()
+            );
+        }
+        changes.unwatched = unwatched;
+    }
+}
+
+/// Hold on to this frame's events for the plugins to look through. If the watcher says it lost
+/// track of some (its queue overflowed, say) a full rescan catches up.
+fn collect_fs_events(mut watcher: ResMut<FsWatcher>, mut rescans: EventWriter<RescanRequested>) {
+    let watcher = &mut *watcher;
+    watcher.events.clear();
+    let receiver = watcher.receiver.lock().unwrap_or_else(|e| e.into_inner());
+    let mut rescan = false;
+    for event in receiver.try_iter() {
+        match event {
+            Ok(event) if event.need_rescan() => rescan = true,
+            Ok(event) => watcher.events.push(event),
+            Err(e) => {
+                log::warn!("Filesystem watcher error, rescanning: {e}");
+                rescan = true;
+            }
+        }
+    }
+    if rescan {
+        rescans.write(RescanRequested::all());
+    }
+}
+
+/// Note down every path the events mention
+fn note_changed_paths(watcher: Res<FsWatcher>, mut changes: ResMut<FsEventChanges>) {
+    for event in &watcher.events {
+        // Access events (opening a file to read it, say) change nothing
+        if !event.kind.is_access() {
+            changes.paths.extend(event.paths.iter().cloned());
+        }
+    }
+}
+
+/// The events mention a directory that's being waited for, or one on the way to it, so scan it to
+/// see whether it's there yet. That also moves the watch down if only part of the way has
+/// turned up.
+fn rescan_waited_for_dirs(watcher: Res<FsWatcher>, mut rescans: EventWriter<RescanRequested>) {
+    if watcher.waiting.is_empty() {
+        return;
+    }
+    let mut found = HashSet::new();
+    for path in watcher
+        .events
+        .iter()
+        .filter(|event| event.kind.is_create() || event.kind.is_modify())
+        .flat_map(|event| &event.paths)
+    {
+        for root in watcher.waiting.keys() {
+            if root.starts_with(path) && found.insert(root) {
+                rescans.write(RescanRequested::dir(root.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, RequestRedraw, WindowMode, WindowResolution},
+};
+
+use crate::AppState;
+
+/// Send this to switch the main window in or out of borderless fullscreen
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenRequested {
+    Toggle,
+    Enter,
+    Exit,
+}
+
+/// The window's size and place from before it went fullscreen, to put it back afterwards
+#[derive(Resource, Default)]
+struct WindowedGeometry(Option<(WindowResolution, WindowPosition)>);
+
+/// F11 or Alt+Enter toggle fullscreen
+pub struct FullscreenPlugin;
+
+impl Plugin for FullscreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FullscreenRequested>();
+        app.init_resource::<WindowedGeometry>();
+        app.add_systems(
+            Update,
+            (
+                fullscreen_hotkey_system.run_if(in_state(AppState::Running)),
+                fullscreen_system,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn fullscreen_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut requests: EventWriter<FullscreenRequested>,
+) {
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if keys.just_pressed(KeyCode::F11) || (alt && keys.just_pressed(KeyCode::Enter)) {
+        requests.write(FullscreenRequested::Toggle);
+    }
+}
+
+fn fullscreen_system(
+    mut requests: EventReader<FullscreenRequested>,
+    mut geometry: ResMut<WindowedGeometry>,
+    mut redraw: EventWriter<RequestRedraw>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+) {
+    let Some(&request) = requests.read().last() else {
+        return;
+    };
+    let fullscreen = window.mode != WindowMode::Windowed;
+    let wanted = match request {
+        FullscreenRequested::Toggle => !fullscreen,
+        FullscreenRequested::Enter => true,
+        FullscreenRequested::Exit => false,
+    };
+    if wanted == fullscreen {
+        return;
+    }
+
+    if wanted {
+        geometry.0 = Some((window.resolution.clone(), window.position));
+        window.mode = WindowMode::BorderlessFullscreen(MonitorSelection::Current);
+    } else {
+        window.mode = WindowMode::Windowed;
+        if let Some((resolution, position)) = geometry.0.take() {
+            window.resolution = resolution;
+            window.position = position;
+        }
+    }
+    // The UI and camera pick the new size up from the resize, make sure a frame follows it
+    redraw.write(RequestRedraw);
+}
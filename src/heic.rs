@@ -0,0 +1,121 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, RenderAssetUsages, io::Reader},
+    prelude::*,
+};
+use image::{DynamicImage, RgbaImage};
+use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+pub(crate) const HEIC_EXTENSIONS: &[&str] = &["heic", "heif"];
+
+/// Loads HEIC/HEIF images (what iPhones save) through libheif, which has to be installed for the
+/// `heic` feature to build. Only the primary image is shown, turned and mirrored the way the file
+/// says, so [`crate::PhotoMetadata`] never turns them again.
+pub struct HeicPlugin;
+
+impl Plugin for HeicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset_loader::<HeicLoader>();
+    }
+}
+
+#[derive(Default)]
+struct HeicLoader;
+
+#[derive(Debug)]
+pub(crate) enum HeicError {
+    Io(io::Error),
+    Heif(libheif_rs::HeifError),
+    /// Decoded, but not into the one interleaved plane asked for
+    NoPixels,
+}
+
+impl fmt::Display for HeicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Heif(e) => write!(f, "{e}"),
+            Self::NoPixels => write!(f, "libheif didn't give back any pixels"),
+        }
+    }
+}
+
+impl std::error::Error for HeicError {}
+
+impl From<io::Error> for HeicError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<libheif_rs::HeifError> for HeicError {
+    fn from(e: libheif_rs::HeifError) -> Self {
+        Self::Heif(e)
+    }
+}
+
+impl AssetLoader for HeicLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = HeicError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Image, HeicError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(Image::from_dynamic(
+            decode_heic(&bytes)?,
+            true,
+            RenderAssetUsages::default(),
+        ))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        HEIC_EXTENSIONS
+    }
+}
+
+pub(crate) fn is_heic(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| HEIC_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// The primary image in a HEIF file, decoded
+pub(crate) fn decode_heic(bytes: &[u8]) -> Result<DynamicImage, HeicError> {
+    let context = HeifContext::read_from_bytes(bytes)?;
+    let handle = context.primary_image_handle()?;
+    let decoded = LibHeif::new().decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)?;
+    let planes = decoded.planes();
+    let plane = planes.interleaved.ok_or(HeicError::NoPixels)?;
+
+    // Rows can be padded out past the last pixel
+    let row = plane.width as usize * 4;
+    let mut pixels = Vec::with_capacity(row * plane.height as usize);
+    for y in 0..plane.height as usize {
+        let start = y * plane.stride;
+        pixels.extend_from_slice(
+            plane
+                .data
+                .get(start..start + row)
+                .ok_or(HeicError::NoPixels)?,
+        );
+    }
+    let image =
+        RgbaImage::from_raw(plane.width, plane.height, pixels).ok_or(HeicError::NoPixels)?;
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+/// The size the primary image comes out at, turned the right way up, without decoding it
+pub(crate) fn heic_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let context = HeifContext::read_from_file(path.to_str()?).ok()?;
+    let handle = context.primary_image_handle().ok()?;
+    Some((handle.width(), handle.height()))
+}
@@ -0,0 +1,224 @@
+use bevy::{
+    ecs::spawn::SpawnIter,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+
+use crate::{ImageMarker, ImageTexture, Selection};
+
+/// Per-channel pixel counts of an image, on its quad once it's been worked out for the selection
+#[derive(Component, Debug, Clone)]
+pub struct Histogram {
+    pub r: [u32; 256],
+    pub g: [u32; 256],
+    pub b: [u32; 256],
+}
+
+impl Histogram {
+    /// Images bigger than this (either way) get shrunk to fit before counting, the shape of the
+    /// histogram barely changes and it's a lot less work
+    pub const MAX_DIMENSION: u32 = 4096;
+
+    /// Buckets are merged down to this many bars per channel for display
+    const BARS: usize = 64;
+
+    fn compute(image: Image) -> Option<Self> {
+        let mut image = image.try_into_dynamic().ok()?;
+        if image.width() > Self::MAX_DIMENSION || image.height() > Self::MAX_DIMENSION {
+            image = image.thumbnail(Self::MAX_DIMENSION, Self::MAX_DIMENSION);
+        }
+
+        let mut histogram = Self {
+            r: [0; 256],
+            g: [0; 256],
+            b: [0; 256],
+        };
+        for pixel in image.to_rgb8().pixels() {
+            let [r, g, b] = pixel.0;
+            histogram.r[r as usize] += 1;
+            histogram.g[g as usize] += 1;
+            histogram.b[b as usize] += 1;
+        }
+        Some(histogram)
+    }
+
+    /// `channel` summed down to [`Self::BARS`] buckets
+    fn bars(channel: &[u32; 256]) -> impl Iterator<Item = u32> + '_ {
+        channel
+            .chunks(256 / Self::BARS)
+            .map(|bucket| bucket.iter().sum())
+    }
+}
+
+/// A histogram being counted in the background for the quad it's on
+#[derive(Component)]
+struct HistogramTask(Task<Option<Histogram>>);
+
+/// One row of bars in the info panel, per channel
+#[derive(Component, Clone, Copy)]
+enum HistogramRow {
+    Red,
+    Green,
+    Blue,
+}
+
+impl HistogramRow {
+    fn color(self) -> Color {
+        match self {
+            HistogramRow::Red => Color::srgb(0.9, 0.25, 0.25),
+            HistogramRow::Green => Color::srgb(0.25, 0.8, 0.3),
+            HistogramRow::Blue => Color::srgb(0.3, 0.45, 0.95),
+        }
+    }
+
+    fn channel(self, histogram: &Histogram) -> &[u32; 256] {
+        match self {
+            HistogramRow::Red => &histogram.r,
+            HistogramRow::Green => &histogram.g,
+            HistogramRow::Blue => &histogram.b,
+        }
+    }
+}
+
+/// The bars for the selected image's histogram, for the info panel
+pub(crate) fn histogram_view() -> impl Bundle {
+    (
+        Node {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(2.0),
+            ..default()
+        },
+        children![
+            histogram_row(HistogramRow::Red),
+            histogram_row(HistogramRow::Green),
+            histogram_row(HistogramRow::Blue),
+        ],
+    )
+}
+
+fn histogram_row(row: HistogramRow) -> impl Bundle {
+    let bars: Vec<_> = (0..Histogram::BARS)
+        .map(|_| {
+            (
+                Node {
+                    width: Val::Px(3.0),
+                    height: Val::Percent(0.0),
+                    ..default()
+                },
+                BackgroundColor(row.color()),
+            )
+        })
+        .collect();
+
+    (
+        row,
+        Node {
+            height: Val::Px(32.0),
+            align_items: AlignItems::FlexEnd,
+            ..default()
+        },
+        Children::spawn(SpawnIter(bars.into_iter())),
+    )
+}
+
+pub struct HistogramPlugin;
+
+impl Plugin for HistogramPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                compute_histogram_system.run_if(resource_changed::<Selection>),
+                poll_histogram_tasks,
+                update_histogram_view,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Start counting the selected image's pixels, unless it's already been done or is underway
+fn compute_histogram_system(
+    mut commands: Commands,
+    selected: Res<Selection>,
+    images: Res<Assets<Image>>,
+    quads: Query<
+        (Entity, &ImageMarker, &ImageTexture),
+        (Without<Histogram>, Without<HistogramTask>),
+    >,
+) {
+    let Some(path) = selected.path() else {
+        return;
+    };
+    let Some((entity, _, texture)) = quads.iter().find(|(_, marker, _)| marker.target == *path)
+    else {
+        return;
+    };
+    // Not loaded yet, we'll try again next time it's selected
+    let Some(image) = images.get(&texture.0).cloned() else {
+        return;
+    };
+
+    let task = AsyncComputeTaskPool::get().spawn(async move { Histogram::compute(image) });
+    commands.entity(entity).insert(HistogramTask(task));
+}
+
+fn poll_histogram_tasks(mut commands: Commands, mut tasks: Query<(Entity, &mut HistogramTask)>) {
+    for (entity, mut task) in &mut tasks {
+        let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        let mut entity = commands.entity(entity);
+        entity.remove::<HistogramTask>();
+        match result {
+            Some(histogram) => {
+                entity.insert(histogram);
+            }
+            None => log::warn!("Couldn't read pixels for a histogram"),
+        }
+    }
+}
+
+/// Size the bars to the selected image's histogram, scaled so the tallest bucket fills its row
+fn update_histogram_view(
+    selected: Res<Selection>,
+    quads: Query<(&ImageMarker, Ref<Histogram>)>,
+    rows: Query<(&HistogramRow, &Children)>,
+    mut bars: Query<&mut Node>,
+) {
+    let histogram = selected
+        .path()
+        .and_then(|path| quads.iter().find(|(marker, _)| marker.target == *path))
+        .map(|(_, histogram)| histogram);
+    let histogram_changed = histogram.as_ref().is_some_and(|h| h.is_changed());
+    if !selected.is_changed() && !histogram_changed {
+        return;
+    }
+
+    let peak = histogram
+        .as_ref()
+        .map(|histogram| {
+            [HistogramRow::Red, HistogramRow::Green, HistogramRow::Blue]
+                .into_iter()
+                .flat_map(|row| Histogram::bars(row.channel(histogram)))
+                .max()
+                .unwrap_or(0)
+        })
+        .unwrap_or(0)
+        .max(1);
+
+    for (row, children) in &rows {
+        let heights: Vec<f32> = match &histogram {
+            Some(histogram) => Histogram::bars(row.channel(histogram))
+                .map(|count| count as f32 / peak as f32 * 100.0)
+                .collect(),
+            None => vec![0.0; Histogram::BARS],
+        };
+        for (child, height) in children.iter().zip(heights) {
+            if let Ok(mut node) = bars.get_mut(child) {
+                node.height = Val::Percent(height);
+            }
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use bevy::prelude::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    Favorites, ManualRotation, RescanRequested, ScanErrors, StatusBar, WatchedDirs,
+    WatchedDirsSnapshot,
+    delete::trash_files,
+    platform,
+    rating::{Rating, set_ratings},
+    rotation::{rotate_images, set_rotations},
+    text_input_inactive,
+};
+
+/// Something the user did that can be taken back. Whatever does the action in the first place
+/// records it in the [`History`] afterwards; [`Action::apply`] is only for redoing it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Started watching a directory, with the watched directories before and after
+    WatchDir {
+        dir: PathBuf,
+        before: WatchedDirsSnapshot,
+        after: WatchedDirsSnapshot,
+    },
+    /// Stopped watching a directory, with the watched directories before and after
+    UnwatchDir {
+        dir: PathBuf,
+        before: WatchedDirsSnapshot,
+        after: WatchedDirsSnapshot,
+    },
+    /// Turned scanning a watched directory's subdirectories on or off
+    SetRecursive { dir: PathBuf, recursive: bool },
+    /// Favorited or unfavorited some images, along with whether each one was a favorite before
+    SetFavorite {
+        favorite: bool,
+        previous: Vec<(PathBuf, bool)>,
+    },
+    /// Gave some images a rating (0 clearing it), along with what each one had before
+    SetRating {
+        rating: Rating,
+        previous: Vec<(PathBuf, Option<Rating>)>,
+    },
+    /// Moved images to the trash
+    Trash(Vec<PathBuf>),
+    /// Turned images by some quarter turns, clockwise if positive
+    Rotate {
+        paths: Vec<PathBuf>,
+        quarter_turns: i32,
+    },
+    /// Put images back the right way up, with how far each one was turned before
+    ResetRotation {
+        previous: Vec<(PathBuf, ManualRotation)>,
+    },
+}
+
+impl Action {
+    /// Whether [`Self::revert`] can work at all here. Undo skips over the ones that can't, rather
+    /// than pretending.
+    pub fn is_reversible(&self) -> bool {
+        match self {
+            Action::Trash(_) => platform::CAN_RESTORE_FROM_TRASH,
+            _ => true,
+        }
+    }
+
+    /// What the action was, for status messages ("Undid ...")
+    pub fn describe(&self) -> String {
+        let images = |count: usize| match count {
+            1 => "1 image".to_string(),
+            count => format!("{count} images"),
+        };
+        match self {
+            Action::WatchDir { dir, .. } => format!("watching {}", dir.display()),
+            Action::UnwatchDir { dir, .. } => format!("unwatching {}", dir.display()),
+            Action::SetRecursive { dir, recursive } => {
+                let verb = if *recursive {
+                    "including"
+                } else {
+                    "leaving out"
+                };
+                format!("{verb} the subfolders of {}", dir.display())
+            }
+            Action::SetFavorite { favorite, previous } => {
+                let verb = if *favorite {
+                    "favoriting"
+                } else {
+                    "unfavoriting"
+                };
+                format!("{verb} {}", images(previous.len()))
+            }
+            Action::SetRating { rating, previous } => match rating.0 {
+                0 => format!("clearing the rating of {}", images(previous.len())),
+                1 => format!("rating {} 1 star", images(previous.len())),
+                stars => format!("rating {} {stars} stars", images(previous.len())),
+            },
+            Action::Trash(paths) => format!("trashing {}", images(paths.len())),
+            Action::Rotate { paths, .. } => format!("rotating {}", images(paths.len())),
+            Action::ResetRotation { previous } => {
+                format!("resetting the rotation of {}", images(previous.len()))
+            }
+        }
+    }
+
+    /// Do the action (again)
+    pub fn apply(&self, world: &mut World) -> Result<(), String> {
+        match self {
+            Action::WatchDir { after, .. } | Action::UnwatchDir { after, .. } => {
+                restore_dirs(world, after)
+            }
+            Action::SetRecursive { dir, recursive } => set_recursive(world, dir, *recursive),
+            Action::SetFavorite { favorite, previous } => {
+                let mut favorites = world.resource_mut::<Favorites>();
+                for (path, _) in previous {
+                    favorites.set(path.clone(), *favorite);
+                }
+                Ok(())
+            }
+            Action::SetRating { rating, previous } => rate(
+                world,
+                previous
+                    .iter()
+                    .map(|(path, _)| (path.clone(), *rating))
+                    .collect(),
+            ),
+            Action::Trash(paths) => {
+                let trashed = world
+                    .run_system_cached_with(trash_files, paths.clone())
+                    .map_err(|e| e.to_string())?;
+                if trashed.len() == paths.len() {
+                    Ok(())
+                } else {
+                    Err("some of the images couldn't be trashed".to_string())
+                }
+            }
+            Action::Rotate {
+                paths,
+                quarter_turns,
+            } => rotate(world, paths, *quarter_turns),
+            Action::ResetRotation { previous } => set_rotations_to(
+                world,
+                previous
+                    .iter()
+                    .map(|(path, _)| (path.clone(), ManualRotation::default()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Undo the action
+    pub fn revert(&self, world: &mut World) -> Result<(), String> {
+        match self {
+            Action::WatchDir { before, .. } | Action::UnwatchDir { before, .. } => {
+                restore_dirs(world, before)
+            }
+            Action::SetRecursive { dir, recursive } => set_recursive(world, dir, !*recursive),
+            Action::SetFavorite { previous, .. } => {
+                let mut favorites = world.resource_mut::<Favorites>();
+                for (path, was_favorite) in previous {
+                    favorites.set(path.clone(), *was_favorite);
+                }
+                Ok(())
+            }
+            Action::SetRating { previous, .. } => rate(
+                world,
+                previous
+                    .iter()
+                    .map(|(path, rating)| (path.clone(), rating.unwrap_or_default()))
+                    .collect(),
+            ),
+            Action::Trash(paths) => {
+                platform::restore_from_trash(paths).map_err(|e| e.to_string())?;
+                // Let the scanner put them back in the grid
+                world.send_event(RescanRequested::all());
+                Ok(())
+            }
+            Action::Rotate {
+                paths,
+                quarter_turns,
+            } => rotate(world, paths, -*quarter_turns),
+            Action::ResetRotation { previous } => set_rotations_to(world, previous.clone()),
+        }
+    }
+}
+
+/// Put the watched directories back how `snapshot` has them, scanning the ones that came back
+fn restore_dirs(world: &mut World, snapshot: &WatchedDirsSnapshot) -> Result<(), String> {
+    let rescan = world
+        .resource_mut::<WatchedDirs>()
+        .restore(snapshot.clone());
+    for dir in rescan {
+        world.send_event(RescanRequested::dir(dir));
+    }
+    Ok(())
+}
+
+fn set_recursive(world: &mut World, dir: &Path, recursive: bool) -> Result<(), String> {
+    let mut watched_dirs = world.resource_mut::<WatchedDirs>();
+    if watched_dirs.watched_dir(dir).is_none() {
+        return Err(format!("{} isn't being watched", dir.display()));
+    }
+    watched_dirs.set_recursive(dir, recursive);
+    world.send_event(RescanRequested::dir(dir));
+    Ok(())
+}
+
+fn rate(world: &mut World, ratings: HashMap<PathBuf, Rating>) -> Result<(), String> {
+    world
+        .run_system_cached_with(set_ratings, ratings)
+        .map_err(|e| e.to_string())
+}
+
+fn rotate(world: &mut World, paths: &[PathBuf], quarter_turns: i32) -> Result<(), String> {
+    let rotated = world
+        .run_system_cached_with(rotate_images, (paths.to_vec(), quarter_turns))
+        .map_err(|e| e.to_string())?;
+    if rotated.len() == paths.len() {
+        Ok(())
+    } else {
+        Err("some of the images couldn't be rotated".to_string())
+    }
+}
+
+fn set_rotations_to(
+    world: &mut World,
+    rotations: Vec<(PathBuf, ManualRotation)>,
+) -> Result<(), String> {
+    let count = rotations.len();
+    let rotated = world
+        .run_system_cached_with(set_rotations, rotations)
+        .map_err(|e| e.to_string())?;
+    if rotated.len() == count {
+        Ok(())
+    } else {
+        Err("some of the images couldn't be rotated".to_string())
+    }
+}
+
+/// Recent [`Action`]s, for Ctrl+Z and Ctrl+Shift+Z to walk back and forth through. Only the last
+/// [`History::CAPACITY`] are kept, and anything undone is forgotten as soon as something new is
+/// recorded, along with whatever paths it was holding on to.
+#[derive(Resource, Debug, Default)]
+pub struct History {
+    undo: VecDeque<Action>,
+    redo: Vec<Action>,
+}
+
+impl History {
+    pub const CAPACITY: usize = 100;
+
+    /// Remember an action that just happened
+    pub fn record(&mut self, action: Action) {
+        self.redo.clear();
+        self.undo.push_back(action);
+        if self.undo.len() > Self::CAPACITY {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo = VecDeque::new();
+        self.redo = Vec::new();
+    }
+}
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>();
+        app.add_systems(Update, undo_hotkey_system.run_if(text_input_inactive));
+    }
+}
+
+/// Ctrl+Z undoes, Ctrl+Shift+Z (or Ctrl+Y) redoes
+fn undo_hotkey_system(mut commands: Commands, keys: Res<ButtonInput<KeyCode>>) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !ctrl {
+        return;
+    }
+    if keys.just_pressed(KeyCode::KeyZ) && !shift {
+        commands.queue(undo);
+    } else if keys.just_pressed(KeyCode::KeyZ) || keys.just_pressed(KeyCode::KeyY) {
+        commands.queue(redo);
+    }
+}
+
+fn undo(world: &mut World) {
+    let Some(action) = world.resource_mut::<History>().undo.pop_back() else {
+        world.resource_mut::<StatusBar>().set("Nothing to undo");
+        return;
+    };
+    if !action.is_reversible() {
+        world.resource_mut::<StatusBar>().set(format!(
+            "Can't undo {} on this platform, skipped it",
+            action.describe()
+        ));
+        return;
+    }
+
+    match action.revert(world) {
+        Ok(()) => {
+            world
+                .resource_mut::<StatusBar>()
+                .set(format!("Undid {}", action.describe()));
+            world.resource_mut::<History>().redo.push(action);
+        }
+        Err(e) => {
+            log::warn!("Couldn't undo {}: {e}", action.describe());
+            world
+                .resource_mut::<ScanErrors>()
+                .push(format!("Couldn't undo {}: {e}", action.describe()));
+        }
+    }
+}
+
+fn redo(world: &mut World) {
+    let Some(action) = world.resource_mut::<History>().redo.pop() else {
+        world.resource_mut::<StatusBar>().set("Nothing to redo");
+        return;
+    };
+
+    match action.apply(world) {
+        Ok(()) => {
+            world
+                .resource_mut::<StatusBar>()
+                .set(format!("Redid {}", action.describe()));
+            world.resource_mut::<History>().undo.push_back(action);
+        }
+        Err(e) => {
+            log::warn!("Couldn't redo {}: {e}", action.describe());
+            world
+                .resource_mut::<ScanErrors>()
+                .push(format!("Couldn't redo {}: {e}", action.describe()));
+        }
+    }
+}
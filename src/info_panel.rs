@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+
+use crate::{
+    ImageMarker, Selection, SourceArchive, Tags, TextInput, Themed,
+    histogram::histogram_view,
+    tags::{TagInput, tag_input},
+};
+
+/// Root of the panel describing the selected image. Hidden while nothing is selected.
+#[derive(Component)]
+pub struct InfoPanel;
+
+#[derive(Component)]
+struct InfoPanelTitle;
+
+#[derive(Component)]
+struct InfoPanelTags;
+
+pub struct InfoPanelPlugin;
+
+impl Plugin for InfoPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_info_panel);
+        app.add_systems(Update, update_info_panel);
+    }
+}
+
+fn spawn_info_panel(mut commands: Commands) {
+    commands.spawn((
+        InfoPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            right: Val::Px(0.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            padding: UiRect::all(Val::Px(12.0)),
+            display: Display::None,
+            ..default()
+        },
+        Themed::Panel,
+        children![
+            (InfoPanelTitle, Text::default(), Themed::Text),
+            (
+                InfoPanelTags,
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                Themed::Text,
+            ),
+            tag_input(),
+            histogram_view(),
+        ],
+    ));
+}
+
+fn update_info_panel(
+    selected: Res<Selection>,
+    quads: Query<(&ImageMarker, Option<Ref<Tags>>, Option<&SourceArchive>)>,
+    mut panel: Single<&mut Node, With<InfoPanel>>,
+    mut title: Single<&mut Text, (With<InfoPanelTitle>, Without<InfoPanelTags>)>,
+    mut tags_text: Single<&mut Text, (With<InfoPanelTags>, Without<InfoPanelTitle>)>,
+    mut tag_field: Single<&mut TextInput, With<TagInput>>,
+) {
+    let Some(path) = selected.path() else {
+        if selected.is_changed() {
+            panel.display = Display::None;
+        }
+        return;
+    };
+    let (tags, archive) = quads
+        .iter()
+        .find(|(marker, ..)| marker.target == *path)
+        .map_or((None, None), |(_, tags, archive)| (tags, archive));
+    let tags_changed = tags.as_ref().is_some_and(|tags| tags.is_changed());
+    if !selected.is_changed() && !tags_changed {
+        return;
+    }
+
+    panel.display = Display::Flex;
+    title.0 = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned();
+    // Its real path is somewhere in a temporary directory, which says nothing useful
+    if let Some(archive) = archive {
+        title.0 = format!("{}\nfrom {}", title.0, archive.0.display());
+    }
+
+    let joined = tags.map(|tags| tags.0.join(", ")).unwrap_or_default();
+    tags_text.0 = if joined.is_empty() {
+        "No tags".to_string()
+    } else {
+        format!("Tags: {joined}")
+    };
+    // Start editing from what's already there
+    tag_field.value = joined;
+}
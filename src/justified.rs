@@ -0,0 +1,174 @@
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowResized},
+};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    CurrentPage, DimensionsProbed, DirWatchingSet, GridConfig, GridLayout, WatchedDirs,
+    dimensions::fitted_quad_size, paging::update_current_page,
+};
+
+/// Where each image on the [`CurrentPage`] goes in the [`GridLayout::Justified`] rows, and how
+/// big it is there. Empty with any other layout.
+///
+/// Images keep their aspect ratios (square until their size has been probed, see
+/// [`crate::DimensionsPlugin`]) and are packed into rows about [`GridConfig::row_height`] tall.
+/// Each full row is then scaled so it exactly fills the width, which is picked to give the
+/// whole grid the window's shape. The last row keeps the target height rather than being
+/// stretched out.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct JustifiedLayout {
+    cells: HashMap<PathBuf, JustifiedCell>,
+    /// Width and height of the whole layout, gaps included
+    extent: Option<Vec2>,
+}
+
+/// An image's place in the [`JustifiedLayout`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JustifiedCell {
+    /// Centre of the image, like [`crate::calculate_grid_position_2d`]
+    pub center: Vec2,
+    /// Width and height of the image
+    pub size: Vec2,
+    /// What the quad is scaled by to get to `size`. Quads are made to fit in a
+    /// [`GridConfig::quad_size`] square, see [`crate::DimensionsPlugin`].
+    pub scale: f32,
+}
+
+impl JustifiedLayout {
+    /// Lay out `images`, with their pixel sizes from `dimensions`, in rows whose width is
+    /// `aspect` times the height of the whole layout (roughly)
+    pub fn new(
+        images: &[PathBuf],
+        dimensions: impl Fn(&Path) -> Option<(u32, u32)>,
+        config: &GridConfig,
+        aspect: f32,
+    ) -> Self {
+        let row_height = config.row_height.max(f32::EPSILON);
+        let gap = (config.spacing - config.quad_size).max(0.0);
+        let sizes: Vec<Option<(u32, u32)>> = images.iter().map(|path| dimensions(path)).collect();
+        let ratios: Vec<f32> = sizes
+            .iter()
+            .map(|size| match size {
+                Some((width, height)) => *width as f32 / (*height).max(1) as f32,
+                None => 1.0,
+            })
+            .collect();
+
+        // Wide enough for the area the images take up at the target height to come out at the
+        // window's shape, and for the widest of them on its own
+        let area: f32 = ratios
+            .iter()
+            .map(|ratio| (ratio * row_height + gap) * (row_height + gap))
+            .sum();
+        let widest = ratios.iter().copied().fold(1.0, f32::max) * row_height;
+        let width = (area * aspect.max(f32::EPSILON)).sqrt().max(widest);
+
+        // (first image, last image + 1, height) of each row
+        let mut rows = vec![];
+        let mut start = 0;
+        let mut ratio_sum = 0.0;
+        for (index, ratio) in ratios.iter().enumerate() {
+            ratio_sum += ratio;
+            let count = (index + 1 - start) as f32;
+            let natural = ratio_sum * row_height + gap * (count - 1.0);
+            if natural >= width {
+                let height = (width - gap * (count - 1.0)) / ratio_sum;
+                rows.push((start, index + 1, height));
+                start = index + 1;
+                ratio_sum = 0.0;
+            }
+        }
+        if start < ratios.len() {
+            rows.push((start, ratios.len(), row_height));
+        }
+
+        let height = rows.iter().map(|&(_, _, height)| height).sum::<f32>()
+            + gap * rows.len().saturating_sub(1) as f32;
+        let mut layout = Self {
+            extent: Some(Vec2::new(width, height)),
+            ..default()
+        };
+        // Centred on the origin like the other layouts, first row at the top
+        let mut top = height * 0.5;
+        for (start, end, row_height) in rows {
+            let mut left = -width * 0.5;
+            let row = images[start..end]
+                .iter()
+                .zip(&ratios[start..end])
+                .zip(&sizes[start..end]);
+            for ((path, ratio), pixels) in row {
+                let size = Vec2::new(ratio * row_height, row_height);
+                let quad = fitted_quad_size(*pixels, config.quad_size);
+                layout.cells.insert(
+                    path.clone(),
+                    JustifiedCell {
+                        center: Vec2::new(left + size.x * 0.5, top - size.y * 0.5),
+                        size,
+                        scale: size.y / quad.y.max(f32::EPSILON),
+                    },
+                );
+                left += size.x + gap;
+            }
+            top -= row_height + gap;
+        }
+        layout
+    }
+
+    /// Where `image` goes, `None` if it's not laid out
+    pub fn cell(&self, image: &Path) -> Option<JustifiedCell> {
+        self.cells.get(image).copied()
+    }
+
+    /// How wide and tall the layout is. `None` with any other layout.
+    pub fn extent(&self) -> Option<Vec2> {
+        self.extent
+    }
+}
+
+pub struct JustifiedLayoutPlugin;
+
+impl Plugin for JustifiedLayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<JustifiedLayout>();
+        app.add_systems(
+            PreUpdate,
+            update_justified_layout
+                .run_if(
+                    resource_changed::<CurrentPage>
+                        .or(resource_changed::<GridConfig>)
+                        .or(resource_changed::<WatchedDirs>)
+                        // Sizes are added without touching the image list
+                        .or(on_event::<DimensionsProbed>)
+                        .or(on_event::<WindowResized>),
+                )
+                .after(update_current_page)
+                .before(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+fn update_justified_layout(
+    page: Res<CurrentPage>,
+    grid_config: Res<GridConfig>,
+    watched_dirs: Res<WatchedDirs>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    mut layout: ResMut<JustifiedLayout>,
+) {
+    if grid_config.layout != GridLayout::Justified {
+        layout.set_if_neq(JustifiedLayout::default());
+        return;
+    }
+    let aspect = window.map_or(16.0 / 9.0, |window| {
+        window.width() / window.height().max(1.0)
+    });
+    layout.set_if_neq(JustifiedLayout::new(
+        page.images(),
+        |path| watched_dirs.dimensions(path),
+        &grid_config,
+        aspect,
+    ));
+}
@@ -0,0 +1,304 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+use crate::{CurrentPage, DirectoryGroups, JustifiedLayout, Timeline, TimelineBucket};
+
+/// How the quads are arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GridLayout {
+    /// Roughly square grid, `ceil(sqrt(n))` columns
+    #[default]
+    Square,
+    /// Everything in one long row
+    Strip,
+    /// Grouped by when they were taken, a row or more per day (or month), see [`Timeline`]
+    Timeline,
+    /// Rows of images in their own aspect ratios, each row scaled to fill the width, see
+    /// [`JustifiedLayout`]
+    Justified,
+    /// A square grid for each watched directory, side by side, see [`DirectoryGroups`]
+    GroupByDirectory,
+}
+
+/// Which plane the grid is laid out on, and so which way the quads face
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GridPlane {
+    /// Upright and facing +Z, where the default camera is looking from
+    #[default]
+    Xy,
+    /// Flat on the ground at y = 0 and facing up, first row furthest from the default camera
+    Xz,
+    /// Upright across the camera's view through the origin, facing the camera
+    FacingCamera,
+}
+
+impl GridPlane {
+    /// Where a quad goes given its `cell` ([`calculate_grid_position_2d`]). `FacingCamera` needs
+    /// the camera, and falls back to `Xy` without one.
+    pub fn place(self, cell: Vec2, camera: Option<&Transform>) -> Transform {
+        match (self, camera) {
+            (GridPlane::Xz, _) => Transform::from_xyz(cell.x, 0.0, -cell.y)
+                // Turn the quad's +Z normal to point up
+                .with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)),
+            (GridPlane::FacingCamera, Some(camera)) => {
+                Transform::from_translation(camera.rotation * cell.extend(0.0))
+                    .with_rotation(camera.rotation)
+            }
+            (GridPlane::Xy | GridPlane::FacingCamera, _) => {
+                Transform::from_translation(cell.extend(0.0))
+            }
+        }
+    }
+}
+
+/// A surface to wrap the grid around, with the camera in the middle looking out at it. Takes over
+/// from the [`GridPlane`] when it isn't `Flat`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum WallMode {
+    /// Just the [`GridPlane`]
+    #[default]
+    Flat,
+    /// Columns spread evenly all the way round the Y axis, rows stacked up it
+    Cylinder { radius: f32 },
+    /// Columns round the Y axis like the cylinder, rows spread from pole to pole
+    Sphere { radius: f32 },
+}
+
+impl WallMode {
+    /// Radius the walls start out with when switched to without one
+    pub const DEFAULT_RADIUS: f32 = 20.0;
+
+    /// Flat, then the cylinder, then the sphere, then flat again. The radius carries over.
+    pub fn next(self) -> Self {
+        match self {
+            WallMode::Flat => WallMode::Cylinder {
+                radius: Self::DEFAULT_RADIUS,
+            },
+            WallMode::Cylinder { radius } => WallMode::Sphere { radius },
+            WallMode::Sphere { .. } => WallMode::Flat,
+        }
+    }
+
+    /// Where a quad at `cell` ([`calculate_grid_position_2d`]) goes on the wall, facing in towards
+    /// the origin, for a grid `extent` across and down. `None` when flat.
+    pub fn place(self, cell: Vec2, extent: Vec2) -> Option<Transform> {
+        use std::f32::consts::{PI, TAU};
+
+        // The middle of the grid is straight ahead, down -Z, and it's the far edges that meet
+        let around = Quat::from_rotation_y(-TAU * cell.x / extent.x.max(f32::EPSILON));
+        let (rotation, radius, height) = match self {
+            WallMode::Flat => return None,
+            WallMode::Cylinder { radius } => (around, radius, cell.y),
+            WallMode::Sphere { radius } => {
+                let elevation = PI * cell.y / extent.y.max(f32::EPSILON);
+                (around * Quat::from_rotation_x(elevation), radius, 0.0)
+            }
+        };
+        // The quad's +Z normal is turned back towards the middle along with it
+        Some(
+            Transform::from_translation(rotation * Vec3::new(0.0, 0.0, -radius) + Vec3::Y * height)
+                .with_rotation(rotation),
+        )
+    }
+}
+
+/// Layout settings for the image grid
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GridConfig {
+    pub layout: GridLayout,
+    pub plane: GridPlane,
+    /// Distance between the centres of neighbouring quads
+    pub spacing: f32,
+    /// Edge length of each (square) quad
+    pub quad_size: f32,
+    /// What the [`GridLayout::Timeline`] groups by
+    pub timeline: TimelineBucket,
+    pub wall: WallMode,
+    /// How tall the rows of the [`GridLayout::Justified`] aim to be, before they're scaled to
+    /// fill the width
+    pub row_height: f32,
+    /// Space between the directories' grids in the [`GridLayout::GroupByDirectory`], on top of
+    /// the usual spacing
+    pub group_gap: f32,
+    /// Size the [`GridLayout::Square`] to the window rather than the image count, reflowing it
+    /// as the window's resized. Only [`RenderMode::Sprite2d`] does this, see
+    /// [`crate::SpriteModePlugin`].
+    pub fit_to_window: bool,
+    /// Fewest columns fitting to the window will leave the grid with
+    pub min_columns: i32,
+    /// Most columns fitting to the window will give the grid
+    pub max_columns: i32,
+    /// How many columns fit across the window just now, worked out again whenever it's resized
+    #[serde(skip)]
+    pub fitted_columns: Option<i32>,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            layout: GridLayout::default(),
+            plane: GridPlane::default(),
+            spacing: 2.5,
+            quad_size: 2.0,
+            timeline: TimelineBucket::default(),
+            wall: WallMode::default(),
+            row_height: 2.0,
+            group_gap: 5.0,
+            fit_to_window: false,
+            min_columns: 1,
+            max_columns: 64,
+            fitted_columns: None,
+        }
+    }
+}
+
+impl GridConfig {
+    /// (columns, rows) needed to fit `count` images, never less than 1x1. The timeline is as wide
+    /// as the square grid, but its gaps make it taller than this, see [`Timeline`]. A square grid
+    /// that's [`Self::fit_to_window`] is as wide as the window allows instead.
+    pub fn dimensions(&self, count: usize) -> (i32, i32) {
+        let count = count.max(1) as i32;
+        let square = (count as f32).sqrt().ceil() as i32;
+        let columns = match self.layout {
+            GridLayout::Square => match self.fitted_columns {
+                Some(columns) if self.fit_to_window => columns.max(1),
+                _ => square,
+            },
+            GridLayout::Timeline | GridLayout::Justified | GridLayout::GroupByDirectory => square,
+            GridLayout::Strip => count,
+        };
+        let rows = (count + columns - 1) / columns;
+        (columns, rows)
+    }
+}
+
+/// Helper function to calculate the grid cell for an image quad, centred on the origin with the
+/// rows running down +Z. This is only the flat cell, [`calculate_grid_position_2d`] hands it to
+/// [`GridPlane::place`] to put it on the configured plane.
+pub fn calculate_grid_position(index: usize, columns: i32, rows: i32, spacing: f32) -> Vec3 {
+    let row = (index as i32) / columns;
+    let col = (index as i32) % columns;
+
+    // Center the grid around origin
+    let offset_x = (columns as f32 - 1.0) * spacing * 0.5;
+    let offset_z = (rows as f32 - 1.0) * spacing * 0.5;
+
+    Vec3::new(
+        (col as f32 * spacing) - offset_x,
+        0.0, // small bump
+        (row as f32 * spacing) - offset_z,
+    )
+}
+
+/// [`calculate_grid_position`] as the `cell` [`GridPlane::place`] takes, first row at the top
+pub fn calculate_grid_position_2d(index: usize, columns: i32, rows: i32, spacing: f32) -> Vec2 {
+    let position = calculate_grid_position(index, columns, rows, spacing);
+    Vec2::new(position.x, -position.z)
+}
+
+/// What the grid is drawn with
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Quads with our own unlit material, seen through a `Camera3d`, each cell put on the
+    /// [`GridPlane`] (or the [`WallMode`]) by [`RenderMode::place`]
+    #[default]
+    Mesh3d,
+    /// Plain sprites on the XY plane (whatever the [`GridPlane`]), seen through an orthographic
+    /// `Camera2d`. The extras that work off the 3D camera (culling, the texture budget, zoom and
+    /// pan keys, ...) leave these alone.
+    Sprite2d,
+}
+
+impl RenderMode {
+    /// Where the quad for image `index` goes in this mode
+    pub fn grid_transform(
+        self,
+        index: usize,
+        columns: i32,
+        rows: i32,
+        config: &GridConfig,
+        camera: Option<&Transform>,
+    ) -> Transform {
+        let cell = calculate_grid_position_2d(index, columns, rows, config.spacing);
+        let extent = Vec2::new(columns as f32, rows as f32) * config.spacing;
+        self.place(cell, extent, config, camera)
+    }
+
+    /// Where a quad goes given its `cell` in a grid `extent` across and down, see
+    /// [`GridPlane::place`] and [`WallMode::place`]
+    pub fn place(
+        self,
+        cell: Vec2,
+        extent: Vec2,
+        config: &GridConfig,
+        camera: Option<&Transform>,
+    ) -> Transform {
+        match self {
+            RenderMode::Mesh3d => config
+                .wall
+                .place(cell, extent)
+                .unwrap_or_else(|| config.plane.place(cell, camera)),
+            RenderMode::Sprite2d => Transform::from_translation(cell.extend(0.0)),
+        }
+    }
+}
+
+/// Everything that goes into where a quad sits: the [`GridConfig`], the [`RenderMode`], the
+/// [`Timeline`], [`JustifiedLayout`] or [`DirectoryGroups`] when that's the layout, the size of
+/// the [`CurrentPage`] for the [`WallMode`] and the camera for [`GridPlane::FacingCamera`]
+#[derive(SystemParam)]
+pub struct GridPlacement<'w> {
+    pub config: Res<'w, GridConfig>,
+    pub render_mode: Res<'w, RenderMode>,
+    timeline: Res<'w, Timeline>,
+    justified: Res<'w, JustifiedLayout>,
+    groups: Res<'w, DirectoryGroups>,
+    page: Res<'w, CurrentPage>,
+    camera: Option<Single<'w, &'static Transform, With<Camera3d>>>,
+}
+
+impl GridPlacement<'_> {
+    /// Where the quad for `image` goes, it being at `index` of the `count` images on the page.
+    /// Only the justified rows scale it.
+    pub fn transform(&self, index: usize, image: &Path, count: usize) -> Transform {
+        let (cell, scale) = self.cell(index, image, count);
+        self.place(cell).with_scale(Vec3::splat(scale))
+    }
+
+    /// The flat cell the layout gives `image` before [`Self::place`] puts it on the plane (or
+    /// wall), first row at the top, and how much it's scaled
+    pub fn cell(&self, index: usize, image: &Path, count: usize) -> (Vec2, f32) {
+        if let Some(cell) = self.justified.cell(image) {
+            return (cell.center, cell.scale);
+        }
+        let cell = self
+            .timeline
+            .cell(image)
+            .or_else(|| self.groups.cell(image))
+            .unwrap_or_else(|| {
+                let (columns, rows) = self.config.dimensions(count);
+                calculate_grid_position_2d(index, columns, rows, self.config.spacing)
+            });
+        (cell, 1.0)
+    }
+
+    /// Where something at `cell` goes
+    pub fn place(&self, cell: Vec2) -> Transform {
+        let extent = self
+            .justified
+            .extent()
+            .or_else(|| self.groups.extent())
+            .unwrap_or_else(|| {
+                let (columns, rows) = self
+                    .timeline
+                    .dimensions()
+                    .unwrap_or_else(|| self.config.dimensions(self.page.images().len()));
+                Vec2::new(columns as f32, rows as f32) * self.config.spacing
+            });
+        self.render_mode
+            .place(cell, extent, &self.config, self.camera.as_deref().copied())
+    }
+}
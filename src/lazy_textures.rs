@@ -0,0 +1,137 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    AtlasSlot, GridConfig, GridPlane, GridPosition, ImageDisplayMaterial, ImageLoadState,
+    ImageMarker, ImageTexture, ScanErrors, WallMode, load_image,
+};
+
+/// Only ask for the textures of quads near where the camera's looking, rather than every quad's
+/// the moment it spawns. The rest show a grey placeholder, marked [`TextureUnloaded`], until the
+/// camera comes within `radius` of them. Only for the 3D quads, sprites load straight away.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct LazyTextures {
+    pub enabled: bool,
+    /// How far from the point the camera's looking at a quad can be and still load, in world
+    /// units
+    pub radius: f32,
+}
+
+impl Default for LazyTextures {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 20.0,
+        }
+    }
+}
+
+/// On quads that haven't asked for their texture yet, see [`LazyTextures`]. Their
+/// [`ImageLoadState`] is `Evicted` meanwhile, they hold no texture either way.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct TextureUnloaded;
+
+/// What quads without their texture show instead
+#[derive(Resource, Debug, Clone)]
+pub struct PlaceholderTexture(pub Handle<Image>);
+
+impl FromWorld for PlaceholderTexture {
+    fn from_world(world: &mut World) -> Self {
+        let image = Image::new_fill(
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[96, 96, 96, 255],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        Self(world.resource_mut::<Assets<Image>>().add(image))
+    }
+}
+
+pub struct LazyTexturePlugin;
+
+impl Plugin for LazyTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LazyTextures>();
+        app.init_resource::<PlaceholderTexture>();
+        app.add_systems(
+            Update,
+            lazy_load_textures_system.run_if(|lazy: Res<LazyTextures>| lazy.enabled),
+        );
+    }
+}
+
+/// Where the camera's looking: where its view ray meets the grid's plane, or `radius` in front
+/// of it when it doesn't (looking away from the grid, or out at a wall)
+fn look_at_point(grid_config: &GridConfig, camera: &Transform, radius: f32) -> Vec3 {
+    let normal = match grid_config.plane {
+        GridPlane::Xy => Dir3::Z,
+        GridPlane::Xz => Dir3::Y,
+        GridPlane::FacingCamera => camera.back(),
+    };
+    let ray = Ray3d::new(camera.translation, camera.forward());
+    ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(normal))
+        .filter(|_| grid_config.wall == WallMode::Flat)
+        .map_or(
+            camera.translation + *camera.forward() * radius,
+            |distance| ray.get_point(distance),
+        )
+}
+
+/// Ask for the textures of the unloaded quads within [`LazyTextures::radius`] of where the
+/// camera's looking
+pub fn lazy_load_textures_system(
+    mut commands: Commands,
+    lazy: Res<LazyTextures>,
+    grid_config: Res<GridConfig>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut errors: ResMut<ScanErrors>,
+    camera: Single<&Transform, With<Camera3d>>,
+    mut quads: Query<
+        (
+            Entity,
+            &ImageMarker,
+            &GridPosition,
+            &mut ImageTexture,
+            &mut ImageLoadState,
+            Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+        ),
+        (With<TextureUnloaded>, Without<AtlasSlot>),
+    >,
+) {
+    let center = look_at_point(&grid_config, &camera, lazy.radius);
+    for (entity, marker, position, mut texture, mut state, material) in &mut quads {
+        // Something else (a prefetch, a retry) has asked for it already
+        if *state != ImageLoadState::Evicted {
+            commands.entity(entity).remove::<TextureUnloaded>();
+            continue;
+        }
+        if position.target.distance(center) > lazy.radius {
+            continue;
+        }
+        commands.entity(entity).remove::<TextureUnloaded>();
+        match load_image(&asset_server, &marker.target) {
+            Ok(handle) => {
+                texture.0 = handle;
+                *state = ImageLoadState::Pending;
+            }
+            Err(e) => {
+                log::warn!("{e}");
+                errors.push(e);
+                *state = ImageLoadState::Failed;
+                continue;
+            }
+        }
+        if let Some(material) = material.and_then(|material| materials.get_mut(&material.0)) {
+            material.base_color_texture = Some(texture.0.clone());
+        }
+    }
+}
@@ -1,204 +1,3365 @@
 #[allow(dead_code, clippy::type_complexity)] // FIXME: remove when done prototyping...
 use bevy::prelude::*;
 
+use bevy::asset::{AssetPath, UnapprovedPathMode};
+use bevy::ecs::system::SystemParam;
+use bevy::picking::mesh_picking::MeshPickingPlugin;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+mod animated;
+mod archive;
+mod atlas;
+mod button_action;
+#[cfg(not(target_arch = "wasm32"))]
+mod catalog;
+mod color_search;
+mod command;
+mod compare;
+mod config;
+mod context_menu;
+mod culling;
+mod debounce;
+mod debug_gizmos;
+mod delete;
+mod diagnostics;
+mod dimensions;
+mod dir_groups;
+mod dir_tint;
+mod drop;
+mod duplicates;
+mod exif;
+mod export;
+mod file_watch;
+mod filter;
+#[cfg(not(target_arch = "wasm32"))]
+mod fs_events;
+mod fullscreen;
+#[cfg(feature = "heic")]
+mod heic;
+mod histogram;
+mod history;
+mod info_panel;
+mod justified;
+mod layout;
+mod lazy_textures;
+mod loading;
+mod manifest;
+mod material;
+mod metadata;
+mod minimap;
+mod navigation;
+mod paging;
+pub mod platform;
+mod playlist;
+mod rating;
+#[cfg(feature = "raw")]
+mod raw;
+mod recovery;
+mod regrid;
+mod rotation;
+mod scan_cache;
+mod scan_errors;
+mod scanner;
+mod scrubber;
+mod selection;
+mod slideshow;
+#[cfg(feature = "spatial_audio")]
+mod spatial_audio;
+mod spawn_animation;
+mod sprite_mode;
+mod status;
+mod tags;
+mod text_input;
+mod texture_budget;
+mod theme;
+mod thumbnails;
+mod timeline;
+#[cfg(feature = "video")]
+mod video;
+mod wall;
+mod zoom;
+
+pub use animated::{AnimatedImagePlugin, AnimatedImages, FrameSequence};
+pub use archive::{ArchivePlugin, ArchiveSource, SourceArchive};
+pub use atlas::{AtlasSlot, ThumbnailAtlas, ThumbnailAtlasPlugin, ThumbnailAtlases};
+pub use button_action::{ButtonAction, ButtonActionPlugin};
+#[cfg(not(target_arch = "wasm32"))]
+pub use catalog::{Catalog, CatalogPlugin};
+pub use color_search::{
+    ColorPalette, ColorSearchFilter, ColorSearchPlugin, color_distance, color_search_bar,
+};
+pub use command::{ViewerCommand, ViewerCommandPlugin};
+pub use compare::{ComparePlugin, CompareView};
+pub use config::{CameraPose, ConfigPersistencePlugin, PhotoviewConfig, SessionState};
+pub use context_menu::{ContextMenu, ContextMenuAction, ContextMenuPlugin};
+pub use culling::{VisibilityCulling, VisibilityCullingPlugin};
+pub use debug_gizmos::{DebugGizmosPlugin, DebugVisualization, GizmoSystemSet};
+pub use delete::{DeleteConfig, DeletePlugin, PendingDelete, request_delete};
+pub use diagnostics::{DiagnosticsOverlayPlugin, StatsOverlay};
+pub use dimensions::{DimensionsPlugin, DimensionsProbed, probe_dimensions};
+pub use dir_groups::{DirectoryGroup, DirectoryGroups, DirectoryGroupsPlugin};
+pub use dir_tint::{DirectoryColorMap, DirectoryTintPlugin, ShowDirectoryTint};
+pub use drop::FileDropPlugin;
+pub use duplicates::{DuplicateConfig, Duplicates, DuplicatesPlugin};
+pub use export::{ContactSheetConfig, ExportGallery, ExportKind, ExportPlugin, ExportRequested};
+pub use file_watch::FileWatchPlugin;
+pub use filter::{
+    DiscoveredAt, FilterPlugin, FilteredOut, ImageFilter, RecentlyAddedFilter, filter_bar,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use fs_events::FsEventsPlugin;
+pub use fullscreen::{FullscreenPlugin, FullscreenRequested};
+#[cfg(feature = "heic")]
+pub use heic::HeicPlugin;
+pub use histogram::{Histogram, HistogramPlugin};
+pub use history::{Action, History, HistoryPlugin};
+pub use info_panel::{InfoPanel, InfoPanelPlugin};
+pub use justified::{JustifiedCell, JustifiedLayout, JustifiedLayoutPlugin};
+pub use layout::{
+    GridConfig, GridLayout, GridPlacement, GridPlane, RenderMode, WallMode,
+    calculate_grid_position, calculate_grid_position_2d,
+};
+pub use lazy_textures::{LazyTexturePlugin, LazyTextures, PlaceholderTexture, TextureUnloaded};
+pub use loading::{AppState, LoadingScreenPlugin, ViewMode};
+pub use manifest::ImageManifest;
+pub use material::{ImageDisplayMaterial, ImageDisplayMaterialPlugin, RenderQuality};
+pub use metadata::{GpsPosition, MetadataPlugin, PhotoMetadata};
+pub use minimap::{Minimap, MinimapPlugin, ShowMinimap};
+pub use navigation::NavigationPlugin;
+pub use paging::{CurrentPage, PageConfig, PagingPlugin, TurnPage};
+pub use rating::{Rating, RatingPlugin};
+#[cfg(feature = "raw")]
+pub use raw::RawPlugin;
+pub use recovery::{ContentHash, MissingFile, MissingFilePlugin};
+pub use regrid::{GridPosition, RegridNeeded, RegridPlugin};
+pub use rotation::{ExifOrientation, ManualRotation, RotationPlugin};
+pub use scan_cache::{CachedImage, ScanCache, ScanCachePlugin, ScanCacheReconciled};
+pub use scan_errors::{ScanErrors, ScanErrorsPlugin};
+pub use scanner::{
+    CaptureDate, FileMetadata, FileSystem, ImageEntry, MemoryFileSystem, RealFileSystem, ScanError,
+    ScanOptions, Scanner, WalkProgress, scan_dirs, sort_images,
+};
+pub use scrubber::{ShowTimelineScrubber, TimelineScrubber, TimelineScrubberPlugin};
+pub use selection::{Selection, SelectionPlugin};
+pub use slideshow::{KenBurnsAnimation, SlideshowConfig, SlideshowPlugin};
+#[cfg(feature = "spatial_audio")]
+pub use spatial_audio::{AudioCue, SpatialAudioPlugin};
+pub use spawn_animation::{JustAdded, SpawnAnimation, SpawnAnimationPlugin};
+pub use sprite_mode::SpriteModePlugin;
+pub use status::{StatusBar, StatusBarPlugin};
+pub use tags::{Tags, TagsPlugin};
+pub use text_input::{
+    TextInput, TextInputFocus, TextInputPlugin, TextInputSubmitted, text_input_inactive,
+};
+pub use texture_budget::{TextureBudget, TextureBudgetPlugin, TextureUsage};
+pub use theme::{SceneBackground, ThemeKind, ThemePlugin, Themed, UiTheme};
+pub use thumbnails::{ThumbnailCache, ThumbnailPlugin};
+pub use timeline::{DateSource, Timeline, TimelineBucket, TimelineGroup, TimelinePlugin};
+#[cfg(feature = "video")]
+pub use video::{VideoPlayback, VideoPlugin};
+pub use wall::WallPlugin;
+pub use zoom::{CameraAnimation, CameraConfig, ZoomPlugin};
+
+use archive::{Archive, ExtractedArchive};
+use debounce::ScanDebounce;
+#[cfg(not(target_arch = "wasm32"))]
+use fs_events::FsEventChanges;
+use playlist::Playlist;
+use recovery::Recovering;
+use scan_cache::ScanCacheState;
+
+/// Resource for watched directories, a 'watched' dir is one we're looking at the contents of,
+/// and periodically scanning for images.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct WatchedDirs {
+    dirs: Vec<WatchedDir>,
+    /// Holds file timestamps, which reflection can't do anything with
+    #[reflect(ignore)]
+    playlists: Vec<Playlist>,
+    /// ZIP files shown as if they were directories, see [`Self::add_archive`]
+    #[reflect(ignore)]
+    archives: Vec<Archive>,
+    /// Images added one at a time rather than through a directory or playlist, see
+    /// [`Self::add_image`]
+    loose: Vec<PathBuf>,
+    imgs: Vec<PathBuf>,
+    /// Images past the [`MaxImages`] cap, in order, kept so rescans can tell what really changed
+    overflow: Vec<PathBuf>,
+    statuses: HashMap<PathBuf, DirStatus>,
+    /// Whether the last scan stopped at [`ScanConfig::scan_limit`]
+    limit_reached: bool,
+    /// What each scanned image looked like on disk last time, to tell when one's been rewritten
+    #[reflect(ignore)]
+    stamps: HashMap<PathBuf, FileStamp>,
+    /// Images whose [`FileStamp`] changed in a scan, waiting for their textures to be reloaded
+    #[reflect(ignore)]
+    modified: Vec<PathBuf>,
+    /// When each scanned image was taken, or that it doesn't say, as of its modification time.
+    /// Only filled in with [`ScanConfig::capture_dates`] on.
+    #[reflect(ignore)]
+    captured: HashMap<PathBuf, CaptureDate>,
+    /// Pixel sizes read from the images' headers, see [`DimensionsPlugin`]
+    #[reflect(ignore)]
+    dimensions: HashMap<PathBuf, ProbedDimensions>,
+}
+
+/// An image file's modification time and size, as of the last scan. Either one changing means
+/// the file has been rewritten and its texture is stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub modified: Option<SystemTime>,
+    pub len: u64,
+}
+
+/// What applying a scan did to the image list (counting the images held back by [`MaxImages`])
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageChanges {
+    /// In the order they're listed
+    pub added: Vec<PathBuf>,
+    /// In the order they were listed
+    pub removed: Vec<PathBuf>,
+}
+
+impl ImageChanges {
+    fn between(before: &[PathBuf], after: &[PathBuf]) -> Self {
+        let before_set: HashSet<&PathBuf> = before.iter().collect();
+        let after_set: HashSet<&PathBuf> = after.iter().collect();
+        Self {
+            added: after
+                .iter()
+                .filter(|img| !before_set.contains(img))
+                .cloned()
+                .collect(),
+            removed: before
+                .iter()
+                .filter(|img| !after_set.contains(img))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Same images as before, though they may have been reordered
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// An image's width and height as read from its header, `None` if that couldn't be done, along
+/// with the modification time of the file that was read. A different time means it needs
+/// reading again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbedDimensions {
+    pub modified: Option<SystemTime>,
+    pub size: Option<(u32, u32)>,
+}
+
+/// Which directories [`WatchedDirs`] was watching at some point, in order and with how deep to
+/// look in each, for putting them back with [`WatchedDirs::restore`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WatchedDirsSnapshot {
+    pub dirs: Vec<WatchedDir>,
+}
+
+/// One of the [`WatchedDirs`], and how deep to look in it
+#[derive(Reflect, Debug, Clone, PartialEq, Eq)]
+pub struct WatchedDir {
+    pub path: PathBuf,
+    /// Descend into subdirectories, otherwise only the top level is scanned
+    pub recursive: bool,
+}
+
+impl WatchedDir {
+    pub fn new(path: impl Into<PathBuf>, recursive: bool) -> Self {
+        Self {
+            path: path.into(),
+            recursive,
+        }
+    }
+
+    /// Would a scan of this directory find `image`?
+    pub fn covers(&self, image: &Path) -> bool {
+        if self.recursive {
+            image.starts_with(&self.path)
+        } else {
+            image.parent() == Some(self.path.as_path())
+        }
+    }
+
+    fn scan_options(&self, config: &ScanConfig) -> ScanOptions {
+        ScanOptions {
+            max_depth: if self.recursive { None } else { Some(0) },
+            ..config.scan_options()
+        }
+    }
+}
+
+/// How the last attempt to scan a watched directory went. Directories that fail (a network share
+/// that's gone away, say) are retried after 10, 20 and then 40 seconds, and after that left alone
+/// until a rescan is asked for. Their images stay in the grid meanwhile.
+///
+/// A directory that doesn't exist at all (a mount point before the drive is plugged in, say)
+/// isn't an error: it's checked for on every scan, shows up empty until it appears, and is then
+/// picked up without a restart.
+#[derive(Reflect, Debug, Clone, PartialEq, Eq)]
+pub struct DirStatus {
+    pub reachable: bool,
+    pub last_error: Option<String>,
+    /// The closest ancestor that does exist, while we wait for the directory itself to appear
+    pub waiting_on: Option<PathBuf>,
+    /// Failed scans in a row
+    failures: u32,
+    /// When to try again while unreachable, `None` once we've given up
+    retry_at: Option<Duration>,
+}
+
+impl DirStatus {
+    const RETRY_DELAYS: [Duration; 3] = [
+        Duration::from_secs(10),
+        Duration::from_secs(20),
+        Duration::from_secs(40),
+    ];
+
+    fn reachable() -> Self {
+        Self {
+            reachable: true,
+            last_error: None,
+            waiting_on: None,
+            failures: 0,
+            retry_at: None,
+        }
+    }
+
+    fn waiting_on(ancestor: PathBuf) -> Self {
+        Self {
+            reachable: false,
+            waiting_on: Some(ancestor),
+            ..Self::reachable()
+        }
+    }
+
+    /// Retries have run out, only a requested rescan will try again
+    pub fn gave_up(&self) -> bool {
+        !self.reachable && self.retry_at.is_none() && !self.is_waiting()
+    }
+
+    /// The directory doesn't exist (yet)
+    pub fn is_waiting(&self) -> bool {
+        self.waiting_on.is_some()
+    }
+
+    fn is_due(&self, now: Duration) -> bool {
+        self.reachable || self.is_waiting() || self.retry_at.is_some_and(|at| now >= at)
+    }
+
+    fn failed(&mut self, error: String, now: Duration) {
+        self.reachable = false;
+        self.waiting_on = None;
+        self.last_error = Some(error);
+        self.retry_at = Self::RETRY_DELAYS
+            .get(self.failures as usize)
+            .map(|delay| now + *delay);
+        self.failures += 1;
+    }
+}
+
+/// Everything a scan needs to know besides the directories themselves. It's all owned, so the
+/// scan can run on the [`AsyncComputeTaskPool`].
+struct ScanPass {
+    config: ScanConfig,
+    /// Problems worth showing, handed over to [`ScanErrors`] once the scan's done
+    errors: Vec<String>,
+    /// What each directory held last time, kept for the ones we can't reach
+    previous: Vec<PathBuf>,
+    statuses: HashMap<PathBuf, DirStatus>,
+    now: Duration,
+    /// Asked for by the user, so retry directories even if we've given up on them
+    forced: bool,
+    /// Images found so far, counting towards [`ScanConfig::scan_limit`]
+    found: usize,
+    limit_reached: bool,
+    /// Of the directories that were actually scanned
+    stamps: HashMap<PathBuf, FileStamp>,
+    captured: HashMap<PathBuf, CaptureDate>,
+    /// What `captured` held before the pass, so unchanged files aren't read again
+    known_dates: Arc<HashMap<PathBuf, CaptureDate>>,
+    /// Where the walks report how they're going, see [`ScanProgress`]
+    progress: Sender<WalkProgress>,
+    /// Archives that are new or were replaced, to be extracted along with the walk
+    archives: Vec<PathBuf>,
+}
+
+impl ScanPass {
+    fn finish(self, archives: Vec<ExtractedArchive>) -> PassOutcome {
+        PassOutcome {
+            errors: self.errors,
+            statuses: self.statuses,
+            limit_reached: self.limit_reached,
+            stamps: self.stamps,
+            captured: self.captured,
+            archives,
+        }
+    }
+}
+
+/// What a [`ScanPass`] leaves behind besides the images
+struct PassOutcome {
+    errors: Vec<String>,
+    statuses: HashMap<PathBuf, DirStatus>,
+    limit_reached: bool,
+    stamps: HashMap<PathBuf, FileStamp>,
+    captured: HashMap<PathBuf, CaptureDate>,
+    archives: Vec<ExtractedArchive>,
+}
+
+/// How far the current scan has got, or how the last one went once `done`. There's no telling
+/// how big a tree is before walking it, so it's a count rather than a percentage. The walks report
+/// in as they go (see [`Scanner::with_progress`]) from the thread they run on, and it's updated
+/// every frame until they're finished.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub dirs_seen: usize,
+    pub images_found: usize,
+    pub done: bool,
+}
+
+/// The channel each scan's walks send their [`WalkProgress`] down
+#[derive(Resource)]
+struct ScanProgressChannel {
+    sender: Sender<WalkProgress>,
+    /// Only ever drained from one system, but resources have to be `Sync`
+    receiver: Mutex<Receiver<WalkProgress>>,
+}
+
+impl Default for ScanProgressChannel {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+impl ScanProgressChannel {
+    /// Add up everything reported since last time
+    fn drain(&self) -> WalkProgress {
+        let mut total = WalkProgress::default();
+        if let Ok(receiver) = self.receiver.lock() {
+            for progress in receiver.try_iter() {
+                total.dirs_seen += progress.dirs_seen;
+                total.images_found += progress.images_found;
+            }
+        }
+        total
+    }
+}
+
+/// Sent when a scan stops early because it found [`ScanConfig::scan_limit`] images. Only sent
+/// once until a scan gets through without hitting the limit again.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxImagesReached {
+    pub limit: usize,
+}
+
+/// Order images are listed (and so laid out) in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SortOrder {
+    /// By full path
+    #[default]
+    Name,
+    /// Oldest modification time first
+    Modified,
+    /// Smallest file first
+    Size,
+}
+
+/// How changes in the watched directories are noticed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanBackend {
+    /// Walk every directory again every [`ScanConfig::interval`]
+    #[default]
+    Polling,
+    /// Listen for the filesystem's own change events and only look at what they mention, see
+    /// [`FsEventsPlugin`]. Falls back to polling wherever the directories can't be watched.
+    Events,
+}
+
+/// How, and how often, the watched directories get scanned
+#[derive(Resource, Debug, Clone)]
+pub struct ScanConfig {
+    /// Time between background rescans
+    pub interval: Duration,
+    /// Descend into subdirectories. This is the default for directories that don't say, see
+    /// [`WatchedDir::recursive`].
+    pub recursive: bool,
+    /// Lowercase file extensions (no dot) that count as images
+    pub extensions: Vec<String>,
+    pub sort: SortOrder,
+    /// Where to keep a [`ScanCache`], `None` to always start with a full scan
+    pub cache_path: Option<PathBuf>,
+    /// How long the directories have to stay unchanged before a new scan result is applied.
+    /// Zero applies every scan straight away.
+    pub debounce: Duration,
+    /// Stop scanning once this many images have been found, so pointing the app at `/` doesn't
+    /// hang it. Unlike [`MaxImages`] nothing past the limit is even looked at.
+    pub scan_limit: Option<usize>,
+    /// Read when each photo was taken while scanning, see [`ImageEntry::taken`]. That's a read
+    /// of every image on every scan, so it's left off unless the [`GridLayout::Timeline`] is
+    /// in use, which turns it on.
+    pub capture_dates: bool,
+    pub backend: ScanBackend,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            recursive: true,
+            extensions: WatchedDirs::SUPPORTED_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            sort: SortOrder::default(),
+            cache_path: None,
+            debounce: Duration::from_millis(300),
+            scan_limit: Some(5000),
+            capture_dates: false,
+            backend: ScanBackend::default(),
+        }
+    }
+}
+
+impl ScanConfig {
+    /// Check if a file has one of the configured image extensions
+    pub fn is_supported_image(&self, path: &Path) -> bool {
+        scanner::has_extension(path, &self.extensions)
+    }
+
+    /// The [`Scanner`] settings these amount to
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            extensions: self.extensions.clone(),
+            max_depth: if self.recursive { None } else { Some(0) },
+            sort: self.sort,
+            capture_dates: self.capture_dates,
+            ..default()
+        }
+    }
+}
+
+/// For later spawn/despawn usage, you can make a system that matches on Paths and remove/add quads for an image not already added/that you wanna remove..
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct ImageMarker {
+    target: PathBuf,
+}
+
+/// Where a quad's texture is in the asset pipeline
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageLoadState {
+    #[default]
+    Pending,
+    Loaded,
+    Failed,
+    /// Isn't holding its texture: it was loaded, then dropped to stay within the
+    /// [`TextureBudget`], or it's [`TextureUnloaded`] and hasn't been asked for yet
+    Evicted,
+}
+
+/// The texture handle a quad was spawned with, so we can ask the asset server how it's going
+#[derive(Component)]
+pub struct ImageTexture(pub Handle<Image>);
+
+/// Start loading an image from disk. The [`AssetPath`] is built from the `Path` itself rather than
+/// parsed from a string, so non-UTF-8 bytes, `#` (which parsing takes as the start of a label) and
+/// Windows `\\?\` prefixes all come through untouched.
+///
+/// Watched images live outside the assets folder, which the asset server only allows through
+/// [`AssetServer::load_override`], so the app's `AssetPlugin::unapproved_path_mode` has to be
+/// [`UnapprovedPathMode::Deny`] rather than the default `Forbid`. Fails if the file name isn't
+/// UTF-8, since that's where the asset server looks for the extension to pick a loader by.
+pub fn load_image(asset_server: &AssetServer, path: &Path) -> Result<Handle<Image>, String> {
+    Ok(asset_server.load_override(image_asset_path(path)?))
+}
+
+/// The asset path [`load_image`] loads `path` through. Taken as the path it is, so a `#` in a
+/// file name isn't read as the start of a label, nor a drive letter as an asset source.
+fn image_asset_path(path: &Path) -> Result<AssetPath<'_>, String> {
+    if path.file_name().and_then(|name| name.to_str()).is_none() {
+        return Err(format!(
+            "{} can't be loaded, its file name isn't valid UTF-8",
+            path.display()
+        ));
+    }
+    Ok(AssetPath::from_path(path))
+}
+
+/// How many scans have completed since startup
+#[derive(Resource, Default, Debug)]
+pub struct ScanCounter(pub u64);
+
+/// How the most recent scan went, for working out why scanning is slow
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastScan {
+    pub duration: Duration,
+    /// Images found, before any [`MaxImages`] cap
+    pub files: usize,
+}
+
+/// Images the user has starred
+#[derive(Resource, Default, Debug)]
+pub struct Favorites(HashSet<PathBuf>);
+
+impl Favorites {
+    pub fn contains(&self, path: &Path) -> bool {
+        self.0.contains(path)
+    }
+
+    pub fn set(&mut self, path: PathBuf, favorite: bool) {
+        if favorite {
+            self.0.insert(path);
+        } else {
+            self.0.remove(&path);
+        }
+    }
+
+    /// Flip an image in or out of the favorites, returns whether it's now a favorite
+    pub fn toggle(&mut self, path: PathBuf) -> bool {
+        if self.0.remove(&path) {
+            false
+        } else {
+            self.0.insert(path);
+            true
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PathBuf> {
+        self.0.iter()
+    }
+}
+
+/// Send this to make the scanner run right away instead of waiting out the scan interval.
+/// `dir: None` rescans everything, `Some(dir)` only that watched directory.
+#[derive(Event, Debug, Clone, Default)]
+pub struct RescanRequested {
+    pub dir: Option<PathBuf>,
+}
+
+impl RescanRequested {
+    pub fn all() -> Self {
+        Self { dir: None }
+    }
+
+    pub fn dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+        }
+    }
+}
+
+/// While true, no scanning happens at all, periodic or requested. Requests made in the meantime
+/// are held on to, and unpausing runs a full catch-up scan straight away.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanPaused(pub bool);
+
+/// At most this many images (the first ones in sort order) are shown, `None` for no limit. The
+/// rest are counted in [`ImageOverflow`].
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaxImages(pub Option<usize>);
+
+/// How many images [`MaxImages`] let through out of how many were found
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImageOverflow {
+    pub shown: usize,
+    pub total: usize,
+}
+
+impl ImageOverflow {
+    /// Number of images left out
+    pub fn hidden(&self) -> usize {
+        self.total - self.shown
+    }
+}
+
+/// Sent after every scan, periodic or requested, once [`WatchedDirs`] is up to date
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ScanCompleted {
+    pub image_count: usize,
+    /// How long the walk took, see [`LastScan`]
+    pub duration: Duration,
+}
+
+/// Images a scan found that weren't there before, in the order they're listed. Sent just before
+/// the [`ScanCompleted`] for the scan, once they're in [`WatchedDirs`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ImagesAdded(pub Vec<PathBuf>);
+
+/// Images that a scan found gone, in the order they were listed. Sent alongside
+/// [`ImagesAdded`], once they're out of [`WatchedDirs`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ImagesRemoved(pub Vec<PathBuf>);
+
+/// Everything a scan announces, so reacting to one doesn't take watching [`WatchedDirs`] for
+/// changes
+#[derive(SystemParam)]
+pub(crate) struct ScanEvents<'w> {
+    completed: EventWriter<'w, ScanCompleted>,
+    added: EventWriter<'w, ImagesAdded>,
+    removed: EventWriter<'w, ImagesRemoved>,
+}
+
+impl ScanEvents<'_> {
+    /// Send what [`apply_images`] changed, if anything
+    pub(crate) fn changed(&mut self, changes: Option<ImageChanges>) {
+        let Some(changes) = changes else {
+            return;
+        };
+        if !changes.removed.is_empty() {
+            self.removed.write(ImagesRemoved(changes.removed));
+        }
+        if !changes.added.is_empty() {
+            self.added.write(ImagesAdded(changes.added));
+        }
+    }
+
+    pub(crate) fn completed(&mut self, watched_dirs: &WatchedDirs, duration: Duration) {
+        self.completed.write(ScanCompleted {
+            image_count: watched_dirs.image_count(),
+            duration,
+        });
+    }
+}
+
+/// Rescan requests that haven't been serviced yet. Any number of events collapse into one of
+/// these, so a burst of requests still only costs a single scan.
+#[derive(Default)]
+enum PendingRescan {
+    #[default]
+    Nothing,
+    All,
+    Dirs(Vec<PathBuf>),
+}
+
+impl PendingRescan {
+    fn merge(&mut self, request: &RescanRequested) {
+        match (&mut *self, &request.dir) {
+            (PendingRescan::All, _) => {}
+            (_, None) => *self = PendingRescan::All,
+            (PendingRescan::Nothing, Some(dir)) => *self = PendingRescan::Dirs(vec![dir.clone()]),
+            (PendingRescan::Dirs(dirs), Some(dir)) => {
+                if !dirs.contains(dir) {
+                    dirs.push(dir.clone());
+                }
+            }
+        }
+    }
+}
+
+/// What a [`ScanTask`] has been asked to do
+#[cfg(not(target_arch = "wasm32"))]
+enum ScanJob {
+    All,
+    Dirs(Vec<PathBuf>),
+    /// Look at just these paths, see [`ScanBackend::Events`]
+    Changes(HashSet<PathBuf>),
+}
+
+/// A scan running on the [`AsyncComputeTaskPool`], so walking a big or slow directory doesn't
+/// hold up the frame. Only one runs at a time.
+#[cfg(not(target_arch = "wasm32"))]
+struct ScanTask {
+    task: Task<(Vec<PathBuf>, PassOutcome)>,
+    /// What it set out to walk, to tell whether the result's still any use once it's in
+    targets: ScanTargets,
+    started: Instant,
+}
+
+/// The steps [`DirWatchingPlugin`] runs each frame, so other plugins can schedule around them,
+/// e.g. `.after(DirWatchingSet::SpawnQuads)` to see this frame's new quads. They run in this order
+/// in both `PreUpdate` and `Update`, though scanning mostly happens in `PreUpdate` and the quad
+/// steps in `Update`.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirWatchingSet {
+    /// Scanning directories and keeping [`WatchedDirs`] up to date
+    Scan,
+    /// Spawning quads for images that don't have one yet
+    SpawnQuads,
+    /// Despawning quads for images that shouldn't be shown anymore
+    DespawnQuads,
+}
+
+/// Wrap everything in a plugin for modularity. Start from [`DirWatchingPlugin::with_dirs`] and
+/// chain the other builder methods to override the defaults.
+#[derive(Default, Clone)]
+pub struct DirWatchingPlugin {
+    dirs: Vec<PathBuf>,
+    shallow_dirs: Vec<PathBuf>,
+    playlists: Vec<PathBuf>,
+    archives: Vec<PathBuf>,
+    scan_config: ScanConfig,
+    grid_config: GridConfig,
+    delete_config: DeleteConfig,
+    duplicate_config: DuplicateConfig,
+    max_images: MaxImages,
+    page_config: PageConfig,
+    render_mode: RenderMode,
+    tint_directories: ShowDirectoryTint,
+    timeline_scrubber: ShowTimelineScrubber,
+    thumbnail_atlas: ThumbnailAtlas,
+    texture_budget: TextureBudget,
+    lazy_textures: LazyTextures,
+    thumbnail_cache: ThumbnailCache,
+    #[cfg(not(target_arch = "wasm32"))]
+    catalog: Catalog,
+}
+
+impl DirWatchingPlugin {
+    pub fn with_dirs(dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self {
+            dirs: dirs.into_iter().map(Into::into).collect(),
+            ..default()
+        }
+    }
+
+    /// Watch the directories listed in the environment variable `var`, separated like `PATH`
+    /// is (`:` on Unix, `;` on Windows). Ones that aren't directories are skipped with a
+    /// warning. Without the variable this is the same as [`Self::default`].
+    pub fn from_env(var: &str) -> Self {
+        let Some(value) = std::env::var_os(var) else {
+            return Self::default();
+        };
+        let dirs = std::env::split_paths(&value)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .filter(|dir| {
+                let exists = dir.is_dir();
+                if !exists {
+                    log::warn!("Skipping {dir:?} from ${var}, it isn't a directory");
+                }
+                exists
+            });
+        Self::with_dirs(dirs)
+    }
+
+    /// Replace the directories to watch
+    pub fn dirs(mut self, dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.dirs = dirs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Replace the directories to watch without descending into their subdirectories, whatever
+    /// [`Self::recursive`] says
+    pub fn shallow_dirs(mut self, dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.shallow_dirs = dirs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Also show the images listed in a playlist file, see [`WatchedDirs::add_playlist`]
+    pub fn playlist(mut self, path: impl Into<PathBuf>) -> Self {
+        self.playlists.push(path.into());
+        self
+    }
+
+    /// Also show the images in a ZIP file, see [`WatchedDirs::add_archive`]
+    pub fn archive(mut self, path: impl Into<PathBuf>) -> Self {
+        self.archives.push(path.into());
+        self
+    }
+
+    /// Start from the scan and grid settings of a saved [`PhotoviewConfig`]
+    pub fn from_config(config: &PhotoviewConfig) -> Self {
+        Self::with_dirs(config.dirs.iter().cloned())
+            .shallow_dirs(config.shallow_dirs.iter().cloned())
+            .scan_interval(config.scan_interval())
+            .sort_order(config.sort)
+            .grid(config.grid.clone())
+    }
+
+    pub fn scan_interval(mut self, interval: Duration) -> Self {
+        self.scan_config.interval = interval;
+        self
+    }
+
+    /// See [`ScanConfig::debounce`]
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.scan_config.debounce = debounce;
+        self
+    }
+
+    /// See [`ScanConfig::scan_limit`], `None` scans everything however big
+    pub fn scan_limit(mut self, limit: Option<usize>) -> Self {
+        self.scan_config.scan_limit = limit;
+        self
+    }
+
+    pub fn sort_order(mut self, sort: SortOrder) -> Self {
+        self.scan_config.sort = sort;
+        self
+    }
+
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.scan_config.recursive = recursive;
+        self
+    }
+
+    /// See [`ScanBackend`]
+    pub fn scan_backend(mut self, backend: ScanBackend) -> Self {
+        self.scan_config.backend = backend;
+        self
+    }
+
+    /// Replace the default list of image extensions
+    pub fn extensions(mut self, extensions: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.scan_config.extensions = extensions
+            .into_iter()
+            .map(|ext| ext.as_ref().trim_start_matches('.').to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Draw the grid with sprites and a 2D camera instead of 3D quads, see [`RenderMode`]
+    pub fn render_mode(mut self, mode: RenderMode) -> Self {
+        self.render_mode = mode;
+        self
+    }
+
+    /// Tint each directory's images with its own colour, see [`DirectoryColorMap`]
+    pub fn tint_directories(mut self, tint: bool) -> Self {
+        self.tint_directories = ShowDirectoryTint(tint);
+        self
+    }
+
+    /// Show the [`TimelineScrubber`] along the bottom of the window, reading capture dates while
+    /// scanning so it has something to show
+    pub fn timeline_scrubber(mut self, show: bool) -> Self {
+        self.timeline_scrubber = ShowTimelineScrubber(show);
+        self
+    }
+
+    /// Pack the images into a few atlas textures as thumbnails to save on draw calls, see
+    /// [`ThumbnailAtlas`]
+    pub fn thumbnail_atlas(mut self, atlas: bool) -> Self {
+        self.thumbnail_atlas.enabled = atlas;
+        self
+    }
+
+    /// Cap the texture memory photos can hold on to at `max_bytes`, see [`TextureBudget`]
+    pub fn texture_budget(mut self, max_bytes: u64) -> Self {
+        self.texture_budget.max_bytes = max_bytes;
+        self
+    }
+
+    /// Leave quads further than `radius` from where the camera's looking without their texture
+    /// until it gets closer, see [`LazyTextures`]
+    pub fn lazy_textures(mut self, radius: f32) -> Self {
+        self.lazy_textures = LazyTextures {
+            enabled: true,
+            radius,
+        };
+        self
+    }
+
+    /// Show the grid's images as thumbnails kept in `dir`, see [`ThumbnailCache`]
+    pub fn thumbnail_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.thumbnail_cache.dir = Some(dir.into());
+        self
+    }
+
+    /// See [`ThumbnailCache::max_edge`]
+    pub fn thumbnail_size(mut self, max_edge: u32) -> Self {
+        self.thumbnail_cache.max_edge = max_edge;
+        self
+    }
+
+    /// Only show the first `max` images in sort order, see [`MaxImages`]
+    pub fn max_images(mut self, max: Option<usize>) -> Self {
+        self.max_images = MaxImages(max);
+        self
+    }
+
+    /// Show the images `per_page` at a time, 0 for all at once. See [`PageConfig`].
+    pub fn per_page(mut self, per_page: usize) -> Self {
+        self.page_config = PageConfig { per_page };
+        self
+    }
+
+    /// Remember scan results in `path` so the next launch can show them before scanning
+    pub fn scan_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.scan_config.cache_path = Some(path.into());
+        self
+    }
+
+    /// Keep everything known about the images, ratings included, in a SQLite database at `path`,
+    /// see [`Catalog`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn catalog(mut self, path: impl Into<PathBuf>) -> Self {
+        self.catalog.path = Some(path.into());
+        self
+    }
+
+    pub fn layout(mut self, layout: GridLayout) -> Self {
+        self.grid_config.layout = layout;
+        self
+    }
+
+    /// Whether the [`GridLayout::Timeline`] has a row per day, month or year
+    pub fn timeline_bucket(mut self, bucket: TimelineBucket) -> Self {
+        self.grid_config.timeline = bucket;
+        self
+    }
+
+    /// How tall the rows of the [`GridLayout::Justified`] aim to be
+    pub fn row_height(mut self, height: f32) -> Self {
+        self.grid_config.row_height = height;
+        self
+    }
+
+    /// Give the square grid as many columns as fit across the window, reflowing it as the window
+    /// is resized, see [`GridConfig::fit_to_window`]. Only the [`RenderMode::Sprite2d`] does.
+    pub fn fit_to_window(mut self, fit: bool) -> Self {
+        self.grid_config.fit_to_window = fit;
+        self
+    }
+
+    /// Fewest and most columns [`Self::fit_to_window`] will give the grid, however narrow or
+    /// wide the window gets
+    pub fn column_limits(mut self, min: i32, max: i32) -> Self {
+        self.grid_config.min_columns = min;
+        self.grid_config.max_columns = max;
+        self
+    }
+
+    /// How far apart the directories' grids are in the [`GridLayout::GroupByDirectory`]
+    pub fn group_gap(mut self, gap: f32) -> Self {
+        self.grid_config.group_gap = gap;
+        self
+    }
+
+    /// Wrap the grid round a cylinder or sphere instead of laying it out flat, see [`WallMode`]
+    pub fn wall(mut self, wall: WallMode) -> Self {
+        self.grid_config.wall = wall;
+        self
+    }
+
+    pub fn grid(mut self, grid_config: GridConfig) -> Self {
+        self.grid_config = grid_config;
+        self
+    }
+
+    /// Ask before moving images to the trash (the default)
+    pub fn confirm_delete(mut self, confirm: bool) -> Self {
+        self.delete_config.confirm = confirm;
+        self
+    }
+
+    /// Hash images in the background to find copies of the same photo, see [`Duplicates`]
+    pub fn detect_duplicates(mut self, detect: bool) -> Self {
+        self.duplicate_config.detect = detect;
+        self
+    }
+
+    /// Show one quad per group of duplicates (the default once detection is on)
+    pub fn collapse_duplicates(mut self, collapse: bool) -> Self {
+        self.duplicate_config.collapse = collapse;
+        self
+    }
+}
+
+impl Plugin for DirWatchingPlugin {
+    fn build(&self, app: &mut App) {
+        log::debug!("Adding DirWatchingPlugin");
+        // See `load_image`. The browser's images are under the assets folder anyway.
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(asset_plugin) = app.get_added_plugins::<AssetPlugin>().first()
+            && matches!(
+                asset_plugin.unapproved_path_mode,
+                UnapprovedPathMode::Forbid
+            )
+        {
+            log::warn!(
+                "AssetPlugin::unapproved_path_mode is Forbid, so images outside the assets \
+                 folder won't load. Set it to Deny."
+            );
+        }
+        let dirs = self
+            .dirs
+            .iter()
+            .map(|dir| WatchedDir::new(dir, self.scan_config.recursive))
+            .chain(
+                self.shallow_dirs
+                    .iter()
+                    .map(|dir| WatchedDir::new(dir, false)),
+            );
+        let mut watched_dirs = WatchedDirs::new(dirs.collect());
+        let mut playlist_errors = vec![];
+        for path in &self.playlists {
+            if let Err(e) = watched_dirs.add_playlist(path, &self.scan_config) {
+                log::warn!("Couldn't read playlist {path:?}: {e}");
+                playlist_errors.push(format!("Couldn't read playlist {}: {e}", path.display()));
+            }
+        }
+        for path in &self.archives {
+            watched_dirs.add_archive(path.clone());
+        }
+        app.register_type::<WatchedDirs>();
+        app.register_type::<ImageMarker>();
+        app.insert_resource(watched_dirs);
+        // Without waiting for the timeline to ask, which would mean scanning twice at startup
+        app.insert_resource(ScanConfig {
+            capture_dates: self.scan_config.capture_dates
+                || self.grid_config.layout == GridLayout::Timeline
+                || self.timeline_scrubber.0,
+            ..self.scan_config.clone()
+        });
+        app.insert_resource(self.grid_config.clone());
+        app.insert_resource(self.delete_config.clone());
+        app.insert_resource(self.duplicate_config.clone());
+        app.insert_resource(self.max_images);
+        app.insert_resource(self.page_config);
+        app.insert_resource(self.render_mode);
+        app.insert_resource(self.tint_directories);
+        app.insert_resource(self.timeline_scrubber);
+        app.insert_resource(self.thumbnail_atlas);
+        app.insert_resource(self.texture_budget.clone());
+        app.insert_resource(self.lazy_textures.clone());
+        app.insert_resource(self.thumbnail_cache.clone());
+        #[cfg(not(target_arch = "wasm32"))]
+        app.insert_resource(self.catalog.clone());
+        app.init_resource::<ImageOverflow>();
+
+        app.add_plugins((
+            LoadingScreenPlugin,
+            ThemePlugin,
+            ImageDisplayMaterialPlugin,
+            ScanErrorsPlugin,
+            StatusBarPlugin,
+            SelectionPlugin,
+            DeletePlugin,
+            HistoryPlugin,
+            VisibilityCullingPlugin,
+            TextInputPlugin,
+            TagsPlugin,
+            RotationPlugin,
+            FilterPlugin,
+            ScanCachePlugin,
+            HistogramPlugin,
+        ));
+        // What happens to quads once they're spawned
+        app.add_plugins((
+            PagingPlugin,
+            DimensionsPlugin,
+            DuplicatesPlugin,
+            SpawnAnimationPlugin,
+            TextureBudgetPlugin,
+            RegridPlugin,
+            TimelinePlugin,
+            SpriteModePlugin,
+            DirectoryTintPlugin,
+            ThumbnailAtlasPlugin,
+            ColorSearchPlugin,
+            ArchivePlugin,
+            JustifiedLayoutPlugin,
+            DirectoryGroupsPlugin,
+            TimelineScrubberPlugin,
+        ));
+        app.add_plugins((
+            MissingFilePlugin,
+            ViewerCommandPlugin,
+            LazyTexturePlugin,
+            ThumbnailPlugin,
+            MetadataPlugin,
+            AnimatedImagePlugin,
+            RatingPlugin,
+        ));
+        let mut errors = app.world_mut().resource_mut::<ScanErrors>();
+        for error in playlist_errors {
+            errors.push(error);
+        }
+        #[cfg(feature = "raw")]
+        app.add_plugins(RawPlugin);
+        #[cfg(feature = "heic")]
+        app.add_plugins(HeicPlugin);
+        #[cfg(feature = "video")]
+        app.add_plugins(VideoPlugin);
+        // Quads are meshes, and mesh picking isn't part of the default plugins
+        if !app.is_plugin_added::<MeshPickingPlugin>() {
+            app.add_plugins(MeshPickingPlugin);
+        }
+        app.add_event::<RescanRequested>();
+        app.add_event::<ScanCompleted>();
+        app.add_event::<ImagesAdded>();
+        app.add_event::<ImagesRemoved>();
+        app.add_event::<MaxImagesReached>();
+        app.init_resource::<ScanCounter>();
+        app.init_resource::<LastScan>();
+        app.init_resource::<ScanPaused>();
+        app.init_resource::<ScanProgress>();
+        app.init_resource::<ScanProgressChannel>();
+        app.init_resource::<Favorites>();
+
+        // Scanning, spawning and load tracking are what the loading screen is waiting on, so
+        // those run in every state. Anything driven by the user waits for `AppState::Running`.
+        let sets = || {
+            (
+                DirWatchingSet::Scan,
+                DirWatchingSet::SpawnQuads,
+                DirWatchingSet::DespawnQuads,
+            )
+                .chain()
+        };
+        app.configure_sets(PreUpdate, sets());
+        app.configure_sets(Update, sets());
+
+        // I'd scan in the PreUpdate
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(
+            PreUpdate,
+            (
+                scan_directories_system,
+                sync_image_overflow.run_if(resource_changed::<WatchedDirs>),
+            )
+                .chain()
+                .in_set(DirWatchingSet::Scan),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.add_plugins((FileWatchPlugin, CatalogPlugin));
+            app.init_resource::<FsEventChanges>();
+            if self.scan_config.backend == ScanBackend::Events {
+                app.add_plugins(FsEventsPlugin);
+            }
+        }
+        // There's no filesystem to scan in the browser, the images come from the manifest
+        #[cfg(target_arch = "wasm32")]
+        {
+            app.init_resource::<ImageManifest>();
+            app.add_systems(
+                PreUpdate,
+                (
+                    manifest::apply_image_manifest.run_if(
+                        resource_changed::<ImageManifest>.or(resource_changed::<MaxImages>),
+                    ),
+                    sync_image_overflow.run_if(resource_changed::<WatchedDirs>),
+                )
+                    .chain()
+                    .in_set(DirWatchingSet::Scan),
+            );
+        }
+        app.add_systems(
+            Update,
+            (
+                rescan_hotkey_system,
+                pause_hotkey_system.run_if(text_input_inactive),
+            )
+                .run_if(in_state(AppState::Running))
+                .in_set(DirWatchingSet::Scan),
+        );
+        app.add_systems(Update, update_image_load_states);
+        app.init_resource::<ReloadingTextures>();
+        app.add_event::<ImageModified>();
+        app.add_systems(
+            Update,
+            (
+                reload_modified_images,
+                retry_failed_loads.run_if(on_event::<ScanCompleted>),
+                refresh_reloaded_materials,
+            )
+                .chain()
+                .in_set(DirWatchingSet::Scan),
+        );
+        app.add_systems(
+            Update,
+            report_scan_limit
+                .run_if(on_event::<MaxImagesReached>)
+                .in_set(DirWatchingSet::Scan),
+        );
+
+        // Only worth looking for new images when the list has actually changed
+        app.init_resource::<SpawnedImages>();
+        app.add_observer(forget_despawned_quad);
+        app.add_systems(
+            Update,
+            slap_img_on_quad
+                .run_if(
+                    resource_changed::<CurrentPage>
+                        .or(resource_changed::<Duplicates>)
+                        .or(resource_changed::<DuplicateConfig>)
+                        .or(resource_changed::<Recovering>),
+                )
+                .in_set(DirWatchingSet::SpawnQuads),
+        );
+        app.add_systems(
+            Update,
+            despawn_stale_quads
+                .run_if(resource_changed::<CurrentPage>)
+                .in_set(DirWatchingSet::DespawnQuads),
+        );
+    }
+}
+
+/// System that handles directory scanning. The walking itself happens off on a [`ScanTask`],
+/// this starts them and takes in what they found.
+#[cfg(not(target_arch = "wasm32"))]
+fn scan_directories_system(
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut errors: ResMut<ScanErrors>,
+    mut scan_counter: ResMut<ScanCounter>,
+    mut last_scan_stats: ResMut<LastScan>,
+    config: Res<ScanConfig>,
+    time: Res<Time>,
+    mut rescan_requests: EventReader<RescanRequested>,
+    mut scan_events: ScanEvents,
+    cache_state: Res<ScanCacheState>,
+    paused: Res<ScanPaused>,
+    max_images: Res<MaxImages>,
+    // This is handy syntax for getting a local Resource<T> that you don't have to declare! (not well documented imo)
+    (mut pending, mut was_paused, mut last_scan, mut in_flight): (
+        Local<PendingRescan>,
+        Local<bool>,
+        Local<Option<f32>>,
+        Local<Option<ScanTask>>,
+    ),
+    mut debounce: Local<ScanDebounce>,
+    mut max_images_reached: EventWriter<MaxImagesReached>,
+    (mut progress, progress_channel): (ResMut<ScanProgress>, Res<ScanProgressChannel>),
+    mut fs_changes: ResMut<FsEventChanges>,
+) {
+    for request in rescan_requests.read() {
+        pending.merge(request);
+    }
+
+    // A scan that's already running carries on, and is taken in once we're unpaused
+    if paused.0 {
+        *was_paused = true;
+        return;
+    }
+    if max_images.is_changed() && !max_images.is_added() {
+        let images = watched_dirs.all_images();
+        apply_images(&mut watched_dirs, images, max_images.0);
+    }
+    if std::mem::take(&mut *was_paused) {
+        // Catch up on whatever changed while we weren't looking
+        pending.merge(&RescanRequested::all());
+    }
+
+    // Requested scans skip the interval check. The pending request is only taken once a scan
+    // actually starts, anything asked for while one is running waits for the next.
+    if in_flight.is_none() {
+        *in_flight = start_scan(
+            &watched_dirs,
+            &config,
+            &time,
+            &cache_state,
+            &debounce,
+            &progress_channel.sender,
+            &mut pending,
+            &mut fs_changes,
+            &mut last_scan,
+        );
+        if in_flight.is_some() {
+            progress.set_if_neq(ScanProgress::default());
+        }
+    }
+
+    let walked = progress_channel.drain();
+    if walked != WalkProgress::default() {
+        progress.dirs_seen += walked.dirs_seen;
+        progress.images_found += walked.images_found;
+    }
+    let finished = in_flight.as_mut().and_then(|scan| {
+        let result = block_on(future::poll_once(&mut scan.task))?;
+        Some((result, std::mem::take(&mut scan.targets), scan.started))
+    });
+    let mut started = Instant::now();
+    let mut found = None;
+    if let Some(((images, outcome), targets, scan_started)) = finished {
+        *in_flight = None;
+        if targets != watched_dirs.scan_targets() {
+            // A directory or playlist came or went mid-walk, so this would put back what's just
+            // been taken out, or leave out what's just been added
+            log::debug!("Watched directories changed during the scan, scanning again");
+            pending.merge(&RescanRequested::all());
+        } else {
+            started = scan_started;
+            if !progress.done {
+                progress.done = true;
+            }
+            for message in outcome.errors {
+                errors.push(message);
+            }
+            let mut images = images;
+            if !outcome.archives.is_empty() {
+                watched_dirs.update_archives(&mut images, outcome.archives, &config, &mut errors);
+            }
+            found = Some(images);
+            if outcome.statuses != watched_dirs.statuses {
+                watched_dirs.statuses = outcome.statuses;
+            }
+            // Only worth touching (and so waking everything watching `WatchedDirs`) when
+            // something was rewritten, or turned up for the first time
+            if outcome
+                .stamps
+                .iter()
+                .any(|(path, stamp)| watched_dirs.stamps.get(path) != Some(stamp))
+            {
+                watched_dirs.update_stamps(outcome.stamps);
+            }
+            if outcome
+                .captured
+                .iter()
+                .any(|(path, taken)| watched_dirs.captured.get(path) != Some(taken))
+            {
+                watched_dirs.captured.extend(outcome.captured);
+            }
+            if outcome.limit_reached != watched_dirs.limit_reached {
+                watched_dirs.limit_reached = outcome.limit_reached;
+                if let Some(limit) = config.scan_limit
+                    && outcome.limit_reached
+                {
+                    log::warn!("Stopped scanning at {limit} images, the rest are left out");
+                    max_images_reached.write(MaxImagesReached { limit });
+                }
+            }
+        }
+    }
+
+    // Playlists are cheap to check, so they're picked up straight away instead of on the interval
+    let found = if watched_dirs.playlists_changed() {
+        let current = watched_dirs.all_images();
+        let mut images = found.unwrap_or_else(|| debounce.latest(&current).to_vec());
+        watched_dirs.reload_playlists(&mut images, &config, &mut errors);
+        Some(images)
+    } else {
+        found
+    };
+
+    if let Some(found) = found {
+        scan_counter.0 += 1;
+        *last_scan_stats = LastScan {
+            duration: started.elapsed(),
+            files: found.len(),
+        };
+        let changed = debounce.offer(found, &watched_dirs.all_images(), time.elapsed());
+        if !changed {
+            scan_events.completed(&watched_dirs, last_scan_stats.duration);
+        }
+    }
+
+    // Nothing's on screen yet after the first scan, so there's nothing to wait for
+    let window = if scan_counter.0 <= 1 {
+        Duration::ZERO
+    } else {
+        config.debounce
+    };
+    if let Some(images) = debounce.poll(time.elapsed(), window) {
+        scan_events.changed(apply_images(&mut watched_dirs, images, max_images.0));
+        scan_events.completed(&watched_dirs, last_scan_stats.duration);
+    }
+}
+
+/// Start whichever scan is due, if any: a requested one first, then the paths filesystem events
+/// turned up, then the regular background scan
+#[cfg(not(target_arch = "wasm32"))]
+fn start_scan(
+    watched_dirs: &WatchedDirs,
+    config: &ScanConfig,
+    time: &Time,
+    cache_state: &ScanCacheState,
+    debounce: &ScanDebounce,
+    progress: &Sender<WalkProgress>,
+    pending: &mut PendingRescan,
+    fs_changes: &mut FsEventChanges,
+    last_scan: &mut Option<f32>,
+) -> Option<ScanTask> {
+    let mut forced = true;
+    let job = match std::mem::take(pending) {
+        PendingRescan::All => {
+            *last_scan = Some(time.elapsed_secs());
+            ScanJob::All
+        }
+        PendingRescan::Dirs(dirs) => ScanJob::Dirs(dirs),
+        // The directories are being watched, so there's only anything to do when they say so,
+        // bar the few that couldn't be and the archives, which are still checked on the interval
+        PendingRescan::Nothing if fs_changes.watching => {
+            forced = false;
+            let due = periodic_scan_due(config, time, cache_state, last_scan);
+            if due && !fs_changes.unwatched.is_empty() {
+                ScanJob::Dirs(fs_changes.unwatched.clone())
+            } else if !fs_changes.paths.is_empty() {
+                ScanJob::Changes(std::mem::take(&mut fs_changes.paths))
+            } else if due && watched_dirs.archives_changed() {
+                // No directories, only the archives
+                ScanJob::Dirs(vec![])
+            } else {
+                return None;
+            }
+        }
+        PendingRescan::Nothing => {
+            if !periodic_scan_due(config, time, cache_state, last_scan) {
+                return None;
+            }
+            forced = false;
+            ScanJob::All
+        }
+    };
+
+    // Build on top of anything still being debounced, not what's on screen
+    let current = watched_dirs.all_images();
+    let mut pass = watched_dirs.pass(config, debounce.latest(&current).to_vec(), time, progress);
+    pass.forced = forced;
+    pass.archives = watched_dirs.stale_archives();
+    if matches!(job, ScanJob::Changes(_)) {
+        // Nothing past the limit gets looked at, so there's no way to tell it's been lifted
+        pass.limit_reached = watched_dirs.limit_reached;
+    }
+    let targets = watched_dirs.scan_targets();
+    let walk = targets.clone();
+    Some(ScanTask {
+        task: AsyncComputeTaskPool::get().spawn(async move { walk.run(job, pass) }),
+        targets,
+        started: Instant::now(),
+    })
+}
+
+/// [`WatchedDirs::set_images`], only marking [`WatchedDirs`] changed (and so waking everything
+/// that watches it) if the list actually is different
+pub(crate) fn apply_images(
+    watched_dirs: &mut ResMut<WatchedDirs>,
+    images: Vec<PathBuf>,
+    max: Option<usize>,
+) -> Option<ImageChanges> {
+    let changes = watched_dirs
+        .bypass_change_detection()
+        .set_images(images, max)?;
+    watched_dirs.set_changed();
+    if !changes.is_empty() {
+        log::debug!(
+            "{} images added, {} removed",
+            changes.added.len(),
+            changes.removed.len()
+        );
+    }
+    Some(changes)
+}
+
+/// Whether the regular background scan is due, and if so note that it's starting
+#[cfg(not(target_arch = "wasm32"))]
+fn periodic_scan_due(
+    config: &ScanConfig,
+    time: &Time,
+    cache_state: &ScanCacheState,
+    last_scan: &mut Option<f32>,
+) -> bool {
+    // Only scan every so often to avoid performance hits, you can probs do something more clever than this
+    let scan_interval = config.interval.as_secs_f32();
+
+    // Started from the scan cache: give the cached grid a frame to show up before we go and walk
+    // the whole archive
+    if last_scan.is_none() && cache_state.awaiting_reconcile() {
+        *last_scan = Some(time.elapsed_secs() - scan_interval);
+        return false;
+    }
+
+    if let Some(last) = *last_scan
+        && time.elapsed_secs() - last < scan_interval
+    {
+        return false;
+    }
+
+    *last_scan = Some(time.elapsed_secs());
+    true
+}
+
+/// Poll the asset server for every quad whose texture is still in flight
+fn update_image_load_states(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut quads: Query<(Entity, &ImageTexture, &mut ImageLoadState, &ImageMarker)>,
+) {
+    for (entity, texture, mut state, marker) in &mut quads {
+        if *state != ImageLoadState::Pending {
+            continue;
+        }
+
+        match asset_server.load_state(&texture.0) {
+            bevy::asset::LoadState::Loaded => *state = ImageLoadState::Loaded,
+            bevy::asset::LoadState::Failed(e) => {
+                log::warn!("Failed to load {:?}: {e}", marker.target);
+                *state = ImageLoadState::Failed;
+                commands.entity(entity).insert(FailedLoad {
+                    stamp: file_stamp(&marker.target),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// On quads whose image failed to load, with what the file looked like when it did. It may well
+/// have been caught halfway through being written, so once a scan sees it looking any different
+/// it's tried again, see [`retry_failed_loads`].
+#[derive(Component)]
+struct FailedLoad {
+    stamp: Option<FileStamp>,
+}
+
+/// Sent for every quad whose texture is being reloaded because its image was rewritten on disk,
+/// so anything worked out from the old pixels can be worked out again
+#[derive(Event, Debug, Clone)]
+pub struct ImageModified {
+    pub path: PathBuf,
+}
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let meta = fs::metadata(path).ok()?;
+    Some(FileStamp {
+        modified: meta.modified().ok(),
+        len: meta.len(),
+    })
+}
+
+/// Textures waiting to come back from [`reload_modified_images`], so their materials can be
+/// told once they have
+#[derive(Resource, Default)]
+struct ReloadingTextures(HashSet<AssetId<Image>>);
+
+/// Reload the textures of images that were rewritten on disk. The handles stay the same, so the
+/// quads pick the new pixels up once they're in. Evicted textures are left alone, they'll be
+/// read fresh when they're next needed anyway. Packed ones let their texture go, so they're
+/// loaded again from scratch and packed over their old thumbnail once they're in. Ones that
+/// failed to load get another go.
+fn reload_modified_images(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut reloading: ResMut<ReloadingTextures>,
+    mut image_modified: EventWriter<ImageModified>,
+    mut quads: Query<(
+        Entity,
+        &ImageMarker,
+        &mut ImageTexture,
+        &mut ImageLoadState,
+        Has<AtlasSlot>,
+        Has<FailedLoad>,
+    )>,
+) {
+    // Checking every frame shouldn't count as a change
+    if watched_dirs.modified.is_empty() {
+        return;
+    }
+    let modified: HashSet<PathBuf> = watched_dirs
+        .bypass_change_detection()
+        .take_modified()
+        .into_iter()
+        .collect();
+    for (entity, marker, mut texture, mut state, packed, failed) in &mut quads {
+        // Failed without a `FailedLoad` means it couldn't even be asked for
+        if !modified.contains(&marker.target)
+            || *state == ImageLoadState::Evicted
+            || (*state == ImageLoadState::Failed && !failed)
+        {
+            continue;
+        }
+        log::debug!("{:?} changed on disk, reloading it", marker.target);
+        image_modified.write(ImageModified {
+            path: marker.target.clone(),
+        });
+        if failed {
+            retry_load(&mut commands, &asset_server, entity, marker, &mut state);
+            continue;
+        }
+        if packed && !texture.0.is_strong() {
+            if let Ok(handle) = load_image(&asset_server, &marker.target) {
+                texture.0 = handle;
+                *state = ImageLoadState::Pending;
+            }
+            continue;
+        }
+        asset_server.reload(AssetPath::from_path(&marker.target));
+        reloading.0.insert(texture.0.id());
+    }
+}
+
+/// After each scan, try again to load images that failed to, if their file has changed since.
+/// Catches the ones that were still being written when they failed but had already settled
+/// by the time the scan before that looked at them, so never showed up as modified.
+fn retry_failed_loads(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    watched_dirs: Res<WatchedDirs>,
+    mut quads: Query<(Entity, &ImageMarker, &mut ImageLoadState, &FailedLoad)>,
+) {
+    for (entity, marker, mut state, failed) in &mut quads {
+        if *state != ImageLoadState::Failed
+            || watched_dirs.stamp(&marker.target).is_none()
+            || watched_dirs.stamp(&marker.target) == failed.stamp
+        {
+            continue;
+        }
+        log::debug!(
+            "{:?} changed since it failed to load, trying again",
+            marker.target
+        );
+        retry_load(&mut commands, &asset_server, entity, marker, &mut state);
+    }
+}
+
+/// Ask for a failed image again. Its handle is still the failed one, so reloading its path puts
+/// the new attempt in that same handle, and the quad's material along with it.
+fn retry_load(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    entity: Entity,
+    marker: &ImageMarker,
+    state: &mut ImageLoadState,
+) {
+    asset_server.reload(AssetPath::from_path(&marker.target));
+    *state = ImageLoadState::Pending;
+    commands.entity(entity).remove::<FailedLoad>();
+}
+
+/// A material only looks its textures up again when the material itself changes, so poke the
+/// ones whose texture just got reloaded
+fn refresh_reloaded_materials(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut reloading: ResMut<ReloadingTextures>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    quads: Query<(&ImageTexture, &MeshMaterial3d<ImageDisplayMaterial>)>,
+) {
+    let mut reloaded = HashSet::new();
+    for event in image_events.read() {
+        if let AssetEvent::Modified { id } = event
+            && reloading.0.remove(id)
+        {
+            reloaded.insert(*id);
+        }
+    }
+    if reloaded.is_empty() {
+        return;
+    }
+    for (texture, material) in &quads {
+        if reloaded.contains(&texture.0.id()) {
+            materials.get_mut(&material.0);
+        }
+    }
+}
+
+fn sync_image_overflow(watched_dirs: Res<WatchedDirs>, mut overflow: ResMut<ImageOverflow>) {
+    overflow.set_if_neq(ImageOverflow {
+        shown: watched_dirs.imgs.len(),
+        total: watched_dirs.imgs.len() + watched_dirs.overflow.len(),
+    });
+}
+
+fn report_scan_limit(mut reached: EventReader<MaxImagesReached>, mut status: ResMut<StatusBar>) {
+    if let Some(MaxImagesReached { limit }) = reached.read().last() {
+        status.set(format!(
+            "Stopped scanning at {limit} images, the rest of the folders were left out"
+        ));
+    }
+}
+
+/// F5 forces a full rescan
+fn rescan_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut rescan_requests: EventWriter<RescanRequested>,
+) {
+    if keys.just_pressed(KeyCode::F5) {
+        rescan_requests.write(RescanRequested::all());
+    }
+}
+
+/// Start watching `dir` and scan it right away. Goes in the [`History`], so it can be undone.
+pub fn watch_dir(
+    In(dir): In<PathBuf>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    config: Res<ScanConfig>,
+    mut rescan_requests: EventWriter<RescanRequested>,
+    mut status: ResMut<StatusBar>,
+    mut history: ResMut<History>,
+) {
+    let before = watched_dirs.snapshot();
+    if !watched_dirs.add_dir(WatchedDir::new(&dir, config.recursive)) {
+        status.set(format!("Already watching {}", dir.display()));
+        return;
+    }
+    log::info!("Watching {dir:?}");
+    rescan_requests.write(RescanRequested::dir(dir.clone()));
+    status.set(format!("Watching {}", dir.display()));
+    history.record(Action::WatchDir {
+        dir,
+        before,
+        after: watched_dirs.snapshot(),
+    });
+}
+
+/// Stop watching `dir`, its images go straight away. Goes in the [`History`], so it can be undone.
+pub fn unwatch_dir(
+    In(dir): In<PathBuf>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut status: ResMut<StatusBar>,
+    mut history: ResMut<History>,
+) {
+    let before = watched_dirs.snapshot();
+    if watched_dirs.remove_dir(&dir).is_none() {
+        return;
+    }
+    log::info!("Stopped watching {dir:?}");
+    status.set(format!("Stopped watching {}", dir.display()));
+    history.record(Action::UnwatchDir {
+        dir,
+        before,
+        after: watched_dirs.snapshot(),
+    });
+}
+
+/// Switch whether `dir`'s subdirectories are scanned, and rescan it. Goes in the [`History`], so
+/// it can be undone.
+pub fn set_dir_recursive(
+    In((dir, recursive)): In<(PathBuf, bool)>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut rescan_requests: EventWriter<RescanRequested>,
+    mut status: ResMut<StatusBar>,
+    mut history: ResMut<History>,
+) {
+    if !watched_dirs.set_recursive(&dir, recursive) {
+        return;
+    }
+    rescan_requests.write(RescanRequested::dir(dir.clone()));
+    status.set(if recursive {
+        format!("Scanning {} and its subfolders", dir.display())
+    } else {
+        format!("Only scanning the top of {}", dir.display())
+    });
+    history.record(Action::SetRecursive { dir, recursive });
+}
+
+/// P pauses and resumes scanning
+fn pause_hotkey_system(keys: Res<ButtonInput<KeyCode>>, mut paused: ResMut<ScanPaused>) {
+    if keys.just_pressed(KeyCode::KeyP) {
+        paused.0 = !paused.0;
+    }
+}
+
+impl WatchedDirs {
+    /// Directories are canonicalized (see [`normalize_path`]), so the same one given twice, or
+    /// again through a symlink, is only watched once
+    pub fn new(dirs: Vec<WatchedDir>) -> Self {
+        let mut canonical: Vec<WatchedDir> = vec![];
+        for dir in dirs {
+            let dir = WatchedDir::new(normalize_path(&dir.path), dir.recursive);
+            if !canonical.iter().any(|watched| watched.path == dir.path) {
+                canonical.push(dir);
+            }
+        }
+        Self {
+            dirs: canonical,
+            playlists: vec![],
+            archives: vec![],
+            loose: vec![],
+            imgs: vec![],
+            overflow: vec![],
+            statuses: HashMap::new(),
+            limit_reached: false,
+            stamps: HashMap::new(),
+            modified: vec![],
+            captured: HashMap::new(),
+            dimensions: HashMap::new(),
+        }
+    }
+
+    /// Read a playlist file (see [`Playlist`]) and add the images it lists. The file is checked
+    /// for edits before every scan and re-read when it changes, without rescanning the
+    /// directories. Returns how many images it listed.
+    pub fn add_playlist(&mut self, path: &Path, config: &ScanConfig) -> std::io::Result<usize> {
+        let playlist = Playlist::load(path, config)?;
+        let count = playlist.images.len();
+        self.imgs.extend(playlist.images.iter().cloned());
+        dedup_images(&mut self.imgs);
+        self.playlists.push(playlist);
+        Ok(count)
+    }
+
+    /// Show a single image, whichever directory it's in. It stays through rescans like the images
+    /// from a playlist do. Returns false if it was already showing.
+    pub fn add_image(&mut self, path: &Path) -> bool {
+        let path = normalize_path(path);
+        if self.loose.contains(&path) || self.imgs.contains(&path) {
+            return false;
+        }
+        self.loose.push(path.clone());
+        self.imgs.push(path);
+        true
+    }
+
+    /// The playlist files being followed, see [`Self::add_playlist`]
+    pub fn playlists(&self) -> impl Iterator<Item = &Path> {
+        self.playlists
+            .iter()
+            .map(|playlist| playlist.path.as_path())
+    }
+
+    fn playlists_changed(&self) -> bool {
+        self.playlists.iter().any(Playlist::is_stale)
+    }
+
+    /// Re-read any playlists that were edited, swapping the images they used to list in `images`
+    /// for the ones they list now
+    fn reload_playlists(
+        &mut self,
+        images: &mut Vec<PathBuf>,
+        config: &ScanConfig,
+        errors: &mut ScanErrors,
+    ) {
+        for playlist in self
+            .playlists
+            .iter_mut()
+            .filter(|playlist| playlist.is_stale())
+        {
+            let reloaded = match Playlist::load(&playlist.path, config) {
+                Ok(reloaded) => reloaded,
+                Err(e) => {
+                    log::warn!("Couldn't read playlist {:?}: {e}", playlist.path);
+                    errors.push(format!(
+                        "Couldn't read playlist {}: {e}",
+                        playlist.path.display()
+                    ));
+                    // Keep showing what it listed last time
+                    continue;
+                }
+            };
+
+            let dropped: HashSet<&Path> = playlist.images.iter().map(PathBuf::as_path).collect();
+            // Anything that's also in a watched directory stays
+            images.retain(|img| {
+                !dropped.contains(img.as_path()) || self.dirs.iter().any(|dir| dir.covers(img))
+            });
+            images.extend(reloaded.images.iter().cloned());
+            *playlist = reloaded;
+        }
+        dedup_images(images);
+        sort_images(images, config.sort);
+    }
+
+    /// Show the images in a ZIP file as if it were another watched directory. They're extracted
+    /// to a temporary directory by the next scan, and again by the first one after the file
+    /// changes. Returns false if it was already added.
+    pub fn add_archive(&mut self, path: PathBuf) -> bool {
+        let path = normalize_path(&path);
+        if self.archives.iter().any(|archive| archive.source.0 == path) {
+            return false;
+        }
+        self.archives.push(Archive::new(ArchiveSource(path)));
+        true
+    }
+
+    /// The ZIP files being shown, see [`Self::add_archive`]
+    pub fn archives(&self) -> impl Iterator<Item = &ArchiveSource> {
+        self.archives.iter().map(|archive| &archive.source)
+    }
+
+    /// The archive `image` was extracted from, if it's from one
+    pub fn archive_of(&self, image: &Path) -> Option<&ArchiveSource> {
+        self.archives
+            .iter()
+            .find(|archive| archive.contains(image))
+            .map(|archive| &archive.source)
+    }
+
+    fn archives_changed(&self) -> bool {
+        self.archives.iter().any(Archive::is_stale)
+    }
+
+    /// The archives that are new or were replaced, for the next scan to extract
+    fn stale_archives(&self) -> Vec<PathBuf> {
+        self.archives
+            .iter()
+            .filter(|archive| archive.is_stale())
+            .map(|archive| archive.source.0.clone())
+            .collect()
+    }
+
+    /// Take in the archives a scan extracted, swapping their old images in `images` for the new
+    /// ones
+    fn update_archives(
+        &mut self,
+        images: &mut Vec<PathBuf>,
+        extracted: Vec<ExtractedArchive>,
+        config: &ScanConfig,
+        errors: &mut ScanErrors,
+    ) {
+        for read in extracted {
+            let Some(archive) = self
+                .archives
+                .iter_mut()
+                .find(|archive| archive.source.0 == read.source)
+            else {
+                continue;
+            };
+            let dropped = archive.images.clone();
+            if let Err(e) = archive.update(read) {
+                log::warn!("Couldn't read archive {:?}: {e}", archive.source.0);
+                errors.push(format!(
+                    "Couldn't read archive {}: {e}",
+                    archive.source.0.display()
+                ));
+                // Keep showing what it had last time
+                continue;
+            }
+            // Extracted somewhere new, so none of the old paths are anywhere else
+            images.retain(|img| !dropped.contains(img));
+            images.extend(archive.images.iter().cloned());
+        }
+        dedup_images(images);
+        sort_images(images, config.sort);
+    }
+
+    /// Delete everything extracted from the archives, which stop showing any images
+    fn close_archives(&mut self) {
+        for archive in &mut self.archives {
+            archive.close();
+        }
+    }
+
+    /// The directories currently being watched
+    pub fn watched_dirs(&self) -> &[WatchedDir] {
+        &self.dirs
+    }
+
+    /// The watched directory `path` names, normalized like [`Self::contains_dir`]
+    pub fn watched_dir(&self, path: &Path) -> Option<&WatchedDir> {
+        find_watched_dir(&self.dirs, path)
+    }
+
+    /// The innermost watched directory whose scan finds `image`
+    pub fn dir_of(&self, image: &Path) -> Option<&WatchedDir> {
+        innermost_dir(&self.dirs, image)
+    }
+
+    /// Start watching another directory. Nothing from it shows up until it's scanned, see
+    /// [`watch_dir`]. Canonicalized like in [`Self::new`], and returns false if it was already
+    /// watched.
+    pub fn add_dir(&mut self, dir: WatchedDir) -> bool {
+        if self.contains_dir(&dir.path) {
+            return false;
+        }
+        self.dirs
+            .push(WatchedDir::new(normalize_path(&dir.path), dir.recursive));
+        true
+    }
+
+    /// Change whether a watched directory's subdirectories are scanned. Its images stay as they
+    /// are until it's rescanned, see [`set_dir_recursive`]. Returns false if nothing changed.
+    pub fn set_recursive(&mut self, path: &Path, recursive: bool) -> bool {
+        let wanted = normalize_path(path);
+        let Some(dir) = self
+            .dirs
+            .iter_mut()
+            .find(|watched| normalize_path(&watched.path) == wanted)
+        else {
+            return false;
+        };
+        let changed = dir.recursive != recursive;
+        dir.recursive = recursive;
+        changed
+    }
+
+    /// Stop watching a directory, dropping its images unless another watched directory or a
+    /// playlist still covers them. Returns the directory, if it was watched.
+    pub fn remove_dir(&mut self, dir: &Path) -> Option<WatchedDir> {
+        let wanted = normalize_path(dir);
+        let index = self
+            .dirs
+            .iter()
+            .position(|watched| normalize_path(&watched.path) == wanted)?;
+        let removed = self.dirs.remove(index);
+        self.statuses.remove(&removed.path);
+
+        let still_covered = |img: &PathBuf| {
+            !removed.covers(img)
+                || self.dirs.iter().any(|dir| dir.covers(img))
+                || self
+                    .playlists
+                    .iter()
+                    .any(|playlist| playlist.images.contains(img))
+                || self.archives.iter().any(|archive| archive.contains(img))
+        };
+        let shown = self.imgs.len();
+        let imgs: Vec<PathBuf> = self.imgs.drain(..).filter(still_covered).collect();
+        let overflow: Vec<PathBuf> = self.overflow.drain(..).filter(still_covered).collect();
+        self.imgs = imgs;
+        self.overflow = overflow;
+        // Keep showing as many as before, the cap hasn't changed
+        let refill = shown
+            .saturating_sub(self.imgs.len())
+            .min(self.overflow.len());
+        self.imgs.extend(self.overflow.drain(..refill));
+        Some(removed)
+    }
+
+    pub fn snapshot(&self) -> WatchedDirsSnapshot {
+        WatchedDirsSnapshot {
+            dirs: self.dirs.clone(),
+        }
+    }
 
-/// Resource for watched directories, a 'watched' dir is one we're looking at the contents of,
-/// and periodically scanning for images.
-#[derive(Resource, Default)]
-struct WatchedDirs {
-    dirs: Vec<PathBuf>,
-    imgs: Vec<PathBuf>,
-}
+    /// Watch exactly the directories in `snapshot` again, in its order. Ones that aren't in it
+    /// are dropped like with [`Self::remove_dir`], images and all. Ones that come back (or now
+    /// look at a different depth) only get their images once they're rescanned, so they're
+    /// returned for that.
+    pub fn restore(&mut self, snapshot: WatchedDirsSnapshot) -> Vec<PathBuf> {
+        let gone: Vec<PathBuf> = self
+            .dirs
+            .iter()
+            .filter(|dir| !snapshot.dirs.iter().any(|kept| kept.path == dir.path))
+            .map(|dir| dir.path.clone())
+            .collect();
+        for dir in gone {
+            self.remove_dir(&dir);
+        }
 
-/// For later spawn/despawn usage, you can make a system that matches on Paths and remove/add quads for an image not already added/that you wanna remove..
-#[derive(Component)]
-struct ImageMarker {
-    target: PathBuf,
-}
+        let mut rescan = vec![];
+        for dir in &snapshot.dirs {
+            match self.watched_dir(&dir.path) {
+                Some(current) if current.recursive == dir.recursive => {}
+                Some(_) => {
+                    self.set_recursive(&dir.path, dir.recursive);
+                    rescan.push(dir.path.clone());
+                }
+                None => {
+                    self.add_dir(dir.clone());
+                    rescan.push(dir.path.clone());
+                }
+            }
+        }
+        self.dirs = snapshot.dirs;
+        rescan
+    }
 
-/// Wrap everything in a plugin for modularity
-pub struct DirWatchingPlugin;
+    /// Every image found by the last scan, across all watched directories
+    pub fn images(&self) -> &[PathBuf] {
+        &self.imgs
+    }
 
-impl Plugin for DirWatchingPlugin {
-    fn build(&self, app: &mut App) {
-        log::debug!("Adding DirWatchingPlugin");
-        // Probs do this for yours:
-        // app.insert_resource(WatchedDirs::default());
+    /// The images under `dir`, subdirectories included, in the same order as [`Self::images`].
+    /// `dir` is normalized like [`Self::contains_dir`] (images are canonical already), and doesn't
+    /// have to be one of the watched directories.
+    pub fn iter_images_in_dir<'a>(
+        &'a self,
+        dir: &'a Path,
+    ) -> impl Iterator<Item = &'a PathBuf> + 'a {
+        let dir = normalize_path(dir);
+        self.imgs.iter().filter(move |img| img.starts_with(&dir))
+    }
 
-        // for demo purposes I've just hardcoded something.
-        let img_dirs_testing = PathBuf::from("/media/jer/ARCHIVE/jpg/2024/December");
-        app.insert_resource(WatchedDirs {
-            dirs: vec![img_dirs_testing],
-            imgs: vec![],
-        });
+    /// [`Self::iter_images_in_dir`], collected
+    pub fn images_in_dir(&self, dir: &Path) -> Vec<&PathBuf> {
+        let dir = normalize_path(dir);
+        self.imgs
+            .iter()
+            .filter(|img| img.starts_with(&dir))
+            .collect()
+    }
 
-        // I'd scan in the PreUpdate
-        app.add_systems(PreUpdate, scan_directories_system);
+    pub fn image_count(&self) -> usize {
+        self.imgs.len()
+    }
 
-        //HACKS:
-        // this probably needs a marker to only one once, or on_event::<Event<T>> rather than my hack here.
-        app.add_systems(Update, slap_img_on_quad.run_if(WatchedDirs::should_run));
+    pub fn dir_count(&self) -> usize {
+        self.dirs.len()
     }
-}
 
-/// System that handles directory scanning
-fn scan_directories_system(
-    mut watched_dirs: ResMut<WatchedDirs>,
-    time: Res<Time>,
-    mut last_scan: Local<Option<f32>>, // This is handy syntax for getting a local Resource<T> that you don't have to declare! (not well documented imo)
-) {
-    // Only scan every 5 seconds to avoid performance hits, you can probs do something more clever than this
-    let scan_interval = 5.0;
+    /// Is `path` one of the watched directories? Paths are normalized first, so `foo/` and
+    /// `./foo` both match `foo`.
+    pub fn contains_dir(&self, path: &Path) -> bool {
+        let path = normalize_path(path);
+        self.dirs
+            .iter()
+            .any(|dir| normalize_path(&dir.path) == path)
+    }
 
-    if let Some(last) = *last_scan
-        && time.elapsed_secs() - last < scan_interval {
-            return;
+    /// Was `path` found by the last scan? Normalized the same way as [`Self::contains_dir`], which
+    /// the images already are.
+    pub fn contains_image(&self, path: &Path) -> bool {
+        self.imgs.contains(&normalize_path(path))
+    }
+
+    /// Default image extensions, see [`ScanConfig::extensions`]. The RAW ones are shown by the
+    /// previews embedded in them, see [`RawPlugin`], HEIC through [`HeicPlugin`], and videos by
+    /// their first frame, see [`VideoPlugin`].
+    pub const SUPPORTED_EXTENSIONS: &'static [&'static str] = &[
+        "jpg",
+        "jpeg",
+        "png",
+        "gif",
+        "bmp",
+        "tiff",
+        "tif",
+        "webp",
+        "ico",
+        "svg",
+        #[cfg(feature = "raw")]
+        "cr2",
+        #[cfg(feature = "raw")]
+        "nef",
+        #[cfg(feature = "raw")]
+        "arw",
+        #[cfg(feature = "raw")]
+        "dng",
+        #[cfg(feature = "heic")]
+        "heic",
+        #[cfg(feature = "heic")]
+        "heif",
+        #[cfg(feature = "video")]
+        "mp4",
+        #[cfg(feature = "video")]
+        "mov",
+        #[cfg(feature = "video")]
+        "m4v",
+        #[cfg(feature = "video")]
+        "mkv",
+        #[cfg(feature = "video")]
+        "webm",
+        #[cfg(feature = "video")]
+        "avi",
+    ];
+
+    /// Did the last scan stop early at [`ScanConfig::scan_limit`]?
+    pub fn scan_limit_reached(&self) -> bool {
+        self.limit_reached
+    }
+
+    /// How each watched directory's last scan went, see [`DirStatus`]
+    pub fn dir_status(&self, dir: &Path) -> Option<&DirStatus> {
+        self.statuses.get(dir)
+    }
+
+    /// Watched directories whose last scan failed
+    pub fn unreachable_dirs(&self) -> impl Iterator<Item = (&Path, &DirStatus)> {
+        self.dirs.iter().filter_map(|dir| {
+            self.statuses
+                .get(&dir.path)
+                .filter(|status| !status.reachable)
+                .map(|status| (dir.path.as_path(), status))
+        })
+    }
+
+    fn pass(
+        &self,
+        config: &ScanConfig,
+        previous: Vec<PathBuf>,
+        time: &Time,
+        progress: &Sender<WalkProgress>,
+    ) -> ScanPass {
+        ScanPass {
+            config: config.clone(),
+            errors: vec![],
+            stamps: HashMap::with_capacity(previous.len()),
+            previous,
+            statuses: self.statuses.clone(),
+            now: time.elapsed(),
+            forced: false,
+            found: 0,
+            limit_reached: false,
+            captured: HashMap::new(),
+            known_dates: Arc::new(if config.capture_dates {
+                self.captured.clone()
+            } else {
+                HashMap::new()
+            }),
+            progress: progress.clone(),
+            archives: vec![],
+        }
+    }
+
+    /// What a scan needs to walk, see [`ScanTargets`]
+    fn scan_targets(&self) -> ScanTargets {
+        let playlists = self.playlists.iter().flat_map(|playlist| &playlist.images);
+        let archives = self.archives.iter().flat_map(|archive| &archive.images);
+        ScanTargets {
+            dirs: self.dirs.clone(),
+            listed: playlists
+                .chain(archives)
+                .chain(&self.loose)
+                .cloned()
+                .collect(),
         }
+    }
 
-    watched_dirs.scan();
-    *last_scan = Some(time.elapsed_secs());
-}
+    /// Forget about an image without rescanning, e.g. because we just deleted it. Returns whether
+    /// it was there.
+    pub fn remove_image(&mut self, path: &Path) -> bool {
+        let shown = self.imgs.len();
+        self.imgs.retain(|img| img != path);
+        let was_shown = self.imgs.len() != shown;
 
-impl WatchedDirs {
-    // we use patterns like this all the time @ dayjob
-    fn should_run(res: Res<WatchedDirs>) -> bool {
-        !res.imgs.is_empty()
+        let held_back = self.overflow.len();
+        self.overflow.retain(|img| img != path);
+        // Let the next image in under the cap
+        if was_shown && !self.overflow.is_empty() {
+            self.imgs.push(self.overflow.remove(0));
+        }
+        was_shown || self.overflow.len() != held_back
     }
 
-    /// Supported image extensions
-    const SUPPORTED_EXTENSIONS: &'static [&'static str] = &[
-        "jpg", "jpeg", "png", "gif", "bmp", "tiff", "tif", "webp", "ico", "svg",
-    ];
+    /// How an image looked on disk as of the last scan that reached it. `None` for images that
+    /// haven't been scanned, e.g. the ones from playlists.
+    pub fn stamp(&self, path: &Path) -> Option<FileStamp> {
+        self.stamps.get(path).copied()
+    }
+
+    /// When an image was taken: its EXIF date if a scan read one (see
+    /// [`ScanConfig::capture_dates`]), otherwise when it was last modified. `None` for images
+    /// that haven't been scanned.
+    pub fn capture_date(&self, path: &Path) -> Option<SystemTime> {
+        self.exif_date(path).or_else(|| self.stamp(path)?.modified)
+    }
+
+    /// The date a scan read out of an image's EXIF data, if it had one
+    pub fn exif_date(&self, path: &Path) -> Option<SystemTime> {
+        self.captured.get(path)?.taken
+    }
+
+    /// An image's width and height, once [`DimensionsPlugin`] has read them
+    pub fn dimensions(&self, path: &Path) -> Option<(u32, u32)> {
+        self.dimensions.get(path)?.size
+    }
+
+    /// Images whose size hasn't been read yet, or was read from an older version of the file
+    fn unprobed(&self) -> impl Iterator<Item = &PathBuf> {
+        self.imgs
+            .iter()
+            .filter(|path| match self.dimensions.get(*path) {
+                Some(probed) => self
+                    .stamp(path)
+                    .is_some_and(|stamp| stamp.modified != probed.modified),
+                None => true,
+            })
+    }
+
+    fn add_dimensions(&mut self, probed: impl IntoIterator<Item = (PathBuf, ProbedDimensions)>) {
+        self.dimensions.extend(probed);
+    }
 
-    /// Check if a file has a supported image extension
-    fn is_supported_image(path: &Path) -> bool {
-        if let Some(extension) = path.extension()
-            && let Some(ext_str) = extension.to_str() {
-                return Self::SUPPORTED_EXTENSIONS.contains(&ext_str.to_lowercase().as_str());
+    /// Take in a scan's [`FileStamp`]s, queueing up the images that changed since the last one
+    fn update_stamps(&mut self, stamps: HashMap<PathBuf, FileStamp>) {
+        for (path, stamp) in stamps {
+            if let Some(previous) = self.stamps.insert(path.clone(), stamp)
+                && previous != stamp
+                && !self.modified.contains(&path)
+            {
+                self.modified.push(path);
             }
-        false
+        }
     }
 
-    /// Recursively collect all image files from a directory
-    fn collect_images_recursive(
-        dir: &Path,
-        images: &mut Vec<PathBuf>,
-    ) -> Result<(), std::io::Error> {
-        if !dir.is_dir() {
-            return Ok(());
+    /// Queue an image's texture to be reloaded because the file was seen changing, catching its
+    /// [`FileStamp`] up so the next scan doesn't reload it all over again
+    fn mark_modified(&mut self, path: &Path) {
+        if let Some(stamp) = self.stamps.get_mut(path)
+            && let Some(current) = file_stamp(path)
+        {
+            *stamp = current;
+        }
+        if !self.modified.iter().any(|modified| modified == path) {
+            self.modified.push(path.to_path_buf());
         }
+    }
 
-        let entries = fs::read_dir(dir)?;
-        for entry in entries {
-            //NOTE: a call to .flatten() over an iterator to .collect() would be more my style,
-            // but i've tried to use for-loops here as they're more what bevy's source uses.
-            let entry = entry?;
-            let path = entry.path();
+    /// Hand over the images that were rewritten since they were last loaded
+    fn take_modified(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.modified)
+    }
 
-            if path.is_dir() {
-                // Recursively scan subdirectories
-                Self::collect_images_recursive(&path, images)?;
-            } else if path.is_file() && Self::is_supported_image(&path) {
-                images.push(path);
+    /// Make `images` the image list, holding back any past `max`. Returns what came and went, or
+    /// `None` if the list came out exactly as it was, in which case nothing's touched. Go
+    /// through [`apply_images`] from a system, so `WatchedDirs` only reads as changed when it
+    /// did.
+    fn set_images(&mut self, mut images: Vec<PathBuf>, max: Option<usize>) -> Option<ImageChanges> {
+        let overflow = match max {
+            Some(max) if images.len() > max => images.split_off(max),
+            _ => vec![],
+        };
+        if images == self.imgs && overflow == self.overflow {
+            return None;
+        }
+
+        let before = self.all_images();
+        // Scans come back sorted, so whatever stays keeps its place relative to the rest and
+        // new images slot in around it
+        self.imgs = images;
+        self.overflow = overflow;
+        let changes = ImageChanges::between(&before, &self.all_images());
+        if !changes.removed.is_empty() {
+            let current: HashSet<&PathBuf> = self.imgs.iter().chain(&self.overflow).collect();
+            self.stamps.retain(|path, _| current.contains(path));
+            self.captured.retain(|path, _| current.contains(path));
+            self.dimensions.retain(|path, _| current.contains(path));
+        }
+        Some(changes)
+    }
+
+    /// Everything the last scan found, including images held back by [`MaxImages`]
+    fn all_images(&self) -> Vec<PathBuf> {
+        self.imgs.iter().chain(&self.overflow).cloned().collect()
+    }
+}
+
+/// What a scan walks, copied out of [`WatchedDirs`] so the walk can run on another thread
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ScanTargets {
+    dirs: Vec<WatchedDir>,
+    /// From the playlists and archives, and the images opened on their own, none of which get
+    /// walked
+    listed: Vec<PathBuf>,
+}
+
+impl ScanTargets {
+    /// Append the images under `dir` to `images`, reporting whatever couldn't be read. Returns
+    /// false, leaving `images` alone, if the directory is unreachable or waiting out a retry.
+    fn scan_into(watched: &WatchedDir, images: &mut Vec<PathBuf>, pass: &mut ScanPass) -> bool {
+        let dir = watched.path.as_path();
+        let status = pass
+            .statuses
+            .entry(dir.to_path_buf())
+            .or_insert_with(DirStatus::reachable);
+        if !pass.forced && !status.is_due(pass.now) {
+            return false;
+        }
+
+        // Missing isn't the same as unreachable: there's nothing to keep showing, and nothing to
+        // back off from, since checking again costs next to nothing
+        if !dir.exists() {
+            let ancestor = dir
+                .ancestors()
+                .skip(1)
+                .find(|ancestor| ancestor.is_dir())
+                .unwrap_or(Path::new("."));
+            if !status.is_waiting() {
+                log::info!(
+                    "{} doesn't exist yet, it'll be picked up once it does",
+                    dir.display()
+                );
+            }
+            // Also when part of the way there has turned up, so the watch on it moves down
+            if status.waiting_on.as_deref() != Some(ancestor) {
+                *status = DirStatus::waiting_on(ancestor.to_path_buf());
+            }
+            return true;
+        }
+        if status.is_waiting() {
+            log::info!("{} is available now", dir.display());
+        }
+
+        let remaining = pass
+            .config
+            .scan_limit
+            .map(|limit| limit.saturating_sub(pass.found));
+        if pass.limit_reached {
+            // Earlier directories went past the whole limit
+            return true;
+        }
+
+        let scanner = Scanner::new(
+            RealFileSystem,
+            ScanOptions {
+                max_images: remaining,
+                expected_images: pass
+                    .previous
+                    .iter()
+                    .filter(|img| watched.covers(img))
+                    .count(),
+                ..watched.scan_options(&pass.config)
+            },
+        )
+        .with_progress(pass.progress.clone())
+        .with_known_dates(pass.known_dates.clone());
+        let (entries, failures) = match scanner.scan(dir) {
+            Ok(entries) => (entries, vec![]),
+            Err(e @ ScanError::LimitReached { .. }) => {
+                pass.limit_reached = true;
+                e.into_partial()
+            }
+            // Nothing at all could be read, most likely the directory (or the share it's on) is
+            // gone. Back off rather than hammering it on every scan.
+            Err(e @ (ScanError::NotADirectory(_) | ScanError::Io { .. })) => {
+                let message = match e {
+                    ScanError::NotADirectory(_) => {
+                        format!("Not a directory: {}", dir.display())
+                    }
+                    e => format!("Error scanning {e}"),
+                };
+                log::warn!("{message}");
+                status.failed(message.clone(), pass.now);
+                if status.failures == 1 {
+                    pass.errors.push(message);
+                } else if status.gave_up() {
+                    pass.errors.push(format!(
+                        "Gave up on {} after {} tries, rescan to try again",
+                        dir.display(),
+                        status.failures
+                    ));
+                }
+                return false;
+            }
+            Err(e) => e.into_partial(),
+        };
+
+        *status = DirStatus::reachable();
+        for failure in failures {
+            log::warn!("Error scanning directory {dir:?}: {failure}");
+            pass.errors.push(format!("Error scanning {failure}"));
+        }
+        pass.found += entries.len();
+        images.reserve(entries.len());
+        // The walk can pass through symlinks, so the same file can turn up under two paths
+        for entry in entries {
+            let path = normalize_path(&entry.path);
+            pass.stamps.insert(
+                path.clone(),
+                FileStamp {
+                    modified: entry.modified,
+                    len: entry.len,
+                },
+            );
+            if pass.config.capture_dates {
+                pass.captured.insert(
+                    path.clone(),
+                    CaptureDate {
+                        modified: entry.modified,
+                        taken: entry.taken,
+                    },
+                );
             }
+            images.push(path);
         }
-        Ok(())
+        true
     }
 
-    /// Scan all directories and populate the imgs vector with found image files
-    fn scan(&mut self) {
-        self.imgs.clear();
+    /// Scan all directories for image files. The result isn't applied to [`WatchedDirs::images`]
+    /// here, that's up to the debouncing in the scan system.
+    fn collect_all(&self, pass: &mut ScanPass) -> Vec<PathBuf> {
+        // Most scans find about what the last one did
+        let mut images = Vec::with_capacity(pass.previous.len());
 
         for dir in &self.dirs {
-            if dir.exists() {
-                if let Err(e) = Self::collect_images_recursive(dir, &mut self.imgs) {
-                    log::warn!("Error scanning directory {dir:?}: {e}");
-                }
-            } else {
-                log::warn!("Directory does not exist: {dir:?}");
+            if !Self::scan_into(dir, &mut images, pass) {
+                let previous: Vec<_> = pass
+                    .previous
+                    .iter()
+                    .filter(|img| dir.covers(img))
+                    .cloned()
+                    .collect();
+                pass.found += previous.len();
+                images.extend(previous);
             }
         }
+        images.extend(self.listed.iter().cloned());
+        dedup_images(&mut images);
+        sort_images(&mut images, pass.config.sort);
 
         log::debug!(
             "Found {} images across {} directories",
-            self.imgs.len(),
+            images.len(),
             self.dirs.len()
         );
+        images
+    }
+
+    /// Rescan a single watched directory into `images`, leaving images from the other
+    /// directories alone
+    fn collect_dir(&self, images: &mut Vec<PathBuf>, dir: &Path, pass: &mut ScanPass) {
+        let Some(root) = find_watched_dir(&self.dirs, dir).cloned() else {
+            log::warn!("Rescan requested for a directory that isn't watched: {dir:?}");
+            return;
+        };
+
+        // The other directories' images count towards the limit too
+        pass.found = images
+            .iter()
+            .filter(|img| !img.starts_with(&root.path))
+            .count();
+        let mut found = vec![];
+        if !Self::scan_into(&root, &mut found, pass) {
+            return;
+        }
+        // Everything under it, not just what it covers, in case it just stopped being recursive
+        images.retain(|img| !img.starts_with(&root.path));
+        images.extend(found);
+        // Playlists can list images from inside the directory too
+        dedup_images(images);
+        sort_images(images, pass.config.sort);
+    }
+
+    /// Bring `images` up to date with the paths [`FsEventsPlugin`] heard about, without walking
+    /// anything that wasn't mentioned. A directory that's turned up (moved in, say) gets the
+    /// watched directory it's in rescanned, there's no telling what it brought with it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply_fs_changes(
+        &self,
+        images: &mut Vec<PathBuf>,
+        paths: HashSet<PathBuf>,
+        pass: &mut ScanPass,
+    ) {
+        let mut rescan = HashSet::new();
+        for path in paths {
+            let path = normalize_path(&path);
+            if path.is_dir() {
+                if let Some(root) = innermost_dir(&self.dirs, &path) {
+                    rescan.insert(root.path.clone());
+                }
+            } else if path.is_file() {
+                if !pass.config.is_supported_image(&path)
+                    || innermost_dir(&self.dirs, &path).is_none()
+                {
+                    continue;
+                }
+                let Some(stamp) = file_stamp(&path) else {
+                    continue;
+                };
+                pass.stamps.insert(path.clone(), stamp);
+                if pass.config.capture_dates {
+                    pass.captured.insert(
+                        path.clone(),
+                        CaptureDate {
+                            modified: stamp.modified,
+                            taken: exif::capture_date(&path),
+                        },
+                    );
+                }
+                if !pass.limit_reached {
+                    images.push(path);
+                }
+            } else {
+                // Gone, along with everything under it if it was a directory
+                images.retain(|img| !img.starts_with(&path));
+            }
+        }
+        dedup_images(images);
+        sort_images(images, pass.config.sort);
+        for root in &rescan {
+            self.collect_dir(images, root, pass);
+        }
+    }
+
+    /// Carry out `job`, starting from the images in [`ScanPass::previous`]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run(&self, job: ScanJob, mut pass: ScanPass) -> (Vec<PathBuf>, PassOutcome) {
+        let images = match job {
+            ScanJob::All => self.collect_all(&mut pass),
+            ScanJob::Dirs(dirs) => {
+                let mut images = pass.previous.clone();
+                for dir in &dirs {
+                    self.collect_dir(&mut images, dir, &mut pass);
+                }
+                images
+            }
+            ScanJob::Changes(paths) => {
+                let mut images = pass.previous.clone();
+                self.apply_fs_changes(&mut images, paths, &mut pass);
+                images
+            }
+        };
+        let archives = pass
+            .archives
+            .iter()
+            .map(|archive| ExtractedArchive::new(archive, &pass.config))
+            .collect();
+        (images, pass.finish(archives))
+    }
+}
+
+/// The watched directory in `dirs` that `path` names, normalized like
+/// [`WatchedDirs::contains_dir`]
+fn find_watched_dir<'a>(dirs: &'a [WatchedDir], path: &Path) -> Option<&'a WatchedDir> {
+    let wanted = normalize_path(path);
+    dirs.iter()
+        .find(|watched| normalize_path(&watched.path) == wanted)
+}
+
+/// The innermost directory in `dirs` whose scan finds `image`
+fn innermost_dir<'a>(dirs: &'a [WatchedDir], image: &Path) -> Option<&'a WatchedDir> {
+    dirs.iter()
+        .filter(|dir| dir.covers(image))
+        .max_by_key(|dir| dir.path.components().count())
+}
+
+/// The mesh every quad shares, only remade if [`GridConfig::quad_size`] changes
+#[derive(Resource)]
+struct QuadMesh {
+    handle: Handle<Mesh>,
+    size: f32,
+}
+
+/// Paths that already have a quad. Kept up to date as quads spawn and despawn, so the spawn
+/// system doesn't have to rebuild it from every quad each time.
+#[derive(Resource, Default)]
+pub(crate) struct SpawnedImages(pub(crate) HashSet<PathBuf>);
+
+fn forget_despawned_quad(
+    trigger: Trigger<OnRemove, ImageMarker>,
+    markers: Query<&ImageMarker>,
+    mut spawned: ResMut<SpawnedImages>,
+) {
+    if let Ok(marker) = markers.get(trigger.target()) {
+        spawned.0.remove(&marker.target);
+    }
+}
+
+/// Quads whose image has dropped out of the [`CurrentPage`], because the file is gone, its
+/// directory was unwatched, it's over the [`MaxImages`] cap or it's on another page now. A file
+/// that's gone might only have been renamed or moved, so its quad is kept for a few scans as a
+/// [`MissingFile`] in case it turns up again, see [`MissingFilePlugin`].
+fn despawn_stale_quads(
+    mut commands: Commands,
+    page: Res<CurrentPage>,
+    watched_dirs: Res<WatchedDirs>,
+    mut removed: EventReader<ImagesRemoved>,
+    quads: Query<(Entity, &ImageMarker, Option<&ContentHash>, Has<MissingFile>)>,
+) {
+    // What a scan of a directory that's still watched didn't find again has gone from disk, rather
+    // than been unwatched or paged away
+    let gone: HashSet<&PathBuf> = removed
+        .read()
+        .flat_map(|ImagesRemoved(paths)| paths)
+        .filter(|path| {
+            watched_dirs
+                .watched_dirs()
+                .iter()
+                .any(|dir| dir.covers(path))
+        })
+        .collect();
+    let current: HashSet<&Path> = page.images().iter().map(PathBuf::as_path).collect();
+    for (entity, marker, hash, missing) in &quads {
+        if current.contains(marker.target.as_path()) || missing {
+            continue;
+        }
+        match hash {
+            Some(hash) if gone.contains(&marker.target) => {
+                commands
+                    .entity(entity)
+                    .insert(MissingFile::new(marker.target.clone(), *hash));
+            }
+            _ => {
+                commands.entity(entity).despawn();
+            }
+        }
     }
 }
 
 fn slap_img_on_quad(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    render_quality: Res<RenderQuality>,
     asset_server: Res<AssetServer>,
-    watched_dirs: Res<WatchedDirs>,
-    existing_quads: Query<&ImageMarker>,
+    page: Res<CurrentPage>,
+    placement: GridPlacement,
+    quad_mesh: Option<Res<QuadMesh>>,
+    duplicates: Res<Duplicates>,
+    duplicate_config: Res<DuplicateConfig>,
+    spawn_animation: Res<SpawnAnimation>,
+    mut spawned: ResMut<SpawnedImages>,
+    mut errors: ResMut<ScanErrors>,
+    scan_counter: Res<ScanCounter>,
+    (lazy, placeholder, recovering): (Res<LazyTextures>, Res<PlaceholderTexture>, Res<Recovering>),
 ) {
-    let existing_paths: std::collections::HashSet<&Path> = existing_quads
+    // Images that might be a missing file turning up again wait to find out
+    let wanted = |img_path: &PathBuf| {
+        let hidden = duplicate_config.collapse && duplicates.is_hidden_copy(img_path);
+        !(hidden || recovering.paths.contains(img_path))
+    };
+    if page
+        .images()
         .iter()
-        .map(|marker| marker.target.as_path())
-        .collect();
+        .filter(|img_path| wanted(img_path))
+        .all(|img_path| spawned.0.contains(img_path))
+    {
+        return;
+    }
+
+    // Everything on the first go is "new", only animate what turns up after that
+    let animate = spawn_animation.enabled && !spawned.0.is_empty();
+    let lazy = lazy.enabled && *placement.render_mode == RenderMode::Mesh3d;
 
     // Grid configuration (I just did this because I wanted to see how many imagse we can spawn... it's a lot...)
-    let grid_size = (watched_dirs.imgs.len() as f32).sqrt().ceil() as i32;
-    let quad_spacing = 2.5f32;
-    let quad_size = 2.0f32;
+    let count = page.images().len();
+    let grid_config = &placement.config;
 
-    let quad_mesh = meshes.add(Rectangle::new(quad_size, quad_size));
+    let quad_mesh = match quad_mesh {
+        Some(quad_mesh) if quad_mesh.size == grid_config.quad_size => quad_mesh.handle.clone(),
+        _ => {
+            let handle = meshes.add(Rectangle::new(grid_config.quad_size, grid_config.quad_size));
+            commands.insert_resource(QuadMesh {
+                handle: handle.clone(),
+                size: grid_config.quad_size,
+            });
+            handle
+        }
+    };
 
     // Spawn quads for new images
-    watched_dirs
-        .imgs
+    page.images()
         .iter()
         .enumerate()
         .for_each(|(index, img_path)| {
-            if !existing_paths.contains(img_path.as_path()) {
+            if wanted(img_path) && spawned.0.insert(img_path.clone()) {
                 // Calculate grid position
-                let grid_pos = calculate_grid_position(index, grid_size, quad_spacing);
-
-                // Load the image as a texture
-                let texture_handle: Handle<Image> =
-                    asset_server.load(img_path.to_string_lossy().to_string());
-
-                // tex -> Bevy Material
-                let material = materials.add(StandardMaterial {
-                    base_color_texture: Some(texture_handle),
-                    unlit: true, // Important to skip the pbr pipeline on images...
-                    ..default()
+                let (cell, scale) = placement.cell(index, img_path, count);
+                let mut grid_transform = placement.place(cell).with_scale(Vec3::splat(scale));
+                // Turned the way it was left last time
+                let rotation = ManualRotation::load(img_path).unwrap_or_else(|e| {
+                    log::warn!("Couldn't read the rotation of {img_path:?}: {e}");
+                    errors.push(format!(
+                        "Couldn't read the rotation of {}: {e}",
+                        img_path.display()
+                    ));
+                    None
                 });
+                if let Some(rotation) = rotation {
+                    grid_transform.rotation *= rotation.quat();
+                }
+
+                // Load the image as a texture. One that can't even be asked for shows up as
+                // failed straight away, like one the loader gave up on.
+                let (texture_handle, load_state) = if lazy {
+                    (Handle::default(), ImageLoadState::Evicted)
+                } else {
+                    match load_image(&asset_server, img_path) {
+                        Ok(handle) => (handle, ImageLoadState::Pending),
+                        Err(e) => {
+                            log::warn!("{e}");
+                            errors.push(e);
+                            (Handle::default(), ImageLoadState::Failed)
+                        }
+                    }
+                };
 
                 // Spawn the quad, slap the Material in it's `bundle`
-                commands.spawn((
-                    Mesh3d(quad_mesh.clone()),
-                    MeshMaterial3d(material),
-                    Transform::from_translation(grid_pos),
+                let mut quad = commands.spawn((
+                    grid_transform,
                     // .looking_at(Vec3::ZERO, Vec3::Y),
                     ImageMarker {
                         target: img_path.clone(),
                     },
+                    GridPosition {
+                        scale,
+                        ..GridPosition::at(index, cell, grid_transform.translation)
+                    },
+                    DiscoveredAt {
+                        scan_number: scan_counter.0,
+                    },
+                    ImageTexture(texture_handle.clone()),
+                    load_state,
                     // Visibility::default(),
                     // InheritedVisibility::default(),
                     ViewVisibility::default(),
                 ));
+                if let Some(rotation) = rotation {
+                    quad.insert(rotation);
+                }
+                if lazy {
+                    quad.insert(TextureUnloaded);
+                }
+                match *placement.render_mode {
+                    RenderMode::Mesh3d => {
+                        // tex -> Bevy Material, our own unlit one so we skip the pbr pipeline entirely
+                        let shown = if lazy {
+                            placeholder.0.clone()
+                        } else {
+                            texture_handle
+                        };
+                        let material =
+                            materials.add(ImageDisplayMaterial::new(shown, &render_quality));
+                        quad.insert((Mesh3d(quad_mesh.clone()), MeshMaterial3d(material)));
+                    }
+                    RenderMode::Sprite2d => {
+                        quad.insert(Sprite {
+                            image: texture_handle,
+                            custom_size: Some(Vec2::splat(grid_config.quad_size)),
+                            // Keep the aspect ratio rather than squashing it into the square
+                            image_mode: SpriteImageMode::Scale(
+                                bevy::sprite::ScalingMode::FitCenter,
+                            ),
+                            ..default()
+                        });
+                    }
+                }
+                if animate {
+                    quad.insert((
+                        JustAdded::new(spawn_animation.duration),
+                        grid_transform.with_scale(Vec3::ZERO),
+                    ));
+                }
             }
         });
 }
 
-/// Helper function to calculate grid position for an image quad
-fn calculate_grid_position(index: usize, grid_size: i32, spacing: f32) -> Vec3 {
-    let row = (index as i32) / grid_size;
-    let col = (index as i32) % grid_size;
+/// Drop repeated paths, keeping the first of each. Paths are canonical by the time they get here,
+/// so this catches the same file reached two different ways.
+fn dedup_images(images: &mut Vec<PathBuf>) {
+    let mut seen = HashSet::new();
+    images.retain(|img| seen.insert(img.clone()));
+}
+
+/// Canonicalize a path if it exists on disk, otherwise fall back to a purely lexical cleanup
+/// (dropping `.` components and resolving `..` where possible).
+pub fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => match normalized.components().next_back() {
+                Some(std::path::Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `/..` is just `/`
+                Some(std::path::Component::RootDir | std::path::Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::io::AssetSourceId;
+
+    /// What `plugin` would watch, the way [`DirWatchingPlugin::build`] makes it
+    fn watched(plugin: &DirWatchingPlugin) -> Vec<PathBuf> {
+        let dirs = plugin.dirs.iter().map(|dir| WatchedDir::new(dir, true));
+        WatchedDirs::new(dirs.collect())
+            .watched_dirs()
+            .iter()
+            .map(|dir| dir.path.clone())
+            .collect()
+    }
+
+    /// Each test sets a variable of its own, so they can run at the same time
+    fn set_env(var: &str, value: impl AsRef<std::ffi::OsStr>) {
+        // SAFETY: nothing else reads or writes this variable
+        unsafe { std::env::set_var(var, value) };
+    }
+
+    #[test]
+    fn from_env_without_the_variable_is_the_default() {
+        let plugin = DirWatchingPlugin::from_env("PHOTOVIEW_TEST_FROM_ENV_UNSET");
+        assert_eq!(plugin.dirs, DirWatchingPlugin::default().dirs);
+        assert!(watched(&plugin).is_empty());
+    }
+
+    #[test]
+    fn from_env_skips_paths_that_are_not_directories() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let missing = a.path().join("not-there");
+        let value = std::env::join_paths([a.path(), missing.as_path(), b.path()]).unwrap();
+        set_env("PHOTOVIEW_TEST_FROM_ENV_MIXED", value);
+
+        let plugin = DirWatchingPlugin::from_env("PHOTOVIEW_TEST_FROM_ENV_MIXED");
+        assert_eq!(plugin.dirs, [a.path(), b.path()]);
+        assert_eq!(
+            watched(&plugin),
+            [
+                fs::canonicalize(a.path()).unwrap(),
+                fs::canonicalize(b.path()).unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn from_env_ignores_empty_segments() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let separator = if cfg!(windows) { ";" } else { ":" };
+        let value = format!(
+            "{separator}{}{separator}{separator}{}{separator}",
+            a.path().display(),
+            b.path().display()
+        );
+        set_env("PHOTOVIEW_TEST_FROM_ENV_EMPTY", value);
+
+        let plugin = DirWatchingPlugin::from_env("PHOTOVIEW_TEST_FROM_ENV_EMPTY");
+        assert_eq!(plugin.dirs, [a.path(), b.path()]);
+
+        set_env("PHOTOVIEW_TEST_FROM_ENV_BLANK", "");
+        let plugin = DirWatchingPlugin::from_env("PHOTOVIEW_TEST_FROM_ENV_BLANK");
+        assert!(plugin.dirs.is_empty());
+    }
+
+    #[test]
+    fn from_env_splits_like_path_does() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs: Vec<PathBuf> = ["one", "two", "three"]
+            .iter()
+            .map(|name| root.path().join(name))
+            .collect();
+        for dir in &dirs {
+            fs::create_dir(dir).unwrap();
+        }
+        set_env(
+            "PHOTOVIEW_TEST_FROM_ENV_SPLIT",
+            std::env::join_paths(&dirs).unwrap(),
+        );
+
+        let plugin = DirWatchingPlugin::from_env("PHOTOVIEW_TEST_FROM_ENV_SPLIT");
+        assert_eq!(plugin.dirs, dirs);
+    }
+
+    /// Only `:` separates on Unix, so a `;` is part of the name
+    #[cfg(unix)]
+    #[test]
+    fn from_env_keeps_semicolons_in_unix_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("a;b");
+        fs::create_dir(&dir).unwrap();
+        set_env("PHOTOVIEW_TEST_FROM_ENV_SEMICOLON", &dir);
+
+        let plugin = DirWatchingPlugin::from_env("PHOTOVIEW_TEST_FROM_ENV_SEMICOLON");
+        assert_eq!(plugin.dirs, [dir]);
+    }
+
+    /// Run `job` the way a scan task would, from what `watched` holds now, and keep the result
+    fn scan(watched: &mut WatchedDirs, job: ScanJob) {
+        let (progress, _) = mpsc::channel();
+        let previous = watched.images().to_vec();
+        let pass = watched.pass(
+            &ScanConfig::default(),
+            previous,
+            &Time::default(),
+            &progress,
+        );
+        let (images, _) = watched.scan_targets().run(job, pass);
+        watched.set_images(images, None);
+    }
+
+    fn changes(paths: &[&Path]) -> ScanJob {
+        ScanJob::Changes(paths.iter().map(|path| path.to_path_buf()).collect())
+    }
+
+    /// A watched tempdir, canonical so the paths it comes back with compare equal
+    fn watched_tempdir() -> (tempfile::TempDir, PathBuf, WatchedDirs) {
+        let root = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(root.path()).unwrap();
+        let watched = WatchedDirs::new(vec![WatchedDir::new(&dir, true)]);
+        (root, dir, watched)
+    }
+
+    #[test]
+    fn fs_events_add_created_images() {
+        let (_root, dir, mut watched) = watched_tempdir();
+        fs::write(dir.join("a.jpg"), b"").unwrap();
+        scan(&mut watched, ScanJob::All);
+        assert_eq!(watched.images(), [dir.join("a.jpg")]);
+
+        fs::write(dir.join("b.jpg"), b"").unwrap();
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("c.jpg"), b"").unwrap();
+        scan(
+            &mut watched,
+            changes(&[
+                &dir.join("b.jpg"),
+                &dir.join("notes.txt"),
+                &outside.path().join("c.jpg"),
+            ]),
+        );
+        assert_eq!(watched.images(), [dir.join("a.jpg"), dir.join("b.jpg")]);
+    }
+
+    #[test]
+    fn fs_events_drop_removed_images_and_directories() {
+        let (_root, dir, mut watched) = watched_tempdir();
+        fs::create_dir(dir.join("sub")).unwrap();
+        for image in ["a.jpg", "b.jpg", "sub/c.jpg", "sub/d.jpg"] {
+            fs::write(dir.join(image), b"").unwrap();
+        }
+        scan(&mut watched, ScanJob::All);
+        assert_eq!(watched.images().len(), 4);
+
+        fs::remove_file(dir.join("a.jpg")).unwrap();
+        fs::remove_dir_all(dir.join("sub")).unwrap();
+        scan(
+            &mut watched,
+            changes(&[&dir.join("a.jpg"), &dir.join("sub")]),
+        );
+        assert_eq!(watched.images(), [dir.join("b.jpg")]);
+    }
+
+    #[test]
+    fn fs_events_rescan_directories_that_appear() {
+        let (_root, dir, mut watched) = watched_tempdir();
+        fs::write(dir.join("a.jpg"), b"").unwrap();
+        scan(&mut watched, ScanJob::All);
+
+        // Moved in whole, so the only event is for the directory itself
+        let elsewhere = tempfile::tempdir().unwrap();
+        let moved = elsewhere.path().join("moved");
+        fs::create_dir_all(moved.join("deeper")).unwrap();
+        fs::write(moved.join("b.jpg"), b"").unwrap();
+        fs::write(moved.join("deeper/c.jpg"), b"").unwrap();
+        fs::rename(&moved, dir.join("moved")).unwrap();
+
+        scan(&mut watched, changes(&[&dir.join("moved")]));
+        assert_eq!(
+            watched.images(),
+            [
+                dir.join("a.jpg"),
+                dir.join("moved/b.jpg"),
+                dir.join("moved/deeper/c.jpg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_directories_wait_on_the_closest_ancestor_there_is() {
+        let (_root, dir, _) = watched_tempdir();
+        let missing = dir.join("a/b/c");
+        let mut watched = WatchedDirs::new(vec![WatchedDir::new(&missing, true)]);
+        let mut waiting_on = || {
+            let (progress, _) = mpsc::channel();
+            let pass = watched.pass(&ScanConfig::default(), vec![], &Time::default(), &progress);
+            let (_, outcome) = watched.scan_targets().run(ScanJob::All, pass);
+            watched.statuses = outcome.statuses;
+            watched.statuses[&missing].waiting_on.clone()
+        };
+        assert_eq!(waiting_on(), Some(dir.clone()));
+
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        assert_eq!(waiting_on(), Some(dir.join("a/b")));
+
+        fs::create_dir(&missing).unwrap();
+        assert_eq!(waiting_on(), None);
+    }
+
+    fn write_zip(path: &Path, names: &[&str]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for name in names {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn scans_extract_new_and_replaced_archives() {
+        let root = tempfile::tempdir().unwrap();
+        let path = fs::canonicalize(root.path()).unwrap().join("photos.zip");
+        write_zip(&path, &["a.jpg", "notes.txt"]);
+        let mut watched = WatchedDirs::default();
+        watched.add_archive(path.clone());
 
-    // Center the grid around origin
-    let offset_x = (grid_size as f32 - 1.0) * spacing * 0.5;
-    let offset_z = (grid_size as f32 - 1.0) * spacing * 0.5;
+        let scan = |watched: &mut WatchedDirs| {
+            let (progress, _) = mpsc::channel();
+            let config = ScanConfig::default();
+            let previous = watched.images().to_vec();
+            let mut pass = watched.pass(&config, previous, &Time::default(), &progress);
+            pass.archives = watched.stale_archives();
+            let (mut images, outcome) = watched.scan_targets().run(ScanJob::All, pass);
+            let extracted = outcome.archives.len();
+            watched.update_archives(
+                &mut images,
+                outcome.archives,
+                &config,
+                &mut ScanErrors::default(),
+            );
+            watched.set_images(images, None);
+            extracted
+        };
+        let names = |watched: &WatchedDirs| -> Vec<PathBuf> {
+            watched
+                .images()
+                .iter()
+                .map(|image| PathBuf::from(image.file_name().unwrap()))
+                .collect()
+        };
 
-    Vec3::new(
-        (col as f32 * spacing) - offset_x,
-        0.0, // small bump
-        (row as f32 * spacing) - offset_z,
-    )
+        assert_eq!(scan(&mut watched), 1);
+        assert_eq!(names(&watched), [Path::new("a.jpg")]);
+        let first = watched.images()[0].clone();
+        assert_eq!(
+            watched.archive_of(&first),
+            Some(&ArchiveSource(path.clone()))
+        );
+
+        // Nothing's changed, so nothing's extracted again
+        assert_eq!(scan(&mut watched), 0);
+        assert_eq!(names(&watched), [Path::new("a.jpg")]);
+
+        write_zip(&path, &["b.jpg", "c.jpg"]);
+        let later = SystemTime::now() + Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(scan(&mut watched), 1);
+        assert_eq!(names(&watched), [Path::new("b.jpg"), Path::new("c.jpg")]);
+        assert!(!first.exists());
+    }
+
+    /// `path` relative to the current directory, by climbing all the way up out of it
+    #[cfg(unix)]
+    fn relative_to_cwd(path: &Path) -> PathBuf {
+        let cwd = std::env::current_dir().unwrap();
+        let up = cwd.components().skip(1).map(|_| Path::new(".."));
+        up.collect::<PathBuf>()
+            .join(path.strip_prefix("/").unwrap())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_same_file_reached_several_ways_is_one_image() {
+        use std::os::unix::fs::symlink;
+
+        let root = tempfile::tempdir().unwrap();
+        let photos = root.path().join("photos");
+        fs::create_dir(&photos).unwrap();
+        fs::write(photos.join("a.jpg"), b"").unwrap();
+        fs::write(photos.join("b.jpg"), b"").unwrap();
+        symlink(photos.join("a.jpg"), photos.join("link.jpg")).unwrap();
+        let alias = root.path().join("alias");
+        symlink(&photos, &alias).unwrap();
+
+        let mut watched = WatchedDirs::new(vec![
+            WatchedDir::new(relative_to_cwd(&photos), true),
+            WatchedDir::new(&alias, true),
+        ]);
+        assert_eq!(watched.watched_dirs().len(), 1);
+
+        scan(&mut watched, ScanJob::All);
+
+        let photos = fs::canonicalize(&photos).unwrap();
+        assert_eq!(
+            watched.images(),
+            [photos.join("a.jpg"), photos.join("b.jpg")]
+        );
+    }
+
+    /// `path` should come through as the same file on the default source, with no label
+    fn assert_loads_as_is(path: &str, extension: &str) {
+        let path = Path::new(path);
+        let asset_path = image_asset_path(path).unwrap();
+        assert_eq!(asset_path.path(), path);
+        assert_eq!(asset_path.source(), &AssetSourceId::Default);
+        assert_eq!(asset_path.label(), None);
+        assert_eq!(asset_path.get_full_extension().as_deref(), Some(extension));
+    }
+
+    #[test]
+    fn asset_paths_keep_awkward_file_names() {
+        assert_loads_as_is("/photos/summer trip/day 1.jpg", "jpg");
+        assert_loads_as_is("/photos/#1 favourite.png", "png");
+        assert_loads_as_is("/photos/a#b/c.jpg", "jpg");
+        assert_loads_as_is("/photos/what?.webp", "webp");
+        assert_loads_as_is("/photos/Zürich/東京 2024.jpeg", "jpeg");
+        assert_loads_as_is("relative/dir/IMG_0001.JPG", "JPG");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn asset_paths_keep_windows_prefixes() {
+        assert_loads_as_is(r"C:\Users\me\Pictures\a.jpg", "jpg");
+        assert_loads_as_is(r"D:\#photos\b.png", "png");
+        assert_loads_as_is(r"\\server\share\photos\c.jpg", "jpg");
+        assert_loads_as_is(r"\\?\C:\long\path\d.jpg", "jpg");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn asset_paths_need_utf8_file_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.jpg");
+        assert!(image_asset_path(&Path::new("/photos").join(name)).is_err());
+    }
+
+    /// Just the paging and the quad spawner, the spawner running every frame. Textures are left
+    /// lazy so nothing gets read off the disk.
+    fn spawner_app(images: Vec<PathBuf>) -> App {
+        let mut watched_dirs = WatchedDirs::default();
+        watched_dirs.set_images(images, None);
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_asset::<Image>()
+            .init_asset::<ImageDisplayMaterial>()
+            .add_event::<TurnPage>()
+            .insert_resource(watched_dirs)
+            .insert_resource(LazyTextures {
+                enabled: true,
+                ..default()
+            })
+            .insert_resource(PlaceholderTexture(Handle::default()))
+            .init_resource::<PageConfig>()
+            .init_resource::<ImageFilter>()
+            .init_resource::<Selection>()
+            .init_resource::<CurrentPage>()
+            .init_resource::<GridConfig>()
+            .init_resource::<RenderMode>()
+            .init_resource::<RenderQuality>()
+            .init_resource::<Timeline>()
+            .init_resource::<JustifiedLayout>()
+            .init_resource::<DirectoryGroups>()
+            .init_resource::<Duplicates>()
+            .init_resource::<DuplicateConfig>()
+            .init_resource::<SpawnAnimation>()
+            .init_resource::<SpawnedImages>()
+            .init_resource::<Recovering>()
+            .init_resource::<ScanErrors>()
+            .init_resource::<ScanCounter>()
+            .add_systems(PreUpdate, paging::update_current_page)
+            .add_systems(Update, slap_img_on_quad);
+        app
+    }
+
+    /// How many meshes and quads there are
+    fn meshes_and_quads(app: &mut App) -> (usize, usize) {
+        let meshes = app.world().resource::<Assets<Mesh>>().len();
+        let mut quads = app.world_mut().query::<&ImageMarker>();
+        (meshes, quads.iter(app.world()).count())
+    }
+
+    #[test]
+    fn idle_frames_add_no_meshes_or_quads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut images: Vec<PathBuf> = (0..3)
+            .map(|i| dir.path().join(format!("{i}.jpg")))
+            .collect();
+        let mut app = spawner_app(images.clone());
+        app.update();
+        assert_eq!(meshes_and_quads(&mut app), (1, 3));
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(meshes_and_quads(&mut app), (1, 3));
+
+        // The new size gets its mesh along with the next quad, the one mesh for all of them
+        app.world_mut().resource_mut::<GridConfig>().quad_size = 3.0;
+        images.push(dir.path().join("3.jpg"));
+        app.world_mut()
+            .resource_mut::<WatchedDirs>()
+            .set_images(images, None);
+        app.update();
+        assert_eq!(meshes_and_quads(&mut app), (2, 4));
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(meshes_and_quads(&mut app), (2, 4));
+    }
 }
@@ -0,0 +1,183 @@
+use bevy::{prelude::*, winit::WinitSettings};
+
+use crate::{
+    CurrentPage, ImageLoadState, ScanCounter, ScanProgress, Themed, scan_cache::ScanCacheState,
+};
+
+/// Top level app state. We sit on the loading screen until the first scan has finished and every
+/// image it found has either loaded or failed.
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    #[default]
+    LoadingScreen,
+    Running,
+}
+
+/// What the main view is showing, once we're running
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::Running)]
+pub enum ViewMode {
+    #[default]
+    Grid,
+    /// Two selected images side by side, see [`crate::ComparePlugin`]
+    Compare,
+    /// One image after another, see [`crate::SlideshowPlugin`]
+    Slideshow,
+}
+
+/// Root node of the loading overlay
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingText;
+
+#[derive(Component)]
+struct LoadingBarFill;
+
+#[derive(Component)]
+struct SkipLoadingButton;
+
+/// The winit settings that were active before the loading screen switched to continuous updates
+#[derive(Resource)]
+struct SavedWinitSettings(WinitSettings);
+
+pub struct LoadingScreenPlugin;
+
+impl Plugin for LoadingScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>();
+        app.enable_state_scoped_entities::<AppState>();
+        app.add_sub_state::<ViewMode>();
+        app.enable_state_scoped_entities::<ViewMode>();
+
+        app.add_systems(OnEnter(AppState::LoadingScreen), spawn_loading_screen);
+        app.add_systems(OnExit(AppState::LoadingScreen), restore_winit_settings);
+        app.add_systems(
+            Update,
+            (update_loading_screen, skip_loading_button_system)
+                .run_if(in_state(AppState::LoadingScreen)),
+        );
+    }
+}
+
+fn spawn_loading_screen(mut commands: Commands, winit_settings: Option<Res<WinitSettings>>) {
+    // A reactive (desktop) app would only redraw the progress bar when the mouse moves
+    if let Some(settings) = winit_settings {
+        commands.insert_resource(SavedWinitSettings(settings.clone()));
+        commands.insert_resource(WinitSettings::game());
+    }
+
+    commands.spawn((
+        LoadingScreen,
+        StateScoped(AppState::LoadingScreen),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(16.0),
+            ..default()
+        },
+        Themed::Background,
+        GlobalZIndex(100),
+        children![
+            (LoadingText, Text::new("Scanning..."), Themed::Text),
+            (
+                Node {
+                    width: Val::Percent(60.0),
+                    height: Val::Px(16.0),
+                    ..default()
+                },
+                Themed::Panel,
+                children![(
+                    LoadingBarFill,
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    Themed::Accent,
+                )],
+            ),
+            (
+                Button,
+                SkipLoadingButton,
+                Node {
+                    padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                BorderRadius::all(Val::Px(4.0)),
+                children![(Text::new("Skip"), Themed::Text)],
+            ),
+        ],
+    ));
+}
+
+fn update_loading_screen(
+    page: Res<CurrentPage>,
+    scan_counter: Res<ScanCounter>,
+    scan_progress: Res<ScanProgress>,
+    cache_state: Res<ScanCacheState>,
+    load_states: Query<&ImageLoadState>,
+    mut text: Single<&mut Text, With<LoadingText>>,
+    mut fill: Single<&mut Node, With<LoadingBarFill>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // A cached image list is as good as a finished scan for getting textures going
+    if scan_counter.0 == 0 && !cache_state.loaded {
+        if scan_progress.is_changed() {
+            text.0 = format!(
+                "Scanning... {} folders, {} images so far",
+                scan_progress.dirs_seen, scan_progress.images_found
+            );
+        }
+        return;
+    }
+
+    // Only the page that's showing gets quads to wait on
+    let discovered = page.images().len();
+    let (mut loaded, mut failed) = (0, 0);
+    for state in &load_states {
+        match state {
+            ImageLoadState::Loaded | ImageLoadState::Evicted => loaded += 1,
+            ImageLoadState::Failed => failed += 1,
+            ImageLoadState::Pending => {}
+        }
+    }
+
+    let finished = loaded + failed;
+    let progress = if discovered == 0 {
+        1.0
+    } else {
+        (finished as f32 / discovered as f32).min(1.0)
+    };
+
+    text.0 = format!("Loading images {finished} / {discovered}");
+    fill.width = Val::Percent(progress * 100.0);
+
+    if finished >= discovered {
+        next_state.set(AppState::Running);
+    }
+}
+
+fn skip_loading_button_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SkipLoadingButton>)>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            next_state.set(AppState::Running);
+        }
+    }
+}
+
+fn restore_winit_settings(mut commands: Commands, saved: Option<Res<SavedWinitSettings>>) {
+    if let Some(saved) = saved {
+        commands.insert_resource(saved.0.clone());
+        commands.remove_resource::<SavedWinitSettings>();
+    }
+}
@@ -1,78 +1,684 @@
-use bevy::{color::palettes::css::*, prelude::*, winit::WinitSettings};
-use photoview::DirWatchingPlugin;
+use bevy::{prelude::*, window::WindowMode, winit::WinitSettings};
+use clap::{CommandFactory, Parser, ValueEnum, error::ErrorKind};
+use photoview::{
+    ButtonAction, ButtonActionPlugin, CameraConfig, ColorSearchFilter, ComparePlugin,
+    ConfigPersistencePlugin, ContextMenuPlugin, CurrentPage, DebugGizmosPlugin,
+    DiagnosticsOverlayPlugin, DirWatchingPlugin, ExportKind, ExportPlugin, FileDropPlugin,
+    FilteredOut, FullscreenPlugin, GridLayout, ImageFilter, ImageMarker, ImageOverflow,
+    InfoPanelPlugin, MinimapPlugin, NavigationPlugin, PhotoviewConfig, RecentlyAddedFilter,
+    RenderMode, RescanRequested, ScanBackend, ScanCacheReconciled, ScanCompleted, ScanPaused,
+    ScanProgress, SceneBackground, SlideshowConfig, SlideshowPlugin, SortOrder, ThemeKind, Themed,
+    TimelineBucket, UiTheme, WallMode, WallPlugin, WatchedDirs, ZoomPlugin, color_search_bar,
+    filter_bar,
+};
 
-const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
-const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
+use std::path::PathBuf;
+use std::time::Duration;
 
-fn button_system(
-    mut interaction_query: Query<
-        (
-            &Interaction,
-            &mut BackgroundColor,
-            &mut BorderColor,
-            &Children,
-        ),
-        (Changed<Interaction>, With<Button>),
-    >,
-    mut text_query: Query<&mut Text>,
-) {
-    for (interaction, mut color, mut border_color, children) in &mut interaction_query {
-        let mut text = text_query.get_mut(children[0]).unwrap();
-        match *interaction {
-            Interaction::Pressed => {
-                **text = "Press".to_string();
-                *color = PRESSED_BUTTON.into();
-                border_color.0 = RED.into();
-            }
-            Interaction::Hovered => {
-                **text = "Hover".to_string();
-                *color = HOVERED_BUTTON.into();
-                border_color.0 = WHITE.into();
+/// Browse folders of photos as one big grid
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Directories to watch for images [default: the ones from last time, or "."]
+    dirs: Vec<PathBuf>,
+
+    /// Text file listing images to show, one path per line. Can be given more than once.
+    #[arg(long = "playlist", value_name = "FILE")]
+    playlists: Vec<PathBuf>,
+
+    /// ZIP file to show the images inside, as if it were a directory. Can be given more than
+    /// once.
+    #[arg(long = "archive", value_name = "FILE")]
+    archives: Vec<PathBuf>,
+
+    /// Seconds between background rescans [default: 5, or the saved setting]
+    #[arg(long)]
+    interval: Option<f32>,
+
+    /// Order images are laid out in [default: name, or the saved setting]
+    #[arg(long, value_enum)]
+    sort: Option<SortArg>,
+
+    /// Only look at the top level of each directory
+    #[arg(long)]
+    no_recursive: bool,
+
+    /// Follow filesystem events instead of rescanning on the interval, where the platform
+    /// supports it
+    #[arg(long)]
+    watch_events: bool,
+
+    /// Also watch DIR, but only its top level. Can be given more than once.
+    #[arg(long, value_name = "DIR")]
+    shallow: Vec<PathBuf>,
+
+    /// Comma separated file extensions to treat as images, replacing the built-in list
+    #[arg(long, value_delimiter = ',')]
+    extensions: Option<Vec<String>>,
+
+    /// Always start with a full scan instead of showing the cached results from last time
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Keep sizes, EXIF data, thumbnails, ratings and tags in a SQLite catalog in the data
+    /// folder, so the next launch only reads the files that changed
+    #[arg(long)]
+    catalog: bool,
+
+    /// Only show the first N images (in sort order) when there are more
+    #[arg(long, value_name = "N")]
+    max_images: Option<usize>,
+
+    /// Show N images at a time, PageUp/PageDown flip through the rest. 0 shows them all
+    #[arg(long, value_name = "N")]
+    per_page: Option<usize>,
+
+    /// Texture memory photos may use, in MiB; the least recently seen are dropped past it
+    /// [default: 1024]
+    #[arg(long, value_name = "MIB")]
+    texture_budget: Option<u64>,
+
+    /// Only load the photos within R of where the camera's looking, the rest wait as grey
+    /// placeholders until it gets closer
+    #[arg(long, value_name = "R")]
+    lazy_radius: Option<f32>,
+
+    /// Stop scanning after finding N images, 0 for no limit [default: 5000]
+    #[arg(long, value_name = "N")]
+    scan_limit: Option<usize>,
+
+    /// Look for copies of the same image and only show one of each (D toggles collapsing)
+    #[arg(long)]
+    duplicates: bool,
+
+    /// Draw the grid with flat sprites and a 2D camera (wheel zooms, middle-drag pans)
+    #[arg(long)]
+    sprites: bool,
+
+    /// Move the camera to take in the whole grid once the first images are laid out
+    #[arg(long)]
+    fit: bool,
+
+    /// With --sprites, give the grid as many columns as fit across the window, reflowing it as
+    /// the window is resized
+    #[arg(long, requires = "sprites")]
+    reflow: bool,
+
+    /// Fewest columns --reflow goes down to [default: 1, or the saved setting]
+    #[arg(long, value_name = "N", requires = "reflow")]
+    min_columns: Option<i32>,
+
+    /// Most columns --reflow goes up to [default: 64, or the saved setting]
+    #[arg(long, value_name = "N", requires = "reflow")]
+    max_columns: Option<i32>,
+
+    /// Seconds each image is shown for in the slideshow (Space) [default: 5]
+    #[arg(long, value_name = "SECS")]
+    slideshow_interval: Option<f32>,
+
+    /// Show the slideshow still, without slowly zooming and panning across each image
+    #[arg(long)]
+    no_ken_burns: bool,
+
+    /// How many images either side of the one in the slideshow to load ahead [default: 2]
+    #[arg(long, value_name = "N")]
+    prefetch: Option<usize>,
+
+    /// Show a bar along the bottom running from the oldest photo to the newest, drag along it to
+    /// go to the ones taken around then
+    #[arg(long)]
+    scrubber: bool,
+
+    /// Tint images with a colour per watched directory
+    #[arg(long)]
+    tint_dirs: bool,
+
+    /// Pack images into shared atlas textures as thumbnails, far fewer draw calls for big grids
+    #[arg(long)]
+    atlas: bool,
+
+    /// Show the grid as thumbnails of at most PX pixels across, made once and kept in the cache
+    /// folder
+    #[arg(long, value_name = "PX", num_args = 0..=1, default_missing_value = "512")]
+    thumbnails: Option<u32>,
+
+    /// How the grid is arranged at startup [default: square, or the saved setting]
+    #[arg(long, value_enum)]
+    layout: Option<LayoutArg>,
+
+    /// Whether the timeline layout has a row per day, month or year [default: day, or the saved
+    /// setting]
+    #[arg(long, value_enum)]
+    timeline_by: Option<TimelineArg>,
+
+    /// Height the justified layout's rows aim for, before they're scaled to fill the width
+    /// [default: 2, or the saved setting]
+    #[arg(long)]
+    row_height: Option<f32>,
+
+    /// Extra space between the directories' grids in the group-by-directory layout [default: 5,
+    /// or the saved setting]
+    #[arg(long)]
+    group_gap: Option<f32>,
+
+    /// Wrap the grid round the camera (W switches while running) [default: flat, or the saved
+    /// setting]
+    #[arg(long, value_enum)]
+    wall: Option<WallArg>,
+
+    /// Radius of the --wall [default: 20]
+    #[arg(long, requires = "wall")]
+    wall_radius: Option<f32>,
+
+    /// Colour scheme of the UI (T switches while running) [default: dark, or the saved setting]
+    #[arg(long, value_enum)]
+    theme: Option<ThemeArg>,
+
+    /// Scene background instead of the theme's: black, grey, white or a hex colour like #202020
+    /// (B steps through them while running) [default: the saved setting]
+    #[arg(long, value_name = "COLOR", value_parser = parse_background)]
+    background: Option<Color>,
+
+    /// Settings file to load and keep updated [default: config.ron in the platform config dir]
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Go back to the page and selection from last time, and keep remembering them
+    #[arg(long, conflicts_with = "no_config")]
+    remember_session: bool,
+
+    /// Don't load or save any settings
+    #[arg(long, conflicts_with = "config")]
+    no_config: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SortArg {
+    Name,
+    Mtime,
+    Size,
+}
+
+impl From<SortArg> for SortOrder {
+    fn from(arg: SortArg) -> Self {
+        match arg {
+            SortArg::Name => SortOrder::Name,
+            SortArg::Mtime => SortOrder::Modified,
+            SortArg::Size => SortOrder::Size,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum LayoutArg {
+    Square,
+    Strip,
+    Timeline,
+    Justified,
+    GroupByDirectory,
+}
+
+impl From<LayoutArg> for GridLayout {
+    fn from(arg: LayoutArg) -> Self {
+        match arg {
+            LayoutArg::Square => GridLayout::Square,
+            LayoutArg::Strip => GridLayout::Strip,
+            LayoutArg::Timeline => GridLayout::Timeline,
+            LayoutArg::Justified => GridLayout::Justified,
+            LayoutArg::GroupByDirectory => GridLayout::GroupByDirectory,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TimelineArg {
+    Day,
+    Month,
+    Year,
+}
+
+impl From<TimelineArg> for TimelineBucket {
+    fn from(arg: TimelineArg) -> Self {
+        match arg {
+            TimelineArg::Day => TimelineBucket::Day,
+            TimelineArg::Month => TimelineBucket::Month,
+            TimelineArg::Year => TimelineBucket::Year,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum WallArg {
+    Flat,
+    Cylinder,
+    Sphere,
+}
+
+impl WallArg {
+    fn mode(self, radius: f32) -> WallMode {
+        match self {
+            WallArg::Flat => WallMode::Flat,
+            WallArg::Cylinder => WallMode::Cylinder { radius },
+            WallArg::Sphere => WallMode::Sphere { radius },
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ThemeArg {
+    Dark,
+    Light,
+}
+
+impl From<ThemeArg> for ThemeKind {
+    fn from(arg: ThemeArg) -> Self {
+        match arg {
+            ThemeArg::Dark => ThemeKind::Dark,
+            ThemeArg::Light => ThemeKind::Light,
+        }
+    }
+}
+
+fn parse_background(arg: &str) -> Result<Color, String> {
+    match arg.to_ascii_lowercase().as_str() {
+        "black" => Ok(SceneBackground::PRESETS[0]),
+        "grey" | "gray" => Ok(SceneBackground::PRESETS[1]),
+        "white" => Ok(SceneBackground::PRESETS[2]),
+        hex => Srgba::hex(hex)
+            .map(Color::from)
+            .map_err(|_| format!("expected black, grey, white or a hex colour, got {arg:?}")),
+    }
+}
+
+impl Cli {
+    /// Parse the command line, exiting with a usage error for anything we can't run with. This
+    /// happens before the window opens so mistakes don't end up buried in the log.
+    fn parse_and_validate() -> Self {
+        Self::parse().validate().unwrap_or_else(|e| e.exit())
+    }
+
+    /// Check what clap can't: that the numbers are in range and the paths are there
+    fn validate(self) -> Result<Self, clap::Error> {
+        if let Some(interval) = self.interval
+            && (!interval.is_finite() || interval <= 0.0)
+        {
+            return Err(Self::command().error(
+                ErrorKind::InvalidValue,
+                "--interval must be a positive number of seconds",
+            ));
+        }
+        if let Some(interval) = self.slideshow_interval
+            && (!interval.is_finite() || interval <= 0.0)
+        {
+            return Err(Self::command().error(
+                ErrorKind::InvalidValue,
+                "--slideshow-interval must be a positive number of seconds",
+            ));
+        }
+        if let Some(height) = self.row_height
+            && (!height.is_finite() || height <= 0.0)
+        {
+            return Err(Self::command().error(
+                ErrorKind::InvalidValue,
+                "--row-height must be a positive number",
+            ));
+        }
+        if let Some(gap) = self.group_gap
+            && (!gap.is_finite() || gap < 0.0)
+        {
+            return Err(
+                Self::command().error(ErrorKind::InvalidValue, "--group-gap can't be negative")
+            );
+        }
+        if self.min_columns.is_some_and(|min| min < 1)
+            || self
+                .max_columns
+                .is_some_and(|max| max < self.min_columns.unwrap_or(1))
+        {
+            return Err(Self::command().error(
+                ErrorKind::InvalidValue,
+                "--min-columns and --max-columns must be at least 1, with the max no less \
+                 than the min",
+            ));
+        }
+        if let Some(radius) = self.wall_radius
+            && (!radius.is_finite() || radius <= 0.0)
+        {
+            return Err(Self::command().error(
+                ErrorKind::InvalidValue,
+                "--wall-radius must be a positive number",
+            ));
+        }
+        for dir in self.dirs.iter().chain(&self.shallow) {
+            if !dir.is_dir() {
+                return Err(Self::command().error(
+                    ErrorKind::ValueValidation,
+                    format!("{} is not a directory", dir.display()),
+                ));
             }
-            Interaction::None => {
-                **text = "Button".to_string();
-                *color = NORMAL_BUTTON.into();
-                border_color.0 = BLACK.into();
+        }
+        for archive in &self.archives {
+            if !archive.is_file() {
+                return Err(Self::command().error(
+                    ErrorKind::ValueValidation,
+                    format!("{} is not a file", archive.display()),
+                ));
             }
         }
+
+        Ok(self)
+    }
+
+    fn config_path(&self) -> Option<PathBuf> {
+        if self.no_config {
+            return None;
+        }
+        self.config.clone().or_else(PhotoviewConfig::default_path)
+    }
+
+    /// The saved settings with anything given on the command line laid over the top
+    fn slideshow_config(&self) -> SlideshowConfig {
+        let defaults = SlideshowConfig::default();
+        SlideshowConfig {
+            interval: self
+                .slideshow_interval
+                .map_or(defaults.interval, Duration::from_secs_f32),
+            ken_burns: !self.no_ken_burns,
+            prefetch: self.prefetch.unwrap_or(defaults.prefetch),
+        }
+    }
+
+    fn dir_watching_plugin(&self, config: &PhotoviewConfig) -> DirWatchingPlugin {
+        let mut plugin = DirWatchingPlugin::from_config(config).recursive(!self.no_recursive);
+        if !self.dirs.is_empty() || !self.shallow.is_empty() {
+            plugin = plugin
+                .dirs(self.dirs.iter().cloned())
+                .shallow_dirs(self.shallow.iter().cloned());
+        } else if config.dirs.is_empty()
+            && config.shallow_dirs.is_empty()
+            && self.playlists.is_empty()
+            && self.archives.is_empty()
+        {
+            plugin = plugin.dirs(["."]);
+        }
+        for playlist in &self.playlists {
+            plugin = plugin.playlist(playlist);
+        }
+        for archive in &self.archives {
+            plugin = plugin.archive(archive);
+        }
+        if let Some(interval) = self.interval {
+            plugin = plugin.scan_interval(Duration::from_secs_f32(interval));
+        }
+        if self.watch_events {
+            plugin = plugin.scan_backend(ScanBackend::Events);
+        }
+        if let Some(sort) = self.sort {
+            plugin = plugin.sort_order(sort.into());
+        }
+        if let Some(layout) = self.layout {
+            plugin = plugin.layout(layout.into());
+        }
+        if let Some(bucket) = self.timeline_by {
+            plugin = plugin.timeline_bucket(bucket.into());
+        }
+        if let Some(height) = self.row_height {
+            plugin = plugin.row_height(height);
+        }
+        if let Some(gap) = self.group_gap {
+            plugin = plugin.group_gap(gap);
+        }
+        if let Some(wall) = self.wall {
+            let radius = self.wall_radius.unwrap_or(WallMode::DEFAULT_RADIUS);
+            plugin = plugin.wall(wall.mode(radius));
+        }
+        if let Some(max) = self.max_images {
+            plugin = plugin.max_images(Some(max));
+        }
+        if let Some(per_page) = self.per_page {
+            plugin = plugin.per_page(per_page);
+        }
+        if let Some(mib) = self.texture_budget {
+            plugin = plugin.texture_budget(mib.saturating_mul(1 << 20));
+        }
+        if let Some(radius) = self.lazy_radius {
+            plugin = plugin.lazy_textures(radius);
+        }
+        if let Some(limit) = self.scan_limit {
+            plugin = plugin.scan_limit((limit > 0).then_some(limit));
+        }
+        if self.duplicates {
+            plugin = plugin.detect_duplicates(true);
+        }
+        if self.tint_dirs {
+            plugin = plugin.tint_directories(true);
+        }
+        if self.scrubber {
+            plugin = plugin.timeline_scrubber(true);
+        }
+        if self.atlas {
+            plugin = plugin.thumbnail_atlas(true);
+        }
+        if let Some(size) = self.thumbnails
+            && let Some(cache_dir) = dirs::cache_dir()
+        {
+            plugin = plugin
+                .thumbnail_cache(cache_dir.join("photoview").join("thumbnails"))
+                .thumbnail_size(size);
+        }
+        if self.sprites {
+            plugin = plugin.render_mode(RenderMode::Sprite2d);
+        }
+        if self.reflow {
+            plugin = plugin.fit_to_window(true);
+        }
+        if self.min_columns.is_some() || self.max_columns.is_some() {
+            let defaults = &config.grid;
+            plugin = plugin.column_limits(
+                self.min_columns.unwrap_or(defaults.min_columns),
+                self.max_columns.unwrap_or(defaults.max_columns),
+            );
+        }
+        if let Some(extensions) = &self.extensions {
+            plugin = plugin.extensions(extensions);
+        }
+        if !self.no_cache
+            && let Some(cache_dir) = dirs::cache_dir()
+        {
+            plugin = plugin.scan_cache(cache_dir.join("photoview").join("scan-cache.json"));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.catalog
+            && let Some(data_dir) = dirs::data_dir()
+        {
+            plugin = plugin.catalog(data_dir.join("photoview").join("catalog.sqlite"));
+        }
+        plugin
+    }
+}
+
+/// Sidebar header line with how many images we're showing
+#[derive(Component)]
+struct ImageCountText;
+
+/// Sidebar header line saying whether a scan is going on
+#[derive(Component)]
+struct ScanStatusText;
+
+/// Sidebar header line with how far the current (or last) scan got
+#[derive(Component)]
+struct ScanProgressText;
+
+fn scan_progress_system(
+    progress: Res<ScanProgress>,
+    mut text: Single<&mut Text, With<ScanProgressText>>,
+) {
+    text.0 = if progress.done {
+        format!(
+            "Last scan: {} folders, {} images",
+            progress.dirs_seen, progress.images_found
+        )
+    } else {
+        format!(
+            "{} folders, {} images so far",
+            progress.dirs_seen, progress.images_found
+        )
+    };
+}
+
+/// Sidebar line with how many images turned up since the last session's scan cache
+#[derive(Component)]
+struct NewImagesText;
+
+/// Sidebar warnings for watched directories that couldn't be scanned
+#[derive(Component)]
+struct UnreachableDirsText;
+
+fn unreachable_dirs_system(
+    watched_dirs: Res<WatchedDirs>,
+    mut text: Single<(&mut Text, &mut Node), With<UnreachableDirsText>>,
+) {
+    let (text, node) = &mut *text;
+    let lines: Vec<_> = watched_dirs
+        .unreachable_dirs()
+        .map(|(dir, status)| {
+            let state = if status.is_waiting() {
+                "waiting for it to appear"
+            } else if status.gave_up() {
+                "unreachable"
+            } else {
+                "unreachable, retrying"
+            };
+            format!("⚠ {} ({state})", dir.display())
+        })
+        .collect();
+    node.display = if lines.is_empty() {
+        Display::None
+    } else {
+        Display::Flex
+    };
+    text.0 = lines.join("\n");
+}
+
+fn header_system(
+    watched_dirs: Res<WatchedDirs>,
+    filter: Res<ImageFilter>,
+    recent: Res<RecentlyAddedFilter>,
+    color: Res<ColorSearchFilter>,
+    overflow: Res<ImageOverflow>,
+    matching: Query<(), (With<ImageMarker>, Without<FilteredOut>)>,
+    mut rescan_requests: EventReader<RescanRequested>,
+    mut scans_completed: EventReader<ScanCompleted>,
+    paused: Res<ScanPaused>,
+    mut count_text: Single<&mut Text, (With<ImageCountText>, Without<ScanStatusText>)>,
+    mut status_text: Single<&mut Text, (With<ScanStatusText>, Without<ImageCountText>)>,
+    // Nothing has been found until the first scan finishes
+    mut scanning: Local<Option<bool>>,
+) {
+    let was_scanning = *scanning;
+    if rescan_requests.read().count() > 0 {
+        *scanning = Some(true);
+    }
+    if scans_completed.read().count() > 0 {
+        *scanning = Some(false);
+    }
+
+    // Filtering happens a frame after the filter changes, so just recount every frame while one
+    // is active
+    if filter.is_active() || recent.active || color.active {
+        count_text.0 = format!(
+            "{} of {} images match",
+            matching.iter().count(),
+            watched_dirs.image_count()
+        );
+    } else if overflow.hidden() > 0 {
+        if overflow.is_changed() || filter.is_changed() || recent.is_changed() || color.is_changed()
+        {
+            count_text.0 = format!(
+                "Showing {} of {} images in {} directories",
+                overflow.shown,
+                overflow.total,
+                watched_dirs.dir_count()
+            );
+        }
+    } else if watched_dirs.is_changed()
+        || filter.is_changed()
+        || recent.is_changed()
+        || color.is_changed()
+        || overflow.is_changed()
+    {
+        count_text.0 = format!(
+            "{} images in {} directories",
+            watched_dirs.image_count(),
+            watched_dirs.dir_count()
+        );
+    }
+    if was_scanning != *scanning || paused.is_changed() {
+        status_text.0 = match *scanning {
+            _ if paused.0 => "Scanning paused".to_string(),
+            Some(false) => "Up to date".to_string(),
+            _ => "Scanning…".to_string(),
+        };
     }
 }
 
-fn _button(asset_server: &AssetServer) -> impl Bundle + use<> {
+/// Only known once the first scan has been checked against the cache, and never without one
+fn new_images_system(
+    mut reconciled: EventReader<ScanCacheReconciled>,
+    mut text: Single<(&mut Text, &mut Node), With<NewImagesText>>,
+) {
+    let Some(diff) = reconciled.read().last() else {
+        return;
+    };
+    let (text, node) = &mut *text;
+    text.0 = match diff.added.len() {
+        0 => "Nothing new since last session".to_string(),
+        1 => "1 new image since last session".to_string(),
+        count => format!("{count} new images since last session"),
+    };
+    node.display = Display::Flex;
+}
+
+/// The sidebar row with the page arrows, only shown when there's more than one page
+#[derive(Component)]
+struct PageBar;
+
+#[derive(Component)]
+struct PageText;
+
+fn page_bar_system(
+    page: Res<CurrentPage>,
+    mut bar: Single<&mut Node, With<PageBar>>,
+    mut text: Single<&mut Text, With<PageText>>,
+) {
+    bar.display = if page.is_paged() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    text.0 = format!("Page {} / {}", page.index + 1, page.count);
+}
+
+fn sidebar_button(label: &str, action: ButtonAction) -> impl Bundle {
     (
+        Button,
+        action,
         Node {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            align_items: AlignItems::Center,
+            padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+            margin: UiRect::top(Val::Px(8.0)),
+            border: UiRect::all(Val::Px(2.0)),
             justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
             ..default()
         },
+        BorderRadius::all(Val::Px(4.0)),
         children![(
-            Button,
-            Node {
-                width: Val::Px(150.0),
-                height: Val::Px(65.0),
-                border: UiRect::all(Val::Px(5.0)),
-                // horizontally center child text
-                justify_content: JustifyContent::Center,
-                // vertically center child text
-                align_items: AlignItems::Center,
+            Text::new(label),
+            TextFont {
+                font_size: 16.0,
                 ..default()
             },
-            BorderColor(Color::BLACK),
-            BorderRadius::MAX,
-            BackgroundColor(NORMAL_BUTTON),
-            children![(
-                Text::new("Button"),
-                TextFont {
-                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                    font_size: 33.0,
-                    ..default()
-                },
-                TextColor(Color::srgb(0.9, 0.9, 0.9)),
-                TextShadow::default(),
-            )]
+            Themed::Text,
         )],
     )
 }
@@ -99,35 +705,285 @@ fn main_ui(_asset_server: &AssetServer) -> impl Bundle + use<> {
         top_layout,
         children![(
             selection_layout,
-            BackgroundColor(NORMAL_BUTTON),
-            children![Text::new("First"), Text::new("Second")]
+            Themed::Panel,
+            children![
+                (
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    },
+                    children![
+                        (ImageCountText, Text::default(), Themed::Text),
+                        (ScanStatusText, Text::new("Scanning…"), Themed::Text),
+                        (
+                            ScanProgressText,
+                            Text::default(),
+                            TextFont {
+                                font_size: 14.0,
+                                ..default()
+                            },
+                            Themed::Text,
+                        ),
+                        (
+                            NewImagesText,
+                            Text::default(),
+                            Themed::Text,
+                            Node {
+                                display: Display::None,
+                                ..default()
+                            },
+                        ),
+                    ],
+                ),
+                (
+                    UnreachableDirsText,
+                    Text::default(),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    TextColor(Color::srgb(0.95, 0.65, 0.2)),
+                    Node {
+                        display: Display::None,
+                        ..default()
+                    },
+                ),
+                filter_bar(),
+                color_search_bar(),
+                (
+                    PageBar,
+                    Node {
+                        display: Display::None,
+                        column_gap: Val::Px(8.0),
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    children![
+                        sidebar_button("<", ButtonAction::PrevPage),
+                        (
+                            PageText,
+                            Text::default(),
+                            Node {
+                                margin: UiRect::top(Val::Px(8.0)),
+                                ..default()
+                            },
+                            Themed::Text,
+                        ),
+                        sidebar_button(">", ButtonAction::NextPage),
+                    ],
+                ),
+                sidebar_button("Rescan (F5)", ButtonAction::RescanNow),
+                sidebar_button("Watch folder...", ButtonAction::AddFolder),
+                sidebar_button("Pause/resume scanning (P)", ButtonAction::TogglePause),
+                sidebar_button("Toggle theme (T)", ButtonAction::ToggleTheme),
+                // `children!` only takes 12 at a time, so the export buttons get a column of
+                // their own
+                (
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Start,
+                        ..default()
+                    },
+                    children![
+                        sidebar_button(
+                            "Screenshot (Ctrl+S)",
+                            ButtonAction::Export(ExportKind::Screenshot)
+                        ),
+                        sidebar_button(
+                            "Contact sheet (Ctrl+Shift+S)",
+                            ButtonAction::Export(ExportKind::ContactSheet)
+                        ),
+                        sidebar_button("Export gallery", ButtonAction::Export(ExportKind::Gallery)),
+                    ],
+                ),
+            ]
         )],
     )
 }
 
-fn setup(mut commands: Commands, assets: Res<AssetServer>) {
+fn setup(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    render_mode: Res<RenderMode>,
+    camera_config: Res<CameraConfig>,
+) {
     // ui camera
-    commands.spawn((
-        Camera3d::default(),
-        Transform::from_xyz(0.0, 0.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
-    ));
+    match *render_mode {
+        RenderMode::Mesh3d => {
+            commands.spawn((Camera3d::default(), camera_config.transform()));
+        }
+        // Fitted to the grid once there is one
+        RenderMode::Sprite2d => {
+            commands.spawn(Camera2d);
+        }
+    }
 
     commands.spawn(main_ui(&assets));
 }
 
 fn main() {
     // _ = env_logger::init();
+    let cli = Cli::parse_and_validate();
+    let config_path = cli.config_path();
+    let mut config = config_path
+        .as_deref()
+        .map(PhotoviewConfig::load_or_default)
+        .unwrap_or_default();
+    if let Some(theme) = cli.theme {
+        config.theme = theme.into();
+    }
+    if cli.remember_session {
+        config.remember_session = true;
+    }
+    if let Some(background) = cli.background {
+        let srgba = background.to_srgba();
+        config.background = Some([srgba.red, srgba.green, srgba.blue]);
+    }
+
+    let mut window = Window::default();
+    if let Some([width, height]) = config.window_size {
+        window.resolution = (width, height).into();
+    }
+    if config.fullscreen {
+        window.mode = WindowMode::BorderlessFullscreen(MonitorSelection::Current);
+    }
 
-    App::new()
-        .add_plugins((
-            DefaultPlugins.set(AssetPlugin {
-                unapproved_path_mode: bevy::asset::UnapprovedPathMode::Allow,
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(AssetPlugin {
+                // The watched images are outside the assets folder, `load_image` asks for
+                // those explicitly
+                unapproved_path_mode: bevy::asset::UnapprovedPathMode::Deny,
                 ..Default::default()
+            })
+            .set(WindowPlugin {
+                primary_window: Some(window),
+                ..default()
             }),
-            DirWatchingPlugin,
-        ))
-        .insert_resource(WinitSettings::desktop_app())
-        .add_systems(Startup, setup)
-        .add_systems(Update, button_system)
-        .run();
+        cli.dir_watching_plugin(&config),
+        ContextMenuPlugin,
+        ExportPlugin,
+        InfoPanelPlugin,
+        ZoomPlugin,
+        NavigationPlugin,
+        WallPlugin,
+        DiagnosticsOverlayPlugin,
+        FullscreenPlugin,
+        ComparePlugin,
+        FileDropPlugin::default(),
+        ButtonActionPlugin,
+        DebugGizmosPlugin,
+    ))
+    .add_plugins((SlideshowPlugin, MinimapPlugin))
+    .insert_resource(WinitSettings::desktop_app())
+    .insert_resource(UiTheme::from_kind(config.theme))
+    .insert_resource(config.scene_background())
+    .insert_resource(CameraConfig {
+        fit_grid: cli.fit,
+        ..default()
+    })
+    .insert_resource(cli.slideshow_config())
+    .add_systems(Startup, setup)
+    .add_systems(
+        Update,
+        (
+            header_system,
+            new_images_system,
+            scan_progress_system.run_if(resource_changed::<ScanProgress>),
+            unreachable_dirs_system.run_if(resource_changed::<WatchedDirs>),
+            page_bar_system.run_if(resource_changed::<CurrentPage>),
+        ),
+    );
+    #[cfg(feature = "spatial_audio")]
+    app.add_plugins(photoview::SpatialAudioPlugin);
+    if let Some(path) = config_path {
+        app.add_plugins(ConfigPersistencePlugin { path, config });
+    }
+    app.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("photoview").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn no_arguments_leaves_everything_to_the_saved_settings() {
+        let cli = parse(&[]).unwrap().validate().unwrap();
+        assert!(cli.dirs.is_empty());
+        assert!(cli.shallow.is_empty());
+        assert!(!cli.no_recursive);
+        assert!(!cli.watch_events);
+        assert!(cli.interval.is_none());
+        assert!(cli.sort.is_none());
+        assert!(cli.max_images.is_none());
+        assert!(cli.thumbnails.is_none());
+        assert!(cli.config.is_none() && !cli.no_config);
+    }
+
+    #[test]
+    fn several_directories_are_all_watched() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let args = [a.path().to_str().unwrap(), b.path().to_str().unwrap()];
+        let cli = parse(&args).unwrap().validate().unwrap();
+        assert_eq!(cli.dirs, [a.path(), b.path()]);
+    }
+
+    #[test]
+    fn recursion_can_be_turned_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let cli = parse(&["--no-recursive", dir.path().to_str().unwrap()]).unwrap();
+        assert!(cli.no_recursive);
+        assert_eq!(cli.dirs, [dir.path()]);
+    }
+
+    #[test]
+    fn values_clap_cant_parse_are_errors() {
+        let sort = parse(&["--sort", "sideways"]).unwrap_err();
+        assert_eq!(sort.kind(), ErrorKind::InvalidValue);
+        assert!(parse(&["--interval", "soon"]).is_err());
+        assert!(parse(&["--max-images", "-1"]).is_err());
+        let reflow = parse(&["--reflow"]).unwrap_err();
+        assert_eq!(reflow.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn out_of_range_numbers_fail_validation() {
+        for args in [
+            &["--interval", "0"][..],
+            &["--row-height=-2"],
+            &[
+                "--sprites",
+                "--reflow",
+                "--min-columns",
+                "4",
+                "--max-columns",
+                "2",
+            ],
+        ] {
+            let error = parse(args).unwrap().validate().unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidValue, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn missing_directories_fail_validation() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("not-there");
+        let cli = parse(&[missing.to_str().unwrap()]).unwrap();
+        let error = cli.validate().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+
+        let cli = parse(&["--shallow", missing.to_str().unwrap()]).unwrap();
+        assert!(cli.validate().is_err());
+    }
 }
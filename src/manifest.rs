@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+
+#[cfg(target_arch = "wasm32")]
+use std::path::PathBuf;
+
+#[cfg(target_arch = "wasm32")]
+use crate::{MaxImages, ScanCounter, ScanEvents, WatchedDirs};
+
+/// The images to show when there's no filesystem to scan, which is the case in the browser. On
+/// `wasm32` this stands in for the watched directories: whenever it changes, it becomes the image
+/// list as is, in this order, and nothing gets scanned. Native builds ignore it.
+///
+/// Each entry is an asset path relative to where the page serves its assets from (`assets/` next
+/// to it, unless the `AssetPlugin` says otherwise), since that's what the browser asset reader
+/// fetches from. Insert it before (or alongside) the [`crate::DirWatchingPlugin`], e.g.
+/// `app.insert_resource(ImageManifest(vec!["photos/beach.jpg".into()]))`, and replace it
+/// whenever the list changes.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageManifest(pub Vec<String>);
+
+/// Take the [`ImageManifest`] as the result of a scan, so the loading screen and everything
+/// waiting on [`crate::ScanCompleted`] carry on as usual
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn apply_image_manifest(
+    manifest: Res<ImageManifest>,
+    max_images: Res<MaxImages>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut scan_counter: ResMut<ScanCounter>,
+    mut scan_events: ScanEvents,
+) {
+    let mut images: Vec<PathBuf> = manifest.0.iter().map(PathBuf::from).collect();
+    crate::dedup_images(&mut images);
+    scan_events.changed(crate::apply_images(&mut watched_dirs, images, max_images.0));
+    scan_counter.0 += 1;
+    // Nothing to walk
+    scan_events.completed(&watched_dirs, std::time::Duration::ZERO);
+}
@@ -0,0 +1,90 @@
+use bevy::{
+    asset::embedded_asset,
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+/// Unlit material for the image quads. Compared to an unlit `StandardMaterial` it skips the PBR
+/// shader entirely and can filter with a bicubic (Catmull-Rom) kernel, which stays a lot sharper
+/// when images aren't drawn at 1:1.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct ImageDisplayMaterial {
+    /// Non-zero for bicubic filtering, zero for plain bilinear. A `u32` because WGSL uniforms
+    /// can't hold a `bool`.
+    #[uniform(0)]
+    pub anti_alias: u32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub base_color_texture: Option<Handle<Image>>,
+    /// Multiplied into the image, white leaves it alone
+    #[uniform(3)]
+    pub tint: LinearRgba,
+    /// Blended over the image by its alpha, e.g. to mark the selection. Transparent leaves it
+    /// alone.
+    #[uniform(4)]
+    pub highlight: LinearRgba,
+    /// Non-zero to show the image flipped left to right, see [`crate::ExifOrientation`]
+    #[uniform(5)]
+    pub mirrored: u32,
+    pub alpha_mode: AlphaMode,
+}
+
+impl ImageDisplayMaterial {
+    pub fn new(texture: Handle<Image>, quality: &RenderQuality) -> Self {
+        Self {
+            anti_alias: quality.anti_alias as u32,
+            base_color_texture: Some(texture),
+            tint: LinearRgba::WHITE,
+            highlight: LinearRgba::NONE,
+            mirrored: 0,
+            alpha_mode: AlphaMode::Opaque,
+        }
+    }
+}
+
+impl Material for ImageDisplayMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://photoview/shaders/image_display.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+}
+
+/// How hard the image shader works. Changing this updates every existing image material.
+#[derive(Resource, Debug, Clone)]
+pub struct RenderQuality {
+    /// Bicubic filtering when on, bilinear when off
+    pub anti_alias: bool,
+}
+
+impl Default for RenderQuality {
+    fn default() -> Self {
+        Self { anti_alias: true }
+    }
+}
+
+pub struct ImageDisplayMaterialPlugin;
+
+impl Plugin for ImageDisplayMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "shaders/image_display.wgsl");
+        app.add_plugins(MaterialPlugin::<ImageDisplayMaterial>::default());
+        app.init_resource::<RenderQuality>();
+        app.add_systems(
+            Update,
+            apply_render_quality.run_if(resource_changed::<RenderQuality>),
+        );
+    }
+}
+
+fn apply_render_quality(
+    quality: Res<RenderQuality>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+) {
+    let anti_alias = quality.anti_alias as u32;
+    for (_, material) in materials.iter_mut() {
+        material.anti_alias = anti_alias;
+    }
+}
@@ -0,0 +1,138 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::{DirWatchingSet, ExifOrientation, ImageMarker, ImageModified, ImagesRemoved, exif};
+
+/// What a photo's EXIF data says about it, on each quad once it's been read (see
+/// [`MetadataPlugin`]). Anything the file doesn't say is `None`, and so is everything for files
+/// that aren't JPEGs or TIFFs.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct PhotoMetadata {
+    /// When it was taken, in the camera's local time (EXIF dates have no time zone) treated as
+    /// UTC
+    pub taken: Option<SystemTime>,
+    /// Make and model of the camera
+    pub camera: Option<String>,
+    pub lens: Option<String>,
+    pub iso: Option<u32>,
+    /// The f-number
+    pub aperture: Option<f32>,
+    /// Exposure time, in seconds
+    pub shutter: Option<f32>,
+    /// Which way up it goes, the Orientation tag
+    pub orientation: ExifOrientation,
+    pub gps: Option<GpsPosition>,
+}
+
+/// Where a photo was taken, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    /// North of the equator, negative to the south
+    pub latitude: f64,
+    /// East of Greenwich, negative to the west
+    pub longitude: f64,
+}
+
+/// Reads every quad's [`PhotoMetadata`] in the background, a batch at a time, and keeps what it
+/// read, so quads spawned again later (turning back to a page, say) get theirs straight away.
+/// Images rewritten on disk are read again.
+pub struct MetadataPlugin;
+
+#[derive(Resource, Default)]
+pub(crate) struct MetadataReader {
+    task: Option<Task<Vec<(PathBuf, PhotoMetadata)>>>,
+    /// Everything read so far, by image
+    pub(crate) read: HashMap<PathBuf, PhotoMetadata>,
+}
+
+impl MetadataReader {
+    /// Only the start of each file is read, so these go quickly
+    const BATCH: usize = 256;
+}
+
+impl Plugin for MetadataPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MetadataReader>();
+        app.add_systems(
+            Update,
+            (forget_metadata, poll_metadata_reader, attach_metadata)
+                .chain()
+                .after(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+/// Drop what was read from images that have been rewritten or have gone. Quads of rewritten ones
+/// lose their [`PhotoMetadata`] until it's been read again.
+fn forget_metadata(
+    mut commands: Commands,
+    mut modified: EventReader<ImageModified>,
+    mut removed: EventReader<ImagesRemoved>,
+    mut reader: ResMut<MetadataReader>,
+    quads: Query<(Entity, &ImageMarker), With<PhotoMetadata>>,
+) {
+    for ImagesRemoved(paths) in removed.read() {
+        for path in paths {
+            reader.read.remove(path);
+        }
+    }
+    let modified: HashSet<PathBuf> = modified.read().map(|event| event.path.clone()).collect();
+    if modified.is_empty() {
+        return;
+    }
+    for path in &modified {
+        reader.read.remove(path);
+    }
+    for (entity, marker) in &quads {
+        if modified.contains(&marker.target) {
+            commands.entity(entity).remove::<PhotoMetadata>();
+        }
+    }
+}
+
+fn poll_metadata_reader(mut reader: ResMut<MetadataReader>) {
+    let Some(task) = &mut reader.task else {
+        return;
+    };
+    let Some(read) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    reader.task = None;
+    reader.read.extend(read);
+}
+
+/// Give quads the metadata that's been read for their image, and start reading the next batch of
+/// images that haven't been read yet
+fn attach_metadata(
+    mut commands: Commands,
+    mut reader: ResMut<MetadataReader>,
+    quads: Query<(Entity, &ImageMarker), Without<PhotoMetadata>>,
+) {
+    let reading = reader.task.is_some();
+    let mut batch = Vec::new();
+    for (entity, marker) in &quads {
+        if let Some(metadata) = reader.read.get(&marker.target) {
+            commands.entity(entity).insert(metadata.clone());
+        } else if !reading && batch.len() < MetadataReader::BATCH {
+            batch.push(marker.target.clone());
+        }
+    }
+    if batch.is_empty() {
+        return;
+    }
+    reader.task = Some(IoTaskPool::get().spawn(async move {
+        batch
+            .into_iter()
+            .map(|path| {
+                let metadata = exif::photo_metadata(&path).unwrap_or_default();
+                (path, metadata)
+            })
+            .collect()
+    }));
+}
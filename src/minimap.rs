@@ -0,0 +1,313 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    ui::RelativeCursorPosition,
+};
+
+use crate::{
+    AppState, ColorPalette, GridConfig, GridPlane, GridPosition, ImageMarker, RenderMode, Themed,
+    WallMode, text_input_inactive,
+};
+
+/// Whether the minimap is showing. M shows and hides it.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShowMinimap(pub bool);
+
+impl Default for ShowMinimap {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// The grid from above, drawn into `image` with a dot per quad in its main colour (grey until
+/// its [`ColorPalette`] is in)
+#[derive(Resource, Debug, Clone)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+    /// The part of the grid's plane the image covers, in plane coordinates (see [`plane_point`])
+    pub bounds: Rect,
+}
+
+impl Minimap {
+    /// Width and height of the image, in pixels
+    const SIZE: u32 = 160;
+
+    /// Where `point` on the grid's plane is on the minimap, 0 to 1 from the top-left corner
+    fn to_map(&self, point: Vec2) -> Vec2 {
+        let size = self.bounds.size().max(Vec2::splat(f32::EPSILON));
+        let relative = (point - self.bounds.min) / size;
+        Vec2::new(relative.x, 1.0 - relative.y)
+    }
+
+    /// The other way round from [`Self::to_map`]
+    fn to_plane(&self, map: Vec2) -> Vec2 {
+        self.bounds.min + Vec2::new(map.x, 1.0 - map.y) * self.bounds.size()
+    }
+}
+
+#[derive(Component)]
+struct MinimapRoot;
+
+/// The outline of what the camera can see
+#[derive(Component)]
+struct MinimapFootprint;
+
+/// Shows a map of the whole grid in the bottom-right corner, with the camera's view outlined on
+/// it. Clicking or dragging on the map moves the camera there. Only for flat grids facing a fixed
+/// way, walls and grids facing the camera don't have a single "above" to see them from.
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShowMinimap>();
+        app.add_systems(Startup, spawn_minimap);
+        app.add_systems(
+            Update,
+            (
+                toggle_minimap_hotkey_system.run_if(text_input_inactive),
+                show_minimap,
+                (
+                    draw_minimap.run_if(minimap_changed),
+                    minimap_footprint_system,
+                    minimap_click_system,
+                )
+                    .chain()
+                    .run_if(|show: Res<ShowMinimap>| show.0),
+            )
+                .chain()
+                .run_if(in_state(AppState::Running).and(minimap_supported)),
+        );
+    }
+}
+
+/// Whether the grid is somewhere the minimap can show it
+fn minimap_supported(grid_config: Res<GridConfig>, render_mode: Res<RenderMode>) -> bool {
+    *render_mode == RenderMode::Mesh3d
+        && grid_config.wall == WallMode::Flat
+        && grid_config.plane != GridPlane::FacingCamera
+}
+
+/// Where a world position is on the grid's plane, looking down on it with the first row at the
+/// top
+pub fn plane_point(plane: GridPlane, position: Vec3) -> Vec2 {
+    match plane {
+        GridPlane::Xz => Vec2::new(position.x, -position.z),
+        GridPlane::Xy | GridPlane::FacingCamera => position.truncate(),
+    }
+}
+
+/// Where a point on the grid's plane is in the world, the other way round from [`plane_point`]
+fn world_point(plane: GridPlane, point: Vec2) -> Vec3 {
+    match plane {
+        GridPlane::Xz => Vec3::new(point.x, 0.0, -point.y),
+        GridPlane::Xy | GridPlane::FacingCamera => point.extend(0.0),
+    }
+}
+
+/// Where `ray` meets the grid's plane, if it does in front of where it starts
+fn hit_plane(plane: GridPlane, ray: Ray3d) -> Option<Vec2> {
+    let normal = match plane {
+        GridPlane::Xz => Dir3::Y,
+        GridPlane::Xy | GridPlane::FacingCamera => Dir3::Z,
+    };
+    let distance = ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(normal))?;
+    Some(plane_point(plane, ray.get_point(distance)))
+}
+
+fn spawn_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: Minimap::SIZE,
+            height: Minimap::SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    ));
+    commands.insert_resource(Minimap {
+        image: image.clone(),
+        bounds: Rect::new(-1.0, -1.0, 1.0, 1.0),
+    });
+    commands.spawn((
+        MinimapRoot,
+        Interaction::default(),
+        RelativeCursorPosition::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(32.0),
+            right: Val::Px(8.0),
+            width: Val::Px(Minimap::SIZE as f32),
+            height: Val::Px(Minimap::SIZE as f32),
+            border: UiRect::all(Val::Px(1.0)),
+            overflow: Overflow::clip(),
+            ..default()
+        },
+        BorderRadius::all(Val::Px(3.0)),
+        Themed::Panel,
+        GlobalZIndex(10),
+        children![
+            (
+                ImageNode::new(image),
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+            ),
+            (
+                MinimapFootprint,
+                Node {
+                    position_type: PositionType::Absolute,
+                    border: UiRect::all(Val::Px(1.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                BorderColor(Color::WHITE),
+            ),
+        ],
+    ));
+}
+
+fn toggle_minimap_hotkey_system(keys: Res<ButtonInput<KeyCode>>, mut show: ResMut<ShowMinimap>) {
+    if keys.just_pressed(KeyCode::KeyM) {
+        show.0 = !show.0;
+    }
+}
+
+fn show_minimap(show: Res<ShowMinimap>, mut root: Single<&mut Node, With<MinimapRoot>>) {
+    let display = if show.0 { Display::Flex } else { Display::None };
+    if root.display != display {
+        root.display = display;
+    }
+}
+
+/// Something on the map has moved, come, gone or got its colour
+fn minimap_changed(
+    grid_config: Res<GridConfig>,
+    moved: Query<(), Changed<GridPosition>>,
+    coloured: Query<(), Changed<ColorPalette>>,
+    mut removed: RemovedComponents<ImageMarker>,
+) -> bool {
+    grid_config.is_changed()
+        || !moved.is_empty()
+        || !coloured.is_empty()
+        || removed.read().count() > 0
+}
+
+/// Paint a dot for every quad where it's headed, sizing the map to fit them all
+fn draw_minimap(
+    grid_config: Res<GridConfig>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    quads: Query<(&GridPosition, Option<&ColorPalette>), With<ImageMarker>>,
+) {
+    const GREY: [u8; 4] = [128, 128, 128, 255];
+
+    let plane = grid_config.plane;
+    let half = Vec2::splat(grid_config.quad_size * 0.5);
+    let Some(bounds) = quads
+        .iter()
+        .map(|(position, _)| {
+            let center = plane_point(plane, position.target);
+            Rect::from_corners(center - half, center + half)
+        })
+        .reduce(|bounds, quad| bounds.union(quad))
+    else {
+        return;
+    };
+    // Square, so the map isn't stretched to fit its square image
+    let side = bounds.width().max(bounds.height());
+    minimap.bounds = Rect::from_center_size(bounds.center(), Vec2::splat(side));
+
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+    let Some(data) = image.data.as_mut() else {
+        return;
+    };
+    data.fill(0);
+    let size = Minimap::SIZE as f32;
+    for (position, palette) in &quads {
+        let color = palette
+            .and_then(|palette| palette.colors.first())
+            .map_or(GREY, |color| color.to_srgba().to_u8_array());
+        let center = plane_point(plane, position.target);
+        // At least a pixel each, however far out the map is
+        let min = (minimap.to_map(center + Vec2::new(-half.x, half.y)) * size).floor();
+        let max = (minimap.to_map(center + Vec2::new(half.x, -half.y)) * size).ceil();
+        let (min, max) = (min.as_uvec2(), max.as_uvec2().max(min.as_uvec2() + 1));
+        for y in min.y..max.y.min(Minimap::SIZE) {
+            for x in min.x..max.x.min(Minimap::SIZE) {
+                let offset = ((y * Minimap::SIZE + x) * 4) as usize;
+                data[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Outline where the corners of the window land on the grid's plane
+fn minimap_footprint_system(
+    grid_config: Res<GridConfig>,
+    minimap: Res<Minimap>,
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut footprint: Single<&mut Node, With<MinimapFootprint>>,
+) {
+    let (camera, camera_transform) = *camera;
+    let Some(viewport) = camera.logical_viewport_rect() else {
+        return;
+    };
+    let corners = [
+        viewport.min,
+        Vec2::new(viewport.max.x, viewport.min.y),
+        viewport.max,
+        Vec2::new(viewport.min.x, viewport.max.y),
+    ];
+    let hits: Option<Vec<Vec2>> = corners
+        .into_iter()
+        .map(|corner| {
+            let ray = camera.viewport_to_world(camera_transform, corner).ok()?;
+            hit_plane(grid_config.plane, ray).map(|point| minimap.to_map(point))
+        })
+        .collect();
+    // Looking over the horizon, there's no edge to draw
+    let Some(hits) = hits else {
+        footprint.display = Display::None;
+        return;
+    };
+    let (min, max) = hits.iter().fold(
+        (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+        |(min, max), hit| (min.min(*hit), max.max(*hit)),
+    );
+    footprint.display = Display::Flex;
+    footprint.left = Val::Percent(min.x * 100.0);
+    footprint.top = Val::Percent(min.y * 100.0);
+    footprint.width = Val::Percent((max.x - min.x) * 100.0);
+    footprint.height = Val::Percent((max.y - min.y) * 100.0);
+}
+
+/// While the map is held down, slide the camera so the middle of the view is under the cursor
+fn minimap_click_system(
+    grid_config: Res<GridConfig>,
+    minimap: Res<Minimap>,
+    root: Single<(&Interaction, &RelativeCursorPosition), With<MinimapRoot>>,
+    mut camera: Single<&mut Transform, With<Camera3d>>,
+) {
+    let (interaction, cursor) = *root;
+    if *interaction != Interaction::Pressed {
+        return;
+    }
+    // Relative to the middle of the map, so the top-left corner is -0.5
+    let Some(position) = cursor.normalized else {
+        return;
+    };
+    let target = minimap.to_plane((position + 0.5).clamp(Vec2::ZERO, Vec2::ONE));
+    let ray = Ray3d::new(camera.translation, camera.forward());
+    let Some(looking_at) = hit_plane(grid_config.plane, ray) else {
+        return;
+    };
+    let plane = grid_config.plane;
+    camera.translation += world_point(plane, target) - world_point(plane, looking_at);
+}
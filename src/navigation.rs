@@ -0,0 +1,290 @@
+use bevy::prelude::*;
+
+use std::path::PathBuf;
+
+use crate::{
+    CameraAnimation, FilteredOut, GridConfig, GridPosition, ImageMarker, Selection, ViewMode,
+    WallMode, text_input_inactive,
+};
+
+/// Arrow keys move the selection around the grid, Home and End jump to the first and last image
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (navigate_selection_system, pan_to_selection)
+                .chain()
+                .run_if(in_state(ViewMode::Grid).and(text_input_inactive)),
+        );
+    }
+}
+
+/// Which way to move the selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Left,
+    Right,
+    Up,
+    Down,
+    First,
+    Last,
+}
+
+impl Step {
+    fn from_keys(keys: &ButtonInput<KeyCode>, has_selection: bool) -> Option<Self> {
+        const STEPS: [(KeyCode, Step); 4] = [
+            (KeyCode::ArrowLeft, Step::Left),
+            (KeyCode::ArrowRight, Step::Right),
+            (KeyCode::ArrowUp, Step::Up),
+            (KeyCode::ArrowDown, Step::Down),
+        ];
+        if let Some((_, step)) = STEPS.iter().find(|(key, _)| keys.just_pressed(*key)) {
+            return Some(*step);
+        }
+        // Without a selection Home is zoom to fit, see the zoom plugin
+        if !has_selection {
+            return None;
+        }
+        if keys.just_pressed(KeyCode::Home) {
+            Some(Step::First)
+        } else if keys.just_pressed(KeyCode::End) {
+            Some(Step::Last)
+        } else {
+            None
+        }
+    }
+}
+
+/// The quads that can be navigated to, grouped into rows top to bottom, each row left to right,
+/// with how far across each one is. Goes by the cells the layout gave the quads
+/// ([`GridPosition::cell`]), so it's the grid the layout actually produced, whichever layout and
+/// whichever plane it's on.
+pub(crate) fn grid_rows(quads: &[(PathBuf, Vec2)]) -> Vec<Vec<(PathBuf, f32)>> {
+    // The cells in a row all get the same y, this only soaks up rounding
+    const SAME_ROW: f32 = 1e-3;
+
+    let mut sorted: Vec<_> = quads.iter().collect();
+    sorted.sort_by(|(_, a), (_, b)| b.y.total_cmp(&a.y));
+
+    let mut rows: Vec<(f32, Vec<(PathBuf, f32)>)> = vec![];
+    for (path, cell) in sorted {
+        match rows.last_mut() {
+            Some((y, row)) if (*y - cell.y).abs() < SAME_ROW => row.push((path.clone(), cell.x)),
+            _ => rows.push((cell.y, vec![(path.clone(), cell.x)])),
+        }
+    }
+    rows.into_iter()
+        .map(|(_, mut row)| {
+            row.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+            row
+        })
+        .collect()
+}
+
+/// The quads in the order they read on screen, row by row, see [`grid_rows`]
+pub(crate) fn grid_order(quads: &[(PathBuf, Vec2)]) -> Vec<PathBuf> {
+    grid_rows(quads)
+        .into_iter()
+        .flatten()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Where `step` takes the selection from (`row`, `column`)
+fn step_from(
+    rows: &[Vec<(PathBuf, f32)>],
+    row: usize,
+    column: usize,
+    step: Step,
+) -> (usize, usize) {
+    let last_row = rows.len() - 1;
+    match step {
+        Step::First => (0, 0),
+        Step::Last => (last_row, rows[last_row].len() - 1),
+        // Left and right run on into the neighbouring rows, like reading order
+        Step::Left if column > 0 => (row, column - 1),
+        Step::Left if row > 0 => (row - 1, rows[row - 1].len() - 1),
+        Step::Right if column + 1 < rows[row].len() => (row, column + 1),
+        Step::Right if row < last_row => (row + 1, 0),
+        Step::Left | Step::Right => (row, column),
+        Step::Up | Step::Down => {
+            let target = match step {
+                Step::Up if row > 0 => row - 1,
+                Step::Down if row < last_row => row + 1,
+                _ => return (row, column),
+            };
+            // Rows can be short (the last one, or gaps left by a filter), so go to whichever quad
+            // in the next row is closest horizontally
+            let x = rows[row][column].1;
+            let target_column = rows[target]
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| (a.1 - x).abs().total_cmp(&(b.1 - x).abs()))
+                .map_or(0, |(index, _)| index);
+            (target, target_column)
+        }
+    }
+}
+
+fn navigate_selection_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut selected: ResMut<Selection>,
+    quads: Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+) {
+    let Some(step) = Step::from_keys(&keys, !selected.is_empty()) else {
+        return;
+    };
+
+    let quads: Vec<_> = quads
+        .iter()
+        .map(|(marker, position)| (marker.target.clone(), position.cell))
+        .collect();
+    let rows = grid_rows(&quads);
+    if rows.is_empty() {
+        return;
+    }
+
+    let current = selected.path().and_then(|path| {
+        rows.iter().enumerate().find_map(|(row, quads)| {
+            quads
+                .iter()
+                .position(|(quad, _)| quad == path)
+                .map(|column| (row, column))
+        })
+    });
+    // With nothing selected (or the selection filtered away) any key starts at the beginning
+    let (row, column) = match current {
+        Some((row, column)) => step_from(&rows, row, column, step),
+        None => (0, 0),
+    };
+
+    let path = rows[row][column].0.clone();
+    if selected.len() != 1 || selected.path() != Some(&path) {
+        selected.select(path);
+    }
+}
+
+/// When the selection ends up off screen, slide the camera sideways until it's back in the middle.
+/// In the middle of a [`WallMode`] wall it turns to face it instead.
+fn pan_to_selection(
+    mut commands: Commands,
+    selected: Res<Selection>,
+    grid_config: Res<GridConfig>,
+    quads: Query<(&ImageMarker, &GlobalTransform)>,
+    camera: Single<(Entity, &Camera, &GlobalTransform, &Transform), With<Camera3d>>,
+) {
+    // Leave a bit of room so a quad half off the edge counts as off screen
+    const LIMIT: f32 = 0.8;
+
+    if !selected.is_changed() {
+        return;
+    }
+    let Some(path) = selected.path() else {
+        return;
+    };
+    let Some((_, quad)) = quads.iter().find(|(marker, _)| marker.target == *path) else {
+        return;
+    };
+
+    let (entity, camera, camera_global, transform) = *camera;
+    let position = quad.translation();
+    let on_screen = camera
+        .world_to_ndc(camera_global, position)
+        .is_some_and(|ndc| ndc.x.abs() <= LIMIT && ndc.y.abs() <= LIMIT && ndc.z > 0.0);
+    if on_screen {
+        return;
+    }
+
+    if grid_config.wall != WallMode::Flat {
+        let target = transform.looking_at(position, Vec3::Y);
+        commands.entity(entity).insert(CameraAnimation::to(target));
+        return;
+    }
+
+    // Keep the view direction and distance, just move so the quad sits on the look ray
+    let forward = transform.forward();
+    let to_quad = position - transform.translation;
+    let offset = to_quad - *forward * to_quad.dot(*forward).max(0.0);
+    let target = transform.with_translation(transform.translation + offset);
+    commands.entity(entity).insert(CameraAnimation::to(target));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(name: &str, x: f32, y: f32) -> (PathBuf, Vec2) {
+        (PathBuf::from(name), Vec2::new(x, y))
+    }
+
+    fn names(rows: &[Vec<(PathBuf, f32)>]) -> Vec<Vec<&str>> {
+        rows.iter()
+            .map(|row| row.iter().map(|(path, _)| path.to_str().unwrap()).collect())
+            .collect()
+    }
+
+    /// Justified-style rows: a wide image then two narrow ones on top, three in the middle and
+    /// one on its own at the bottom, given in no particular order
+    fn uneven() -> Vec<Vec<(PathBuf, f32)>> {
+        grid_rows(&[
+            quad("g", 0.0, -2.0),
+            quad("c", 2.5, 2.0),
+            quad("a", -2.0, 2.0),
+            quad("e", 0.2, 0.0),
+            quad("b", 1.0, 2.0),
+            quad("f", 2.0, 0.0),
+            quad("d", -1.6, 0.0),
+        ])
+    }
+
+    #[test]
+    fn rows_come_from_the_cells() {
+        assert_eq!(
+            names(&uneven()),
+            vec![vec!["a", "b", "c"], vec!["d", "e", "f"], vec!["g"]]
+        );
+        let order = grid_order(&[
+            quad("b", 1.0, 2.0),
+            quad("c", -3.0, 0.0),
+            quad("a", 0.0, 2.0),
+        ]);
+        assert_eq!(
+            order,
+            [PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")]
+        );
+    }
+
+    #[test]
+    fn rounding_stays_in_the_row() {
+        let rows = grid_rows(&[quad("b", 1.0, 1.0 - 1e-5), quad("a", 0.0, 1.0)]);
+        assert_eq!(names(&rows), vec![vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn up_and_down_go_to_the_nearest_across() {
+        let rows = uneven();
+        // "c" is furthest right on top, and "f" is nearest below it
+        assert_eq!(step_from(&rows, 0, 2, Step::Down), (1, 2));
+        // "e" is at 0.2, closer to "b" at 1.0 than to "a" at -2.0
+        assert_eq!(step_from(&rows, 1, 1, Step::Up), (0, 1));
+        // "d" is at -1.6, under "a"
+        assert_eq!(step_from(&rows, 1, 0, Step::Up), (0, 0));
+        // Everything in the middle row goes down to the one at the bottom
+        assert_eq!(step_from(&rows, 1, 2, Step::Down), (2, 0));
+        assert_eq!(step_from(&rows, 2, 0, Step::Down), (2, 0));
+        assert_eq!(step_from(&rows, 0, 1, Step::Up), (0, 1));
+    }
+
+    #[test]
+    fn left_and_right_read_on_into_the_next_row() {
+        let rows = uneven();
+        assert_eq!(step_from(&rows, 0, 2, Step::Right), (1, 0));
+        assert_eq!(step_from(&rows, 1, 0, Step::Left), (0, 2));
+        assert_eq!(step_from(&rows, 2, 0, Step::Right), (2, 0));
+        assert_eq!(step_from(&rows, 0, 0, Step::Left), (0, 0));
+        assert_eq!(step_from(&rows, 1, 1, Step::First), (0, 0));
+        assert_eq!(step_from(&rows, 1, 1, Step::Last), (2, 0));
+    }
+}
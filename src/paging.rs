@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::{AppState, DirWatchingSet, ImageFilter, Selection, WatchedDirs, text_input_inactive};
+
+/// Split the grid into pages of at most `per_page` images, so a huge collection doesn't need a
+/// quad for every image at once. `0` means no paging, everything on one page.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageConfig {
+    pub per_page: usize,
+}
+
+/// The page that's showing and the images on it, in grid order. These are the images that get
+/// quads; the layout goes by their position here rather than in [`WatchedDirs`].
+///
+/// Without paging that's every image in [`WatchedDirs::images`], with the [`ImageFilter`] hiding
+/// quads in place as usual. With paging the pages are cut from the images whose file names match
+/// the filter, so they come out full. `tag:` terms can only be checked once an image's quad has
+/// loaded its tags, so those still hide quads within a page.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct CurrentPage {
+    /// Where we are, counting from 0
+    pub index: usize,
+    /// How many pages there are, at least 1
+    pub count: usize,
+    images: Vec<PathBuf>,
+}
+
+impl CurrentPage {
+    pub fn images(&self) -> &[PathBuf] {
+        &self.images
+    }
+
+    pub fn is_paged(&self) -> bool {
+        self.count > 1
+    }
+}
+
+/// Ask for a different page, `offset` pages on from the current one. Clamped to the pages there
+/// are.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurnPage(pub isize);
+
+pub struct PagingPlugin;
+
+impl Plugin for PagingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PageConfig>();
+        app.init_resource::<CurrentPage>();
+        app.add_event::<TurnPage>();
+        app.add_systems(
+            Update,
+            page_hotkey_system
+                .run_if(text_input_inactive)
+                .run_if(in_state(AppState::Running)),
+        );
+        // After the scan, so the loading screen never sees the new images without their page
+        app.add_systems(
+            PreUpdate,
+            update_current_page
+                .run_if(
+                    resource_changed::<WatchedDirs>
+                        .or(resource_changed::<PageConfig>)
+                        .or(resource_changed::<ImageFilter>)
+                        .or(on_event::<TurnPage>),
+                )
+                .after(DirWatchingSet::Scan)
+                .before(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+/// PageUp and PageDown flip through the pages
+fn page_hotkey_system(keys: Res<ButtonInput<KeyCode>>, mut turns: EventWriter<TurnPage>) {
+    if keys.just_pressed(KeyCode::PageUp) {
+        turns.write(TurnPage(-1));
+    }
+    if keys.just_pressed(KeyCode::PageDown) {
+        turns.write(TurnPage(1));
+    }
+}
+
+/// Which page `image` is on, cut the same way as the [`CurrentPage`]. `None` if it's not on any
+/// of them, because it's not been scanned or the filter hides it.
+pub(crate) fn page_of(
+    watched_dirs: &WatchedDirs,
+    config: &PageConfig,
+    filter: &ImageFilter,
+    image: &Path,
+) -> Option<usize> {
+    if config.per_page == 0 {
+        return watched_dirs
+            .images()
+            .iter()
+            .any(|path| path == image)
+            .then_some(0);
+    }
+    let position = watched_dirs
+        .images()
+        .iter()
+        .filter(|path| filter.matches_name(path))
+        .position(|path| path == image)?;
+    Some(position / config.per_page)
+}
+
+/// Cut the current page out of the image list. Anything selected that's not on the page
+/// anymore is deselected, so keyboard actions don't reach images that aren't showing.
+pub(crate) fn update_current_page(
+    watched_dirs: Res<WatchedDirs>,
+    config: Res<PageConfig>,
+    filter: Res<ImageFilter>,
+    mut turns: EventReader<TurnPage>,
+    mut page: ResMut<CurrentPage>,
+    mut selection: ResMut<Selection>,
+) {
+    let offset: isize = turns.read().map(|turn| turn.0).sum();
+
+    if config.per_page == 0 {
+        page.set_if_neq(CurrentPage {
+            index: 0,
+            count: 1,
+            images: watched_dirs.images().to_vec(),
+        });
+        return;
+    }
+
+    let matching: Vec<&PathBuf> = watched_dirs
+        .images()
+        .iter()
+        .filter(|path| filter.matches_name(path))
+        .collect();
+    let count = matching.len().div_ceil(config.per_page).max(1);
+    let index = page.index.saturating_add_signed(offset).min(count - 1);
+    let images: Vec<PathBuf> = matching
+        .into_iter()
+        .skip(index * config.per_page)
+        .take(config.per_page)
+        .cloned()
+        .collect();
+
+    page.set_if_neq(CurrentPage {
+        index,
+        count,
+        images,
+    });
+    if page.is_changed() {
+        let on_page: HashSet<&PathBuf> = page.images().iter().collect();
+        if selection.paths().iter().any(|path| !on_page.contains(path)) {
+            selection.0.retain(|path| on_page.contains(path));
+        }
+    }
+}
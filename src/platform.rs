@@ -0,0 +1,246 @@
+//! Talking to the rest of the desktop: opening files in other apps, the file manager, the
+//! clipboard, file dialogs and the trash. In the browser there's none of that, so each of these
+//! just fails (or finds nothing) there.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+/// Open a file with whatever the OS thinks should handle it
+pub fn open_path(path: &Path) -> io::Result<()> {
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        // the empty string is the window title `start` insists on
+        command.args(["/C", "start", ""]).arg(path);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg(path);
+        command
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = {
+        let mut command = Command::new("xdg-open");
+        command.arg(path);
+        command
+    };
+
+    command.spawn().map(|_| ())
+}
+
+/// Show a file in the system file manager, selecting it where the platform supports that
+pub fn reveal_in_file_manager(path: &Path) -> io::Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        let mut select = std::ffi::OsString::from("/select,");
+        select.push(path);
+        Command::new("explorer").arg(select).spawn().map(|_| ())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Command::new("open").arg("-R").arg(path).spawn().map(|_| ())
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // There's no portable "select this file" on Linux, so just open the folder
+        let dir = path.parent().unwrap_or(path);
+        Command::new("xdg-open").arg(dir).spawn().map(|_| ())
+    }
+}
+
+/// Kept alive for the whole process, on X11 the clipboard contents vanish along with the last
+/// `Clipboard` instance.
+#[cfg(not(target_arch = "wasm32"))]
+static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+/// Run `f` with the process's clipboard, opening it the first time
+#[cfg(not(target_arch = "wasm32"))]
+fn with_clipboard(
+    f: impl FnOnce(&mut arboard::Clipboard) -> Result<(), arboard::Error>,
+) -> io::Result<()> {
+    let mut clipboard = CLIPBOARD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let clipboard = match &mut *clipboard {
+        Some(clipboard) => clipboard,
+        empty => empty.insert(arboard::Clipboard::new().map_err(io::Error::other)?),
+    };
+    f(clipboard).map_err(io::Error::other)
+}
+
+/// Put some text on the system clipboard
+pub fn copy_text_to_clipboard(text: &str) -> io::Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        with_clipboard(|clipboard| clipboard.set_text(text))
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = text;
+        Err(unsupported("the clipboard"))
+    }
+}
+
+/// Put a picture on the system clipboard, `rgba` being its `width` by `height` pixels at 8 bits
+/// a channel. Fails where the clipboard only takes text.
+pub fn copy_image_to_clipboard(width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let image = arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: rgba.into(),
+        };
+        with_clipboard(|clipboard| clipboard.set_image(image))
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (width, height, rgba);
+        Err(unsupported("the clipboard"))
+    }
+}
+
+/// Ask the user for a folder. Blocks until they've picked one, `None` if they cancelled.
+pub fn pick_folder() -> Option<PathBuf> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        rfd::FileDialog::new().pick_folder()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        None
+    }
+}
+
+/// Ask the user where to save a file, suggesting `file_name`. Blocks until they've answered,
+/// `None` if they cancelled.
+pub fn pick_save_file(filter: &str, extensions: &[&str], file_name: &str) -> Option<PathBuf> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        rfd::FileDialog::new()
+            .add_filter(filter, extensions)
+            .set_file_name(file_name)
+            .save_file()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (filter, extensions, file_name);
+        None
+    }
+}
+
+/// Move a file to the system trash
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        trash::delete(path).map_err(io::Error::other)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = path;
+        Err(unsupported("the trash"))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{what} isn't available in the browser"),
+    )
+}
+
+/// Whether [`restore_from_trash`] works here. The `trash` crate can only list what's in the trash
+/// on Windows and freedesktop systems, there's no way back out on macOS.
+pub const CAN_RESTORE_FROM_TRASH: bool = cfg!(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+));
+
+/// Put files that were moved to the trash back where they came from. If a file went to the trash
+/// more than once, the most recent one comes back.
+pub fn restore_from_trash(paths: &[PathBuf]) -> io::Result<()> {
+    #[cfg(any(
+        target_os = "windows",
+        all(
+            unix,
+            not(target_os = "macos"),
+            not(target_os = "ios"),
+            not(target_os = "android")
+        )
+    ))]
+    {
+        use std::collections::HashMap;
+
+        // The trash remembers absolute, canonical paths. The directory is still there even though
+        // the file isn't, so that's what gets canonicalized.
+        let mut wanted = HashMap::new();
+        for path in paths {
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                continue;
+            };
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            wanted.insert(parent.canonicalize()?.join(name), path);
+        }
+
+        let mut latest: HashMap<PathBuf, trash::TrashItem> = HashMap::new();
+        for item in trash::os_limited::list().map_err(io::Error::other)? {
+            let original = item.original_path();
+            if !wanted.contains_key(&original) {
+                continue;
+            }
+            match latest.get(&original) {
+                Some(newer) if newer.time_deleted >= item.time_deleted => {}
+                _ => {
+                    latest.insert(original, item);
+                }
+            }
+        }
+        if let Some((_, path)) = wanted
+            .iter()
+            .find(|(original, _)| !latest.contains_key(*original))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} isn't in the trash anymore", path.display()),
+            ));
+        }
+
+        trash::os_limited::restore_all(latest.into_values()).map_err(|e| match e {
+            trash::Error::RestoreCollision { path, .. } => io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is in the way", path.display()),
+            ),
+            e => io::Error::other(e),
+        })
+    }
+    #[cfg(not(any(
+        target_os = "windows",
+        all(
+            unix,
+            not(target_os = "macos"),
+            not(target_os = "ios"),
+            not(target_os = "android")
+        )
+    )))]
+    {
+        let _ = paths;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "restoring from the trash isn't supported on this platform",
+        ))
+    }
+}
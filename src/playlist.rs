@@ -0,0 +1,51 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{ScanConfig, normalize_path};
+
+/// A plain-text (`.m3u`-style) list of images, one path per line. Blank lines and `#` lines
+/// (comments, `#EXTM3U` headers) are skipped, and relative paths are relative to the playlist
+/// file's own directory.
+#[derive(Debug, Clone)]
+pub(crate) struct Playlist {
+    pub(crate) path: PathBuf,
+    /// When the file was last read, to spot edits
+    modified: Option<SystemTime>,
+    pub(crate) images: Vec<PathBuf>,
+}
+
+impl Playlist {
+    /// Read a playlist, keeping only the lines that are existing files with one of the
+    /// configured image extensions
+    pub(crate) fn load(path: &Path, config: &ScanConfig) -> io::Result<Self> {
+        let modified = modified(path);
+        let text = fs::read_to_string(path)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        let images = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| base.join(line))
+            .filter(|img| img.is_file() && config.is_supported_image(img))
+            .map(|img| normalize_path(&img))
+            .collect();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            modified,
+            images,
+        })
+    }
+
+    /// Has the file been changed (or removed) since we read it?
+    pub(crate) fn is_stale(&self) -> bool {
+        modified(&self.path) != self.modified
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
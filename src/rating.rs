@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::{
+    Action, AppState, DirWatchingSet, History, ImageMarker, Selection, StatusBar,
+    text_input_inactive,
+};
+
+/// How many stars the user gave an image, 1 to 5, with 0 for none. Ctrl+1 to Ctrl+5 rate the
+/// selected images and Ctrl+0 clears them, either of which can be undone. Only the catalog keeps them between launches (see
+/// `DirWatchingPlugin::catalog`), without one they last the session.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Rating(pub u8);
+
+/// Every image's rating, including the ones without a quad right now
+#[derive(Resource, Debug, Default)]
+pub(crate) struct Ratings(pub(crate) HashMap<PathBuf, Rating>);
+
+pub struct RatingPlugin;
+
+impl Plugin for RatingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Ratings>();
+        app.add_systems(
+            Update,
+            (
+                rating_hotkey_system
+                    .run_if(in_state(AppState::Running))
+                    .run_if(text_input_inactive),
+                attach_ratings,
+            )
+                .after(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+/// Ctrl and a number from 0 to 5 gives the selected images that many stars
+fn rating_hotkey_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selection>,
+    mut ratings: ResMut<Ratings>,
+    mut history: ResMut<History>,
+    mut status: ResMut<StatusBar>,
+    quads: Query<(Entity, &ImageMarker)>,
+) {
+    const STARS: [KeyCode; 6] = [
+        KeyCode::Digit0,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+    ];
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let Some(stars) = STARS.iter().position(|key| keys.just_pressed(*key)) else {
+        return;
+    };
+    if selected.is_empty() {
+        status.set("Select an image to rate it");
+        return;
+    }
+
+    let rating = Rating(stars as u8);
+    let previous: Vec<(PathBuf, Option<Rating>)> = selected
+        .paths()
+        .iter()
+        .map(|path| (path.clone(), ratings.0.get(path).copied()))
+        .collect();
+    let new: HashMap<PathBuf, Rating> = selected
+        .paths()
+        .iter()
+        .map(|path| (path.clone(), rating))
+        .collect();
+    rate(&mut commands, &mut ratings, &quads, &new);
+    history.record(Action::SetRating { rating, previous });
+
+    let images = match selected.len() {
+        1 => "1 image".to_string(),
+        count => format!("{count} images"),
+    };
+    status.set(match stars {
+        0 => format!("Cleared the rating of {images}"),
+        1 => format!("Rated {images} 1 star"),
+        stars => format!("Rated {images} {stars} stars"),
+    });
+}
+
+/// Give each image its rating, both in the [`Ratings`] and on its quad. 0 clears it.
+fn rate(
+    commands: &mut Commands,
+    ratings: &mut Ratings,
+    quads: &Query<(Entity, &ImageMarker)>,
+    new: &HashMap<PathBuf, Rating>,
+) {
+    for (path, rating) in new {
+        if rating.0 == 0 {
+            ratings.0.remove(path);
+        } else {
+            ratings.0.insert(path.clone(), *rating);
+        }
+    }
+    for (entity, marker) in quads {
+        if let Some(rating) = new.get(&marker.target) {
+            commands.entity(entity).insert(*rating);
+        }
+    }
+}
+
+/// [`rate`] for the [`History`] to undo and redo with
+pub(crate) fn set_ratings(
+    In(new): In<HashMap<PathBuf, Rating>>,
+    mut commands: Commands,
+    mut ratings: ResMut<Ratings>,
+    quads: Query<(Entity, &ImageMarker)>,
+) {
+    rate(&mut commands, &mut ratings, &quads, &new);
+}
+
+/// Give newly spawned quads their image's rating
+fn attach_ratings(
+    mut commands: Commands,
+    ratings: Res<Ratings>,
+    quads: Query<(Entity, &ImageMarker), Added<ImageMarker>>,
+) {
+    for (entity, marker) in &quads {
+        if let Some(rating) = ratings.0.get(&marker.target) {
+            commands.entity(entity).insert(*rating);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rating_can_be_undone_and_redone() {
+        let (rated, unrated) = (PathBuf::from("/a.jpg"), PathBuf::from("/b.jpg"));
+        let mut world = World::new();
+        let mut ratings = Ratings::default();
+        ratings.0.insert(rated.clone(), Rating(2));
+        world.insert_resource(ratings);
+        let quad = world
+            .spawn((
+                ImageMarker {
+                    target: rated.clone(),
+                },
+                Rating(2),
+            ))
+            .id();
+
+        let action = Action::SetRating {
+            rating: Rating(4),
+            previous: vec![(rated.clone(), Some(Rating(2))), (unrated.clone(), None)],
+        };
+        assert_eq!(action.describe(), "rating 2 images 4 stars");
+        action.apply(&mut world).unwrap();
+        let ratings = &world.resource::<Ratings>().0;
+        assert_eq!(ratings.get(&rated), Some(&Rating(4)));
+        assert_eq!(ratings.get(&unrated), Some(&Rating(4)));
+        assert_eq!(world.get::<Rating>(quad), Some(&Rating(4)));
+
+        action.revert(&mut world).unwrap();
+        let ratings = &world.resource::<Ratings>().0;
+        assert_eq!(ratings.get(&rated), Some(&Rating(2)));
+        assert_eq!(ratings.get(&unrated), None);
+        assert_eq!(world.get::<Rating>(quad), Some(&Rating(2)));
+    }
+
+    #[test]
+    fn clearing_a_rating_can_be_undone() {
+        let path = PathBuf::from("/a.jpg");
+        let mut world = World::new();
+        world.init_resource::<Ratings>();
+        let action = Action::SetRating {
+            rating: Rating(0),
+            previous: vec![(path.clone(), Some(Rating(5)))],
+        };
+        assert_eq!(action.describe(), "clearing the rating of 1 image");
+        action.apply(&mut world).unwrap();
+        assert!(world.resource::<Ratings>().0.is_empty());
+        action.revert(&mut world).unwrap();
+        assert_eq!(world.resource::<Ratings>().0.get(&path), Some(&Rating(5)));
+    }
+}
@@ -0,0 +1,339 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, RenderAssetUsages, io::Reader},
+    prelude::*,
+};
+use image::{DynamicImage, ImageFormat};
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::exif::Tiff;
+
+/// The RAW formats that can be shown, all TIFF underneath
+pub(crate) const RAW_EXTENSIONS: &[&str] = &["cr2", "nef", "arw", "dng"];
+
+/// Shows RAW photos by the JPEG preview the camera embeds in them. That's usually full size (NEF
+/// and CR2) or near enough (ARW, most DNGs), and looks the way the camera would have processed it,
+/// which demosaicing the sensor data ourselves wouldn't. Files without a preview we can decode fail
+/// to load like any other broken image. Only there with the `raw` feature.
+pub struct RawPlugin;
+
+impl Plugin for RawPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset_loader::<RawPreviewLoader>();
+    }
+}
+
+#[derive(Default)]
+struct RawPreviewLoader;
+
+#[derive(Debug)]
+pub(crate) enum RawError {
+    Io(io::Error),
+    /// Not a TIFF, or none of the JPEGs in it are ones we can decode
+    NoPreview,
+    Decode(image::ImageError),
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::NoPreview => write!(f, "there's no preview in it"),
+            Self::Decode(e) => write!(f, "couldn't decode its preview: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RawError {}
+
+impl From<io::Error> for RawError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl AssetLoader for RawPreviewLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = RawError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Image, RawError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let preview = decode_preview(&bytes)?;
+        Ok(Image::from_dynamic(
+            preview,
+            true,
+            RenderAssetUsages::default(),
+        ))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        RAW_EXTENSIONS
+    }
+}
+
+pub(crate) fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| RAW_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// The biggest preview in a RAW file, decoded
+pub(crate) fn decode_preview(bytes: &[u8]) -> Result<DynamicImage, RawError> {
+    let tiff = Tiff::new(bytes).ok_or(RawError::NoPreview)?;
+    let mut result = Err(RawError::NoPreview);
+    for range in previews(&tiff) {
+        let Some(jpeg) = bytes.get(range) else {
+            continue;
+        };
+        if frame_size(jpeg).is_none() {
+            continue;
+        }
+        result =
+            image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg).map_err(RawError::Decode);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+/// The size of the preview [`decode_preview`] would pick, reading only its header. `tiff` is the
+/// start of the file at `path`, as far as its IFDs go.
+pub(crate) fn preview_size(tiff: &Tiff, path: &Path) -> Option<(u32, u32)> {
+    // Enough to get past any metadata the preview carries to its frame header
+    const HEADER_LIMIT: usize = 128 << 10;
+
+    let mut file = File::open(path).ok()?;
+    previews(tiff).into_iter().find_map(|range| {
+        file.seek(SeekFrom::Start(range.start as u64)).ok()?;
+        let mut header = Vec::new();
+        (&mut file)
+            .take(range.len().min(HEADER_LIMIT) as u64)
+            .read_to_end(&mut header)
+            .ok()?;
+        frame_size(&header)
+    })
+}
+
+/// Where the JPEGs in a RAW file are, biggest first. They're pointed to from the IFDs in the chain
+/// from the first, or from their SubIFDs (NEF and DNG keep the big one in those), either as a
+/// JPEG thumbnail or as a single strip of JPEG-compressed image data.
+fn previews(tiff: &Tiff) -> Vec<Range<usize>> {
+    const COMPRESSION: u16 = 0x0103;
+    const STRIP_OFFSETS: u16 = 0x0111;
+    const STRIP_BYTE_COUNTS: u16 = 0x0117;
+    const SUB_IFDS: u16 = 0x014A;
+    const JPEG_OFFSET: u16 = 0x0201;
+    const JPEG_LENGTH: u16 = 0x0202;
+    /// Old-style and new-style JPEG compression
+    const JPEG: [u32; 2] = [6, 7];
+    /// Files that point their IFDs back at each other shouldn't keep us here forever
+    const MAX_IFDS: usize = 64;
+
+    let mut previews = vec![];
+    let mut pending: Vec<usize> = tiff.first_ifd().into_iter().collect();
+    let mut seen = HashSet::new();
+    while let Some(ifd) = pending.pop() {
+        if seen.len() >= MAX_IFDS || !seen.insert(ifd) {
+            continue;
+        }
+        pending.extend(tiff.next_ifd(ifd));
+        if let Some(entry) = tiff.entry(ifd, SUB_IFDS) {
+            pending.extend(tiff.longs(entry).into_iter().map(|offset| offset as usize));
+        }
+
+        let number = |tag| tiff.number(tiff.entry(ifd, tag)?);
+        let longs = |tag| {
+            tiff.entry(ifd, tag)
+                .map_or(vec![], |entry| tiff.longs(entry))
+        };
+        let found = match (number(JPEG_OFFSET), number(JPEG_LENGTH)) {
+            (Some(offset), Some(len)) => Some((offset, len)),
+            _ if number(COMPRESSION).is_some_and(|compression| JPEG.contains(&compression)) => {
+                match (&longs(STRIP_OFFSETS)[..], &longs(STRIP_BYTE_COUNTS)[..]) {
+                    (&[offset], &[len]) => Some((offset, len)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some((offset, len)) = found
+            && len > 0
+        {
+            previews.push(offset as usize..offset as usize + len as usize);
+        }
+    }
+    previews.sort_by_key(|range| Reverse(range.len()));
+    previews.dedup();
+    previews
+}
+
+/// The width and height in a JPEG's frame header, if it's a kind the decoder can do. CR2 and DNG
+/// files keep the sensor data itself as lossless JPEG, which it can't.
+fn frame_size(jpeg: &[u8]) -> Option<(u32, u32)> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut at = 2;
+    loop {
+        let &[0xFF, marker, high, low] = jpeg.get(at..at + 4)? else {
+            return None;
+        };
+        match marker {
+            // Baseline, extended and progressive
+            0xC0..=0xC2 => {
+                let frame = jpeg.get(at + 5..at + 9)?;
+                let height = u16::from_be_bytes([frame[0], frame[1]]);
+                let width = u16::from_be_bytes([frame[2], frame[3]]);
+                return Some((width.into(), height.into()));
+            }
+            // Any other frame, or the image data starting without one
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF | 0xD9 | 0xDA => return None,
+            _ => at += 2 + u16::from_be_bytes([high, low]) as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exif::tests::{Ifd, Value, tiff};
+
+    use std::io::{Cursor, Write};
+
+    const COMPRESSION: u16 = 0x0103;
+    const STRIP_OFFSETS: u16 = 0x0111;
+    const STRIP_BYTE_COUNTS: u16 = 0x0117;
+    const SUB_IFDS: u16 = 0x014A;
+    const JPEG_OFFSET: u16 = 0x0201;
+    const JPEG_LENGTH: u16 = 0x0202;
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = vec![];
+        DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+            .unwrap();
+        bytes
+    }
+
+    /// The start of a lossless JPEG, like the sensor data in a CR2, padded out to be the biggest
+    fn lossless_jpeg() -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xC3, 0, 11, 16, 0x0F, 0xA0, 0x17, 0x70];
+        bytes.extend([1, 1, 0x11, 0]);
+        bytes.resize(20_000, 0);
+        bytes
+    }
+
+    fn strip(compression: u16, jpeg: Vec<u8>) -> Ifd {
+        Ifd {
+            entries: vec![
+                (COMPRESSION, Value::Short(compression)),
+                (STRIP_BYTE_COUNTS, Value::Long(jpeg.len() as u32)),
+                (STRIP_OFFSETS, Value::Offset(jpeg)),
+            ],
+            next: None,
+        }
+    }
+
+    /// A small thumbnail in the first IFD, the sensor data and a bigger preview in its SubIFDs,
+    /// laid out the way NEFs and DNGs are
+    fn raw_file() -> Vec<u8> {
+        let thumbnail = jpeg(8, 6);
+        tiff(
+            false,
+            &[
+                Ifd {
+                    entries: vec![
+                        (SUB_IFDS, Value::Ifds(vec![1, 2])),
+                        (JPEG_LENGTH, Value::Long(thumbnail.len() as u32)),
+                        (JPEG_OFFSET, Value::Offset(thumbnail)),
+                    ],
+                    next: None,
+                },
+                strip(7, lossless_jpeg()),
+                strip(6, jpeg(32, 24)),
+            ],
+        )
+    }
+
+    #[test]
+    fn finds_every_preview_biggest_first() {
+        let raw = raw_file();
+        let previews = previews(&Tiff::new(&raw).unwrap());
+        let sizes: Vec<_> = previews
+            .iter()
+            .map(|range| frame_size(&raw[range.clone()]))
+            .collect();
+        assert_eq!(sizes, [None, Some((32, 24)), Some((8, 6))]);
+    }
+
+    #[test]
+    fn decodes_the_biggest_preview_it_can() {
+        let preview = decode_preview(&raw_file()).unwrap();
+        assert_eq!((preview.width(), preview.height()), (32, 24));
+    }
+
+    #[test]
+    fn sizes_the_preview_from_its_header() {
+        let raw = raw_file();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&raw).unwrap();
+        let size = preview_size(&Tiff::new(&raw).unwrap(), file.path());
+        assert_eq!(size, Some((32, 24)));
+    }
+
+    #[test]
+    fn frame_sizes_only_come_from_decodable_frames() {
+        let progressive = [
+            0xFF, 0xD8, 0xFF, 0xC2, 0, 11, 8, 0, 24, 0, 32, 1, 1, 0x11, 0,
+        ];
+        assert_eq!(frame_size(&progressive), Some((32, 24)));
+        assert_eq!(frame_size(&lossless_jpeg()), None);
+        assert_eq!(frame_size(&[0xFF, 0xD8, 0xFF, 0xDA, 0, 2]), None);
+        assert_eq!(frame_size(b"not a jpeg"), None);
+        // Cut off before the frame header
+        assert_eq!(frame_size(&jpeg(32, 24)[..30]), None);
+        assert_eq!(frame_size(&progressive[..8]), None);
+    }
+
+    #[test]
+    fn truncated_files_have_no_preview() {
+        let raw = raw_file();
+        // The header, three IFDs of three entries and the SubIFD list, but none of the JPEGs
+        let cut = 8 + 3 * (2 + 3 * 12 + 4) + 8;
+        assert!(matches!(
+            decode_preview(&raw[..cut]),
+            Err(RawError::NoPreview)
+        ));
+        assert!(matches!(decode_preview(b"II"), Err(RawError::NoPreview)));
+        assert!(matches!(
+            decode_preview(b"not a tiff"),
+            Err(RawError::NoPreview)
+        ));
+    }
+
+    #[test]
+    fn ifds_that_loop_back_are_only_read_once() {
+        let raw = tiff(
+            true,
+            &[Ifd {
+                entries: vec![(COMPRESSION, Value::Short(1))],
+                next: Some(0),
+            }],
+        );
+        assert!(previews(&Tiff::new(&raw).unwrap()).is_empty());
+    }
+}
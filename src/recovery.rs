@@ -0,0 +1,375 @@
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task, block_on, futures_lite::future},
+};
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    DirWatchingSet, ImageMarker, ImagesAdded, ImagesRemoved, RegridNeeded, ScanCompleted,
+    Selection, SpawnedImages,
+    duplicates::{PARTIAL_HASH_BYTES, hash_file},
+};
+
+/// What a quad's file looked like when it was spawned: a hash of its size and first bytes. Worked
+/// out in the background, so quads spawned a moment ago don't have one yet.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentHash(pub u64);
+
+impl ContentHash {
+    /// Hash the file at `path` the same way. Cheap enough to do for a handful of files, it only
+    /// reads the start of each.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let len = fs::metadata(path)?.len();
+        let mut hasher = DefaultHasher::new();
+        len.hash(&mut hasher);
+        hash_file(path, Some(PARTIAL_HASH_BYTES))?.hash(&mut hasher);
+        Ok(Self(hasher.finish()))
+    }
+}
+
+/// A quad whose file has gone from where it was. It's left where it is for
+/// [`MissingFile::GRACE_SCANS`] scans in case the file turns up somewhere else, renamed or moved,
+/// and is only despawned after that.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct MissingFile {
+    pub original_path: PathBuf,
+    pub content_hash: u64,
+    /// Scans still to go before giving up on it
+    pub scans_left: u32,
+}
+
+impl MissingFile {
+    pub const GRACE_SCANS: u32 = 2;
+
+    pub fn new(original_path: PathBuf, content_hash: ContentHash) -> Self {
+        Self {
+            original_path,
+            content_hash: content_hash.0,
+            scans_left: Self::GRACE_SCANS,
+        }
+    }
+}
+
+/// Quads waiting for their [`ContentHash`], and the background pass working some out. One pass
+/// runs at a time, quads that spawn meanwhile wait for the next.
+#[derive(Resource, Default)]
+struct ContentHashing {
+    queue: Vec<(Entity, PathBuf)>,
+    task: Option<Task<Vec<(Entity, ContentHash)>>>,
+}
+
+/// New images being hashed in the background to see whether any of them is a [`MissingFile`]
+/// turning up somewhere else. Their quads wait until that's known, so a match doesn't get a quad
+/// only for it to be despawned again. Only done while there's a quad whose file has gone.
+#[derive(Resource, Default)]
+pub(crate) struct Recovering {
+    /// Everything queued or being hashed
+    pub(crate) paths: HashSet<PathBuf>,
+    queue: Vec<PathBuf>,
+    /// Resolves to every path it was given, with its hash if it could be read
+    task: Option<Task<Vec<(PathBuf, Option<ContentHash>)>>>,
+}
+
+/// Follows images that are renamed or moved inside the watched directories. When a quad's file
+/// disappears and a new file with the same [`ContentHash`] shows up, the quad is pointed at the
+/// new path rather than being despawned and another spawned (and loaded) in its place.
+pub struct MissingFilePlugin;
+
+impl Plugin for MissingFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContentHashing>();
+        app.init_resource::<Recovering>();
+        app.add_systems(
+            Update,
+            (
+                expire_missing_files_system.run_if(on_event::<ScanCompleted>),
+                recover_missing_files_system,
+                hash_new_images,
+            )
+                .chain()
+                // Between the scan finding the new path and a quad being spawned for it
+                .after(DirWatchingSet::Scan)
+                .before(DirWatchingSet::SpawnQuads),
+        );
+        app.add_systems(
+            Update,
+            (queue_content_hashes, poll_content_hashes)
+                .chain()
+                .after(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+fn queue_content_hashes(
+    mut hashing: ResMut<ContentHashing>,
+    quads: Query<(Entity, &ImageMarker), Added<ImageMarker>>,
+) {
+    hashing.queue.extend(
+        quads
+            .iter()
+            .map(|(entity, marker)| (entity, marker.target.clone())),
+    );
+    if hashing.task.is_some() || hashing.queue.is_empty() {
+        return;
+    }
+    let queue = std::mem::take(&mut hashing.queue);
+    hashing.task = Some(IoTaskPool::get().spawn(async move {
+        queue
+            .into_iter()
+            .filter_map(|(entity, path)| match ContentHash::of(&path) {
+                Ok(hash) => Some((entity, hash)),
+                Err(e) => {
+                    log::debug!("Couldn't hash {path:?}: {e}");
+                    None
+                }
+            })
+            .collect()
+    }));
+}
+
+fn poll_content_hashes(mut commands: Commands, mut hashing: ResMut<ContentHashing>) {
+    let Some(task) = &mut hashing.task else {
+        return;
+    };
+    let Some(hashes) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    hashing.task = None;
+    for (entity, hash) in hashes {
+        // The quad might have gone while its file was being read
+        commands.entity(entity).try_insert(hash);
+    }
+}
+
+/// Give up on missing files that haven't turned up in time
+fn expire_missing_files_system(
+    mut commands: Commands,
+    mut scans: EventReader<ScanCompleted>,
+    mut quads: Query<(Entity, &mut MissingFile)>,
+) {
+    let count = scans.read().count() as u32;
+    for (entity, mut missing) in &mut quads {
+        missing.scans_left = missing.scans_left.saturating_sub(count);
+        if missing.scans_left == 0 {
+            log::info!(
+                "{:?} didn't turn up anywhere else, dropping it",
+                missing.original_path
+            );
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Start hashing the images a scan just found when there's a quad whose file has gone, which one
+/// of them might be
+fn hash_new_images(
+    mut added: EventReader<ImagesAdded>,
+    mut removed: EventReader<ImagesRemoved>,
+    mut recovering: ResMut<Recovering>,
+    spawned: Res<SpawnedImages>,
+    quads: Query<(&ImageMarker, Has<ContentHash>, Has<MissingFile>)>,
+) {
+    let removed: HashSet<&PathBuf> = removed
+        .read()
+        .flat_map(|ImagesRemoved(paths)| paths)
+        .collect();
+    let added: Vec<PathBuf> = added
+        .read()
+        .flat_map(|ImagesAdded(paths)| paths)
+        .filter(|path| !spawned.0.contains(*path))
+        .cloned()
+        .collect();
+    // Quads only become a `MissingFile` after this frame's spawning, so go by what the scan said
+    // went as well
+    let lost = quads
+        .iter()
+        .any(|(marker, hashed, missing)| missing || (hashed && removed.contains(&marker.target)));
+    if lost && !added.is_empty() {
+        recovering.paths.extend(added.iter().cloned());
+        recovering.queue.extend(added);
+    }
+
+    if recovering.task.is_some() || recovering.queue.is_empty() {
+        return;
+    }
+    let queue = std::mem::take(&mut recovering.queue);
+    recovering.task = Some(IoTaskPool::get().spawn(async move {
+        queue
+            .into_iter()
+            .map(|path| {
+                let hash = ContentHash::of(&path)
+                    .inspect_err(|e| log::debug!("Couldn't hash {path:?}: {e}"))
+                    .ok();
+                (path, hash)
+            })
+            .collect()
+    }));
+}
+
+/// Once the new images have been hashed, move each quad whose file has gone over to the new image
+/// with the same hash. The rest of the new images get quads of their own as usual.
+fn recover_missing_files_system(
+    mut commands: Commands,
+    mut recovering: ResMut<Recovering>,
+    mut spawned: ResMut<SpawnedImages>,
+    mut selection: ResMut<Selection>,
+    mut regrid: EventWriter<RegridNeeded>,
+    mut quads: Query<(Entity, &mut ImageMarker, &MissingFile)>,
+) {
+    // Only a finished pass is a change, the images it held back get spawned then
+    let Some(task) = &mut recovering.bypass_change_detection().task else {
+        return;
+    };
+    let Some(hashes) = block_on(future::poll_once(task)) else {
+        return;
+    };
+    recovering.task = None;
+    let mut found: HashMap<u64, PathBuf> = HashMap::new();
+    for (path, hash) in hashes {
+        recovering.paths.remove(&path);
+        if let Some(hash) = hash {
+            found.entry(hash.0).or_insert(path);
+        }
+    }
+
+    let mut moved = false;
+    for (entity, mut marker, missing) in &mut quads {
+        let Some(new_path) = found.remove(&missing.content_hash) else {
+            continue;
+        };
+        let old_path = std::mem::replace(&mut marker.target, new_path.clone());
+        log::info!("{old_path:?} moved to {new_path:?}");
+        spawned.0.remove(&old_path);
+        spawned.0.insert(new_path.clone());
+        if selection.contains(&old_path) {
+            for selected in &mut selection.0 {
+                if *selected == old_path {
+                    *selected = new_path.clone();
+                }
+            }
+        }
+        commands.entity(entity).remove::<MissingFile>();
+        moved = true;
+    }
+    // It's a different image in the layout's eyes
+    if moved {
+        regrid.write(RegridNeeded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    fn recovery_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, MissingFilePlugin))
+            .add_event::<ImagesAdded>()
+            .add_event::<ImagesRemoved>()
+            .add_event::<ScanCompleted>()
+            .add_event::<RegridNeeded>()
+            .init_resource::<SpawnedImages>()
+            .init_resource::<Selection>();
+        app
+    }
+
+    /// Run frames until nothing's waiting to be hashed
+    fn finish_hashing(app: &mut App) {
+        for _ in 0..500 {
+            app.update();
+            let recovering = app.world().resource::<Recovering>();
+            if recovering.paths.is_empty() && recovering.task.is_none() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("hashing never finished");
+    }
+
+    #[test]
+    fn missing_quad_moves_to_its_new_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let (old, new, other) = (
+            dir.path().join("old.jpg"),
+            dir.path().join("new.jpg"),
+            dir.path().join("other.jpg"),
+        );
+        fs::write(&new, b"the same bytes").unwrap();
+        fs::write(&other, b"different bytes").unwrap();
+        let hash = ContentHash::of(&new).unwrap();
+
+        let mut app = recovery_app();
+        let quad = app
+            .world_mut()
+            .spawn((
+                ImageMarker {
+                    target: old.clone(),
+                },
+                hash,
+                MissingFile::new(old.clone(), hash),
+            ))
+            .id();
+        app.world_mut()
+            .resource_mut::<SpawnedImages>()
+            .0
+            .insert(old.clone());
+        app.world_mut()
+            .resource_mut::<Selection>()
+            .select(old.clone());
+        app.world_mut()
+            .send_event(ImagesAdded(vec![new.clone(), other.clone()]));
+
+        app.update();
+        // Neither gets a quad until it's known which is which
+        let recovering = app.world().resource::<Recovering>();
+        assert!(recovering.paths.contains(&new) && recovering.paths.contains(&other));
+
+        finish_hashing(&mut app);
+        let world = app.world();
+        assert_eq!(world.get::<ImageMarker>(quad).unwrap().target, new);
+        assert!(world.get::<MissingFile>(quad).is_none());
+        let spawned = &world.resource::<SpawnedImages>().0;
+        assert!(spawned.contains(&new) && !spawned.contains(&old) && !spawned.contains(&other));
+        assert_eq!(world.resource::<Selection>().paths(), [new]);
+    }
+
+    #[test]
+    fn nothing_is_hashed_without_a_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.jpg");
+        fs::write(&path, b"bytes").unwrap();
+
+        let mut app = recovery_app();
+        app.world_mut().send_event(ImagesAdded(vec![path]));
+        app.update();
+        let recovering = app.world().resource::<Recovering>();
+        assert!(recovering.paths.is_empty() && recovering.task.is_none());
+    }
+
+    #[test]
+    fn quad_whose_file_just_went_holds_back_new_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let (old, new) = (dir.path().join("old.jpg"), dir.path().join("new.jpg"));
+        fs::write(&new, b"bytes").unwrap();
+
+        let mut app = recovery_app();
+        app.world_mut().spawn((
+            ImageMarker {
+                target: old.clone(),
+            },
+            ContentHash(1),
+        ));
+        app.world_mut().send_event(ImagesRemoved(vec![old]));
+        app.world_mut().send_event(ImagesAdded(vec![new.clone()]));
+        app.update();
+        assert!(app.world().resource::<Recovering>().paths.contains(&new));
+        finish_hashing(&mut app);
+    }
+}
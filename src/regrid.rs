@@ -0,0 +1,167 @@
+use bevy::{prelude::*, window::RequestRedraw};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{
+    CurrentPage, DirWatchingSet, DirectoryGroups, ExifOrientation, GridConfig, GridPlacement,
+    ImageMarker, JustAdded, JustifiedLayout, ManualRotation, Timeline,
+};
+
+/// Where a quad belongs in the grid, and where it's got to on the way there. Quads glide to a
+/// new `target` whenever the grid is worked out again.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct GridPosition {
+    /// Position of the image on the [`CurrentPage`], which is what the layout goes by
+    pub index: usize,
+    /// Where the layout put it in the flat grid, before it went onto the plane (see
+    /// [`crate::GridPlacement::cell`]). Rows share a `y`, first row at the top.
+    pub cell: Vec2,
+    pub target: Vec3,
+    pub current: Vec3,
+    /// How much bigger than a plain quad it's drawn, which is 1 except in the
+    /// [`crate::GridLayout::Justified`] rows
+    pub scale: f32,
+}
+
+impl GridPosition {
+    /// A quad that's already where it should be
+    pub fn at(index: usize, cell: Vec2, position: Vec3) -> Self {
+        Self {
+            index,
+            cell,
+            target: position,
+            current: position,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Ask for every quad's place in the grid to be worked out again. Sent automatically when the
+/// images or the [`GridConfig`] change.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct RegridNeeded;
+
+pub struct RegridPlugin;
+
+impl Plugin for RegridPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RegridNeeded>();
+        app.add_systems(
+            Update,
+            (
+                request_regrid.run_if(
+                    resource_changed::<CurrentPage>
+                        .or(resource_changed::<GridConfig>)
+                        .or(resource_changed::<Timeline>)
+                        .or(resource_changed::<JustifiedLayout>)
+                        .or(resource_changed::<DirectoryGroups>),
+                ),
+                regrid_system,
+                animate_grid_positions,
+            )
+                .chain()
+                // So quads spawned this frame are in the new layout too
+                .after(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+fn request_regrid(mut regrid: EventWriter<RegridNeeded>) {
+    regrid.write(RegridNeeded);
+}
+
+/// Lay the grid out again for the current image count, giving each quad its new target
+fn regrid_system(
+    mut requests: EventReader<RegridNeeded>,
+    page: Res<CurrentPage>,
+    placement: GridPlacement,
+    mut quads: Query<
+        (
+            &ImageMarker,
+            Option<&ManualRotation>,
+            Option<&ExifOrientation>,
+            &mut GridPosition,
+            &mut Transform,
+            Has<JustAdded>,
+        ),
+        Without<Camera3d>,
+    >,
+) {
+    if requests.read().last().is_none() {
+        return;
+    }
+
+    let count = page.images().len();
+    let indices: HashMap<&Path, usize> = page
+        .images()
+        .iter()
+        .enumerate()
+        .map(|(index, path)| (path.as_path(), index))
+        .collect();
+    for (marker, manual_rotation, orientation, mut position, mut transform, growing) in &mut quads {
+        let Some(&index) = indices.get(marker.target.as_path()) else {
+            continue;
+        };
+        let (cell, scale) = placement.cell(index, &marker.target, count);
+        let target = placement.place(cell).with_scale(Vec3::splat(scale));
+        if position.index != index || position.cell != cell || position.target != target.translation
+        {
+            position.index = index;
+            position.cell = cell;
+            position.target = target.translation;
+        }
+        // Only the position glides, a new plane turns (and a new row height sizes) the quads
+        // straight away. Any turn its EXIF data asks for goes on top, then any the user gave it.
+        let rotation = target.rotation
+            * orientation.copied().unwrap_or_default().quat()
+            * manual_rotation.copied().unwrap_or_default().quat();
+        if transform.rotation != rotation {
+            transform.rotation = rotation;
+        }
+        if position.scale != target.scale.x {
+            position.scale = target.scale.x;
+        }
+        // Quads still growing in get there by themselves
+        if !growing && transform.scale != target.scale {
+            transform.scale = target.scale;
+        }
+    }
+}
+
+/// Ease quads towards their targets. Each frame closes the same fraction of the remaining gap,
+/// which is a critically damped spring without the velocity, settling in about half a second.
+fn animate_grid_positions(
+    time: Res<Time<Real>>,
+    mut redraw: EventWriter<RequestRedraw>,
+    mut quads: Query<(&mut GridPosition, &mut Transform)>,
+) {
+    // Leaves about 1% of the distance after SETTLE_TIME
+    const SETTLE_TIME: f32 = 0.5;
+    const RATE: f32 = 4.6 / SETTLE_TIME;
+
+    // The keypress or resize that starts a regrid can follow a long idle wait, and easing by all
+    // of it would snap the quads straight to their targets (see `animate_just_added`)
+    let delta = time.delta().min(Duration::from_secs_f32(1.0 / 30.0));
+    let keep = (-RATE * delta.as_secs_f32()).exp();
+
+    let mut moving = false;
+    for (mut position, mut transform) in &mut quads {
+        if position.current == position.target {
+            continue;
+        }
+        let target = position.target;
+        let current = target + (position.current - target) * keep;
+        position.current = if current.distance_squared(target) < 1e-6 {
+            target
+        } else {
+            moving = true;
+            current
+        };
+        transform.translation = position.current;
+    }
+    if moving {
+        redraw.write(RequestRedraw);
+    }
+}
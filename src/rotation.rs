@@ -0,0 +1,361 @@
+use bevy::prelude::*;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    Action, AppState, AtlasSlot, History, ImageDisplayMaterial, ImageMarker, PhotoMetadata,
+    ScanErrors, Selection, StatusBar, ViewMode, text_input_inactive,
+};
+
+/// How far the user has turned an image clockwise, in degrees, on top of however the layout
+/// faces the quad. Always a multiple of 90 in `0..360`. Kept in a `<file name>.gamirot` sidecar
+/// next to the image so it sticks between launches, and read back whenever the image's quad is
+/// spawned. Any turn the image's own metadata asks for goes underneath this one.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ManualRotation(pub i32);
+
+impl ManualRotation {
+    /// This rotation turned a further `quarter_turns` clockwise (negative for counterclockwise)
+    pub fn rotated(self, quarter_turns: i32) -> Self {
+        Self((self.0 + quarter_turns * 90).rem_euclid(360))
+    }
+
+    /// The turn in the quad's own plane, to go after the layout's rotation. The quad faces +Z,
+    /// so clockwise as seen from the front is a negative turn about Z.
+    pub fn quat(self) -> Quat {
+        Quat::from_rotation_z(-(self.0 as f32).to_radians())
+    }
+
+    pub fn sidecar_path(image: &Path) -> PathBuf {
+        let mut name = image.file_name().unwrap_or_default().to_os_string();
+        name.push(".gamirot");
+        image.with_file_name(name)
+    }
+
+    /// Read an image's sidecar, `Ok(None)` if it hasn't got one
+    pub fn load(image: &Path) -> io::Result<Option<Self>> {
+        let text = match fs::read_to_string(Self::sidecar_path(image)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match text.trim().parse::<i32>() {
+            Ok(degrees) if degrees % 90 == 0 => Ok(Some(Self(degrees).rotated(0))),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} isn't a multiple of 90 degrees", text.trim()),
+            )),
+        }
+    }
+
+    /// Write an image's sidecar, or remove it once the image is back the right way up
+    pub fn save(self, image: &Path) -> io::Result<()> {
+        let sidecar = Self::sidecar_path(image);
+        if self.0 == 0 {
+            return match fs::remove_file(sidecar) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        fs::write(sidecar, format!("{}\n", self.0))
+    }
+}
+
+/// How an image's EXIF data says to turn it the right way up, since cameras save the pixels
+/// however the sensor was held. Applied to each quad once its [`PhotoMetadata`] has been read:
+/// the turn to its `Transform`, underneath any [`ManualRotation`], and the mirroring to its
+/// material (or sprite).
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExifOrientation {
+    /// Quarter turns clockwise, in `0..4`, once it's been mirrored
+    pub quarter_turns: i32,
+    /// Flipped left to right
+    pub mirrored: bool,
+}
+
+impl ExifOrientation {
+    /// From the value of the Orientation tag, 1 to 8. Anything else leaves the image alone.
+    pub fn from_tag(value: u32) -> Self {
+        let (quarter_turns, mirrored) = match value {
+            2 => (0, true),
+            3 => (2, false),
+            4 => (2, true),
+            5 => (3, true),
+            6 => (1, false),
+            7 => (1, true),
+            8 => (3, false),
+            _ => (0, false),
+        };
+        Self {
+            quarter_turns,
+            mirrored,
+        }
+    }
+
+    /// The turn in the quad's own plane, like [`ManualRotation::quat`]
+    pub fn quat(self) -> Quat {
+        ManualRotation(self.quarter_turns * 90).quat()
+    }
+}
+
+pub struct RotationPlugin;
+
+impl Plugin for RotationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                rotate_hotkey_system.run_if(in_state(AppState::Running)),
+                // The grid has the brackets for jumping through the timeline
+                slideshow_rotate_hotkey_system.run_if(in_state(ViewMode::Slideshow)),
+            )
+                .run_if(text_input_inactive),
+        );
+        app.add_systems(Update, apply_exif_orientation);
+    }
+}
+
+/// Turn quads the way their image's EXIF data says once it's been read, or read again after the
+/// image was rewritten
+fn apply_exif_orientation(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut quads: Query<
+        (
+            Entity,
+            &ImageMarker,
+            &PhotoMetadata,
+            Option<&ExifOrientation>,
+            &mut Transform,
+            Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+            Option<&mut Sprite>,
+            Has<AtlasSlot>,
+        ),
+        Changed<PhotoMetadata>,
+    >,
+) {
+    for (entity, marker, metadata, applied, mut transform, material, sprite, packed) in &mut quads {
+        let old = applied.copied().unwrap_or_default();
+        let new = metadata.orientation;
+        if old == new {
+            continue;
+        }
+        // Both turns are about the quad's own Z, so the manual one can stay where it is
+        transform.rotation = transform.rotation * old.quat().inverse() * new.quat();
+        if old.mirrored != new.mirrored {
+            if packed {
+                // Shares its material with the rest of its atlas page, and was packed the old
+                // way round
+                log::debug!(
+                    "{:?} was packed before its orientation changed",
+                    marker.target
+                );
+            } else if let Some(material) =
+                material.and_then(|material| materials.get_mut(&material.0))
+            {
+                material.mirrored = new.mirrored as u32;
+            }
+            if let Some(mut sprite) = sprite {
+                sprite.flip_x = new.mirrored;
+            }
+        }
+        commands.entity(entity).insert(new);
+    }
+}
+
+/// R turns the selected images 90° clockwise, Shift+R counterclockwise, and Ctrl+R puts them
+/// back the way they were
+fn rotate_hotkey_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selection>,
+) {
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if alt || !keys.just_pressed(KeyCode::KeyR) || selected.is_empty() {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        commands.run_system_cached_with(reset_rotation, selected.paths().to_vec());
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let quarter_turns = if shift { -1 } else { 1 };
+    commands.run_system_cached_with(rotate, (selected.paths().to_vec(), quarter_turns));
+}
+
+/// In the slideshow ] turns the image being shown clockwise and [ counterclockwise, like R and
+/// Shift+R
+fn slideshow_rotate_hotkey_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selection>,
+) {
+    let quarter_turns = if keys.just_pressed(KeyCode::BracketRight) {
+        1
+    } else if keys.just_pressed(KeyCode::BracketLeft) {
+        -1
+    } else {
+        return;
+    };
+    if let Some(path) = selected.path() {
+        commands.run_system_cached_with(rotate, (vec![path.clone()], quarter_turns));
+    }
+}
+
+/// Undo any turn given to the images, and remember how they were in the [`History`]
+fn reset_rotation(In(paths): In<Vec<PathBuf>>, world: &mut World) {
+    let mut quads = world.query::<(&ImageMarker, &ManualRotation)>();
+    let previous: Vec<(PathBuf, ManualRotation)> = quads
+        .iter(world)
+        .filter(|(marker, rotation)| rotation.0 != 0 && paths.contains(&marker.target))
+        .map(|(marker, rotation)| (marker.target.clone(), *rotation))
+        .collect();
+    if previous.is_empty() {
+        world
+            .resource_mut::<StatusBar>()
+            .set("Those images aren't rotated");
+        return;
+    }
+    let zero: Vec<_> = previous
+        .iter()
+        .map(|(path, _)| (path.clone(), ManualRotation::default()))
+        .collect();
+    let Ok(reset) = world.run_system_cached_with(set_rotations, zero) else {
+        return;
+    };
+    if reset.is_empty() {
+        return;
+    }
+    let message = match reset.len() {
+        1 => "Reset the rotation of 1 image".to_string(),
+        count => format!("Reset the rotation of {count} images"),
+    };
+    world.resource_mut::<StatusBar>().set(message);
+    let previous = previous
+        .into_iter()
+        .filter(|(path, _)| reset.contains(path))
+        .collect();
+    world
+        .resource_mut::<History>()
+        .record(Action::ResetRotation { previous });
+}
+
+/// Turn the images, and remember the ones that turned in the [`History`]
+fn rotate(In((paths, quarter_turns)): In<(Vec<PathBuf>, i32)>, world: &mut World) {
+    let Ok(rotated) = world.run_system_cached_with(rotate_images, (paths, quarter_turns)) else {
+        return;
+    };
+    if rotated.is_empty() {
+        return;
+    }
+    let direction = if quarter_turns < 0 {
+        "counterclockwise"
+    } else {
+        "clockwise"
+    };
+    let message = match rotated.len() {
+        1 => format!("Rotated 1 image {direction}"),
+        count => format!("Rotated {count} images {direction}"),
+    };
+    world.resource_mut::<StatusBar>().set(message);
+    world.resource_mut::<History>().record(Action::Rotate {
+        paths: rotated,
+        quarter_turns,
+    });
+}
+
+/// Actually turn the images: write each one's sidecar, then its quad. Failures go to the
+/// [`ScanErrors`] banner and leave that quad alone. Returns the ones that turned.
+pub(crate) fn rotate_images(
+    In((paths, quarter_turns)): In<(Vec<PathBuf>, i32)>,
+    commands: Commands,
+    errors: ResMut<ScanErrors>,
+    quads: Query<(
+        Entity,
+        &ImageMarker,
+        Option<&mut ManualRotation>,
+        &mut Transform,
+    )>,
+) -> Vec<PathBuf> {
+    turn_images(
+        paths,
+        |_, old| old.rotated(quarter_turns),
+        commands,
+        errors,
+        quads,
+    )
+}
+
+/// [`rotate_images`], but to a given rotation rather than by one
+pub(crate) fn set_rotations(
+    In(rotations): In<Vec<(PathBuf, ManualRotation)>>,
+    commands: Commands,
+    errors: ResMut<ScanErrors>,
+    quads: Query<(
+        Entity,
+        &ImageMarker,
+        Option<&mut ManualRotation>,
+        &mut Transform,
+    )>,
+) -> Vec<PathBuf> {
+    let paths = rotations.iter().map(|(path, _)| path.clone()).collect();
+    turn_images(
+        paths,
+        |path, old| {
+            rotations
+                .iter()
+                .find(|(rotated, _)| rotated == path)
+                .map_or(old, |(_, rotation)| *rotation)
+        },
+        commands,
+        errors,
+        quads,
+    )
+}
+
+fn turn_images(
+    paths: Vec<PathBuf>,
+    new_rotation: impl Fn(&Path, ManualRotation) -> ManualRotation,
+    mut commands: Commands,
+    mut errors: ResMut<ScanErrors>,
+    mut quads: Query<(
+        Entity,
+        &ImageMarker,
+        Option<&mut ManualRotation>,
+        &mut Transform,
+    )>,
+) -> Vec<PathBuf> {
+    let mut rotated = vec![];
+    for path in paths {
+        let quad = quads
+            .iter_mut()
+            .find(|(_, marker, ..)| marker.target == path);
+        let Some((entity, _, current, mut transform)) = quad else {
+            continue;
+        };
+
+        let old = current.as_deref().copied().unwrap_or_default();
+        let new = new_rotation(&path, old);
+        if let Err(e) = new.save(&path) {
+            log::warn!("Couldn't save the rotation of {path:?}: {e}");
+            errors.push(format!(
+                "Couldn't save the rotation of {}: {e}",
+                path.display()
+            ));
+            continue;
+        }
+
+        // Swap the old turn for the new one, leaving the layout's part alone
+        transform.rotation = transform.rotation * old.quat().inverse() * new.quat();
+        match current {
+            Some(mut current) => *current = new,
+            None => {
+                commands.entity(entity).insert(new);
+            }
+        }
+        rotated.push(path);
+    }
+    rotated
+}
@@ -0,0 +1,259 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{
+    DimensionsProbed, DirWatchingSet, ImageMarker, ProbedDimensions, ScanCompleted, ScanConfig,
+    WatchedDirs,
+};
+
+/// On-disk copy of the last scan, so a big archive shows up straight away on the next launch
+/// instead of after a full directory walk. Bump [`ScanCache::VERSION`] whenever the format
+/// changes, older files are then ignored (and overwritten after the next scan).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ScanCache {
+    pub version: u32,
+    /// What was being watched when this was written, a cache for other directories is useless
+    pub dirs: Vec<PathBuf>,
+    pub images: Vec<CachedImage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CachedImage {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    /// Width and height as of `modified`, if they've been read, see [`crate::DimensionsPlugin`]
+    #[serde(default)]
+    pub dimensions: Option<(u32, u32)>,
+}
+
+impl CachedImage {
+    fn stat(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: fs::metadata(path).and_then(|meta| meta.modified()).ok(),
+            dimensions: None,
+        }
+    }
+}
+
+impl ScanCache {
+    pub const VERSION: u32 = 1;
+
+    /// Snapshot what `watched_dirs` currently holds, stat'ing every image for its mtime
+    pub fn from_watched_dirs(watched_dirs: &WatchedDirs) -> Self {
+        Self {
+            version: Self::VERSION,
+            dirs: watched_dirs
+                .watched_dirs()
+                .iter()
+                .map(|dir| dir.path.clone())
+                .collect(),
+            images: watched_dirs
+                .images()
+                .iter()
+                .map(|path| {
+                    let mut image = CachedImage::stat(path);
+                    // Only if they were read from the file as it is now
+                    image.dimensions = watched_dirs
+                        .dimensions
+                        .get(path)
+                        .filter(|probed| probed.modified == image.modified)
+                        .and_then(|probed| probed.size);
+                    image
+                })
+                .collect(),
+        }
+    }
+
+    /// `Ok(None)` if there's no cache yet, or it's from a different version of the format
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let cache: Self = serde_json::from_str(&json)?;
+        Ok((cache.version == Self::VERSION).then_some(cache))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+/// How the first real scan differed from what the cache said was there
+#[derive(Event, Debug, Clone, Default)]
+pub struct ScanCacheReconciled {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    /// Still there, but with a different mtime than was cached
+    pub modified: Vec<PathBuf>,
+}
+
+#[derive(Resource, Debug, Default)]
+pub(crate) struct ScanCacheState {
+    /// What the cache held, until the first scan has been reconciled against it
+    seeded: Option<Vec<CachedImage>>,
+    /// Whether this run started from a cache at all
+    pub(crate) loaded: bool,
+    /// The image list as last written, to skip rewriting an unchanged cache every scan
+    written: Vec<PathBuf>,
+}
+
+impl ScanCacheState {
+    /// True until the first scan after seeding from the cache has been reconciled
+    pub(crate) fn awaiting_reconcile(&self) -> bool {
+        self.seeded.is_some()
+    }
+
+    /// Start `watched_dirs` off with `images`, in order, reconciling them against the first
+    /// scan like a cache that was read from disk
+    pub(crate) fn seed(&mut self, watched_dirs: &mut WatchedDirs, images: Vec<CachedImage>) {
+        watched_dirs.imgs = images.iter().map(|img| img.path.clone()).collect();
+        watched_dirs.add_dimensions(images.iter().filter_map(|img| {
+            let probed = ProbedDimensions {
+                modified: img.modified,
+                size: Some(img.dimensions?),
+            };
+            Some((img.path.clone(), probed))
+        }));
+        self.written.clone_from(&watched_dirs.imgs);
+        self.seeded = Some(images);
+        self.loaded = true;
+    }
+}
+
+pub struct ScanCachePlugin;
+
+impl Plugin for ScanCachePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScanCacheState>();
+        app.add_event::<ScanCacheReconciled>();
+        app.add_systems(Startup, load_scan_cache);
+        app.add_systems(
+            PreUpdate,
+            (reconcile_scan_cache, write_scan_cache)
+                .chain()
+                .in_set(DirWatchingSet::DespawnQuads),
+        );
+    }
+}
+
+/// Seed [`WatchedDirs`] from the cache so quads can spawn before the first scan
+pub(crate) fn load_scan_cache(
+    config: Res<ScanConfig>,
+    mut watched_dirs: ResMut<WatchedDirs>,
+    mut state: ResMut<ScanCacheState>,
+) {
+    let Some(path) = &config.cache_path else {
+        return;
+    };
+
+    let cache = match ScanCache::load(path) {
+        Ok(Some(cache))
+            if cache
+                .dirs
+                .iter()
+                .eq(watched_dirs.watched_dirs().iter().map(|dir| &dir.path)) =>
+        {
+            cache
+        }
+        Ok(_) => return,
+        Err(e) => {
+            // Not worth bothering the user over, the scan will just take the slow path
+            log::warn!("Ignoring unreadable scan cache {path:?}: {e}");
+            return;
+        }
+    };
+
+    log::debug!("Seeded {} images from {path:?}", cache.images.len());
+    state.seed(&mut watched_dirs, cache.images);
+}
+
+/// Once the first real scan is in, work out what changed since the cache was written and drop
+/// quads for files that are gone
+fn reconcile_scan_cache(
+    mut commands: Commands,
+    mut scans: EventReader<ScanCompleted>,
+    mut state: ResMut<ScanCacheState>,
+    watched_dirs: Res<WatchedDirs>,
+    quads: Query<(Entity, &ImageMarker)>,
+    mut reconciled: EventWriter<ScanCacheReconciled>,
+) {
+    if scans.read().count() == 0 || !state.awaiting_reconcile() {
+        return;
+    }
+    let Some(seeded) = state.seeded.take() else {
+        return;
+    };
+
+    let cached: HashMap<&Path, Option<SystemTime>> = seeded
+        .iter()
+        .map(|img| (img.path.as_path(), img.modified))
+        .collect();
+    let mut diff = ScanCacheReconciled::default();
+    for path in watched_dirs.images() {
+        match cached.get(path.as_path()) {
+            None => diff.added.push(path.clone()),
+            Some(modified) if *modified != CachedImage::stat(path).modified => {
+                diff.modified.push(path.clone())
+            }
+            Some(_) => {}
+        }
+    }
+    let current: HashSet<&Path> = watched_dirs.images().iter().map(PathBuf::as_path).collect();
+    diff.removed = seeded
+        .into_iter()
+        .map(|img| img.path)
+        .filter(|path| !current.contains(path.as_path()))
+        .collect();
+
+    let removed: HashSet<&Path> = diff.removed.iter().map(PathBuf::as_path).collect();
+    for (entity, marker) in &quads {
+        if removed.contains(marker.target.as_path()) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    log::debug!(
+        "Scan cache reconciled: {} added, {} removed, {} modified",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.modified.len()
+    );
+    reconciled.write(diff);
+}
+
+/// Write the cache after any scan that changed the image list, and once all the images' sizes
+/// have been read
+fn write_scan_cache(
+    mut scans: EventReader<ScanCompleted>,
+    mut probed: EventReader<DimensionsProbed>,
+    config: Res<ScanConfig>,
+    watched_dirs: Res<WatchedDirs>,
+    mut state: ResMut<ScanCacheState>,
+) {
+    let Some(path) = &config.cache_path else {
+        return;
+    };
+    let scanned = scans.read().count() > 0 && state.written != watched_dirs.imgs;
+    let all_probed = probed.read().any(|probed| probed.remaining == 0);
+    if !scanned && !all_probed {
+        return;
+    }
+
+    if let Err(e) = ScanCache::from_watched_dirs(&watched_dirs).save(path) {
+        log::warn!("Couldn't write scan cache {path:?}: {e}");
+        return;
+    }
+    state.written.clone_from(&watched_dirs.imgs);
+}
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+
+use crate::Themed;
+
+/// Problems worth showing the user rather than just logging: directories that couldn't be read,
+/// files that couldn't be deleted, and so on. Shown in a banner along the top of the window until
+/// dismissed.
+#[derive(Resource, Default, Debug)]
+pub struct ScanErrors {
+    messages: Vec<String>,
+}
+
+impl ScanErrors {
+    /// Add a message, unless the same one is already showing (scans repeat their errors every
+    /// pass)
+    pub fn push(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        if !self.messages.contains(&message) {
+            self.messages.push(message);
+        }
+    }
+
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
+
+#[derive(Component)]
+struct ScanErrorsBanner;
+
+#[derive(Component)]
+struct ScanErrorsText;
+
+#[derive(Component)]
+struct DismissScanErrorsButton;
+
+pub struct ScanErrorsPlugin;
+
+impl Plugin for ScanErrorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScanErrors>();
+        app.add_systems(Startup, spawn_scan_errors_banner);
+        app.add_systems(
+            Update,
+            (
+                dismiss_scan_errors_system,
+                update_scan_errors_banner.run_if(resource_changed::<ScanErrors>),
+            )
+                .chain(),
+        );
+    }
+}
+
+fn spawn_scan_errors_banner(mut commands: Commands) {
+    commands.spawn((
+        ScanErrorsBanner,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(0.0),
+            left: Val::Px(0.0),
+            right: Val::Px(0.0),
+            flex_direction: FlexDirection::Row,
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::Center,
+            padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+            display: Display::None,
+            ..default()
+        },
+        Themed::Panel,
+        GlobalZIndex(40),
+        children![
+            (
+                ScanErrorsText,
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                Themed::Text,
+            ),
+            (
+                Button,
+                DismissScanErrorsButton,
+                Node {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                children![(Text::new("Dismiss"), Themed::Text)],
+            ),
+        ],
+    ));
+}
+
+fn update_scan_errors_banner(
+    errors: Res<ScanErrors>,
+    mut banner: Single<&mut Node, With<ScanErrorsBanner>>,
+    mut text: Single<&mut Text, With<ScanErrorsText>>,
+) {
+    let Some(latest) = errors.messages().last() else {
+        banner.display = Display::None;
+        return;
+    };
+
+    banner.display = Display::Flex;
+    text.0 = match errors.messages().len() {
+        1 => latest.clone(),
+        n => format!("{latest} (and {} more)", n - 1),
+    };
+}
+
+fn dismiss_scan_errors_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<DismissScanErrorsButton>)>,
+    mut errors: ResMut<ScanErrors>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            errors.clear();
+        }
+    }
+}
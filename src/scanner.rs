@@ -0,0 +1,930 @@
+//! Directory walking, kept apart from Bevy so it can be reused and pointed at a fake filesystem.
+//! [`scan_dirs`] does a whole scan the way the app does, for using it without one.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::time::SystemTime;
+
+use crate::{SortOrder, normalize_path};
+
+/// What a [`Scanner`] needs to know about a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub is_dir: bool,
+    /// The path itself is a symlink (the other fields describe what it points at)
+    pub is_symlink: bool,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// The handful of filesystem operations scanning uses
+pub trait FileSystem {
+    /// Full paths of everything directly inside `path`
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|meta| meta.is_dir)
+    }
+
+    /// Used to spot symlink loops, filesystems without links can leave this as is
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    /// When the image at `path` was taken, from its EXIF data. Filesystems with no way to look
+    /// inside files can leave this as is.
+    fn capture_date(&self, _path: &Path) -> Option<SystemTime> {
+        None
+    }
+
+    /// Width and height of the image at `path`, from its header
+    fn dimensions(&self, _path: &Path) -> Option<(u32, u32)> {
+        None
+    }
+}
+
+/// The real disk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let is_symlink = fs::symlink_metadata(path)?.file_type().is_symlink();
+        let meta = fs::metadata(path)?;
+        Ok(FileMetadata {
+            is_dir: meta.is_dir(),
+            is_symlink,
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn capture_date(&self, path: &Path) -> Option<SystemTime> {
+        crate::exif::capture_date(path)
+    }
+
+    fn dimensions(&self, path: &Path) -> Option<(u32, u32)> {
+        crate::probe_dimensions(path)
+    }
+}
+
+/// An in-memory filesystem, for exercising a [`Scanner`] without touching the disk
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+    entries: BTreeMap<PathBuf, MemoryEntry>,
+}
+
+#[derive(Debug, Clone)]
+enum MemoryEntry {
+    Dir {
+        readable: bool,
+    },
+    File {
+        len: u64,
+        modified: Option<SystemTime>,
+    },
+    Symlink {
+        target: PathBuf,
+    },
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a directory, and any missing parents
+    pub fn add_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        let path = path.into();
+        for ancestor in path.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+            self.entries
+                .entry(ancestor.to_path_buf())
+                .or_insert(MemoryEntry::Dir { readable: true });
+        }
+        self
+    }
+
+    /// Add a file of `len` bytes, and any missing parent directories
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, len: u64) -> &mut Self {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            self.add_dir(parent);
+        }
+        self.entries.insert(
+            path,
+            MemoryEntry::File {
+                len,
+                modified: None,
+            },
+        );
+        self
+    }
+
+    /// Make listing a directory fail with permission denied
+    pub fn make_unreadable(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.entries
+            .insert(path.into(), MemoryEntry::Dir { readable: false });
+        self
+    }
+
+    /// Add a symlink at `path` pointing at the full path `target`, and any missing parent
+    /// directories. The target doesn't have to exist.
+    pub fn add_symlink(
+        &mut self,
+        path: impl Into<PathBuf>,
+        target: impl Into<PathBuf>,
+    ) -> &mut Self {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            self.add_dir(parent);
+        }
+        self.entries.insert(
+            path,
+            MemoryEntry::Symlink {
+                target: target.into(),
+            },
+        );
+        self
+    }
+
+    /// `path` with every symlink in it followed, `None` if they go round in circles
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let mut path = path.to_path_buf();
+        // Same as Linux's limit on links followed in one lookup
+        for _ in 0..40 {
+            let link = path
+                .ancestors()
+                .find_map(|ancestor| match self.entries.get(ancestor) {
+                    Some(MemoryEntry::Symlink { target }) => Some((ancestor, target)),
+                    _ => None,
+                });
+            let Some((link, target)) = link else {
+                return Some(path);
+            };
+            path = target.join(path.strip_prefix(link).ok()?);
+        }
+        None
+    }
+
+    fn entry(&self, path: &Path) -> io::Result<(PathBuf, &MemoryEntry)> {
+        let resolved = self
+            .resolve(path)
+            .ok_or_else(|| io::Error::other("too many levels of symbolic links"))?;
+        match self.entries.get(&resolved) {
+            Some(entry) => Ok((resolved, entry)),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        match self.entry(path)? {
+            // Listed under `path`, not wherever the links led
+            (dir, MemoryEntry::Dir { readable: true }) => Ok(self
+                .entries
+                .keys()
+                .filter(|entry| entry.parent() == Some(dir.as_path()))
+                .filter_map(|entry| Some(path.join(entry.file_name()?)))
+                .collect()),
+            (_, MemoryEntry::Dir { readable: false }) => {
+                Err(io::Error::from(io::ErrorKind::PermissionDenied))
+            }
+            (_, MemoryEntry::File { .. }) => Err(io::Error::other("not a directory")),
+            // `entry` has followed every link
+            (_, MemoryEntry::Symlink { .. }) => unreachable!(),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        // The path's own last component isn't followed for this one
+        let unfollowed = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => self.resolve(parent).map(|parent| parent.join(name)),
+            _ => Some(path.to_path_buf()),
+        };
+        let is_symlink = unfollowed.is_some_and(|path| {
+            matches!(self.entries.get(&path), Some(MemoryEntry::Symlink { .. }))
+        });
+        match self.entry(path)?.1 {
+            MemoryEntry::Dir { .. } => Ok(FileMetadata {
+                is_dir: true,
+                is_symlink,
+                len: 0,
+                modified: None,
+            }),
+            MemoryEntry::File { len, modified } => Ok(FileMetadata {
+                is_dir: false,
+                is_symlink,
+                len: *len,
+                modified: *modified,
+            }),
+            MemoryEntry::Symlink { .. } => unreachable!(),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.entry(path).map(|(resolved, _)| resolved)
+    }
+}
+
+/// An image found by a scan, with the metadata we already had to fetch to find it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageEntry {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// When the photo was taken, if [`ScanOptions::capture_dates`] is on and the file says
+    pub taken: Option<SystemTime>,
+    /// Width and height in pixels, if [`ScanOptions::probe_dimensions`] is on and the header
+    /// could be read
+    pub dimensions: Option<(u32, u32)>,
+}
+
+/// An image's capture date as read from a file with this modification time, `taken` being `None`
+/// if it didn't have one. Handed to [`Scanner::with_known_dates`] so unchanged files aren't read
+/// again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureDate {
+    pub modified: Option<SystemTime>,
+    pub taken: Option<SystemTime>,
+}
+
+/// What to pick up while walking
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Lowercase extensions (no dot) that count as images, matched case-insensitively
+    pub extensions: Vec<String>,
+    /// File and directory names to skip. A leading or trailing `*` matches any suffix or prefix,
+    /// so `*.tmp` and `.*` both work.
+    pub ignore: Vec<String>,
+    /// How many levels of subdirectories to descend into, `None` for no limit and `Some(0)` for
+    /// just the top level
+    pub max_depth: Option<usize>,
+    /// Descend into symlinked directories (loops are detected and skipped)
+    pub follow_symlinks: bool,
+    /// Stop walking once this many images have been found, see [`ScanError::LimitReached`]
+    pub max_images: Option<usize>,
+    /// What order [`scan_dirs`] puts its results in. A [`Scanner`] on its own leaves them in
+    /// the order the filesystem listed them.
+    pub sort: SortOrder,
+    /// Read each image's capture date into [`ImageEntry::taken`], see
+    /// [`FileSystem::capture_date`]
+    pub capture_dates: bool,
+    /// Read each image's size from its header into [`ImageEntry::dimensions`], see
+    /// [`FileSystem::dimensions`]. The app does this in the background instead, see
+    /// [`crate::DimensionsPlugin`].
+    pub probe_dimensions: bool,
+    /// Roughly how many images the walk will find (how many it found last time, say), so the
+    /// results can be allocated up front instead of growing one push at a time
+    pub expected_images: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            extensions: crate::WatchedDirs::SUPPORTED_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            ignore: vec![],
+            max_depth: None,
+            follow_symlinks: true,
+            max_images: None,
+            sort: SortOrder::default(),
+            capture_dates: false,
+            probe_dimensions: false,
+            expected_images: 0,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Does `path` have one of [`Self::extensions`]?
+    pub fn is_supported_image(&self, path: &Path) -> bool {
+        has_extension(path, &self.extensions)
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        self.ignore.iter().any(|pattern| {
+            if let Some(suffix) = pattern.strip_prefix('*') {
+                name.ends_with(suffix)
+            } else if let Some(prefix) = pattern.strip_suffix('*') {
+                name.starts_with(prefix)
+            } else {
+                name == pattern
+            }
+        })
+    }
+}
+
+pub(crate) fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_lowercase()))
+}
+
+#[derive(Debug)]
+pub enum ScanError {
+    /// The directory asked for doesn't exist, or isn't a directory
+    NotADirectory(PathBuf),
+    /// Listing or inspecting a path failed
+    Io { path: PathBuf, source: io::Error },
+    /// Parts of the tree couldn't be read. `entries` is everything that could be.
+    Partial {
+        entries: Vec<ImageEntry>,
+        errors: Vec<ScanError>,
+    },
+    /// There were more than [`ScanOptions::max_images`] images, so the walk stopped early.
+    /// `entries` is the ones that fit, and `errors` anything that couldn't be read on the way.
+    LimitReached {
+        limit: usize,
+        entries: Vec<ImageEntry>,
+        errors: Vec<ScanError>,
+    },
+}
+
+impl ScanError {
+    /// Split into whatever was found plus the individual errors, treating total failure as
+    /// finding nothing
+    pub fn into_partial(self) -> (Vec<ImageEntry>, Vec<ScanError>) {
+        match self {
+            ScanError::Partial { entries, errors }
+            | ScanError::LimitReached {
+                entries, errors, ..
+            } => (entries, errors),
+            other => (vec![], vec![other]),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::NotADirectory(path) => {
+                write!(f, "{} is not a directory", path.display())
+            }
+            ScanError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            ScanError::Partial { errors, .. } => {
+                write!(f, "{} paths couldn't be scanned", errors.len())
+            }
+            ScanError::LimitReached { limit, .. } => {
+                write!(f, "stopped scanning at the {limit} image limit")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Scan `dirs` for images the way the app does, without any of the app. Each directory is walked
+/// with `opts` (hidden files are only skipped if [`ScanOptions::ignore`] says so, e.g. `.*`), the
+/// results are canonicalized with [`normalize_path`], repeats are dropped and the rest are put in
+/// [`ScanOptions::sort`] order. [`ScanOptions::max_images`] counts across all of them.
+///
+/// A directory that can't be read doesn't stop the others, its errors come back alongside
+/// whatever was found. Going past the limit adds a [`ScanError::LimitReached`] with no entries
+/// of its own, since they're already in the list.
+pub fn scan_dirs(dirs: &[PathBuf], opts: &ScanOptions) -> (Vec<PathBuf>, Vec<ScanError>) {
+    let mut images = vec![];
+    let mut errors = vec![];
+    let mut limit_reached = None;
+
+    for dir in dirs {
+        if limit_reached.is_some() {
+            break;
+        }
+        // With none left this still looks for one image, to tell whether any were left out
+        let remaining = opts
+            .max_images
+            .map(|limit| limit.saturating_sub(images.len()));
+
+        let scanner = Scanner::new(
+            RealFileSystem,
+            ScanOptions {
+                max_images: remaining,
+                ..opts.clone()
+            },
+        );
+        let (entries, failures) = match scanner.scan(dir) {
+            Ok(entries) => (entries, vec![]),
+            Err(ScanError::LimitReached {
+                entries, errors, ..
+            }) => {
+                limit_reached = opts.max_images;
+                (entries, errors)
+            }
+            Err(e) => e.into_partial(),
+        };
+        errors.extend(failures);
+        images.extend(entries.iter().map(|entry| normalize_path(&entry.path)));
+    }
+
+    crate::dedup_images(&mut images);
+    sort_images(&mut images, opts.sort);
+    if let Some(limit) = limit_reached {
+        errors.push(ScanError::LimitReached {
+            limit,
+            entries: vec![],
+            errors: vec![],
+        });
+    }
+    (images, errors)
+}
+
+/// Put images in `sort` order, looking on disk for their times or sizes if it has to
+pub fn sort_images(images: &mut [PathBuf], sort: SortOrder) {
+    match sort {
+        SortOrder::Name => images.sort(),
+        SortOrder::Modified => images.sort_by_cached_key(|path| {
+            fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        }),
+        SortOrder::Size => {
+            images.sort_by_cached_key(|path| fs::metadata(path).map_or(0, |meta| meta.len()))
+        }
+    }
+}
+
+/// How far a walk has got since it last said, see [`Scanner::with_progress`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkProgress {
+    pub dirs_seen: usize,
+    pub images_found: usize,
+}
+
+impl WalkProgress {
+    fn is_empty(&self) -> bool {
+        self.dirs_seen == 0 && self.images_found == 0
+    }
+}
+
+/// Walks a directory tree for images
+#[derive(Debug, Clone, Default)]
+pub struct Scanner<F = RealFileSystem> {
+    pub fs: F,
+    pub options: ScanOptions,
+    /// Where to send [`WalkProgress`] as the walk goes, see [`Self::with_progress`]
+    pub progress: Option<Sender<WalkProgress>>,
+    /// Capture dates from earlier scans, see [`Self::with_known_dates`]
+    pub known_dates: Arc<HashMap<PathBuf, CaptureDate>>,
+}
+
+impl<F: FileSystem> Scanner<F> {
+    /// Directories and images found between progress reports
+    const PROGRESS_EVERY: usize = 256;
+
+    pub fn new(fs: F, options: ScanOptions) -> Self {
+        Self {
+            fs,
+            options,
+            progress: None,
+            known_dates: Arc::default(),
+        }
+    }
+
+    /// Report how the walk is going to `progress` every so often, and once more at the end. Each
+    /// report only counts what's been seen since the one before, so reports from several walks
+    /// can go down the same channel and be added up.
+    pub fn with_progress(mut self, progress: Sender<WalkProgress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// With [`ScanOptions::capture_dates`] on, take the dates of images whose modification time
+    /// matches one of these instead of reading the file again
+    pub fn with_known_dates(mut self, known_dates: Arc<HashMap<PathBuf, CaptureDate>>) -> Self {
+        self.known_dates = known_dates;
+        self
+    }
+
+    /// Every image under `root`, in the order the filesystem listed them. Unreadable
+    /// subdirectories don't stop the scan, they come back as [`ScanError::Partial`]. Finding more
+    /// than [`ScanOptions::max_images`] does, with [`ScanError::LimitReached`].
+    pub fn scan(&self, root: &Path) -> Result<Vec<ImageEntry>, ScanError> {
+        if !self.fs.is_dir(root) {
+            return Err(ScanError::NotADirectory(root.to_path_buf()));
+        }
+
+        let expected = match self.options.max_images {
+            Some(max) => self.options.expected_images.min(max),
+            None => self.options.expected_images,
+        };
+        let mut entries = Vec::with_capacity(expected);
+        let mut errors = vec![];
+        let mut visited = HashSet::new();
+        let mut progress = WalkProgress::default();
+        let mut overflowed = false;
+        self.walk(
+            root,
+            0,
+            &mut entries,
+            &mut errors,
+            &mut visited,
+            &mut progress,
+            &mut overflowed,
+        );
+        self.report(&mut progress);
+
+        // Exactly `limit` images is everything there is, only one more means some got left out
+        if let Some(limit) = self.options.max_images
+            && overflowed
+        {
+            return Err(ScanError::LimitReached {
+                limit,
+                entries,
+                errors,
+            });
+        }
+        match errors.len() {
+            0 => Ok(entries),
+            // The root itself being unreadable isn't partial, there's nothing at all
+            1 if entries.is_empty()
+                && matches!(&errors[0], ScanError::Io { path, .. } if path == root) =>
+            {
+                Err(errors.remove(0))
+            }
+            _ => Err(ScanError::Partial { entries, errors }),
+        }
+    }
+
+    /// Whether there's no room for another image. The walk only stops once it finds one anyway.
+    fn is_full(&self, entries: &[ImageEntry]) -> bool {
+        self.options
+            .max_images
+            .is_some_and(|limit| entries.len() >= limit)
+    }
+
+    /// Send off what's been seen since the last report, if anyone's listening
+    fn report(&self, progress: &mut WalkProgress) {
+        if let Some(sender) = &self.progress
+            && !progress.is_empty()
+        {
+            // Nobody listening anymore isn't the walk's problem
+            let _ = sender.send(std::mem::take(progress));
+        }
+    }
+
+    fn walk(
+        &self,
+        dir: &Path,
+        depth: usize,
+        entries: &mut Vec<ImageEntry>,
+        errors: &mut Vec<ScanError>,
+        visited: &mut HashSet<PathBuf>,
+        progress: &mut WalkProgress,
+        overflowed: &mut bool,
+    ) {
+        // Guards against symlink loops, and against scanning the same tree twice through links
+        if let Ok(canonical) = self.fs.canonicalize(dir)
+            && !visited.insert(canonical)
+        {
+            return;
+        }
+        progress.dirs_seen += 1;
+
+        let children = match self.fs.read_dir(dir) {
+            Ok(children) => children,
+            Err(source) => {
+                errors.push(ScanError::Io {
+                    path: dir.to_path_buf(),
+                    source,
+                });
+                return;
+            }
+        };
+
+        for path in children {
+            if *overflowed {
+                return;
+            }
+            if progress.dirs_seen + progress.images_found >= Self::PROGRESS_EVERY {
+                self.report(progress);
+            }
+            if self.options.is_ignored(&path) {
+                continue;
+            }
+            let meta = match self.fs.metadata(&path) {
+                Ok(meta) => meta,
+                // Most likely a dangling symlink or something deleted mid-scan
+                Err(source) => {
+                    errors.push(ScanError::Io { path, source });
+                    continue;
+                }
+            };
+
+            if meta.is_dir {
+                let deep_enough = self.options.max_depth.is_some_and(|max| depth >= max);
+                if !deep_enough && (self.options.follow_symlinks || !meta.is_symlink) {
+                    self.walk(
+                        &path,
+                        depth + 1,
+                        entries,
+                        errors,
+                        visited,
+                        progress,
+                        overflowed,
+                    );
+                }
+            } else if self.options.is_supported_image(&path) {
+                if self.is_full(entries) {
+                    *overflowed = true;
+                    return;
+                }
+                let taken = if self.options.capture_dates {
+                    match self.known_dates.get(&path) {
+                        Some(known) if known.modified == meta.modified => known.taken,
+                        _ => self.fs.capture_date(&path),
+                    }
+                } else {
+                    None
+                };
+                let dimensions = if self.options.probe_dimensions {
+                    self.fs.dimensions(&path)
+                } else {
+                    None
+                };
+                progress.images_found += 1;
+                entries.push(ImageEntry {
+                    path,
+                    len: meta.len,
+                    modified: meta.modified,
+                    taken,
+                    dimensions,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(fs: &MemoryFileSystem, options: ScanOptions) -> Result<Vec<ImageEntry>, ScanError> {
+        Scanner::new(fs.clone(), options).scan(Path::new("/photos"))
+    }
+
+    /// The paths found, in name order
+    fn paths(entries: &[ImageEntry]) -> Vec<&Path> {
+        let mut paths: Vec<_> = entries.iter().map(|entry| entry.path.as_path()).collect();
+        paths.sort();
+        paths
+    }
+
+    fn nested() -> MemoryFileSystem {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file("/photos/a.jpg", 1)
+            .add_file("/photos/one/b.png", 2)
+            .add_file("/photos/one/two/c.gif", 3);
+        fs
+    }
+
+    #[test]
+    fn finds_images_in_nested_directories() {
+        let entries = scan(&nested(), ScanOptions::default()).unwrap();
+        assert_eq!(
+            paths(&entries),
+            [
+                Path::new("/photos/a.jpg"),
+                Path::new("/photos/one/b.png"),
+                Path::new("/photos/one/two/c.gif"),
+            ]
+        );
+        let b = entries.iter().find(|entry| entry.path.ends_with("b.png"));
+        assert_eq!(b.unwrap().len, 2);
+    }
+
+    #[test]
+    fn max_depth_stops_the_walk() {
+        let depth = |max_depth| {
+            let options = ScanOptions {
+                max_depth,
+                ..Default::default()
+            };
+            scan(&nested(), options).unwrap().len()
+        };
+        assert_eq!(depth(Some(0)), 1);
+        assert_eq!(depth(Some(1)), 2);
+        assert_eq!(depth(Some(2)), 3);
+        assert_eq!(depth(None), 3);
+    }
+
+    #[test]
+    fn unreadable_subdirectory_is_partial() {
+        let mut fs = nested();
+        fs.add_file("/photos/locked/d.jpg", 4)
+            .make_unreadable("/photos/locked");
+
+        let Err(ScanError::Partial { entries, errors }) = scan(&fs, ScanOptions::default()) else {
+            panic!("expected a partial scan");
+        };
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(
+            errors.as_slice(),
+            [ScanError::Io { path, source }]
+                if path == Path::new("/photos/locked")
+                    && source.kind() == io::ErrorKind::PermissionDenied
+        ));
+    }
+
+    #[test]
+    fn unreadable_root_is_an_io_error() {
+        let mut fs = nested();
+        fs.make_unreadable("/photos");
+        assert!(matches!(
+            scan(&fs, ScanOptions::default()),
+            Err(ScanError::Io { path, .. }) if path == Path::new("/photos")
+        ));
+    }
+
+    #[test]
+    fn missing_root_is_not_a_directory() {
+        let fs = MemoryFileSystem::new();
+        assert!(matches!(
+            scan(&fs, ScanOptions::default()),
+            Err(ScanError::NotADirectory(_))
+        ));
+    }
+
+    #[test]
+    fn empty_directory_finds_nothing() {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_dir("/photos/empty");
+        assert!(scan(&fs, ScanOptions::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn extensions_match_in_any_case() {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file("/photos/UPPER.JPG", 1)
+            .add_file("/photos/Mixed.Png", 1)
+            .add_file("/photos/lower.jpeg", 1)
+            .add_file("/photos/notes.TXT", 1)
+            .add_file("/photos/no_extension", 1);
+        let entries = scan(&fs, ScanOptions::default()).unwrap();
+        assert_eq!(
+            paths(&entries),
+            [
+                Path::new("/photos/Mixed.Png"),
+                Path::new("/photos/UPPER.JPG"),
+                Path::new("/photos/lower.jpeg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_names_are_skipped() {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file("/photos/keep.jpg", 1)
+            .add_file("/photos/.hidden.jpg", 1)
+            .add_file("/photos/keep_thumb.jpg", 1)
+            .add_file("/photos/.cache/inside.jpg", 1)
+            .add_file("/photos/@eaDir/inside.jpg", 1)
+            .add_file("/photos/tmp-1/inside.jpg", 1);
+        let options = ScanOptions {
+            ignore: vec![
+                ".*".into(),
+                "*_thumb.jpg".into(),
+                "@eaDir".into(),
+                "tmp*".into(),
+            ],
+            ..Default::default()
+        };
+        let entries = scan(&fs, options).unwrap();
+        assert_eq!(paths(&entries), [Path::new("/photos/keep.jpg")]);
+    }
+
+    fn limited(limit: usize) -> ScanOptions {
+        ScanOptions {
+            max_images: Some(limit),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exactly_the_limit_is_not_limit_reached() {
+        let mut fs = nested();
+        // Not an image, so it doesn't count as one past the limit
+        fs.add_file("/photos/one/two/notes.txt", 1);
+        assert_eq!(scan(&fs, limited(3)).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn one_past_the_limit_is_limit_reached() {
+        let mut fs = nested();
+        fs.add_file("/photos/one/two/d.jpg", 4);
+        let Err(ScanError::LimitReached {
+            limit,
+            entries,
+            errors,
+        }) = scan(&fs, limited(3))
+        else {
+            panic!("expected the limit to be reached");
+        };
+        assert_eq!(limit, 3);
+        assert_eq!(entries.len(), 3);
+        assert!(errors.is_empty());
+    }
+
+    /// `scan_dirs` goes to the real disk, so these get a tempdir
+    fn image_dirs(counts: &[usize]) -> (tempfile::TempDir, Vec<PathBuf>) {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = counts
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| {
+                let dir = root.path().join(format!("dir{i}"));
+                fs::create_dir(&dir).unwrap();
+                for image in 0..count {
+                    fs::write(dir.join(format!("{image}.jpg")), b"").unwrap();
+                }
+                dir
+            })
+            .collect();
+        (root, dirs)
+    }
+
+    fn limit_reached(errors: &[ScanError]) -> bool {
+        errors
+            .iter()
+            .any(|e| matches!(e, ScanError::LimitReached { .. }))
+    }
+
+    #[test]
+    fn scan_dirs_filling_the_limit_exactly_is_not_limit_reached() {
+        let (_root, dirs) = image_dirs(&[2, 1, 0]);
+        let (images, errors) = scan_dirs(&dirs, &limited(3));
+        assert_eq!(images.len(), 3);
+        assert!(!limit_reached(&errors));
+    }
+
+    #[test]
+    fn scan_dirs_one_past_the_limit_is_limit_reached() {
+        // The first directory alone fills the limit, the extra one is in the last
+        let (_root, dirs) = image_dirs(&[3, 0, 1]);
+        let (images, errors) = scan_dirs(&dirs, &limited(3));
+        assert_eq!(images.len(), 3);
+        assert!(limit_reached(&errors));
+
+        let (_root, dirs) = image_dirs(&[2, 2]);
+        let (images, errors) = scan_dirs(&dirs, &limited(3));
+        assert_eq!(images.len(), 3);
+        assert!(limit_reached(&errors));
+    }
+
+    #[test]
+    fn symlinked_directories_are_only_followed_when_asked() {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file("/photos/a.jpg", 1)
+            .add_file("/elsewhere/b.jpg", 1)
+            .add_symlink("/photos/link", "/elsewhere");
+
+        let followed = scan(&fs, ScanOptions::default()).unwrap();
+        assert_eq!(
+            paths(&followed),
+            [Path::new("/photos/a.jpg"), Path::new("/photos/link/b.jpg")]
+        );
+
+        let options = ScanOptions {
+            follow_symlinks: false,
+            ..Default::default()
+        };
+        let unfollowed = scan(&fs, options).unwrap();
+        assert_eq!(paths(&unfollowed), [Path::new("/photos/a.jpg")]);
+    }
+
+    #[test]
+    fn symlink_loops_are_walked_once() {
+        let mut fs = nested();
+        fs.add_symlink("/photos/one/two/up", "/photos");
+        assert_eq!(scan(&fs, ScanOptions::default()).unwrap().len(), 3);
+    }
+}
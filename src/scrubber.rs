@@ -0,0 +1,355 @@
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{
+    AppState, CurrentPage, Selection, Themed, WatchedDirs,
+    timeline::{civil_from_days, days_from_civil, unix_seconds},
+};
+
+/// Whether the [`TimelineScrubber`] is wanted. It still stays hidden while too few images have a
+/// capture date for it to be any use.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShowTimelineScrubber(pub bool);
+
+/// When the images on the [`CurrentPage`] were taken, for the bar along the bottom of the window
+/// that runs from the oldest to the newest of them. Dragging along it selects the image taken
+/// nearest the date under the cursor, and the camera follows the selection there. In the
+/// [`crate::GridLayout::Timeline`] that scrolls through the grid by date.
+///
+/// Only EXIF dates count, file modification times say more about when a folder was copied than
+/// when its photos were taken.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct TimelineScrubber {
+    /// Seconds since 1970 of each dated image, oldest first
+    dated: Vec<(i64, PathBuf)>,
+    /// Images on the page, dated or not
+    total: usize,
+}
+
+impl TimelineScrubber {
+    /// Fewest images that need a date for the bar to show, as a fraction of the page
+    const MIN_DATED: f32 = 0.1;
+
+    pub fn new(images: &[PathBuf], date: impl Fn(&Path) -> Option<SystemTime>) -> Self {
+        let mut dated: Vec<(i64, PathBuf)> = images
+            .iter()
+            .filter_map(|path| Some((unix_seconds(date(path)?), path.clone())))
+            .collect();
+        dated.sort();
+        Self {
+            dated,
+            total: images.len(),
+        }
+    }
+
+    /// Whether enough of the images are dated to be worth scrubbing through
+    pub fn is_usable(&self) -> bool {
+        !self.dated.is_empty() && self.dated.len() as f32 >= self.total as f32 * Self::MIN_DATED
+    }
+
+    /// The oldest and newest dates, in seconds since 1970
+    pub fn range(&self) -> Option<(i64, i64)> {
+        Some((self.dated.first()?.0, self.dated.last()?.0))
+    }
+
+    /// How far along the bar `seconds` is, 0 at the oldest image and 1 at the newest
+    pub fn fraction(&self, seconds: i64) -> f32 {
+        let Some((oldest, newest)) = self.range() else {
+            return 0.0;
+        };
+        if newest == oldest {
+            return 0.5;
+        }
+        ((seconds - oldest) as f64 / (newest - oldest) as f64).clamp(0.0, 1.0) as f32
+    }
+
+    /// The other way round from [`Self::fraction`]
+    pub fn seconds_at(&self, fraction: f32) -> Option<i64> {
+        let (oldest, newest) = self.range()?;
+        Some(oldest + ((newest - oldest) as f64 * fraction.clamp(0.0, 1.0) as f64) as i64)
+    }
+
+    /// The image taken nearest to `seconds`
+    pub fn nearest(&self, seconds: i64) -> Option<&PathBuf> {
+        let after = self.dated.partition_point(|(taken, _)| *taken < seconds);
+        let candidates = [after.checked_sub(1), Some(after)];
+        candidates
+            .into_iter()
+            .flatten()
+            .filter_map(|index| self.dated.get(index))
+            .min_by_key(|(taken, _)| taken.abs_diff(seconds))
+            .map(|(_, path)| path)
+    }
+
+    /// When `image` was taken, if it's dated
+    pub fn date_of(&self, image: &Path) -> Option<i64> {
+        self.dated
+            .iter()
+            .find(|(_, path)| path == image)
+            .map(|(taken, _)| *taken)
+    }
+}
+
+#[derive(Component)]
+struct ScrubberRoot;
+
+/// A month or year boundary along the bar
+#[derive(Component)]
+struct ScrubberTick;
+
+#[derive(Component)]
+struct ScrubberHandle;
+
+/// The date under the cursor, shown above the bar
+#[derive(Component)]
+struct ScrubberTooltip;
+
+pub struct TimelineScrubberPlugin;
+
+impl Plugin for TimelineScrubberPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShowTimelineScrubber>();
+        app.init_resource::<TimelineScrubber>();
+        app.add_systems(Startup, spawn_scrubber);
+        app.add_systems(
+            Update,
+            (
+                update_scrubber
+                    .run_if(resource_changed::<CurrentPage>.or(resource_changed::<WatchedDirs>)),
+                show_scrubber,
+                sync_scrubber_ticks.run_if(resource_changed::<TimelineScrubber>),
+                (
+                    scrub_system,
+                    position_scrubber_handle,
+                    scrubber_tooltip_system,
+                ),
+            )
+                .chain()
+                .run_if(in_state(AppState::Running).and(|show: Res<ShowTimelineScrubber>| show.0)),
+        );
+    }
+}
+
+fn spawn_scrubber(mut commands: Commands) {
+    commands.spawn((
+        ScrubberRoot,
+        Interaction::default(),
+        RelativeCursorPosition::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(32.0),
+            left: Val::Px(8.0),
+            // Clear of the minimap
+            right: Val::Px(176.0),
+            height: Val::Px(24.0),
+            display: Display::None,
+            ..default()
+        },
+        BorderRadius::all(Val::Px(3.0)),
+        Themed::Panel,
+        GlobalZIndex(10),
+        children![
+            (
+                ScrubberHandle,
+                Node {
+                    position_type: PositionType::Absolute,
+                    width: Val::Px(6.0),
+                    margin: UiRect::left(Val::Px(-3.0)),
+                    top: Val::Px(-2.0),
+                    bottom: Val::Px(-2.0),
+                    border: UiRect::all(Val::Px(1.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                BackgroundColor(Color::WHITE),
+                BorderColor(Color::BLACK),
+                BorderRadius::all(Val::Px(2.0)),
+            ),
+            (
+                ScrubberTooltip,
+                Node {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(28.0),
+                    padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                BorderRadius::all(Val::Px(3.0)),
+                Themed::Panel,
+                children![(
+                    Text::default(),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    Themed::Text,
+                )],
+            ),
+        ],
+    ));
+}
+
+fn update_scrubber(
+    page: Res<CurrentPage>,
+    watched_dirs: Res<WatchedDirs>,
+    mut scrubber: ResMut<TimelineScrubber>,
+) {
+    scrubber.set_if_neq(TimelineScrubber::new(page.images(), |path| {
+        watched_dirs.exif_date(path)
+    }));
+}
+
+fn show_scrubber(scrubber: Res<TimelineScrubber>, mut root: Single<&mut Node, With<ScrubberRoot>>) {
+    let display = if scrubber.is_usable() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    if root.display != display {
+        root.display = display;
+    }
+}
+
+/// A tick at the start of every month, taller ones with the year by them at the start of every
+/// year. Months are left out past ten years, and the years' labels past twenty, to keep the bar
+/// readable.
+fn sync_scrubber_ticks(
+    mut commands: Commands,
+    scrubber: Res<TimelineScrubber>,
+    root: Single<Entity, With<ScrubberRoot>>,
+    ticks: Query<Entity, With<ScrubberTick>>,
+) {
+    for tick in &ticks {
+        commands.entity(tick).despawn();
+    }
+    let Some((oldest, newest)) = scrubber.range() else {
+        return;
+    };
+    let (first_year, ..) = civil_from_days(oldest.div_euclid(86_400));
+    let (last_year, ..) = civil_from_days(newest.div_euclid(86_400));
+    let years = last_year - first_year;
+    for year in first_year..=last_year {
+        for month in 1..=12 {
+            if month > 1 && years > 10 {
+                break;
+            }
+            let seconds = days_from_civil(year, month, 1) * 86_400;
+            if !(oldest..=newest).contains(&seconds) {
+                continue;
+            }
+            let starts_year = month == 1;
+            let mut tick = commands.spawn((
+                ScrubberTick,
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Percent(scrubber.fraction(seconds) * 100.0),
+                    bottom: Val::Px(0.0),
+                    width: Val::Px(1.0),
+                    height: Val::Percent(if starts_year { 60.0 } else { 30.0 }),
+                    ..default()
+                },
+                Themed::Accent,
+                ChildOf(*root),
+            ));
+            if starts_year && years <= 20 {
+                tick.with_child((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(3.0),
+                        bottom: Val::Px(0.0),
+                        ..default()
+                    },
+                    Text::new(year.to_string()),
+                    TextFont {
+                        font_size: 10.0,
+                        ..default()
+                    },
+                    Themed::Text,
+                ));
+            }
+        }
+    }
+}
+
+/// Where the cursor is along the bar, 0 at the left end and 1 at the right
+fn cursor_fraction(cursor: &RelativeCursorPosition) -> Option<f32> {
+    // Relative to the middle of the bar, so the left end is -0.5
+    Some((cursor.normalized?.x + 0.5).clamp(0.0, 1.0))
+}
+
+/// While the bar is held down, select the image taken nearest the date under the cursor
+fn scrub_system(
+    scrubber: Res<TimelineScrubber>,
+    mut selection: ResMut<Selection>,
+    root: Single<(&Interaction, &RelativeCursorPosition), With<ScrubberRoot>>,
+) {
+    let (interaction, cursor) = *root;
+    if *interaction != Interaction::Pressed {
+        return;
+    }
+    let Some(nearest) = cursor_fraction(cursor)
+        .and_then(|fraction| scrubber.seconds_at(fraction))
+        .and_then(|seconds| scrubber.nearest(seconds))
+    else {
+        return;
+    };
+    if selection.len() != 1 || selection.path() != Some(nearest) {
+        selection.select(nearest.clone());
+    }
+}
+
+/// The handle sits under the cursor while dragging, and at the selected image's date otherwise
+fn position_scrubber_handle(
+    scrubber: Res<TimelineScrubber>,
+    selection: Res<Selection>,
+    root: Single<(&Interaction, &RelativeCursorPosition), With<ScrubberRoot>>,
+    mut handle: Single<&mut Node, With<ScrubberHandle>>,
+) {
+    let (interaction, cursor) = *root;
+    let fraction = match interaction {
+        Interaction::Pressed => cursor_fraction(cursor),
+        _ => selection
+            .path()
+            .and_then(|path| scrubber.date_of(path))
+            .map(|seconds| scrubber.fraction(seconds)),
+    };
+    match fraction {
+        Some(fraction) => {
+            handle.display = Display::Flex;
+            handle.left = Val::Percent(fraction * 100.0);
+        }
+        None => handle.display = Display::None,
+    }
+}
+
+/// Show the date under the cursor above the bar while it's hovered
+fn scrubber_tooltip_system(
+    scrubber: Res<TimelineScrubber>,
+    root: Single<(&Interaction, &RelativeCursorPosition), With<ScrubberRoot>>,
+    tooltip: Single<(&mut Node, &Children), With<ScrubberTooltip>>,
+    mut texts: Query<&mut Text>,
+) {
+    let (interaction, cursor) = *root;
+    let (mut node, children) = tooltip.into_inner();
+    let hovered = *interaction != Interaction::None;
+    let at = cursor_fraction(cursor)
+        .filter(|_| hovered)
+        .and_then(|fraction| Some((fraction, scrubber.seconds_at(fraction)?)));
+    let Some((fraction, seconds)) = at else {
+        node.display = Display::None;
+        return;
+    };
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    node.display = Display::Flex;
+    node.left = Val::Percent(fraction * 100.0);
+    let date = format!("{year:04}-{month:02}-{day:02}");
+    if let Some(mut text) = children
+        .first()
+        .and_then(|child| texts.get_mut(*child).ok())
+        && text.0 != date
+    {
+        text.0 = date;
+    }
+}
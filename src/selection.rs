@@ -0,0 +1,367 @@
+use bevy::prelude::*;
+use image::imageops::FilterType;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::{
+    AppState, AtlasSlot, FilteredOut, GridConfig, GridPosition, ImageDisplayMaterial, ImageMarker,
+    ImageTexture, StatusBar, ThumbnailAtlases, UiTheme, ViewMode, navigation::grid_order, platform,
+    text_input_inactive,
+};
+
+/// The images the user has picked, in the order they were picked. Keyboard actions (delete,
+/// copy, ...) act on these. The last one is the primary selection, for anything that only makes
+/// sense on one image at a time.
+#[derive(Resource, Default, Debug, Clone, PartialEq, Eq)]
+pub struct Selection(pub Vec<PathBuf>);
+
+impl Selection {
+    /// The primary selection
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.0.last()
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, path: &PathBuf) -> bool {
+        self.0.contains(path)
+    }
+
+    /// Select just this image
+    pub fn select(&mut self, path: PathBuf) {
+        self.0.clear();
+        self.0.push(path);
+    }
+
+    /// Add an image, or take it back out if it's already in
+    pub fn toggle(&mut self, path: PathBuf) {
+        if let Some(index) = self.0.iter().position(|selected| *selected == path) {
+            self.0.remove(index);
+        } else {
+            self.0.push(path);
+        }
+    }
+
+    pub fn remove(&mut self, path: &PathBuf) {
+        self.0.retain(|selected| selected != path);
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Where a Shift+click range starts from: the last image clicked without Shift
+#[derive(Resource, Default)]
+struct SelectionAnchor(Option<PathBuf>);
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>();
+        app.init_resource::<SelectionAnchor>();
+        app.add_observer(select_on_click);
+        app.add_observer(clear_selection_on_background_click);
+        app.add_systems(
+            Update,
+            (
+                (clear_selection_on_escape, select_all_hotkey_system)
+                    .run_if(in_state(ViewMode::Grid).and(text_input_inactive)),
+                copy_paths_hotkey_system.run_if(text_input_inactive),
+                draw_selection_outline,
+            )
+                .run_if(in_state(AppState::Running)),
+        );
+        app.add_systems(Update, highlight_selected_quads);
+    }
+}
+
+/// Left-clicking a quad selects its image. Ctrl+click adds it to (or takes it out of) the
+/// selection, Shift+click selects everything in grid order between the anchor and it.
+fn select_on_click(
+    trigger: Trigger<Pointer<Click>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    quads: Query<&ImageMarker>,
+    showing: Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+    state: Res<State<AppState>>,
+    mut selection: ResMut<Selection>,
+    mut anchor: ResMut<SelectionAnchor>,
+) {
+    if trigger.button != PointerButton::Primary || *state.get() != AppState::Running {
+        return;
+    }
+    let Ok(marker) = quads.get(trigger.target()) else {
+        return;
+    };
+    let clicked = marker.target.clone();
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    // Fall back on the primary selection if the anchor has since been deselected
+    let start = anchor
+        .0
+        .clone()
+        .filter(|anchor| selection.contains(anchor))
+        .or_else(|| selection.path().cloned());
+    match start {
+        Some(start) if shift => {
+            let in_grid = showing_in_grid_order(&showing);
+            let Some(from) = in_grid.iter().position(|path| *path == start) else {
+                selection.select(clicked);
+                return;
+            };
+            let Some(to) = in_grid.iter().position(|path| *path == clicked) else {
+                return;
+            };
+
+            let range = in_grid[from.min(to)..=from.max(to)].iter().cloned();
+            if !ctrl {
+                selection.clear();
+            }
+            for path in range {
+                if !selection.contains(&path) {
+                    selection.0.push(path);
+                }
+            }
+            // Keep the clicked image as the primary one
+            selection.remove(&clicked);
+            selection.0.push(clicked);
+            // The anchor stays put so the range can be adjusted with another Shift+click
+            anchor.0 = Some(start);
+        }
+        _ => {
+            if ctrl {
+                selection.toggle(clicked.clone());
+            } else {
+                selection.select(clicked.clone());
+            }
+            anchor.0 = Some(clicked);
+        }
+    }
+}
+
+/// The quads the filter lets through, in the order they read on screen
+fn showing_in_grid_order(
+    showing: &Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+) -> Vec<PathBuf> {
+    let quads: Vec<_> = showing
+        .iter()
+        .map(|(marker, position)| (marker.target.clone(), position.cell))
+        .collect();
+    grid_order(&quads)
+}
+
+/// Clicking past the quads, where nothing but the window itself gets hit, clears the selection
+fn clear_selection_on_background_click(
+    trigger: Trigger<Pointer<Click>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    windows: Query<(), With<Window>>,
+    state: Res<State<AppState>>,
+    mut selection: ResMut<Selection>,
+) {
+    let modified = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::ShiftLeft,
+        KeyCode::ShiftRight,
+    ]);
+    if trigger.button != PointerButton::Primary
+        || modified
+        || *state.get() != AppState::Running
+        || !windows.contains(trigger.target())
+    {
+        return;
+    }
+    if !selection.is_empty() {
+        selection.clear();
+    }
+}
+
+fn clear_selection_on_escape(keys: Res<ButtonInput<KeyCode>>, mut selection: ResMut<Selection>) {
+    if keys.just_pressed(KeyCode::Escape) && !selection.is_empty() {
+        selection.clear();
+    }
+}
+
+/// Ctrl+A selects every image the filter lets through, in grid order
+fn select_all_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    showing: Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+    mut selection: ResMut<Selection>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keys.just_pressed(KeyCode::KeyA) {
+        return;
+    }
+
+    selection.set_if_neq(Selection(showing_in_grid_order(&showing)));
+}
+
+/// Ctrl+C copies the selected images' paths, Ctrl+Shift+C the primary selection's picture
+fn copy_paths_hotkey_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    mut status: ResMut<StatusBar>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keys.just_pressed(KeyCode::KeyC) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    match selection.path() {
+        Some(path) if shift => {
+            commands.run_system_cached_with(copy_image_to_clipboard, path.clone());
+        }
+        Some(_) => copy_paths_to_clipboard(selection.paths(), &mut status),
+        None => {}
+    }
+}
+
+/// Put the pixels of `path`'s loaded texture on the clipboard, scaled down first if either side
+/// is over 4096. Where the clipboard won't take pictures its path goes on instead.
+pub(crate) fn copy_image_to_clipboard(
+    In(path): In<PathBuf>,
+    images: Res<Assets<Image>>,
+    quads: Query<(&ImageMarker, &ImageTexture)>,
+    mut status: ResMut<StatusBar>,
+) {
+    const MAX_SIDE: u32 = 4096;
+
+    let loaded = quads
+        .iter()
+        .find(|(marker, _)| marker.target == path)
+        .and_then(|(_, texture)| images.get(&texture.0));
+    let Some(loaded) = loaded else {
+        status.set("That image hasn't loaded yet");
+        return;
+    };
+    let image = match loaded.clone().try_into_dynamic() {
+        Ok(image) => image,
+        Err(e) => {
+            log::warn!("Couldn't copy {path:?} as an image: {e}");
+            status.set(format!("Couldn't copy the image: {e}"));
+            return;
+        }
+    };
+    let image = if image.width().max(image.height()) > MAX_SIDE {
+        image.resize(MAX_SIDE, MAX_SIDE, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    let rgba = image.to_rgba8();
+    match platform::copy_image_to_clipboard(rgba.width(), rgba.height(), rgba.as_raw()) {
+        Ok(()) => status.set(format!(
+            "Copied the picture in {} ({}x{})",
+            path.display(),
+            rgba.width(),
+            rgba.height()
+        )),
+        Err(e) => {
+            log::warn!(
+                "Couldn't copy {path:?} to the clipboard as an image, copying its path: {e}"
+            );
+            copy_paths_to_clipboard(std::slice::from_ref(&path), &mut status);
+            status.set(format!(
+                "The clipboard won't take pictures here, copied the path of {} instead",
+                path.display()
+            ));
+        }
+    }
+}
+
+/// Put the absolute `paths` on the clipboard, one per line, and say so in the status bar
+pub(crate) fn copy_paths_to_clipboard(paths: &[PathBuf], status: &mut StatusBar) {
+    let text = paths
+        .iter()
+        .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.clone()))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Err(e) = platform::copy_text_to_clipboard(&text) {
+        log::warn!("Couldn't copy paths to the clipboard: {e}");
+        status.set("Couldn't copy to the clipboard");
+        return;
+    }
+    match paths {
+        [path] => status.set(format!("Copied {}", path.display())),
+        paths => status.set(format!("Copied {} paths", paths.len())),
+    }
+}
+
+/// Wash the selected quads in the theme's selection colour, on top of the outline
+fn highlight_selected_quads(
+    selection: Res<Selection>,
+    theme: Res<UiTheme>,
+    added: Query<(), Added<ImageMarker>>,
+    mut quads: Query<(
+        &ImageMarker,
+        &mut MeshMaterial3d<ImageDisplayMaterial>,
+        Option<&AtlasSlot>,
+    )>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut atlases: ResMut<ThumbnailAtlases>,
+) {
+    const STRENGTH: f32 = 0.25;
+
+    if !selection.is_changed() && !theme.is_changed() && added.is_empty() {
+        return;
+    }
+    let selected: HashSet<&PathBuf> = selection.paths().iter().collect();
+    let color = LinearRgba::from(theme.selection).with_alpha(STRENGTH);
+
+    for (marker, mut material, slot) in &mut quads {
+        let highlight = if selected.contains(&marker.target) {
+            color
+        } else {
+            LinearRgba::NONE
+        };
+        // Only touch the ones that change, every `get_mut` re-uploads the material
+        if materials
+            .get(&material.0)
+            .is_some_and(|material| material.highlight != highlight)
+        {
+            atlases.restyle(&mut material, slot, &mut materials, |material| {
+                material.highlight = highlight;
+            });
+        }
+    }
+}
+
+/// Outline the selected quads in the theme's selection colour
+fn draw_selection_outline(
+    mut gizmos: Gizmos,
+    selection: Res<Selection>,
+    theme: Res<UiTheme>,
+    grid_config: Res<GridConfig>,
+    quads: Query<(&ImageMarker, &GlobalTransform)>,
+) {
+    if selection.is_empty() {
+        return;
+    }
+    let selected: HashSet<&PathBuf> = selection.paths().iter().collect();
+
+    for (marker, transform) in &quads {
+        if selected.contains(&marker.target) {
+            let (_, rotation, translation) = transform.to_scale_rotation_translation();
+            gizmos.rect(
+                Isometry3d::new(translation, rotation),
+                Vec2::splat(grid_config.quad_size * 1.05),
+                theme.selection,
+            );
+        }
+    }
+}
@@ -0,0 +1,64 @@
+#import bevy_pbr::forward_io::VertexOutput
+
+// non-zero: bicubic (Catmull-Rom) filtering, zero: plain bilinear
+@group(2) @binding(0) var<uniform> anti_alias: u32;
+@group(2) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(2) @binding(2) var base_color_sampler: sampler;
+// multiplied into the image, white leaves it alone
+@group(2) @binding(3) var<uniform> tint: vec4<f32>;
+// blended over the image by its alpha, transparent leaves it alone
+@group(2) @binding(4) var<uniform> highlight: vec4<f32>;
+// non-zero: flipped left to right
+@group(2) @binding(5) var<uniform> mirrored: u32;
+
+// Catmull-Rom filtering folded into 9 bilinear taps instead of 16 point samples, see
+// https://gist.github.com/TheRealMJP/c83b8c0f46b63f3a88a5986f4fa982b1
+fn sample_catmull_rom(uv: vec2<f32>) -> vec4<f32> {
+    let tex_size = vec2<f32>(textureDimensions(base_color_texture));
+    let sample_pos = uv * tex_size;
+    let tex_pos1 = floor(sample_pos - 0.5) + 0.5;
+    let f = sample_pos - tex_pos1;
+
+    let w0 = f * (-0.5 + f * (1.0 - 0.5 * f));
+    let w1 = 1.0 + f * f * (-2.5 + 1.5 * f);
+    let w2 = f * (0.5 + f * (2.0 - 1.5 * f));
+    let w3 = f * f * (-0.5 + 0.5 * f);
+
+    let w12 = w1 + w2;
+    let offset12 = w2 / w12;
+
+    let tex_pos0 = (tex_pos1 - 1.0) / tex_size;
+    let tex_pos3 = (tex_pos1 + 2.0) / tex_size;
+    let tex_pos12 = (tex_pos1 + offset12) / tex_size;
+
+    var result = vec4<f32>(0.0);
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos0.x, tex_pos0.y), 0.0) * w0.x * w0.y;
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos12.x, tex_pos0.y), 0.0) * w12.x * w0.y;
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos3.x, tex_pos0.y), 0.0) * w3.x * w0.y;
+
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos0.x, tex_pos12.y), 0.0) * w0.x * w12.y;
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos12.x, tex_pos12.y), 0.0) * w12.x * w12.y;
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos3.x, tex_pos12.y), 0.0) * w3.x * w12.y;
+
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos0.x, tex_pos3.y), 0.0) * w0.x * w3.y;
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos12.x, tex_pos3.y), 0.0) * w12.x * w3.y;
+    result += textureSampleLevel(base_color_texture, base_color_sampler, vec2(tex_pos3.x, tex_pos3.y), 0.0) * w3.x * w3.y;
+
+    // the negative lobes can overshoot around hard edges
+    return clamp(result, vec4<f32>(0.0), vec4<f32>(1.0));
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    var uv = mesh.uv;
+    if mirrored != 0u {
+        uv.x = 1.0 - uv.x;
+    }
+    var color: vec4<f32>;
+    if anti_alias != 0u {
+        color = sample_catmull_rom(uv) * tint;
+    } else {
+        color = textureSampleLevel(base_color_texture, base_color_sampler, uv, 0.0) * tint;
+    }
+    return vec4(mix(color.rgb, highlight.rgb, highlight.a), color.a);
+}
@@ -0,0 +1,370 @@
+use bevy::{prelude::*, window::RequestRedraw};
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{
+    AtlasSlot, CameraAnimation, FilteredOut, GridConfig, GridPosition, ImageDisplayMaterial,
+    ImageLoadState, ImageMarker, ImageTexture, Selection, StatusBar, ViewMode, text_input_inactive,
+    texture_budget::{LastVisible, reload_texture},
+};
+
+/// How the slideshow runs
+#[derive(Resource, Debug, Clone)]
+pub struct SlideshowConfig {
+    /// How long each image is shown for
+    pub interval: Duration,
+    /// Slowly zoom and pan across each image while it's up, see [`KenBurnsAnimation`]
+    pub ken_burns: bool,
+    /// How many images either side of the one being shown to have loaded already, so stepping
+    /// to them doesn't wait on the disk. They're evicted like any other texture that's off
+    /// screen when the [`crate::TextureBudget`] runs short.
+    pub prefetch: usize,
+}
+
+impl Default for SlideshowConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            ken_burns: true,
+            prefetch: 2,
+        }
+    }
+}
+
+/// A slow zoom and drift across the image being shown in the slideshow. The quad's scale and
+/// offset from its place in the grid go from the start values to the end ones over `duration`
+/// seconds, then stay there.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct KenBurnsAnimation {
+    pub start_scale: f32,
+    pub end_scale: f32,
+    /// Offset in the quad's own plane
+    pub start_offset: Vec2,
+    pub end_offset: Vec2,
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+impl KenBurnsAnimation {
+    /// Biggest zoom either end of the move gets
+    const MAX_SCALE: f32 = 1.2;
+
+    /// A random zoom in or out, and a random drift, for a quad `quad_size` across
+    pub fn random(quad_size: f32, duration: f32) -> Self {
+        let scale = || 1.0 + fastrand::f32() * (Self::MAX_SCALE - 1.0);
+        // Small enough to stay well inside the quad
+        let offset = || (Vec2::new(fastrand::f32(), fastrand::f32()) - 0.5) * quad_size * 0.1;
+        Self {
+            start_scale: scale(),
+            end_scale: scale(),
+            start_offset: offset(),
+            end_offset: offset(),
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// (scale, offset) this far into the move
+    fn current(&self) -> (f32, Vec2) {
+        let t = (self.elapsed / self.duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+        (
+            self.start_scale.lerp(self.end_scale, t),
+            self.start_offset.lerp(self.end_offset, t),
+        )
+    }
+}
+
+/// When the slideshow moves on to the next image
+#[derive(Resource)]
+struct SlideshowTimer(Timer);
+
+/// Space starts a slideshow of the page from the selected image (or the first one), showing each
+/// in turn face on and moving on every [`SlideshowConfig::interval`]. The arrow keys step back
+/// and forth by hand, Space or Escape goes back to the grid.
+pub struct SlideshowPlugin;
+
+impl Plugin for SlideshowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SlideshowConfig>();
+        app.add_systems(
+            Update,
+            start_slideshow_hotkey_system.run_if(in_state(ViewMode::Grid).and(text_input_inactive)),
+        );
+        app.add_systems(
+            OnEnter(ViewMode::Slideshow),
+            (
+                start_slideshow,
+                show_selected_slide,
+                prefetch_neighbours_system,
+            )
+                .chain(),
+        );
+        app.add_systems(OnExit(ViewMode::Slideshow), stop_slideshow);
+        app.add_systems(
+            Update,
+            (
+                (
+                    slideshow_input_system.run_if(text_input_inactive),
+                    advance_slideshow_system,
+                ),
+                (show_selected_slide, prefetch_neighbours_system)
+                    .run_if(resource_changed::<Selection>),
+                ken_burns_system,
+            )
+                .chain()
+                .run_if(in_state(ViewMode::Slideshow)),
+        );
+    }
+}
+
+fn start_slideshow_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut selection: ResMut<Selection>,
+    mut status: ResMut<StatusBar>,
+    mut view_mode: ResMut<NextState<ViewMode>>,
+    quads: Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+) {
+    if !keys.just_pressed(KeyCode::Space) {
+        return;
+    }
+    let slides = slides(&quads);
+    let Some(first) = slides.first() else {
+        status.set("No images to show");
+        return;
+    };
+    if !selection.path().is_some_and(|path| slides.contains(path)) {
+        selection.select(first.clone());
+    }
+    view_mode.set(ViewMode::Slideshow);
+}
+
+/// The images the slideshow goes through, in grid order
+fn slides(quads: &Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>) -> Vec<PathBuf> {
+    let mut slides: Vec<_> = quads
+        .iter()
+        .map(|(marker, position)| (position.index, marker.target.clone()))
+        .collect();
+    slides.sort_by_key(|(index, _)| *index);
+    slides.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Select the image `step` on from the selected one, wrapping round at either end
+fn step_slide(
+    selection: &mut Selection,
+    quads: &Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+    step: isize,
+) {
+    let slides = slides(quads);
+    if slides.is_empty() {
+        return;
+    }
+    let next = match selection
+        .path()
+        .and_then(|path| slides.iter().position(|slide| slide == path))
+    {
+        Some(current) => (current as isize + step).rem_euclid(slides.len() as isize) as usize,
+        None => 0,
+    };
+    selection.select(slides[next].clone());
+}
+
+fn start_slideshow(mut commands: Commands, config: Res<SlideshowConfig>) {
+    commands.insert_resource(SlideshowTimer(Timer::new(
+        config.interval,
+        TimerMode::Repeating,
+    )));
+}
+
+fn stop_slideshow(
+    mut commands: Commands,
+    mut quads: Query<(Entity, &GridPosition, &mut Transform), With<KenBurnsAnimation>>,
+) {
+    commands.remove_resource::<SlideshowTimer>();
+    for (entity, position, mut transform) in &mut quads {
+        reset_ken_burns(&mut commands, entity, position, &mut transform);
+    }
+}
+
+/// Put a quad back the way the grid has it
+fn reset_ken_burns(
+    commands: &mut Commands,
+    entity: Entity,
+    position: &GridPosition,
+    transform: &mut Transform,
+) {
+    transform.translation = position.current;
+    transform.scale = Vec3::splat(position.scale);
+    commands.entity(entity).remove::<KenBurnsAnimation>();
+}
+
+/// The arrow keys step through by hand, starting the timer again, and Space or Escape stops
+fn slideshow_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut selection: ResMut<Selection>,
+    mut timer: ResMut<SlideshowTimer>,
+    mut view_mode: ResMut<NextState<ViewMode>>,
+    quads: Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+) {
+    if keys.any_just_pressed([KeyCode::Space, KeyCode::Escape]) {
+        view_mode.set(ViewMode::Grid);
+        return;
+    }
+    let step = if keys.just_pressed(KeyCode::ArrowRight) {
+        1
+    } else if keys.just_pressed(KeyCode::ArrowLeft) {
+        -1
+    } else {
+        return;
+    };
+    step_slide(&mut selection, &quads, step);
+    timer.0.reset();
+}
+
+fn advance_slideshow_system(
+    time: Res<Time<Real>>,
+    mut redraw: EventWriter<RequestRedraw>,
+    mut selection: ResMut<Selection>,
+    mut timer: ResMut<SlideshowTimer>,
+    quads: Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+) {
+    // Nothing else asks for frames while a slide just sits there, and the timer only ticks on them
+    redraw.write(RequestRedraw);
+    if timer.0.tick(time.delta()).just_finished() {
+        step_slide(&mut selection, &quads, 1);
+    }
+}
+
+/// Fly the camera face on to the selected image, near enough for it to fill the view, and start
+/// its Ken Burns move over again
+fn show_selected_slide(
+    mut commands: Commands,
+    selection: Res<Selection>,
+    config: Res<SlideshowConfig>,
+    grid_config: Res<GridConfig>,
+    mut quads: Query<(
+        Entity,
+        &ImageMarker,
+        &GridPosition,
+        &mut Transform,
+        Has<KenBurnsAnimation>,
+    )>,
+    camera: Option<Single<(Entity, &Camera, &Projection), With<Camera3d>>>,
+) {
+    let Some(path) = selection.path() else {
+        return;
+    };
+    for (entity, marker, position, mut transform, animated) in &mut quads {
+        if animated && marker.target != *path {
+            reset_ken_burns(&mut commands, entity, position, &mut transform);
+        }
+    }
+    let Some((entity, _, position, mut transform, _)) = quads
+        .iter_mut()
+        .find(|(_, marker, _, _, _)| marker.target == *path)
+    else {
+        return;
+    };
+
+    // The sprite mode's camera is left where it is
+    if let Some(camera) = camera
+        && let (camera_entity, camera, Projection::Perspective(perspective)) = *camera
+    {
+        let aspect = camera
+            .logical_viewport_size()
+            .map_or(perspective.aspect_ratio, |size| size.x / size.y.max(1.0));
+        let half_vertical = perspective.fov * 0.5;
+        let half_horizontal = (half_vertical.tan() * aspect).atan();
+        let half_size = grid_config.quad_size * position.scale * 0.5;
+        let distance = half_size / half_vertical.min(half_horizontal).tan();
+
+        let quad = Transform::from_translation(position.target).with_rotation(transform.rotation);
+        let target = Transform::from_translation(quad.translation + quad.back() * distance)
+            .looking_at(quad.translation, quad.up());
+        commands
+            .entity(camera_entity)
+            .insert(CameraAnimation::to(target));
+    }
+
+    reset_ken_burns(&mut commands, entity, position, &mut transform);
+    if config.ken_burns {
+        commands.entity(entity).insert(KenBurnsAnimation::random(
+            grid_config.quad_size,
+            config.interval.as_secs_f32(),
+        ));
+    }
+}
+
+/// Load the textures of the slides either side of the selected one that have been evicted, and
+/// count them as just seen so they're the last to go again
+fn prefetch_neighbours_system(
+    time: Res<Time<Real>>,
+    selection: Res<Selection>,
+    config: Res<SlideshowConfig>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    slide_quads: Query<(&ImageMarker, &GridPosition), Without<FilteredOut>>,
+    mut quads: Query<
+        (
+            &ImageMarker,
+            &mut ImageTexture,
+            &mut ImageLoadState,
+            &MeshMaterial3d<ImageDisplayMaterial>,
+            Option<&mut LastVisible>,
+        ),
+        Without<AtlasSlot>,
+    >,
+) {
+    let slides = slides(&slide_quads);
+    let Some(current) = selection
+        .path()
+        .and_then(|path| slides.iter().position(|slide| slide == path))
+    else {
+        return;
+    };
+    // Wrapping round like stepping does, but no further than the slides there are
+    let reach = config.prefetch.min(slides.len() / 2);
+    let neighbours: Vec<&PathBuf> = (1..=reach)
+        .flat_map(|step| {
+            [
+                (current + step) % slides.len(),
+                (current + slides.len() - step) % slides.len(),
+            ]
+        })
+        .map(|index| &slides[index])
+        .collect();
+
+    let now = time.elapsed();
+    for (marker, mut texture, mut state, material, last_visible) in &mut quads {
+        if !neighbours.contains(&&marker.target) {
+            continue;
+        }
+        if let Some(mut last_visible) = last_visible {
+            last_visible.0 = now;
+        }
+        if *state == ImageLoadState::Evicted {
+            reload_texture(
+                &asset_server,
+                &mut materials,
+                marker,
+                &mut texture,
+                &mut state,
+                material,
+            );
+        }
+    }
+}
+
+fn ken_burns_system(
+    time: Res<Time<Real>>,
+    mut quads: Query<(&mut KenBurnsAnimation, &GridPosition, &mut Transform)>,
+) {
+    // Capped like the other animations, so a long gap between frames doesn't skip it
+    let delta = time.delta_secs().min(1.0 / 30.0);
+    for (mut animation, position, mut transform) in &mut quads {
+        animation.elapsed += delta;
+        let (scale, offset) = animation.current();
+        transform.scale = Vec3::splat(position.scale * scale);
+        transform.translation = position.current + transform.rotation * offset.extend(0.0);
+    }
+}
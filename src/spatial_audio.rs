@@ -0,0 +1,133 @@
+use bevy::{audio::Volume, prelude::*};
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+
+use crate::{GridConfig, ImageMarker};
+
+/// How a quad sounds when the camera sweeps over it. Picked per image so neighbours don't all
+/// sound the same, but stable from one run to the next.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AudioCue {
+    pub volume: f32,
+    /// Playback speed, which also shifts the pitch
+    pub speed: f32,
+}
+
+impl AudioCue {
+    const BASE_VOLUME: f32 = 0.12;
+
+    fn for_image(marker: &ImageMarker) -> Self {
+        let mut hasher = DefaultHasher::new();
+        marker.target.hash(&mut hasher);
+        let hash = hasher.finish();
+        // Two numbers in 0..1 out of the hash
+        let a = (hash & 0xffff) as f32 / 65535.0;
+        let b = ((hash >> 16) & 0xffff) as f32 / 65535.0;
+        Self {
+            volume: Self::BASE_VOLUME * (0.8 + 0.4 * a),
+            speed: 0.9 + 0.2 * b,
+        }
+    }
+}
+
+/// The short tone every cue plays
+#[derive(Resource)]
+struct CueSound(Handle<Pitch>);
+
+/// Plays a soft positional click as the middle of the view passes over quads
+pub struct SpatialAudioPlugin;
+
+impl Plugin for SpatialAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_cue_sound);
+        app.add_observer(add_audio_cue);
+        app.add_systems(Update, (attach_listener, spatial_audio_system));
+    }
+}
+
+fn load_cue_sound(mut commands: Commands, mut pitches: ResMut<Assets<Pitch>>) {
+    let handle = pitches.add(Pitch::new(880.0, Duration::from_millis(35)));
+    commands.insert_resource(CueSound(handle));
+}
+
+fn add_audio_cue(
+    trigger: Trigger<OnAdd, ImageMarker>,
+    mut commands: Commands,
+    quads: Query<&ImageMarker>,
+) {
+    if let Ok(marker) = quads.get(trigger.target()) {
+        commands
+            .entity(trigger.target())
+            .insert(AudioCue::for_image(marker));
+    }
+}
+
+/// Listen from the camera so cues pan left and right
+fn attach_listener(
+    mut commands: Commands,
+    cameras: Query<Entity, (With<Camera3d>, Without<SpatialListener>)>,
+) {
+    for camera in &cameras {
+        commands.entity(camera).insert(SpatialListener::default());
+    }
+}
+
+/// When the camera moves, find the quad nearest its look ray and play that quad's cue if it's a
+/// different one from last time
+fn spatial_audio_system(
+    mut commands: Commands,
+    sound: Option<Res<CueSound>>,
+    grid_config: Res<GridConfig>,
+    time: Res<Time<Real>>,
+    camera: Single<Ref<GlobalTransform>, With<Camera3d>>,
+    quads: Query<(Entity, &GlobalTransform, &AudioCue, &ViewVisibility)>,
+    mut last_cue: Local<Option<Entity>>,
+    mut last_played: Local<Duration>,
+) {
+    // Any faster and a quick pan turns into a buzz
+    const MIN_GAP: Duration = Duration::from_millis(60);
+
+    let Some(sound) = sound else {
+        return;
+    };
+    if !camera.is_changed() {
+        return;
+    }
+
+    let origin = camera.translation();
+    let direction = camera.forward();
+    let reach = grid_config.quad_size * 0.5;
+    let nearest = quads
+        .iter()
+        .filter(|(.., visibility)| visibility.get())
+        .filter_map(|(entity, transform, cue, _)| {
+            let to_quad = transform.translation() - origin;
+            let along = to_quad.dot(*direction);
+            if along <= 0.0 {
+                return None;
+            }
+            let off_ray = (to_quad - *direction * along).length();
+            (off_ray <= reach).then_some((entity, transform.translation(), *cue, off_ray))
+        })
+        .min_by(|a, b| a.3.total_cmp(&b.3));
+
+    let Some((entity, position, cue, _)) = nearest else {
+        *last_cue = None;
+        return;
+    };
+    if *last_cue == Some(entity) || time.elapsed() - *last_played < MIN_GAP {
+        return;
+    }
+    *last_cue = Some(entity);
+    *last_played = time.elapsed();
+
+    commands.spawn((
+        AudioPlayer(sound.0.clone()),
+        PlaybackSettings::DESPAWN
+            .with_spatial(true)
+            .with_volume(Volume::Linear(cue.volume))
+            .with_speed(cue.speed),
+        Transform::from_translation(position),
+    ));
+}
@@ -0,0 +1,78 @@
+use bevy::{prelude::*, window::RequestRedraw};
+
+use std::time::Duration;
+
+use crate::GridPosition;
+
+/// Settings for the grow-in animation on quads for images that turned up after the first scan,
+/// so new arrivals (a camera dumping into the folder, say) stand out
+#[derive(Resource, Debug, Clone)]
+pub struct SpawnAnimation {
+    pub enabled: bool,
+    pub duration: Duration,
+}
+
+impl Default for SpawnAnimation {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            duration: Duration::from_millis(400),
+        }
+    }
+}
+
+/// On quads that are still growing in, removed once the timer runs out
+#[derive(Component, Debug, Clone)]
+pub struct JustAdded {
+    pub timer: Timer,
+}
+
+impl JustAdded {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            timer: Timer::new(duration, TimerMode::Once),
+        }
+    }
+}
+
+pub struct SpawnAnimationPlugin;
+
+impl Plugin for SpawnAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnAnimation>();
+        app.add_systems(Update, animate_just_added);
+    }
+}
+
+/// Scale new quads up from nothing to their [`GridPosition::scale`], easing out
+fn animate_just_added(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut redraw: EventWriter<RequestRedraw>,
+    mut quads: Query<(
+        Entity,
+        &mut Transform,
+        &mut JustAdded,
+        Option<&GridPosition>,
+    )>,
+) {
+    if quads.is_empty() {
+        return;
+    }
+
+    // Frames only come in on input while idle, so the first delta can be huge. Cap it so the
+    // animation is actually seen.
+    let delta = time.delta().min(Duration::from_secs_f32(1.0 / 30.0));
+    for (entity, mut transform, mut just_added, position) in &mut quads {
+        let full = position.map_or(1.0, |position| position.scale);
+        just_added.timer.tick(delta);
+        let t = just_added.timer.fraction();
+        transform.scale = Vec3::splat(full * (1.0 - (1.0 - t).powi(3)));
+
+        if just_added.timer.finished() {
+            transform.scale = Vec3::splat(full);
+            commands.entity(entity).remove::<JustAdded>();
+        }
+    }
+    redraw.write(RequestRedraw);
+}
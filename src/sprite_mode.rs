@@ -0,0 +1,164 @@
+use bevy::{
+    input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit},
+    prelude::*,
+    window::{PrimaryWindow, WindowResized},
+};
+
+use crate::{AppState, GridConfig, ImageMarker, RenderMode, Selection, text_input_inactive};
+
+/// Camera controls for [`RenderMode::Sprite2d`]: the wheel zooms around the cursor, dragging with
+/// the middle button pans, and Home (with nothing selected) fits the grid in the window, as it
+/// also does once the first images turn up. A [`GridConfig::fit_to_window`] grid is reflowed to
+/// the window's width as it's resized.
+pub struct SpriteModePlugin;
+
+/// Fit the whole grid in the window
+#[derive(Event, Debug, Clone, Copy)]
+struct FitGrid2d;
+
+impl Plugin for SpriteModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FitGrid2d>();
+        app.add_systems(
+            Update,
+            (
+                (
+                    fit_hotkey_system.run_if(text_input_inactive),
+                    zoom_2d_system,
+                    pan_2d_system,
+                )
+                    .run_if(in_state(AppState::Running)),
+                fit_grid_2d_system,
+                reflow_to_window_system,
+            )
+                .chain()
+                .run_if(resource_equals(RenderMode::Sprite2d)),
+        );
+    }
+}
+
+/// Home fits the grid, unless it's jumping the selection to the first image instead
+fn fit_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    mut fits: EventWriter<FitGrid2d>,
+) {
+    if keys.just_pressed(KeyCode::Home) && selection.is_empty() {
+        fits.write(FitGrid2d);
+    }
+}
+
+/// Zoom in or out around whatever's under the cursor
+fn zoom_2d_system(
+    scroll: Res<AccumulatedMouseScroll>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform, &mut Transform, &mut Projection), With<Camera2d>>,
+) {
+    if scroll.delta.y == 0.0 {
+        return;
+    }
+    let (camera, global, mut transform, mut projection) = camera.into_inner();
+    let Projection::Orthographic(orthographic) = &mut *projection else {
+        return;
+    };
+
+    let notches = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y,
+        MouseScrollUnit::Pixel => scroll.delta.y / 40.0,
+    };
+    let factor = 1.1_f32.powf(-notches);
+    orthographic.scale *= factor;
+
+    // Keep the point under the cursor where it is
+    if let Some(cursor) = window.cursor_position()
+        && let Ok(under_cursor) = camera.viewport_to_world_2d(global, cursor)
+    {
+        let offset = transform.translation.truncate() - under_cursor;
+        transform.translation = (under_cursor + offset * factor).extend(transform.translation.z);
+    }
+}
+
+fn pan_2d_system(
+    mouse: Res<ButtonInput<MouseButton>>,
+    motion: Res<AccumulatedMouseMotion>,
+    camera: Single<(&mut Transform, &Projection), With<Camera2d>>,
+) {
+    if !mouse.pressed(MouseButton::Middle) || motion.delta == Vec2::ZERO {
+        return;
+    }
+    let (mut transform, projection) = camera.into_inner();
+    let Projection::Orthographic(orthographic) = projection else {
+        return;
+    };
+    // Screen y points down, world y up
+    let delta = Vec2::new(-motion.delta.x, motion.delta.y) * orthographic.scale;
+    transform.translation += delta.extend(0.0);
+}
+
+fn fit_grid_2d_system(
+    mut fits: EventReader<FitGrid2d>,
+    grid_config: Res<GridConfig>,
+    quads: Query<&Transform, (With<ImageMarker>, Without<Camera2d>)>,
+    camera: Single<(&Camera, &mut Transform, &mut Projection), With<Camera2d>>,
+    mut fitted: Local<bool>,
+) {
+    let asked = fits.read().last().is_some();
+    if *fitted && !asked {
+        return;
+    }
+
+    let half_quad = Vec2::splat(grid_config.quad_size * 0.5);
+    let Some((min, max)) = quads
+        .iter()
+        .map(|quad| {
+            let center = quad.translation.truncate();
+            (center - half_quad, center + half_quad)
+        })
+        .reduce(|(min, max), (quad_min, quad_max)| (min.min(quad_min), max.max(quad_max)))
+    else {
+        return;
+    };
+    let (camera, mut transform, mut projection) = camera.into_inner();
+    let Projection::Orthographic(orthographic) = &mut *projection else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+
+    let size = (max - min) * 1.05;
+    orthographic.scale = (size.x / viewport.x).max(size.y / viewport.y);
+    transform.translation = ((min + max) * 0.5).extend(transform.translation.z);
+    *fitted = true;
+}
+
+/// Work out how many columns fit across the window, for [`GridConfig::fit_to_window`]. That only
+/// changes the [`GridConfig`], so the quads glide to their new places rather than being spawned
+/// again, see [`crate::RegridPlugin`].
+fn reflow_to_window_system(
+    mut resizes: EventReader<WindowResized>,
+    camera: Single<(&Camera, &Projection), With<Camera2d>>,
+    mut grid_config: ResMut<GridConfig>,
+) {
+    // Our own change to the config doesn't count, only someone else's
+    if resizes.read().last().is_none() && !grid_config.is_changed() {
+        return;
+    }
+    let (camera, projection) = *camera;
+    let Projection::Orthographic(orthographic) = projection else {
+        return;
+    };
+
+    let fitted = match camera.logical_viewport_size() {
+        Some(viewport) if grid_config.fit_to_window => {
+            let width = viewport.x * orthographic.scale;
+            let columns = (width / grid_config.spacing.max(f32::EPSILON)).floor() as i32;
+            let min = grid_config.min_columns.max(1);
+            Some(columns.clamp(min, grid_config.max_columns.max(min)))
+        }
+        _ => None,
+    };
+    if grid_config.fitted_columns != fitted {
+        grid_config.fitted_columns = fitted;
+    }
+}
@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+
+use crate::Themed;
+
+/// One line of feedback along the bottom of the window ("Saved screenshot to ...", etc). Unlike
+/// [`crate::ScanErrors`] there's only ever one message, each new one replaces the last. The
+/// detail on the right is for ongoing readouts (texture memory, ...) that shouldn't get in the way
+/// of messages.
+#[derive(Resource, Default, Debug)]
+pub struct StatusBar {
+    message: Option<String>,
+    detail: Option<String>,
+}
+
+impl StatusBar {
+    pub fn set(&mut self, message: impl Into<String>) {
+        self.message = Some(message.into());
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn clear(&mut self) {
+        self.message = None;
+    }
+
+    pub fn set_detail(&mut self, detail: impl Into<String>) {
+        self.detail = Some(detail.into());
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+}
+
+#[derive(Component)]
+struct StatusBarText;
+
+#[derive(Component)]
+struct StatusBarDetail;
+
+pub struct StatusBarPlugin;
+
+impl Plugin for StatusBarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StatusBar>();
+        app.add_systems(Startup, spawn_status_bar);
+        app.add_systems(
+            Update,
+            update_status_bar.run_if(resource_changed::<StatusBar>),
+        );
+    }
+}
+
+fn spawn_status_bar(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.0),
+            left: Val::Px(0.0),
+            right: Val::Px(0.0),
+            padding: UiRect::axes(Val::Px(12.0), Val::Px(4.0)),
+            justify_content: JustifyContent::SpaceBetween,
+            ..default()
+        },
+        GlobalZIndex(30),
+        children![
+            (
+                StatusBarText,
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                Themed::Text,
+            ),
+            (
+                StatusBarDetail,
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                Themed::Text,
+            )
+        ],
+    ));
+}
+
+fn update_status_bar(
+    status: Res<StatusBar>,
+    mut text: Single<&mut Text, (With<StatusBarText>, Without<StatusBarDetail>)>,
+    mut detail: Single<&mut Text, (With<StatusBarDetail>, Without<StatusBarText>)>,
+) {
+    text.0 = status.message().unwrap_or_default().to_string();
+    detail.0 = status.detail().unwrap_or_default().to_string();
+}
@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{ImageMarker, ScanErrors, Selection, TextInput, TextInputSubmitted};
+
+/// Free-form labels on an image, kept in a `<file name>.gamitags` JSON sidecar next to it
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+    /// Split user input like `"beach, sunset,,  2024 "` into tidy tags, dropping blanks and
+    /// repeats
+    pub fn parse(input: &str) -> Self {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in input
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+        {
+            if !tags
+                .iter()
+                .any(|existing| existing.eq_ignore_ascii_case(tag))
+            {
+                tags.push(tag.to_string());
+            }
+        }
+        Self(tags)
+    }
+
+    /// Case-insensitive
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn sidecar_path(image: &Path) -> PathBuf {
+        let mut name = image.file_name().unwrap_or_default().to_os_string();
+        name.push(".gamitags");
+        image.with_file_name(name)
+    }
+
+    /// Read an image's sidecar, `Ok(None)` if it hasn't got one
+    pub fn load(image: &Path) -> io::Result<Option<Self>> {
+        let json = match fs::read_to_string(Self::sidecar_path(image)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let tags = serde_json::from_str(&json)?;
+        Ok(Some(Self(tags)))
+    }
+
+    /// Write an image's sidecar, or remove it once there are no tags left
+    pub fn save(&self, image: &Path) -> io::Result<()> {
+        let sidecar = Self::sidecar_path(image);
+        if self.0.is_empty() {
+            return match fs::remove_file(sidecar) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        fs::write(sidecar, serde_json::to_string_pretty(&self.0)?)
+    }
+}
+
+/// The text field in the info panel that edits the selected image's tags
+#[derive(Component)]
+pub(crate) struct TagInput;
+
+pub(crate) fn tag_input() -> impl Bundle {
+    (
+        TagInput,
+        TextInput::new("Add tags, comma separated"),
+        Node {
+            min_width: Val::Px(200.0),
+            padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+    )
+}
+
+pub struct TagsPlugin;
+
+impl Plugin for TagsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (load_tags_system, commit_tags_system));
+    }
+}
+
+/// Pick up the sidecar for each newly spawned quad
+fn load_tags_system(
+    mut commands: Commands,
+    mut errors: ResMut<ScanErrors>,
+    quads: Query<(Entity, &ImageMarker), Added<ImageMarker>>,
+) {
+    for (entity, marker) in &quads {
+        match Tags::load(&marker.target) {
+            Ok(Some(tags)) => {
+                commands.entity(entity).insert(tags);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                log::warn!("Couldn't read tags for {:?}: {e}", marker.target);
+                errors.push(format!(
+                    "Couldn't read tags for {}: {e}",
+                    marker.target.display()
+                ));
+            }
+        }
+    }
+}
+
+/// Enter in the tag field replaces the selected image's tags and writes the sidecar
+fn commit_tags_system(
+    mut commands: Commands,
+    mut submitted: EventReader<TextInputSubmitted>,
+    mut errors: ResMut<ScanErrors>,
+    selected: Res<Selection>,
+    tag_inputs: Query<(), With<TagInput>>,
+    quads: Query<(Entity, &ImageMarker)>,
+) {
+    for event in submitted.read() {
+        if !tag_inputs.contains(event.entity) {
+            continue;
+        }
+        let Some(path) = selected.path() else {
+            continue;
+        };
+
+        let tags = Tags::parse(&event.value);
+        if let Err(e) = tags.save(path) {
+            log::warn!("Couldn't save tags for {path:?}: {e}");
+            errors.push(format!("Couldn't save tags for {}: {e}", path.display()));
+            continue;
+        }
+        for (entity, marker) in &quads {
+            if marker.target == *path {
+                commands.entity(entity).insert(tags.clone());
+            }
+        }
+    }
+}
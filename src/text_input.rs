@@ -0,0 +1,174 @@
+use bevy::{
+    input::{ButtonState, keyboard::KeyboardInput},
+    prelude::*,
+};
+
+use crate::Themed;
+
+/// A minimal single-line text field. Click it to focus, type, Enter submits (see
+/// [`TextInputSubmitted`]), Escape or clicking elsewhere drops focus. Bevy doesn't ship one yet.
+#[derive(Component, Debug, Clone, Default)]
+#[require(Button)]
+pub struct TextInput {
+    pub value: String,
+    /// Shown instead while the field is empty and unfocused
+    pub placeholder: String,
+}
+
+impl TextInput {
+    pub fn new(placeholder: impl Into<String>) -> Self {
+        Self {
+            value: String::new(),
+            placeholder: placeholder.into(),
+        }
+    }
+}
+
+/// The text field keystrokes are going to, if any. While one is focused the single-key hotkeys
+/// stay out of the way, see [`text_input_inactive`].
+#[derive(Resource, Default, Debug, PartialEq, Eq)]
+pub struct TextInputFocus(pub Option<Entity>);
+
+/// Enter was pressed in a focused [`TextInput`]
+#[derive(Event, Debug, Clone)]
+pub struct TextInputSubmitted {
+    pub entity: Entity,
+    pub value: String,
+}
+
+/// The label child of a [`TextInput`]
+#[derive(Component)]
+struct TextInputLabel;
+
+/// Run condition for hotkeys that would otherwise fire while the user is typing
+pub fn text_input_inactive(focus: Res<TextInputFocus>) -> bool {
+    focus.0.is_none()
+}
+
+pub struct TextInputPlugin;
+
+impl Plugin for TextInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TextInputFocus>();
+        app.add_event::<TextInputSubmitted>();
+        app.add_observer(add_text_input_label);
+        app.add_systems(
+            Update,
+            (
+                focus_text_input_system,
+                edit_text_input_system,
+                sync_text_input_labels,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Give every new text field a label to draw its contents with
+fn add_text_input_label(
+    trigger: Trigger<OnAdd, TextInput>,
+    mut commands: Commands,
+    inputs: Query<&TextInput>,
+) {
+    let placeholder = inputs
+        .get(trigger.target())
+        .map(|input| input.placeholder.clone())
+        .unwrap_or_default();
+    commands.entity(trigger.target()).with_child((
+        TextInputLabel,
+        Text::new(placeholder),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        Themed::Text,
+    ));
+}
+
+fn focus_text_input_system(
+    mut focus: ResMut<TextInputFocus>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    inputs: Query<(Entity, &Interaction), With<TextInput>>,
+) {
+    if !mouse.get_just_pressed().any(|_| true) {
+        return;
+    }
+
+    let clicked = inputs
+        .iter()
+        .find(|(_, interaction)| **interaction == Interaction::Pressed)
+        .map(|(entity, _)| entity);
+    focus.set_if_neq(TextInputFocus(clicked));
+}
+
+fn edit_text_input_system(
+    mut focus: ResMut<TextInputFocus>,
+    mut keyboard: EventReader<KeyboardInput>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut inputs: Query<&mut TextInput>,
+    mut submitted: EventWriter<TextInputSubmitted>,
+) {
+    let Some(entity) = focus.0 else {
+        keyboard.clear();
+        return;
+    };
+    let Ok(mut input) = inputs.get_mut(entity) else {
+        // Focused field got despawned
+        focus.0 = None;
+        return;
+    };
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    for event in keyboard.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        match event.key_code {
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                submitted.write(TextInputSubmitted {
+                    entity,
+                    value: input.value.clone(),
+                });
+            }
+            KeyCode::Escape => {
+                focus.0 = None;
+                return;
+            }
+            KeyCode::Backspace => {
+                input.value.pop();
+            }
+            _ if !ctrl => {
+                if let Some(text) = &event.text {
+                    input.value.extend(text.chars().filter(|c| !c.is_control()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Redraw labels whose field changed, or that gained or lost focus
+fn sync_text_input_labels(
+    focus: Res<TextInputFocus>,
+    inputs: Query<(Entity, Ref<TextInput>, &Children)>,
+    mut labels: Query<&mut Text, With<TextInputLabel>>,
+) {
+    for (entity, input, children) in &inputs {
+        if !input.is_changed() && !focus.is_changed() {
+            continue;
+        }
+
+        let focused = focus.0 == Some(entity);
+        let shown = match (focused, input.value.is_empty()) {
+            (true, _) => format!("{}|", input.value),
+            (false, true) => input.placeholder.clone(),
+            (false, false) => input.value.clone(),
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = labels.get_mut(child) {
+                text.0.clone_from(&shown);
+            }
+        }
+    }
+}
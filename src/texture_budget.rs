@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{
+    AtlasSlot, CompareView, ImageDisplayMaterial, ImageLoadState, ImageMarker, ImageTexture,
+    PlaceholderTexture, Selection, StatusBar, TextureUnloaded, WatchedDirs, load_image,
+};
+
+/// How much texture memory photos may hold on to. Textures still loading count towards it too,
+/// going by their probed size, so room is made before they land rather than after. Past it, the
+/// textures that have gone longest without being on screen are dropped, a few per frame, and
+/// their quads show the [`PlaceholderTexture`] until they come back into view and are loaded
+/// again. Textures on screen are never dropped, so a view that needs more than the budget on its
+/// own still gets it, and neither are the selected images (the one up in the slideshow among
+/// them) or the two being compared.
+#[derive(Resource, Debug, Clone)]
+pub struct TextureBudget {
+    pub max_bytes: u64,
+    /// Cap on evictions per frame so getting back under budget doesn't cause a hitch
+    pub evictions_per_frame: usize,
+}
+
+impl Default for TextureBudget {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1 << 30,
+            evictions_per_frame: 8,
+        }
+    }
+}
+
+/// Approximate texture memory in use by photos, as of the last frame
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureUsage {
+    /// Held by loaded textures
+    pub bytes: u64,
+    pub textures: usize,
+    /// Set aside for the textures still loading whose size we know
+    pub pending_bytes: u64,
+}
+
+impl TextureUsage {
+    /// What's counted against the [`TextureBudget`]
+    pub fn total(&self) -> u64 {
+        self.bytes + self.pending_bytes
+    }
+}
+
+/// When a quad was last drawn, or prefetched ahead of being drawn. The longest gone are evicted
+/// first.
+#[derive(Component, Default)]
+pub(crate) struct LastVisible(pub(crate) Duration);
+
+pub struct TextureBudgetPlugin;
+
+impl Plugin for TextureBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TextureBudget>();
+        app.init_resource::<TextureUsage>();
+        app.add_systems(
+            Update,
+            (
+                track_texture_visibility,
+                enforce_texture_budget,
+                reload_evicted_textures,
+                show_texture_usage.run_if(resource_changed::<TextureUsage>),
+            )
+                .chain(),
+        );
+    }
+}
+
+fn track_texture_visibility(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut quads: Query<(Entity, &ViewVisibility, Option<&mut LastVisible>), With<ImageMarker>>,
+) {
+    let now = time.elapsed();
+    for (entity, visibility, last_visible) in &mut quads {
+        match last_visible {
+            Some(mut last_visible) if visibility.get() => last_visible.0 = now,
+            Some(_) => {}
+            None => {
+                commands.entity(entity).insert(LastVisible(now));
+            }
+        }
+    }
+}
+
+/// Total up loaded and loading textures, and evict the stalest loaded ones while over budget
+fn enforce_texture_budget(
+    budget: Res<TextureBudget>,
+    selected: Res<Selection>,
+    compare: Option<Res<CompareView>>,
+    time: Res<Time<Real>>,
+    images: Res<Assets<Image>>,
+    watched_dirs: Res<WatchedDirs>,
+    placeholder: Res<PlaceholderTexture>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut usage: ResMut<TextureUsage>,
+    mut quads: Query<
+        (
+            &ImageMarker,
+            &mut ImageTexture,
+            &mut ImageLoadState,
+            &MeshMaterial3d<ImageDisplayMaterial>,
+            &LastVisible,
+        ),
+        Without<AtlasSlot>,
+    >,
+) {
+    let mut total = 0;
+    let mut pending_bytes = 0;
+    let mut textures = 0;
+    for (marker, texture, state, ..) in &quads {
+        if *state == ImageLoadState::Pending {
+            if let Some((width, height)) = watched_dirs.dimensions(&marker.target) {
+                pending_bytes += width as u64 * height as u64 * 4;
+            }
+            continue;
+        }
+        if *state != ImageLoadState::Loaded || !texture.0.is_strong() {
+            continue;
+        }
+        let Some(image) = images.get(&texture.0) else {
+            continue;
+        };
+        total += texture_bytes(image);
+        textures += 1;
+    }
+    let mut current = TextureUsage {
+        bytes: total,
+        textures,
+        pending_bytes,
+    };
+
+    if current.total() > budget.max_bytes {
+        let now = time.elapsed();
+        let shown = |path: &PathBuf| {
+            selected.contains(path)
+                || compare
+                    .as_ref()
+                    .is_some_and(|compare| compare.left == *path || compare.right == *path)
+        };
+        let mut stalest: Vec<_> = quads
+            .iter_mut()
+            .filter(|(marker, texture, state, _, last_visible)| {
+                **state == ImageLoadState::Loaded
+                    && texture.0.is_strong()
+                    // Anything on screen right now would only be loaded straight back
+                    && last_visible.0 < now
+                    && !shown(&marker.target)
+            })
+            .filter_map(|(_, texture, state, material, last_visible)| {
+                let bytes = texture_bytes(images.get(&texture.0)?);
+                Some((last_visible.0, bytes, texture, state, material))
+            })
+            .collect();
+        stalest.sort_by_key(|(last_visible, ..)| *last_visible);
+
+        for (_, bytes, mut texture, mut state, material) in
+            stalest.into_iter().take(budget.evictions_per_frame)
+        {
+            if current.total() <= budget.max_bytes {
+                break;
+            }
+
+            // Once nothing holds a strong handle the asset is freed
+            texture.0 = texture.0.clone_weak();
+            if let Some(material) = materials.get_mut(&material.0) {
+                material.base_color_texture = Some(placeholder.0.clone());
+            }
+            *state = ImageLoadState::Evicted;
+            current.bytes -= bytes;
+            current.textures -= 1;
+        }
+    }
+
+    usage.set_if_neq(current);
+}
+
+/// How much memory a loaded texture holds on to
+fn texture_bytes(image: &Image) -> u64 {
+    image
+        .data
+        .as_ref()
+        .map_or(image.width() as u64 * image.height() as u64 * 4, |data| {
+            data.len() as u64
+        })
+}
+
+/// Culling reloads evicted textures when a quad scrolls back into view, this covers the ones that
+/// are drawn again without culling having hidden them in between
+fn reload_evicted_textures(
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut quads: Query<
+        (
+            &ImageMarker,
+            &mut ImageTexture,
+            &mut ImageLoadState,
+            &MeshMaterial3d<ImageDisplayMaterial>,
+            &ViewVisibility,
+        ),
+        (Without<AtlasSlot>, Without<TextureUnloaded>),
+    >,
+) {
+    for (marker, mut texture, mut state, material, visibility) in &mut quads {
+        if *state == ImageLoadState::Evicted && visibility.get() {
+            reload_texture(
+                &asset_server,
+                &mut materials,
+                marker,
+                &mut texture,
+                &mut state,
+                material,
+            );
+        }
+    }
+}
+
+/// Ask for an evicted quad's texture again
+pub(crate) fn reload_texture(
+    asset_server: &AssetServer,
+    materials: &mut Assets<ImageDisplayMaterial>,
+    marker: &ImageMarker,
+    texture: &mut ImageTexture,
+    state: &mut ImageLoadState,
+    material: &MeshMaterial3d<ImageDisplayMaterial>,
+) {
+    // It loaded once already, so it can be asked for again
+    let Ok(handle) = load_image(asset_server, &marker.target) else {
+        return;
+    };
+    texture.0 = handle;
+    *state = ImageLoadState::Pending;
+    if let Some(material) = materials.get_mut(&material.0) {
+        material.base_color_texture = Some(texture.0.clone());
+    }
+}
+
+fn show_texture_usage(
+    usage: Res<TextureUsage>,
+    budget: Res<TextureBudget>,
+    mut status: ResMut<StatusBar>,
+) {
+    const MIB: u64 = 1 << 20;
+    status.set_detail(format!(
+        "Textures: {} MiB of {} MiB",
+        usage.total() / MIB,
+        budget.max_bytes / MIB
+    ));
+}
@@ -0,0 +1,221 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, text_input_inactive};
+
+/// Which of the built-in themes is active
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ThemeKind {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Every colour the app draws with. Swap the resource out (or mutate it) and already spawned UI
+/// picks up the change.
+#[derive(Resource, Debug, Clone)]
+pub struct UiTheme {
+    pub kind: ThemeKind,
+    /// Clear colour of the 3D scene
+    pub background: Color,
+    pub panel: Color,
+    pub button_normal: Color,
+    pub button_hovered: Color,
+    pub button_pressed: Color,
+    pub border: Color,
+    pub border_hovered: Color,
+    pub border_pressed: Color,
+    pub text: Color,
+    pub accent: Color,
+    /// Highlight for selected images in the grid
+    pub selection: Color,
+}
+
+impl Default for UiTheme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl UiTheme {
+    pub fn dark() -> Self {
+        Self {
+            kind: ThemeKind::Dark,
+            background: Color::srgb_u8(43, 44, 47),
+            panel: Color::srgb(0.15, 0.15, 0.15),
+            button_normal: Color::srgb(0.15, 0.15, 0.15),
+            button_hovered: Color::srgb(0.25, 0.25, 0.25),
+            button_pressed: Color::srgb(0.35, 0.75, 0.35),
+            border: Color::BLACK,
+            border_hovered: Color::WHITE,
+            border_pressed: Color::srgb(1.0, 0.0, 0.0),
+            text: Color::srgb(0.9, 0.9, 0.9),
+            accent: Color::srgb(0.35, 0.75, 0.35),
+            selection: Color::srgb(1.0, 0.8, 0.2),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            kind: ThemeKind::Light,
+            background: Color::srgb(0.82, 0.82, 0.82),
+            panel: Color::srgb(0.93, 0.93, 0.93),
+            button_normal: Color::srgb(0.88, 0.88, 0.88),
+            button_hovered: Color::srgb(0.8, 0.8, 0.8),
+            button_pressed: Color::srgb(0.55, 0.8, 0.55),
+            border: Color::srgb(0.6, 0.6, 0.6),
+            border_hovered: Color::srgb(0.2, 0.2, 0.2),
+            border_pressed: Color::srgb(0.8, 0.1, 0.1),
+            text: Color::srgb(0.1, 0.1, 0.1),
+            accent: Color::srgb(0.2, 0.55, 0.2),
+            selection: Color::srgb(0.1, 0.4, 0.9),
+        }
+    }
+
+    pub fn from_kind(kind: ThemeKind) -> Self {
+        match kind {
+            ThemeKind::Dark => Self::dark(),
+            ThemeKind::Light => Self::light(),
+        }
+    }
+
+    /// The other built-in theme
+    pub fn toggled(&self) -> Self {
+        match self.kind {
+            ThemeKind::Dark => Self::light(),
+            ThemeKind::Light => Self::dark(),
+        }
+    }
+
+    /// Background and border colour for a button in the given interaction state
+    pub fn button_colors(&self, interaction: Interaction) -> (Color, Color) {
+        match interaction {
+            Interaction::Pressed => (self.button_pressed, self.border_pressed),
+            Interaction::Hovered => (self.button_hovered, self.border_hovered),
+            Interaction::None => (self.button_normal, self.border),
+        }
+    }
+}
+
+/// A scene background that wins over [`UiTheme::background`], for judging photos against the same
+/// backdrop whichever theme the UI is in. `None` follows the theme.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct SceneBackground(pub Option<Color>);
+
+impl SceneBackground {
+    /// What B steps through after the theme's own background: black, 18% grey and white
+    pub const PRESETS: [Color; 3] = [Color::BLACK, Color::srgb(0.46, 0.46, 0.46), Color::WHITE];
+
+    /// The next of the [`Self::PRESETS`], back round to the theme after the last. A custom colour
+    /// goes back to the theme too.
+    pub fn cycled(&self) -> Self {
+        let next = match self.0 {
+            None => Some(0),
+            Some(color) => Self::PRESETS
+                .iter()
+                .position(|preset| *preset == color)
+                .map(|i| i + 1)
+                .filter(|&i| i < Self::PRESETS.len()),
+        };
+        Self(next.map(|i| Self::PRESETS[i]))
+    }
+
+    /// The colour the scene gets cleared to
+    pub fn resolve(&self, theme: &UiTheme) -> Color {
+        self.0.unwrap_or(theme.background)
+    }
+}
+
+/// Which theme colour a node follows. Roles on plain nodes drive `BackgroundColor`, `Text`
+/// drives the `TextColor`. Buttons don't need one, they're styled from their `Interaction`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Themed {
+    Background,
+    Panel,
+    Accent,
+    Text,
+}
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UiTheme>();
+        app.init_resource::<SceneBackground>();
+        app.add_systems(PostUpdate, (apply_theme, apply_theme_to_buttons));
+        app.add_systems(
+            Update,
+            (toggle_theme_hotkey_system, cycle_background_hotkey_system)
+                .run_if(in_state(AppState::Running).and(text_input_inactive)),
+        );
+    }
+}
+
+/// Colour newly themed nodes, and recolour everything when the theme changes
+fn apply_theme(
+    theme: Res<UiTheme>,
+    scene_background: Res<SceneBackground>,
+    mut clear_color: ResMut<ClearColor>,
+    mut nodes: Query<(
+        Ref<Themed>,
+        Option<&mut BackgroundColor>,
+        Option<&mut TextColor>,
+    )>,
+) {
+    if theme.is_changed() || scene_background.is_changed() {
+        clear_color.0 = scene_background.resolve(&theme);
+    }
+
+    for (role, background, text) in &mut nodes {
+        if !theme.is_changed() && !role.is_added() {
+            continue;
+        }
+
+        let color = match *role {
+            Themed::Text => {
+                if let Some(mut text) = text {
+                    text.0 = theme.text;
+                }
+                continue;
+            }
+            Themed::Background => theme.background,
+            Themed::Panel => theme.panel,
+            Themed::Accent => theme.accent,
+        };
+        if let Some(mut background) = background {
+            background.0 = color;
+        }
+    }
+}
+
+fn apply_theme_to_buttons(
+    theme: Res<UiTheme>,
+    mut buttons: Query<(&Interaction, &mut BackgroundColor, &mut BorderColor), With<Button>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    for (interaction, mut background, mut border) in &mut buttons {
+        let (color, border_color) = theme.button_colors(*interaction);
+        background.0 = color;
+        border.0 = border_color;
+    }
+}
+
+/// T flips between the dark and light themes
+fn toggle_theme_hotkey_system(keys: Res<ButtonInput<KeyCode>>, mut theme: ResMut<UiTheme>) {
+    if keys.just_pressed(KeyCode::KeyT) {
+        *theme = theme.toggled();
+    }
+}
+
+/// B steps the scene background through the neutral presets and back to the theme's
+fn cycle_background_hotkey_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut scene_background: ResMut<SceneBackground>,
+) {
+    if keys.just_pressed(KeyCode::KeyB) {
+        *scene_background = scene_background.cycled();
+    }
+}
@@ -0,0 +1,300 @@
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
+};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{
+    AtlasSlot, DirWatchingSet, ImageDisplayMaterial, ImageLoadState, ImageMarker, ImageTexture,
+    WatchedDirs, load_image,
+};
+
+/// Show the grid's images as shrunk-down copies kept in `dir`, rather than as themselves. Each
+/// image is decoded and shrunk in the background the first time it's seen, and the copy swapped
+/// in once it's there; after that it's read straight from `dir`, so a grid of thousands of photos
+/// only ever holds thumbnail-sized textures. Thumbnails are named after the image's path and
+/// modification time, so a rewritten image gets a new one.
+///
+/// Images that are already no bigger than `max_edge` are shown as they are, and so are ones the
+/// `image` crate can't read.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct ThumbnailCache {
+    /// Where thumbnails are kept, `None` to show the images themselves
+    pub dir: Option<PathBuf>,
+    /// Thumbnails are shrunk to fit in a square this many pixels across
+    pub max_edge: u32,
+}
+
+impl Default for ThumbnailCache {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_edge: 512,
+        }
+    }
+}
+
+impl ThumbnailCache {
+    /// Thumbnails being made at once, each holds a whole decoded image while it is
+    const MAX_MAKING: usize = 8;
+
+    pub fn enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+}
+
+/// Every thumbnail found or made so far, by image, with the modification time of the image it was
+/// made from. Quads spawned again get theirs without looking, and the catalog keeps these between
+/// launches.
+#[derive(Resource, Default)]
+pub(crate) struct KnownThumbnails(pub(crate) HashMap<PathBuf, (PathBuf, Option<SystemTime>)>);
+
+/// Where a quad's thumbnail is at
+#[derive(Component)]
+enum Thumbnail {
+    Making(Task<Result<(PathBuf, Option<SystemTime>), String>>),
+    /// On disk at `file`, made from the image as it was when it was last modified at `modified`
+    Ready {
+        file: PathBuf,
+        modified: Option<SystemTime>,
+    },
+    /// Couldn't be made, so the quad shows the image itself
+    Failed,
+}
+
+pub struct ThumbnailPlugin;
+
+impl Plugin for ThumbnailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ThumbnailCache>();
+        app.init_resource::<KnownThumbnails>();
+        app.add_systems(
+            Update,
+            (
+                expire_thumbnails.run_if(resource_changed::<WatchedDirs>),
+                queue_thumbnails,
+                poll_thumbnails,
+                show_thumbnails,
+            )
+                .chain()
+                .after(DirWatchingSet::SpawnQuads)
+                .run_if(|cache: Res<ThumbnailCache>| cache.enabled()),
+        );
+    }
+}
+
+/// Forget the thumbnails of images that have been rewritten since, so they're made again
+fn expire_thumbnails(
+    mut commands: Commands,
+    watched_dirs: Res<WatchedDirs>,
+    quads: Query<(Entity, &ImageMarker, &Thumbnail)>,
+) {
+    for (entity, marker, thumbnail) in &quads {
+        if let Thumbnail::Ready { modified, .. } = thumbnail
+            && let Some(stamp) = watched_dirs.stamp(&marker.target)
+            && stamp.modified != *modified
+        {
+            commands.entity(entity).remove::<Thumbnail>();
+        }
+    }
+}
+
+/// Start finding (or making) the thumbnails of quads that haven't got one yet, a few at a time.
+/// Ones already known of, from the image as it is now, are shown straight away.
+fn queue_thumbnails(
+    mut commands: Commands,
+    cache: Res<ThumbnailCache>,
+    known: Res<KnownThumbnails>,
+    watched_dirs: Res<WatchedDirs>,
+    thumbnails: Query<&Thumbnail>,
+    quads: Query<(Entity, &ImageMarker), Without<Thumbnail>>,
+) {
+    let Some(dir) = &cache.dir else {
+        return;
+    };
+    let making = thumbnails
+        .iter()
+        .filter(|thumbnail| matches!(thumbnail, Thumbnail::Making(_)))
+        .count();
+    let mut room = ThumbnailCache::MAX_MAKING.saturating_sub(making);
+    let pool = AsyncComputeTaskPool::get();
+    for (entity, marker) in &quads {
+        if let Some((file, modified)) = known.0.get(&marker.target)
+            && watched_dirs
+                .stamp(&marker.target)
+                .is_none_or(|stamp| stamp.modified == *modified)
+        {
+            commands.entity(entity).insert(Thumbnail::Ready {
+                file: file.clone(),
+                modified: *modified,
+            });
+            continue;
+        }
+        if room == 0 {
+            continue;
+        }
+        room -= 1;
+        let (image, dir, max_edge) = (marker.target.clone(), dir.clone(), cache.max_edge);
+        let task = pool.spawn(async move { make_thumbnail(&image, &dir, max_edge) });
+        commands.entity(entity).insert(Thumbnail::Making(task));
+    }
+}
+
+fn poll_thumbnails(
+    mut known: ResMut<KnownThumbnails>,
+    mut quads: Query<(&ImageMarker, &mut Thumbnail)>,
+) {
+    for (marker, mut thumbnail) in &mut quads {
+        // Only marked changed once it's done
+        let Thumbnail::Making(task) = thumbnail.bypass_change_detection() else {
+            continue;
+        };
+        let Some(result) = block_on(future::poll_once(task)) else {
+            continue;
+        };
+        *thumbnail = match result {
+            Ok((file, modified)) => {
+                known
+                    .0
+                    .insert(marker.target.clone(), (file.clone(), modified));
+                Thumbnail::Ready { file, modified }
+            }
+            Err(e) => {
+                log::debug!(
+                    "No thumbnail for {:?}, showing it as it is: {e}",
+                    marker.target
+                );
+                Thumbnail::Failed
+            }
+        };
+    }
+}
+
+/// Point quads at their thumbnail once it's ready, and again whenever their texture gets loaded
+/// from the image itself (coming back after being evicted, say). Evicted quads are left for
+/// whatever brings them back, and packed ones already are a thumbnail.
+fn show_thumbnails(
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut quads: Query<
+        (
+            &Thumbnail,
+            &mut ImageTexture,
+            &mut ImageLoadState,
+            Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+            Option<&mut Sprite>,
+        ),
+        (
+            Or<(Changed<Thumbnail>, Changed<ImageTexture>)>,
+            Without<AtlasSlot>,
+        ),
+    >,
+) {
+    for (thumbnail, mut texture, mut state, material, sprite) in &mut quads {
+        let Thumbnail::Ready { file, .. } = thumbnail else {
+            continue;
+        };
+        if matches!(*state, ImageLoadState::Evicted | ImageLoadState::Failed) {
+            continue;
+        }
+        if asset_server
+            .get_path(texture.0.id())
+            .is_some_and(|path| path.path() == file.as_path())
+        {
+            continue;
+        }
+        let Ok(handle) = load_image(&asset_server, file) else {
+            continue;
+        };
+        texture.0 = handle;
+        *state = ImageLoadState::Pending;
+        if let Some(material) = material.and_then(|material| materials.get_mut(&material.0)) {
+            material.base_color_texture = Some(texture.0.clone());
+        }
+        if let Some(mut sprite) = sprite {
+            sprite.image = texture.0.clone();
+        }
+    }
+}
+
+/// The name a thumbnail of `image` goes by in the cache, without its extension
+fn cache_key(image: &Path, modified: Option<SystemTime>, len: u64, max_edge: u32) -> String {
+    let mut hasher = DefaultHasher::new();
+    image.hash(&mut hasher);
+    modified.hash(&mut hasher);
+    len.hash(&mut hasher);
+    max_edge.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// The whole of `image`, through our own loaders for the formats the `image` crate doesn't know
+fn decode(image: &Path) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    #[cfg(feature = "raw")]
+    if crate::raw::is_raw(image) {
+        return Ok(crate::raw::decode_preview(&fs::read(image)?)?);
+    }
+    #[cfg(feature = "heic")]
+    if crate::heic::is_heic(image) {
+        return Ok(crate::heic::decode_heic(&fs::read(image)?)?);
+    }
+    #[cfg(feature = "video")]
+    if crate::video::is_video(image) {
+        return Ok(DynamicImage::ImageRgba8(crate::video::poster_frame(image)?));
+    }
+    Ok(image::open(image)?)
+}
+
+/// Find the thumbnail of `image` in `dir`, making it if it isn't there yet. Returns where it is
+/// and when the image it's of was last modified. Runs on the [`AsyncComputeTaskPool`].
+fn make_thumbnail(
+    image: &Path,
+    dir: &Path,
+    max_edge: u32,
+) -> Result<(PathBuf, Option<SystemTime>), String> {
+    let meta =
+        fs::metadata(image).map_err(|e| format!("Couldn't read {}: {e}", image.display()))?;
+    let modified = meta.modified().ok();
+    let key = cache_key(image, modified, meta.len(), max_edge);
+    // Photos are kept as JPEG, anything with transparency as PNG
+    for ext in ["jpg", "png"] {
+        let cached = dir.join(format!("{key}.{ext}"));
+        if cached.is_file() {
+            return Ok((cached, modified));
+        }
+    }
+
+    let decoded = decode(image).map_err(|e| format!("Couldn't decode it: {e}"))?;
+    let (width, height) = decoded.dimensions();
+    if width <= max_edge && height <= max_edge {
+        return Ok((image.to_path_buf(), modified));
+    }
+    let thumbnail = decoded.thumbnail(max_edge, max_edge);
+    let (thumbnail, format, ext) = if thumbnail.color().has_alpha() {
+        (thumbnail, ImageFormat::Png, "png")
+    } else {
+        // The JPEG encoder won't take an alpha channel, even an unused one
+        let rgb = DynamicImage::ImageRgb8(thumbnail.to_rgb8());
+        (rgb, ImageFormat::Jpeg, "jpg")
+    };
+
+    let cached = dir.join(format!("{key}.{ext}"));
+    let failed = |e: &dyn std::fmt::Display| format!("Couldn't write {}: {e}", cached.display());
+    fs::create_dir_all(dir).map_err(|e| failed(&e))?;
+    // Written alongside and moved into place, so a half-written thumbnail is never picked up
+    let mut file = tempfile::NamedTempFile::new_in(dir).map_err(|e| failed(&e))?;
+    let mut writer = BufWriter::new(file.as_file_mut());
+    thumbnail
+        .write_to(&mut writer, format)
+        .map_err(|e| failed(&e))?;
+    writer.flush().map_err(|e| failed(&e))?;
+    drop(writer);
+    file.persist(&cached).map_err(|e| failed(&e.error))?;
+    Ok((cached, modified))
+}
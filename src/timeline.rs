@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{
+    CurrentPage, DirWatchingSet, GridConfig, GridLayout, GridPlacement, RescanRequested,
+    ScanConfig, Selection, Themed, ViewMode, WatchedDirs, paging::update_current_page,
+    text_input_inactive,
+};
+
+/// How much time each row of the [`GridLayout::Timeline`] covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimelineBucket {
+    #[default]
+    Day,
+    Month,
+    Year,
+}
+
+/// Where an image's date on the [`Timeline`] came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateSource {
+    /// When it was taken, from its EXIF data
+    Exif,
+    /// The file's modification time, for images that don't say when they were taken. Often when
+    /// they were copied rather than taken.
+    FileModified,
+}
+
+/// Where each image on the [`CurrentPage`] goes in the [`GridLayout::Timeline`], and the groups
+/// they fall into. Empty with any other layout.
+///
+/// Images are grouped by when they were taken (see [`WatchedDirs::capture_date`]), falling back
+/// to when they were modified, oldest group first, with the images that have no date at all in an
+/// "Unknown" group at the end. Each group starts a new row, wrapping onto more rows once it's
+/// wider than a square grid of the whole page would be, and there's an empty row between groups
+/// for their labels.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Timeline {
+    cells: HashMap<PathBuf, Vec2>,
+    groups: Vec<TimelineGroup>,
+    /// Index into `groups` of each image's group
+    group_of: HashMap<PathBuf, usize>,
+    /// Columns and rows, gaps included
+    dimensions: Option<(i32, i32)>,
+}
+
+/// A run of images from the same day (or month, or year)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineGroup {
+    /// `2024-03-15`, `2024-03`, `2024`, or "Unknown"
+    pub label: String,
+    /// Cell of the group's first image, see [`crate::calculate_grid_position_2d`]
+    pub first: Vec2,
+    pub first_image: PathBuf,
+    pub len: usize,
+    /// How many of them are only dated by [`DateSource::FileModified`]
+    pub file_dated: usize,
+}
+
+impl Timeline {
+    /// Lay out `images`, dated by `date`
+    pub fn new(
+        images: &[PathBuf],
+        date: impl Fn(&Path) -> Option<(SystemTime, DateSource)>,
+        bucket: TimelineBucket,
+        spacing: f32,
+    ) -> Self {
+        let mut dated: BTreeMap<(i64, u32, u32), Vec<&PathBuf>> = BTreeMap::new();
+        let mut unknown = vec![];
+        let mut file_dated = HashSet::new();
+        for path in images {
+            match date(path) {
+                Some((date, source)) => {
+                    let (year, month, day) = civil_from_time(date);
+                    let key = match bucket {
+                        TimelineBucket::Day => (year, month, day),
+                        TimelineBucket::Month => (year, month, 0),
+                        TimelineBucket::Year => (year, 0, 0),
+                    };
+                    dated.entry(key).or_default().push(path);
+                    if source == DateSource::FileModified {
+                        file_dated.insert(path);
+                    }
+                }
+                None => unknown.push(path),
+            }
+        }
+        let mut groups: Vec<(String, Vec<&PathBuf>)> = dated
+            .into_iter()
+            .map(|((year, month, day), paths)| {
+                let label = match bucket {
+                    TimelineBucket::Day => format!("{year:04}-{month:02}-{day:02}"),
+                    TimelineBucket::Month => format!("{year:04}-{month:02}"),
+                    TimelineBucket::Year => format!("{year:04}"),
+                };
+                (label, paths)
+            })
+            .collect();
+        if !unknown.is_empty() {
+            groups.push(("Unknown".to_string(), unknown));
+        }
+
+        let columns = (images.len().max(1) as f32).sqrt().ceil() as usize;
+        let mut rows = 0;
+        let mut placed = vec![];
+        for (index, (label, paths)) in groups.into_iter().enumerate() {
+            if index > 0 {
+                rows += 1;
+            }
+            let cells: Vec<(&PathBuf, usize, usize)> = paths
+                .iter()
+                .enumerate()
+                .map(|(index, path)| (*path, index % columns, rows + index / columns))
+                .collect();
+            rows += paths.len().div_ceil(columns);
+            placed.push((label, cells));
+        }
+
+        // Centred on the origin, like the other layouts
+        let offset = Vec2::new(columns as f32 - 1.0, rows as f32 - 1.0) * spacing * 0.5;
+        let cell = |column: usize, row: usize| {
+            Vec2::new(
+                column as f32 * spacing - offset.x,
+                offset.y - row as f32 * spacing,
+            )
+        };
+        let mut timeline = Self {
+            dimensions: Some((columns as i32, rows as i32)),
+            ..default()
+        };
+        for (index, (label, cells)) in placed.into_iter().enumerate() {
+            let &(first_image, column, row) = cells.first().expect("groups aren't empty");
+            timeline.groups.push(TimelineGroup {
+                label,
+                first: cell(column, row),
+                first_image: first_image.clone(),
+                len: cells.len(),
+                file_dated: cells
+                    .iter()
+                    .filter(|(path, ..)| file_dated.contains(path))
+                    .count(),
+            });
+            for (path, column, row) in cells {
+                timeline.cells.insert(path.clone(), cell(column, row));
+                timeline.group_of.insert(path.clone(), index);
+            }
+        }
+        timeline
+    }
+
+    /// Where `image` goes, `None` if it's not on the timeline
+    pub fn cell(&self, image: &Path) -> Option<Vec2> {
+        self.cells.get(image).copied()
+    }
+
+    pub fn groups(&self) -> &[TimelineGroup] {
+        &self.groups
+    }
+
+    /// Index into [`Self::groups`] of the group `image` is in
+    pub fn group_of(&self, image: &Path) -> Option<usize> {
+        self.group_of.get(image).copied()
+    }
+
+    /// How many columns and rows the timeline takes up, counting the gaps between groups. `None`
+    /// with any other layout.
+    pub fn dimensions(&self) -> Option<(i32, i32)> {
+        self.dimensions
+    }
+}
+
+/// `[` and `]` select the first image of the previous and next [`TimelineGroup`]
+fn jump_to_group_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    timeline: Res<Timeline>,
+    mut selection: ResMut<Selection>,
+) {
+    let step: isize = if keys.just_pressed(KeyCode::BracketLeft) {
+        -1
+    } else if keys.just_pressed(KeyCode::BracketRight) {
+        1
+    } else {
+        return;
+    };
+    let Some(last) = timeline.groups().len().checked_sub(1) else {
+        return;
+    };
+    let target = match selection.path().and_then(|path| timeline.group_of(path)) {
+        Some(current) => current.saturating_add_signed(step).min(last),
+        // Start from whichever end the key points away from
+        None if step < 0 => last,
+        None => 0,
+    };
+    let first_image = &timeline.groups()[target].first_image;
+    if selection.len() != 1 || selection.path() != Some(first_image) {
+        selection.select(first_image.clone());
+    }
+}
+
+/// The date over a [`TimelineGroup`], shown in the gap above its first row
+#[derive(Component)]
+struct TimelineLabel {
+    /// Cell of the label's top-left corner
+    corner: Vec2,
+}
+
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timeline>();
+        app.add_systems(
+            PreUpdate,
+            update_timeline
+                .run_if(
+                    resource_changed::<CurrentPage>
+                        .or(resource_changed::<GridConfig>)
+                        .or(resource_changed::<WatchedDirs>),
+                )
+                .after(update_current_page)
+                .before(DirWatchingSet::SpawnQuads),
+        );
+        app.add_systems(
+            Update,
+            (
+                read_capture_dates.run_if(resource_changed::<GridConfig>),
+                sync_timeline_labels.run_if(resource_changed::<Timeline>),
+                position_timeline_labels,
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Update,
+            jump_to_group_system.run_if(in_state(ViewMode::Grid).and(text_input_inactive)),
+        );
+    }
+}
+
+/// Capture dates aren't read unless something needs them, so switching to the timeline turns
+/// them on and scans again to fetch them
+fn read_capture_dates(
+    grid_config: Res<GridConfig>,
+    mut scan_config: ResMut<ScanConfig>,
+    mut rescans: EventWriter<RescanRequested>,
+) {
+    if grid_config.layout == GridLayout::Timeline && !scan_config.capture_dates {
+        scan_config.capture_dates = true;
+        rescans.write(RescanRequested::all());
+    }
+}
+
+fn update_timeline(
+    page: Res<CurrentPage>,
+    grid_config: Res<GridConfig>,
+    watched_dirs: Res<WatchedDirs>,
+    mut timeline: ResMut<Timeline>,
+) {
+    if grid_config.layout != GridLayout::Timeline {
+        timeline.set_if_neq(Timeline::default());
+        return;
+    }
+    // Playlist images aren't scanned, so there's no stamp to get their modification time from
+    let date = |path: &Path| match watched_dirs.exif_date(path) {
+        Some(taken) => Some((taken, DateSource::Exif)),
+        None => watched_dirs
+            .capture_date(path)
+            .or_else(|| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+            .map(|modified| (modified, DateSource::FileModified)),
+    };
+    timeline.set_if_neq(Timeline::new(
+        page.images(),
+        date,
+        grid_config.timeline,
+        grid_config.spacing,
+    ));
+}
+
+fn sync_timeline_labels(
+    mut commands: Commands,
+    timeline: Res<Timeline>,
+    grid_config: Res<GridConfig>,
+    labels: Query<Entity, With<TimelineLabel>>,
+) {
+    for label in &labels {
+        commands.entity(label).despawn();
+    }
+    let half = grid_config.quad_size * 0.5;
+    for group in timeline.groups() {
+        let corner = group.first + Vec2::new(-half, grid_config.spacing - half);
+        commands.spawn((
+            TimelineLabel { corner },
+            Node {
+                position_type: PositionType::Absolute,
+                padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                display: Display::None,
+                ..default()
+            },
+            BorderRadius::all(Val::Px(3.0)),
+            Themed::Panel,
+            children![(
+                Text::new(match group.file_dated {
+                    0 => format!("{} ({})", group.label, group.len),
+                    file_dated =>
+                        format!("{} ({}, {file_dated} by file date)", group.label, group.len),
+                }),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                Themed::Text,
+            )],
+        ));
+    }
+}
+
+/// Pin the labels to their place in the world, hiding the ones that aren't on screen
+fn position_timeline_labels(
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    placement: GridPlacement,
+    mut labels: Query<(&TimelineLabel, &mut Node)>,
+) {
+    let (camera, camera_transform) = *camera;
+    for (label, mut node) in &mut labels {
+        let corner = placement.place(label.corner).translation;
+        match camera.world_to_viewport(camera_transform, corner) {
+            Ok(position) => {
+                node.display = Display::Flex;
+                node.left = Val::Px(position.x);
+                node.top = Val::Px(position.y);
+            }
+            Err(_) => node.display = Display::None,
+        }
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // Howard Hinnant's algorithm, with years starting in March so the leap day comes last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The (year, month, day) of a number of days since 1970-01-01, the other way from
+/// [`days_from_civil`]
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Whole seconds since 1970-01-01, negative before then
+pub(crate) fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+    }
+}
+
+/// The UTC date of `time`
+fn civil_from_time(time: SystemTime) -> (i64, u32, u32) {
+    civil_from_days(unix_seconds(time).div_euclid(86_400))
+}
@@ -0,0 +1,401 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, RenderAssetUsages, io::Reader},
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    window::RequestRedraw,
+};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::{
+    Rational, decoder, format::Pixel, media, software::scaling, util::frame::video::Video,
+};
+use image::{DynamicImage, RgbaImage};
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::{DirWatchingSet, ImageDisplayMaterial, ImageMarker, ImageTexture, atlas::KeepUnpacked};
+
+pub(crate) const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "m4v", "mkv", "webm", "avi"];
+
+/// Frames (posters included) are shrunk to fit in a square this many pixels across, plenty for a
+/// quad in the grid
+const MAX_EDGE: u32 = 1280;
+
+/// Frames decoded ahead of the one showing
+const FRAMES_AHEAD: usize = 4;
+
+/// Shows video clips in the grid by their first frame, through ffmpeg, which has to be installed
+/// for the `video` feature to build. Clicking a video's quad plays it there, without sound, and
+/// clicking again (or letting it finish) goes back to the poster.
+pub struct VideoPlugin;
+
+impl Plugin for VideoPlugin {
+    fn build(&self, app: &mut App) {
+        if let Err(e) = ffmpeg::init() {
+            log::warn!("Couldn't start ffmpeg, videos won't load: {e}");
+        }
+        app.init_asset_loader::<VideoPosterLoader>();
+        app.add_observer(toggle_video_on_click);
+        app.add_systems(
+            Update,
+            (keep_videos_unpacked, play_videos).after(DirWatchingSet::SpawnQuads),
+        );
+    }
+}
+
+/// On a video's quad while it's playing. Frames are decoded a few ahead on a thread of their own
+/// and drawn into `image`, which the quad shows instead of its poster. Removing this stops the
+/// thread.
+#[derive(Component)]
+pub struct VideoPlayback {
+    image: Handle<Image>,
+    /// Only ever read from one system, but components have to be `Sync`
+    frames: Mutex<Receiver<VideoFrame>>,
+    /// Received, but not due yet
+    next: Option<VideoFrame>,
+    /// How far into the video it's got
+    position: Duration,
+}
+
+struct VideoFrame {
+    image: RgbaImage,
+    /// When it's shown, from the start of the video
+    at: Duration,
+}
+
+#[derive(Default)]
+struct VideoPosterLoader;
+
+impl AssetLoader for VideoPosterLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = ffmpeg::Error;
+
+    async fn load(
+        &self,
+        _reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Image, ffmpeg::Error> {
+        // ffmpeg opens the file itself rather than reading it from the asset reader
+        let poster = poster_frame(load_context.path())?;
+        Ok(Image::from_dynamic(
+            DynamicImage::ImageRgba8(poster),
+            true,
+            RenderAssetUsages::default(),
+        ))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        VIDEO_EXTENSIONS
+    }
+}
+
+pub(crate) fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// A video plays in its quad's own material, which packing it would swap for a shared one
+fn keep_videos_unpacked(
+    mut commands: Commands,
+    quads: Query<(Entity, &ImageMarker), Added<ImageMarker>>,
+) {
+    for (entity, marker) in &quads {
+        if is_video(&marker.target) {
+            commands.entity(entity).insert(KeepUnpacked);
+        }
+    }
+}
+
+/// Clicking a video's quad starts it playing, or stops it if it already is. Ctrl and Shift clicks
+/// are left to the selection.
+fn toggle_video_on_click(
+    trigger: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut quads: Query<(
+        &ImageMarker,
+        &ImageTexture,
+        Has<VideoPlayback>,
+        Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+        Option<&mut Sprite>,
+    )>,
+) {
+    let modified = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::ShiftLeft,
+        KeyCode::ShiftRight,
+    ]);
+    if trigger.button != PointerButton::Primary || modified {
+        return;
+    }
+    let entity = trigger.target();
+    let Ok((marker, texture, playing, material, sprite)) = quads.get_mut(entity) else {
+        return;
+    };
+    if !is_video(&marker.target) {
+        return;
+    }
+    if playing {
+        commands.entity(entity).remove::<VideoPlayback>();
+        show_poster(texture, material, sprite, &mut materials);
+        return;
+    }
+
+    let (sender, receiver) = mpsc::sync_channel(FRAMES_AHEAD);
+    let path = marker.target.clone();
+    let spawned = thread::Builder::new()
+        .name("video decoder".into())
+        .spawn(move || play_video(path, sender));
+    if let Err(e) = spawned {
+        log::warn!("Couldn't start playing {:?}: {e}", marker.target);
+        return;
+    }
+    // Filled in by the first frame, the poster stays up until then
+    let image = images.add(Image::new_fill(
+        Extent3d::default(),
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    ));
+    commands.entity(entity).insert(VideoPlayback {
+        image,
+        frames: Mutex::new(receiver),
+        next: None,
+        position: Duration::ZERO,
+    });
+}
+
+/// Put the quad's own texture back once its video stops
+fn show_poster(
+    texture: &ImageTexture,
+    material: Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+    sprite: Option<Mut<Sprite>>,
+    materials: &mut Assets<ImageDisplayMaterial>,
+) {
+    if let Some(material) = material.and_then(|material| materials.get_mut(&material.0)) {
+        material.base_color_texture = Some(texture.0.clone());
+    }
+    if let Some(mut sprite) = sprite {
+        sprite.image = texture.0.clone();
+    }
+}
+
+/// Draw each playing video's frames as they come due, and stop the ones that have finished
+fn play_videos(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<ImageDisplayMaterial>>,
+    mut redraw: EventWriter<RequestRedraw>,
+    mut quads: Query<(
+        Entity,
+        &mut VideoPlayback,
+        &ImageTexture,
+        Option<&MeshMaterial3d<ImageDisplayMaterial>>,
+        Option<&mut Sprite>,
+    )>,
+) {
+    let mut playing = false;
+    for (entity, mut playback, texture, material, sprite) in &mut quads {
+        let playback = &mut *playback;
+        playback.position += time.delta();
+        // Only the latest of the frames that are due gets drawn, the rest are too late
+        let mut due = None;
+        let mut finished = false;
+        loop {
+            if playback.next.is_none() {
+                let frames = playback.frames.get_mut().unwrap_or_else(|e| e.into_inner());
+                match frames.try_recv() {
+                    Ok(frame) => playback.next = Some(frame),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        finished = due.is_none();
+                        break;
+                    }
+                }
+            }
+            if playback
+                .next
+                .as_ref()
+                .is_some_and(|frame| frame.at <= playback.position)
+            {
+                due = playback.next.take();
+            } else {
+                break;
+            }
+        }
+
+        if finished {
+            commands.entity(entity).remove::<VideoPlayback>();
+            show_poster(texture, material, sprite, &mut materials);
+            continue;
+        }
+        playing = true;
+        let Some(frame) = due else {
+            continue;
+        };
+        if let Some(image) = images.get_mut(&playback.image) {
+            *image = Image::from_dynamic(
+                DynamicImage::ImageRgba8(frame.image),
+                true,
+                RenderAssetUsages::default(),
+            );
+        }
+        if let Some(material) = material.and_then(|material| materials.get_mut(&material.0))
+            && material.base_color_texture.as_ref() != Some(&playback.image)
+        {
+            material.base_color_texture = Some(playback.image.clone());
+        }
+        if let Some(mut sprite) = sprite
+            && sprite.image != playback.image
+        {
+            sprite.image = playback.image.clone();
+        }
+    }
+    // Nothing else would wake the app up for the next frame
+    if playing {
+        redraw.write(RequestRedraw);
+    }
+}
+
+/// Decode the video at `path` from the start, sending it frame by frame until it ends or nobody's
+/// listening any more. Runs on its own thread, see [`VideoPlayback`].
+fn play_video(path: PathBuf, frames: SyncSender<VideoFrame>) {
+    let result = VideoDecoder::open(&path)
+        .and_then(|mut decoder| decoder.each_frame(|frame| frames.send(frame).is_ok()));
+    if let Err(e) = result {
+        log::warn!("Couldn't play {path:?}: {e}");
+    }
+}
+
+/// The first frame of the video at `path`
+pub(crate) fn poster_frame(path: &Path) -> Result<RgbaImage, ffmpeg::Error> {
+    let mut poster = None;
+    VideoDecoder::open(path)?.each_frame(|frame| {
+        poster = Some(frame.image);
+        false
+    })?;
+    poster.ok_or(ffmpeg::Error::Eof)
+}
+
+/// The width and height of the video at `path`, without decoding any of it
+pub(crate) fn video_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let video = VideoDecoder::open(path).ok()?;
+    Some((video.decoder.width(), video.decoder.height()))
+}
+
+/// A video's best video stream, and what turns its frames into RGBA no bigger than [`MAX_EDGE`]
+struct VideoDecoder {
+    input: ffmpeg::format::context::Input,
+    stream: usize,
+    time_base: Rational,
+    decoder: decoder::Video,
+    scaler: scaling::Context,
+}
+
+impl VideoDecoder {
+    fn open(path: &Path) -> Result<Self, ffmpeg::Error> {
+        let input = ffmpeg::format::input(&path)?;
+        let best = input
+            .streams()
+            .best(media::Type::Video)
+            .ok_or(ffmpeg::Error::StreamNotFound)?;
+        let (stream, time_base) = (best.index(), best.time_base());
+        let decoder = ffmpeg::codec::context::Context::from_parameters(best.parameters())?
+            .decoder()
+            .video()?;
+        let (width, height) = (decoder.width(), decoder.height());
+        let scale = (MAX_EDGE as f32 / width.max(height).max(1) as f32).min(1.0);
+        let scaler = scaling::Context::get(
+            decoder.format(),
+            width,
+            height,
+            Pixel::RGBA,
+            ((width as f32 * scale).round() as u32).max(1),
+            ((height as f32 * scale).round() as u32).max(1),
+            scaling::Flags::BILINEAR,
+        )?;
+        Ok(Self {
+            input,
+            stream,
+            time_base,
+            decoder,
+            scaler,
+        })
+    }
+
+    /// Decode frames in order, handing each to `each` until it returns `false` or the video ends
+    fn each_frame(
+        &mut self,
+        mut each: impl FnMut(VideoFrame) -> bool,
+    ) -> Result<(), ffmpeg::Error> {
+        let Self {
+            input,
+            stream,
+            time_base,
+            decoder,
+            scaler,
+        } = self;
+        // Streams don't have to start at zero
+        let mut start = None;
+        for (packet_stream, packet) in input.packets() {
+            if packet_stream.index() != *stream {
+                continue;
+            }
+            decoder.send_packet(&packet)?;
+            if !receive_frames(decoder, scaler, *time_base, &mut start, &mut each)? {
+                return Ok(());
+            }
+        }
+        decoder.send_eof()?;
+        receive_frames(decoder, scaler, *time_base, &mut start, &mut each)?;
+        Ok(())
+    }
+}
+
+/// Hand every frame the decoder has ready to `each`, returning `false` once it's had enough
+fn receive_frames(
+    decoder: &mut decoder::Video,
+    scaler: &mut scaling::Context,
+    time_base: Rational,
+    start: &mut Option<f64>,
+    each: &mut impl FnMut(VideoFrame) -> bool,
+) -> Result<bool, ffmpeg::Error> {
+    let mut decoded = Video::empty();
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        let mut rgba = Video::empty();
+        scaler.run(&decoded, &mut rgba)?;
+        let seconds = decoded.timestamp().unwrap_or(0) as f64 * f64::from(time_base);
+        let since_start = seconds - *start.get_or_insert(seconds);
+        let frame = VideoFrame {
+            image: rgba_image(&rgba).ok_or(ffmpeg::Error::InvalidData)?,
+            at: Duration::from_secs_f64(since_start.max(0.0)),
+        };
+        if !each(frame) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// An RGBA frame's pixels, without the padding ffmpeg leaves at the end of each row
+fn rgba_image(frame: &Video) -> Option<RgbaImage> {
+    let (width, height) = (frame.width(), frame.height());
+    let (row, stride) = (width as usize * 4, frame.stride(0));
+    let data = frame.data(0);
+    let mut pixels = Vec::with_capacity(row * height as usize);
+    for y in 0..height as usize {
+        pixels.extend_from_slice(data.get(y * stride..y * stride + row)?);
+    }
+    RgbaImage::from_raw(width, height, pixels)
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+
+use crate::{
+    AppState, CameraAnimation, CameraConfig, GridConfig, RenderMode, StatusBar, WallMode,
+    text_input_inactive,
+};
+
+/// W wraps the grid round a cylinder, then a sphere, then lays it flat again (see [`WallMode`]).
+/// The camera moves to the middle of the wall, looking out at the first images, and back to where
+/// it started once the grid's flat again. The quads glide over like with any other regrid.
+pub struct WallPlugin;
+
+impl Plugin for WallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                cycle_wall_mode_system.run_if(in_state(AppState::Running).and(text_input_inactive)),
+                move_camera_to_wall.run_if(resource_changed::<GridConfig>),
+            )
+                .chain()
+                .run_if(resource_equals(RenderMode::Mesh3d)),
+        );
+    }
+}
+
+fn cycle_wall_mode_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut grid_config: ResMut<GridConfig>,
+    mut status: ResMut<StatusBar>,
+) {
+    if !keys.just_pressed(KeyCode::KeyW) {
+        return;
+    }
+    grid_config.wall = grid_config.wall.next();
+    status.set(match grid_config.wall {
+        WallMode::Flat => "Flat grid",
+        WallMode::Cylinder { .. } => "Image wall: cylinder",
+        WallMode::Sphere { .. } => "Image wall: sphere",
+    });
+}
+
+/// Stand the camera in the middle of the wall when there is one, and put it back when there isn't
+fn move_camera_to_wall(
+    mut commands: Commands,
+    grid_config: Res<GridConfig>,
+    camera_config: Res<CameraConfig>,
+    camera: Single<(Entity, &mut Transform), With<Camera3d>>,
+    mut previous: Local<Option<WallMode>>,
+) {
+    let wall = grid_config.wall;
+    let walled = |mode: WallMode| mode != WallMode::Flat;
+    let before = previous.replace(wall);
+    let (entity, mut transform) = camera.into_inner();
+    match before {
+        // Starting out on a wall, there's nowhere to move from
+        None if walled(wall) => *transform = Transform::IDENTITY,
+        Some(before) if walled(before) != walled(wall) => {
+            let target = if walled(wall) {
+                Transform::IDENTITY
+            } else {
+                camera_config.transform()
+            };
+            commands.entity(entity).insert(CameraAnimation::to(target));
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,231 @@
+use bevy::{prelude::*, window::RequestRedraw};
+
+use crate::{
+    AppState, GridConfig, ImageMarker, ImageTexture, Selection, StatusBar, text_input_inactive,
+};
+
+/// An in-progress camera move. The camera eases from wherever it is towards `target` and the
+/// component is removed once it gets there.
+#[derive(Component, Debug, Clone)]
+pub struct CameraAnimation {
+    pub target: Transform,
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+impl CameraAnimation {
+    /// How long the zoom commands take
+    pub const DURATION: f32 = 0.4;
+
+    pub fn to(target: Transform) -> Self {
+        Self {
+            target,
+            elapsed: 0.0,
+            duration: Self::DURATION,
+        }
+    }
+}
+
+/// Where the camera starts out. With `fit_grid` it then moves to take in the whole grid as soon as
+/// the first images are laid out, rather than staying wherever that happens to be.
+#[derive(Resource, Debug, Clone)]
+pub struct CameraConfig {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub fit_grid: bool,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0.0, 0.0, 15.0),
+            target: Vec3::ZERO,
+            fit_grid: false,
+        }
+    }
+}
+
+impl CameraConfig {
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.position).looking_at(self.target, Vec3::Y)
+    }
+}
+
+/// Home zooms out to fit the whole grid (when nothing is selected, otherwise it jumps to the first
+/// image), 1 zooms in to show the selected image at 100%
+pub struct ZoomPlugin;
+
+impl Plugin for ZoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraConfig>();
+        app.add_systems(
+            Update,
+            (
+                fit_first_layout_system,
+                (zoom_to_fit_system, zoom_to_actual_size_system)
+                    .run_if(in_state(AppState::Running).and(text_input_inactive)),
+                animate_camera,
+            )
+                .chain(),
+        );
+    }
+}
+
+fn smooth_step(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Back the camera off along its current view direction until the grid's bounding sphere fits
+fn zoom_to_fit_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selection>,
+    grid_config: Res<GridConfig>,
+    quads: Query<&Transform, With<ImageMarker>>,
+    camera: Single<(Entity, &Transform, &Camera, &Projection), With<Camera3d>>,
+) {
+    if !keys.just_pressed(KeyCode::Home) || !selected.is_empty() {
+        return;
+    }
+    let (entity, transform, camera, projection) = *camera;
+    if let Some(target) = fit_grid(&grid_config, &quads, transform, camera, projection) {
+        commands.entity(entity).insert(CameraAnimation::to(target));
+    }
+}
+
+/// With [`CameraConfig::fit_grid`], jump to the fitted view once the first quads are in
+fn fit_first_layout_system(
+    config: Res<CameraConfig>,
+    grid_config: Res<GridConfig>,
+    quads: Query<&Transform, (With<ImageMarker>, Without<Camera3d>)>,
+    camera: Single<(&mut Transform, &Camera, &Projection), With<Camera3d>>,
+    mut fitted: Local<bool>,
+) {
+    if *fitted || !config.fit_grid || quads.is_empty() {
+        return;
+    }
+    let (mut transform, camera, projection) = camera.into_inner();
+    if let Some(target) = fit_grid(&grid_config, &quads, &transform, camera, projection) {
+        *transform = target;
+        *fitted = true;
+    }
+}
+
+/// Where the camera needs to be, looking the way it is now, for the whole grid to be in view
+fn fit_grid<F: bevy::ecs::query::QueryFilter>(
+    grid_config: &GridConfig,
+    quads: &Query<&Transform, F>,
+    transform: &Transform,
+    camera: &Camera,
+    projection: &Projection,
+) -> Option<Transform> {
+    let Projection::Perspective(perspective) = projection else {
+        return None;
+    };
+
+    let half_quad = Vec3::splat(grid_config.quad_size * 0.5);
+    let (min, max) = quads
+        .iter()
+        .map(|quad| (quad.translation - half_quad, quad.translation + half_quad))
+        .reduce(|(min, max), (quad_min, quad_max)| (min.min(quad_min), max.max(quad_max)))?;
+    let center = (min + max) * 0.5;
+    let radius = (max - min).length() * 0.5;
+
+    // Whichever of the two fields of view is narrower decides how far back we need to be
+    let aspect = camera
+        .logical_viewport_size()
+        .map_or(perspective.aspect_ratio, |size| size.x / size.y.max(1.0));
+    let half_vertical = perspective.fov * 0.5;
+    let half_horizontal = (half_vertical.tan() * aspect).atan();
+    let distance = radius / half_vertical.min(half_horizontal).sin();
+
+    Some(transform.with_translation(center + transform.back() * distance))
+}
+
+/// Move the camera face-on to the selected image, close enough that a pixel of the image is a
+/// pixel on screen
+fn zoom_to_actual_size_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selection>,
+    grid_config: Res<GridConfig>,
+    images: Res<Assets<Image>>,
+    mut status: ResMut<StatusBar>,
+    quads: Query<(&ImageMarker, &ImageTexture, &GlobalTransform)>,
+    camera: Single<(Entity, &Camera, &Projection), With<Camera3d>>,
+) {
+    // Ctrl+1 gives it a star rating instead
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl || !keys.just_pressed(KeyCode::Digit1) {
+        return;
+    }
+    let (entity, camera, projection) = *camera;
+    let Projection::Perspective(perspective) = projection else {
+        return;
+    };
+    let Some(path) = selected.path() else {
+        status.set("Select an image to zoom to 100%");
+        return;
+    };
+    let Some((_, texture, quad)) = quads.iter().find(|(marker, _, _)| marker.target == *path)
+    else {
+        return;
+    };
+    let Some(image) = images.get(&texture.0) else {
+        status.set("That image hasn't loaded yet");
+        return;
+    };
+    let Some(viewport) = camera.physical_viewport_size() else {
+        return;
+    };
+
+    // The image is fitted to the quad by its longer side. At distance d the view is
+    // 2 d tan(fov / 2) world units tall, spread over the viewport's height in pixels.
+    let image_pixels = image.size().max_element().max(1) as f32;
+    let distance = grid_config.quad_size * viewport.y as f32
+        / (2.0 * image_pixels * (perspective.fov * 0.5).tan());
+
+    let quad = quad.compute_transform();
+    let target = Transform::from_translation(quad.translation + quad.back() * distance)
+        .looking_at(quad.translation, quad.up());
+    commands.entity(entity).insert(CameraAnimation::to(target));
+}
+
+fn animate_camera(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut redraw: EventWriter<RequestRedraw>,
+    camera: Option<Single<(Entity, &mut Transform, &mut CameraAnimation)>>,
+) {
+    let Some(camera) = camera else {
+        return;
+    };
+    let (entity, mut transform, mut animation) = camera.into_inner();
+
+    // The click that starts the move can come after a long idle wait, which would all land in
+    // the first step. Cap it so the start of the move isn't skipped.
+    let delta = time.delta_secs().min(1.0 / 30.0);
+    let before = smooth_step(animation.elapsed / animation.duration);
+    animation.elapsed += delta;
+    let after = smooth_step(animation.elapsed / animation.duration);
+
+    // Covering this step's share of what's left keeps us on the eased curve without having to
+    // remember where we started
+    let step = if before >= 1.0 {
+        1.0
+    } else {
+        (after - before) / (1.0 - before)
+    };
+    transform.translation = transform
+        .translation
+        .lerp(animation.target.translation, step);
+    transform.rotation = transform.rotation.slerp(animation.target.rotation, step);
+
+    if after >= 1.0 {
+        *transform = animation.target;
+        commands.entity(entity).remove::<CameraAnimation>();
+    } else {
+        redraw.write(RequestRedraw);
+    }
+}